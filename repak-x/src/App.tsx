@@ -165,6 +165,7 @@ type ViewMode = 'grid' | 'compact' | 'list' | 'list-compact'
 
 type DroppedModParse = {
   is_dir?: boolean
+  is_loose?: boolean
   contains_uassets?: boolean
   [key: string]: any
 }
@@ -369,7 +370,7 @@ function App() {
       try {
         const modsData = await invoke('parse_dropped_files', { paths }) as DroppedModParse[]
         const hasFolderWithUassets = modsData.some((mod: DroppedModParse) =>
-          mod.is_dir === true && mod.contains_uassets !== false
+          (mod.is_dir === true || mod.is_loose === true) && mod.contains_uassets !== false
         )
 
         if (hasFolderWithUassets) {
@@ -454,7 +455,7 @@ function App() {
 
       // Check if ALL mods are PAK files with no uassets - if so, use quick organize
       const allPaksWithNoUassets = modsData.every((mod: DroppedModParse) =>
-        mod.is_dir === false && mod.contains_uassets === false
+        mod.is_dir === false && !mod.is_loose && mod.contains_uassets === false
       )
 
       if (allPaksWithNoUassets && modsData.length > 0) {
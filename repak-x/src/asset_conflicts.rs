@@ -0,0 +1,343 @@
+// Asset conflicts - which mod wins each asset that several enabled mods replace
+//
+// Paks list their files relative to their mount point ("Marvel/Content/...") while IoStore
+// containers list them as mounted packages ("/Game/..."), and a package is split over .uasset,
+// .uexp and .ubulk files in a pak, so every file is reduced to the package path it belongs to
+// before mods are compared. The mod with the highest priority (lowest number, see
+// mod_ops::mod_priority) wins an asset; mods tied at the top leave the winner undecided.
+//
+// A conflict can be settled by preferring one mod (a load-order rule, see load_order), by
+// disabling the losers, or by splitting the contested files out of a mod into a mod of their own.
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use uasset_toolkit::RequestOptions;
+use walkdir::WalkDir;
+
+use crate::game_profile;
+use crate::mod_ops::{self, ModSource};
+use crate::utils::{asset_key, asset_path};
+
+/// A mod that replaces a contested asset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Contender {
+    pub path: PathBuf,
+    pub priority: usize,
+    /// The mod's own files for the asset
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AssetConflict {
+    /// Package path, e.g. /Game/Marvel/Characters/1011/Meshes/SK_1011
+    pub asset: String,
+    /// `None` when the top mods have the same priority
+    pub winner: Option<PathBuf>,
+    /// Highest priority first
+    pub contenders: Vec<Contender>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ConflictReport {
+    pub conflicts: Vec<AssetConflict>,
+    /// Enabled mods whose contents couldn't be read, so they aren't compared
+    pub unreadable: Vec<PathBuf>,
+}
+
+/// Files that don't change what the game loads
+fn is_metadata(file: &str) -> bool {
+    file.ends_with("patched_files")
+}
+
+/// Every asset replaced by more than one of `mods`, given as (path, priority, contained files)
+pub fn find_conflicts(mods: &[(PathBuf, usize, Vec<String>)]) -> Vec<AssetConflict> {
+    // Keyed case-insensitively like the engine's package names, remembering how it was first spelt
+    let mut assets: BTreeMap<String, (String, Vec<Contender>)> = BTreeMap::new();
+    for (path, priority, files) in mods {
+        for file in files.iter().filter(|f| !is_metadata(f)) {
+            let asset = asset_path(file);
+            let (_, contenders) = assets.entry(asset.to_lowercase()).or_insert_with(|| (asset, Vec::new()));
+            match contenders.last_mut().filter(|c| &c.path == path) {
+                Some(contender) => contender.files.push(file.clone()),
+                None => contenders.push(Contender { path: path.clone(), priority: *priority, files: vec![file.clone()] }),
+            }
+        }
+    }
+
+    assets
+        .into_values()
+        .filter(|(_, contenders)| contenders.len() > 1)
+        .map(|(asset, mut contenders)| {
+            contenders.sort_by(|a, b| a.priority.cmp(&b.priority).then_with(|| a.path.cmp(&b.path)));
+            let winner = (contenders[0].priority < contenders[1].priority).then(|| contenders[0].path.clone());
+            AssetConflict { asset, winner, contenders }
+        })
+        .collect()
+}
+
+/// Conflicts between the enabled mods of `mods_dir`, as listed by `source`
+pub fn scan(mods_dir: &Path, source: &mut impl ModSource) -> Result<ConflictReport, String> {
+    if !mods_dir.exists() {
+        return Err("Game path does not exist".to_string());
+    }
+
+    let mut report = ConflictReport::default();
+    let mut mods = Vec::new();
+    for scanned in source.mods(mods_dir).into_iter().filter(|m| m.enabled) {
+        match source.contents(&scanned.path) {
+            Ok(files) => mods.push((scanned.path, scanned.priority, files)),
+            Err(e) => {
+                warn!("[Conflicts] Couldn't read {}: {}", scanned.path.display(), e);
+                report.unreadable.push(scanned.path);
+            }
+        }
+    }
+    report.conflicts = find_conflicts(&mods);
+    info!("[Conflicts] {} contested asset(s) across {} mod(s)", report.conflicts.len(), mods.len());
+    Ok(report)
+}
+
+/// Where the files split out of the mod at `path` go: a new mod of the same name with a _Split
+/// suffix, at the same priority and with the same enabled state
+fn split_target(path: &Path) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("pak");
+    let base = mod_ops::base_stem(stem);
+    let priority = mod_ops::mod_priority(stem);
+    (1..)
+        .map(|n| {
+            let name = if n == 1 { format!("{}_Split", base) } else { format!("{}_Split{}", base, n) };
+            path.with_file_name(format!("{}.{}", mod_ops::priority_stem(&name, priority), extension))
+        })
+        .find(|candidate| !candidate.exists() && !candidate.with_extension("utoc").exists())
+        .unwrap()
+}
+
+/// Move the files of `assets` (package paths, see asset_path) out of the mod at `path` into a
+/// new mod next to it, so they can be ordered or disabled on their own. IoStore mods are
+/// extracted and converted again, which needs `usmap`. Returns the path of the new mod.
+pub fn split_out(path: &Path, assets: &[String], usmap: Option<&Path>, obfuscate: bool) -> Result<PathBuf, String> {
+    if !path.exists() {
+        return Err(format!("Mod file does not exist: {}", path.display()));
+    }
+    let wanted: HashSet<String> = assets.iter().map(|a| asset_key(a)).collect();
+    let target = split_target(path);
+
+    let utoc = path.with_extension("utoc");
+    if utoc.exists() {
+        split_iostore(path, &utoc, &wanted, &target, usmap, obfuscate)?;
+    } else {
+        split_pak(path, &wanted, &target)?;
+    }
+    info!("[Conflicts] Split {} asset(s) out of {} into {}", wanted.len(), path.display(), target.display());
+    Ok(target)
+}
+
+/// Refuse a split that would leave either side empty
+fn check_split(path: &Path, moving: usize, staying: usize) -> Result<(), String> {
+    if moving == 0 {
+        return Err(format!("None of those assets are in {}", path.display()));
+    }
+    if staying == 0 {
+        return Err(format!("Every file of {} is contested; disable it instead of splitting it", path.display()));
+    }
+    Ok(())
+}
+
+fn split_pak(path: &Path, wanted: &HashSet<String>, target: &Path) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open PAK: {}", e))?;
+    let pak = game_profile::active()
+        .open_pak(&mut BufReader::new(file))
+        .map_err(|e| format!("Failed to read PAK: {}", e))?;
+    let (moving, staying): (Vec<String>, Vec<String>) =
+        pak.files().into_iter().partition(|f| wanted.contains(&asset_key(f)));
+    check_split(path, moving.len(), staying.len())?;
+
+    // Both paks are written next to the original under temporary names, then moved into place
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let split = tempfile::NamedTempFile::new_in(dir).map_err(|e| e.to_string())?;
+    let rest = tempfile::NamedTempFile::new_in(dir).map_err(|e| e.to_string())?;
+    write_pak(&pak, path, &moving, split.as_file())?;
+    write_pak(&pak, path, &staying, rest.as_file())?;
+
+    split.persist_noclobber(target).map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+    if let Err(e) = rest.persist(path) {
+        let _ = fs::remove_file(target);
+        return Err(format!("Failed to rewrite {}: {}", path.display(), e));
+    }
+    Ok(())
+}
+
+/// Write the entries `files` of `pak` (read from `source`) into a new pak, compressing each
+/// entry the way it was compressed before
+fn write_pak(pak: &repak::PakReader, source: &Path, files: &[String], out: &File) -> Result<(), String> {
+    let profile = game_profile::active();
+    let (compressor, level) = mod_ops::oodle_settings_for_mode(None)?;
+    let mut writer = profile
+        .pak_builder()
+        .compression(pak.compression())
+        .oodle(compressor, level)
+        .writer(BufWriter::new(out), pak.version(), pak.mount_point().to_string(), pak.path_hash_seed());
+    let entry_builder = writer.entry_builder();
+
+    let mut reader = BufReader::new(File::open(source).map_err(|e| e.to_string())?);
+    for name in files {
+        let compress = pak.get_file_entry(name).is_ok_and(|entry| entry.compression_slot.is_some());
+        let data = pak.get(name, &mut reader).map_err(|e| format!("Failed to read entry {}: {}", name, e))?;
+        let entry = entry_builder
+            .build_entry(compress, data, name)
+            .map_err(|e| format!("Failed to build entry {}: {}", name, e))?;
+        writer.write_entry(name.clone(), entry).map_err(|e| format!("Failed to write entry {}: {}", name, e))?;
+    }
+    writer.write_index().map_err(|e| format!("Failed to write index: {}", e))?;
+    Ok(())
+}
+
+fn split_iostore(
+    path: &Path,
+    utoc: &Path,
+    wanted: &HashSet<String>,
+    target: &Path,
+    usmap: Option<&Path>,
+    obfuscate: bool,
+) -> Result<(), String> {
+    let profile = game_profile::active();
+    let work = tempfile::tempdir().map_err(|e| e.to_string())?;
+    let rest_dir = work.path().join("rest");
+    let split_dir = work.path().join("split");
+    uasset_toolkit::extract_iostore(&utoc.to_string_lossy(), &rest_dir.to_string_lossy(), profile.primary_key_hex().as_deref())
+        .map_err(|e| format!("Failed to extract IoStore: {}", e))?;
+
+    let files: Vec<PathBuf> = WalkDir::new(&rest_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .collect();
+    let mut moving = 0;
+    for file in &files {
+        let relative = file.strip_prefix(&rest_dir).unwrap_or(file);
+        if wanted.contains(&asset_key(&relative.to_string_lossy())) {
+            let dest = split_dir.join(relative);
+            fs::create_dir_all(dest.parent().unwrap()).map_err(|e| e.to_string())?;
+            fs::rename(file, dest).map_err(|e| e.to_string())?;
+            moving += 1;
+        }
+    }
+    check_split(path, moving, files.len() - moving)?;
+
+    // Convert both halves before touching the mods folder
+    let out = work.path().join("out");
+    let mut built = Vec::new();
+    for (input, dest) in [(&split_dir, target), (&rest_dir, path)] {
+        let stem = dest.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        let result = uasset_toolkit::create_mod_iostore(
+            &out.join(stem).to_string_lossy(),
+            &input.to_string_lossy(),
+            usmap.map(|u| u.to_string_lossy().to_string()).as_deref(),
+            Some(&profile.mount_point),
+            Some(true),
+            profile.primary_key_hex().as_deref(),
+            false,
+            obfuscate,
+            &RequestOptions::default(),
+        )
+        .map_err(|e| format!("IoStore conversion failed: {}", e))?;
+        built.push((result, dest));
+    }
+
+    for (result, dest) in built {
+        for (file, dest) in [
+            (&result.pak_path, dest.to_path_buf()),
+            (&result.utoc_path, dest.with_extension("utoc")),
+            (&result.ucas_path, dest.with_extension("ucas")),
+        ] {
+            move_file(Path::new(file), &dest).map_err(|e| format!("Failed to write {}: {}", dest.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Rename, or copy when `from` is on another drive. The copy goes to a temporary name that then
+/// replaces `to`, so a file already at `to` is never written into.
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if fs::rename(from, to).is_err() {
+        let tmp = PathBuf::from(format!("{}.repak_tmp", to.display()));
+        if let Err(e) = fs::copy(from, &tmp).and_then(|_| fs::rename(&tmp, to)) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pak_and_iostore_paths_meet_at_the_package() {
+        let package = "/Game/Marvel/Characters/1011/Meshes/SK_1011";
+        for file in [
+            "Marvel/Content/Marvel/Characters/1011/Meshes/SK_1011.uasset",
+            "Marvel/Content/Marvel/Characters/1011/Meshes/SK_1011.ubulk",
+            "../../../Marvel/Content/Marvel/Characters/1011/Meshes/SK_1011.uexp",
+            "/Game/Marvel/Characters/1011/Meshes/SK_1011.uasset",
+        ] {
+            assert_eq!(asset_path(file), package, "{}", file);
+        }
+        assert_eq!(asset_path("Engine/Content/Fonts/Roboto.uasset"), "/Engine/Fonts/Roboto");
+        assert_eq!(asset_path("Marvel/Plugins/Wwise/Content/Audio/Hit.uasset"), "/Wwise/Audio/Hit");
+        assert_eq!(asset_path("Marvel/Content/WwiseAudio/Media/123.wem"), "/Game/WwiseAudio/Media/123.wem");
+    }
+
+    #[test]
+    fn the_highest_priority_wins_and_ties_stay_undecided() {
+        let mods = vec![
+            (PathBuf::from("Low_99999999_P.pak"), 2, vec!["Marvel/Content/A.uasset".to_string(), "Marvel/Content/A.uexp".to_string()]),
+            (PathBuf::from("!High_9999999_P.pak"), 0, vec!["/Game/a.uasset".to_string(), "/Game/B.uasset".to_string()]),
+            (PathBuf::from("Other_99999999_P.pak"), 2, vec!["Marvel/Content/B.uasset".to_string(), "patched_files".to_string()]),
+            (PathBuf::from("Tied_99999999_P.pak"), 2, vec!["Marvel/Content/C.uasset".to_string()]),
+            (PathBuf::from("Tie_99999999_P.pak"), 2, vec!["Marvel/Content/C.uasset".to_string(), "patched_files".to_string()]),
+        ];
+
+        let conflicts = find_conflicts(&mods);
+        assert_eq!(conflicts.iter().map(|c| c.asset.as_str()).collect::<Vec<_>>(), ["/Game/A", "/Game/B", "/Game/C"]);
+
+        let a = &conflicts[0];
+        assert_eq!(a.winner.as_deref(), Some(Path::new("!High_9999999_P.pak")));
+        assert_eq!(a.contenders[1].files, ["Marvel/Content/A.uasset", "Marvel/Content/A.uexp"]);
+        assert_eq!(conflicts[2].winner, None);
+    }
+
+    #[test]
+    fn splitting_a_pak_keeps_its_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Skin_9999999_P.pak");
+        let mut writer = game_profile::active().pak_builder().writer(
+            BufWriter::new(File::create(&path).unwrap()),
+            repak::Version::V8B,
+            "../../../".to_string(),
+            None,
+        );
+        for file in ["Marvel/Content/A.uasset", "Marvel/Content/A.uexp", "Marvel/Content/B.uasset"] {
+            writer.write_file(file, false, file.as_bytes()).unwrap();
+        }
+        writer.write_index().unwrap();
+
+        let target = split_out(&path, &["/Game/A".to_string()], None, false).unwrap();
+        assert_eq!(target, dir.path().join("Skin_Split_9999999_P.pak"));
+
+        for (pak, files) in [(&path, vec!["Marvel/Content/B.uasset"]), (&target, vec!["Marvel/Content/A.uasset", "Marvel/Content/A.uexp"])] {
+            let reader = game_profile::active().open_pak(&mut BufReader::new(File::open(pak).unwrap())).unwrap();
+            assert_eq!(reader.version(), repak::Version::V8B);
+            let mut contents = reader.files();
+            contents.sort();
+            assert_eq!(contents, files);
+        }
+    }
+}
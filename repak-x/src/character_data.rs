@@ -13,19 +13,23 @@ use once_cell::sync::Lazy;
 
 // === CHARACTER UPDATE CANCELLATION FLAG ===
 // Global flag to signal cancellation of the character data fetch
+#[allow(dead_code)]
 static CANCEL_CHARACTER_UPDATE: AtomicBool = AtomicBool::new(false);
 
 /// Request cancellation of the ongoing character data update
+#[allow(dead_code)]
 pub fn request_cancel_update() {
     CANCEL_CHARACTER_UPDATE.store(true, Ordering::SeqCst);
 }
 
 /// Check if cancellation was requested
+#[allow(dead_code)]
 pub fn is_update_cancelled() -> bool {
     CANCEL_CHARACTER_UPDATE.load(Ordering::SeqCst)
 }
 
 /// Reset the cancellation flag (call before starting a new update)
+#[allow(dead_code)]
 pub fn reset_cancel_flag() {
     CANCEL_CHARACTER_UPDATE.store(false, Ordering::SeqCst);
 }
@@ -34,6 +38,7 @@ pub fn reset_cancel_flag() {
 // GITHUB DATA SOURCE
 // ============================================================================
 
+#[allow(dead_code)]
 const GITHUB_CHARACTER_DATA_URL: &str = 
     "https://raw.githubusercontent.com/donutman07/MarvelRivalsCharacterIDs/main/MarvelRivalsCharacterIDs.md";
 
@@ -260,6 +265,7 @@ pub fn get_character_by_skin_id(skin_id: &str) -> Option<CharacterSkin> {
 }
 
 /// Get all character data
+#[allow(dead_code)]
 pub fn get_all_character_data() -> Vec<CharacterSkin> {
     ensure_cache_initialized();
     
@@ -621,6 +627,7 @@ where
 
 /// Update character data from GitHub with progress callback
 /// (fetches, validates, saves, refreshes cache)
+#[allow(dead_code)]
 pub async fn update_from_github_with_progress<F>(mut on_progress: F) -> Result<usize, String>
 where
     F: FnMut(&str) + Send,
@@ -712,6 +719,7 @@ where
 
 /// Try to determine character/skin info from a mod's file paths
 /// Returns (character_name, skin_name) if found
+#[allow(dead_code)]
 pub fn identify_mod_from_paths(file_paths: &[String]) -> Option<(String, String)> {
     ensure_cache_initialized();
     
@@ -21,6 +21,7 @@ type ModInput = {
   mod_name?: string
   mod_type?: string
   is_dir?: boolean
+  is_loose?: boolean
   contains_uassets?: boolean
   auto_to_repak?: boolean
   auto_fix_texture?: boolean
@@ -162,17 +163,20 @@ const FolderNode = ({ node, selectedFolderId, onSelect, depth = 0 }: FolderNodeP
   )
 }
 
+// Loose files install as a folder mod, whether dropped as a folder or inside an archive
+const isFolderMod = (mod: any = {}) => Boolean(mod?.is_dir || mod?.is_loose)
+
 const hasCookedAssets = (mod: any = {}) => {
-  if (!mod?.is_dir) return false
+  if (!isFolderMod(mod)) return false
   return Boolean(mod.auto_fix_texture || mod.auto_fix_serialize_size)
 }
 
-const isRepakLocked = (mod: any = {}) => mod.is_dir || hasCookedAssets(mod)
+const isRepakLocked = (mod: any = {}) => isFolderMod(mod) || hasCookedAssets(mod)
 
 const buildInitialSettings = (mods: ModInput[] = []): Record<number, ModSetting> => {
   return mods.reduce((acc, mod, idx) => {
     const locked = isRepakLocked(mod)
-    const defaultToRepak = mod.is_dir ? !locked : Boolean(mod.auto_to_repak)
+    const defaultToRepak = isFolderMod(mod) ? !locked : Boolean(mod.auto_to_repak)
     const canApplyPatches = mod.contains_uassets !== false // Default to true if undefined
 
     // For mods with no uassets, we skip repak (IoStore logic) and likely enforce legacy
@@ -349,10 +353,10 @@ export default function InstallModPanel({ mods, allTags, folders = [], onCreateT
               {mods.map((mod, idx) => {
                 const repakLocked = isRepakLocked(mod)
                 const repakTitle = repakLocked
-                  ? (mod.is_dir ? 'Folder drops cannot be repaked' : 'Detected loose assets; repak handled automatically')
+                  ? (isFolderMod(mod) ? 'Folder drops cannot be repaked' : 'Detected loose assets; repak handled automatically')
                   : 'Direct PAK - can repak if needed'
                 const { character, category, additional } = parseModType(mod.mod_type)
-                const modLabel = mod.is_dir ? 'Folder Drop' : 'PAK File'
+                const modLabel = isFolderMod(mod) ? 'Folder Drop' : 'PAK File'
                 return (
                   <div className="install-mod-card" key={mod.path || idx}>
                     {/* Left: Mod Options */}
@@ -376,7 +380,7 @@ export default function InstallModPanel({ mods, allTags, folders = [], onCreateT
                               : mod.mod_name}
                           </span>
                         </div>
-                        <span className={`install-mod-card__pill ${mod.is_dir ? 'pill-folder' : 'pill-pak'}`}>
+                        <span className={`install-mod-card__pill ${isFolderMod(mod) ? 'pill-folder' : 'pill-pak'}`}>
                           {modLabel}
                         </span>
                       </div>
@@ -532,7 +536,7 @@ export default function InstallModPanel({ mods, allTags, folders = [], onCreateT
                             <div className="install-toggle__text">
                               <span className="install-toggle__label">Send to Repak</span>
                               <span className="install-toggle__hint">
-                                {repakLocked ? (mod.is_dir ? 'Not available for folder drops' : 'Loose assets detected') : 'Repaks the pak into IOStore format'}
+                                {repakLocked ? (isFolderMod(mod) ? 'Not available for folder drops' : 'Loose assets detected') : 'Repaks the pak into IOStore format'}
                               </span>
                             </div>
                           </Switch>
@@ -37,6 +37,7 @@ export default function ToolsPanel({ onClose, mods = [], onToggleMod }: ToolsPan
     const [recompressStatus, setRecompressStatus] = useState('');
     const [recompressResult, setRecompressResult] = useState<any | null>(null);
     const [recompressProgress, setRecompressProgress] = useState<RecompressProgress>({ current: 0, total: 0 });
+    const [recompressMode, setRecompressMode] = useState<'fast' | 'max'>('fast');
     const [showThanosSnap, setShowThanosSnap] = useState<number | null>(null); // null or timestamp for cache-busting
     const [thanosIsFading, setThanosIsFading] = useState(false);
 
@@ -164,7 +165,7 @@ export default function ToolsPanel({ onClose, mods = [], onToggleMod }: ToolsPan
         setRecompressStatus('Scanning mods...');
         setRecompressResult(null);
         try {
-            const result = await invoke('recompress_mods') as any;
+            const result = await invoke('recompress_mods', { mode: recompressMode }) as any;
             setRecompressResult(result);
             if (result.recompressed > 0) {
                 setRecompressStatus(`✓ Recompressed ${result.recompressed} mod(s)! (${result.already_oodle} already compressed)`);
@@ -287,6 +288,15 @@ export default function ToolsPanel({ onClose, mods = [], onToggleMod }: ToolsPan
                                     Apply Oodle compression to all IOStore bundles paked with the old Repak GUI.
                                 </p>
                                 <div style={{ display: 'flex', gap: '0.5rem' }}>
+                                    <select
+                                        value={recompressMode}
+                                        onChange={(e) => setRecompressMode(e.target.value as 'fast' | 'max')}
+                                        disabled={isRecompressing}
+                                        title="Oodle encoder used for legacy PAKs"
+                                    >
+                                        <option value="fast">Fast</option>
+                                        <option value="max">Max compression</option>
+                                    </select>
                                     <button
                                         onClick={handleReCompress}
                                         disabled={isRecompressing}
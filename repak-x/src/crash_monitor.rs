@@ -16,8 +16,10 @@ pub struct CrashInfo {
     pub enabled_mods: Vec<String>,
 }
 
-/// Get the path to Marvel Rivals crash logs directory
+/// Get the path to the game's crash logs directory
 pub fn get_crash_log_path() -> PathBuf {
+    let profile = crate::game_profile::active();
+    
     #[cfg(target_os = "windows")]
     {
         let local_appdata = std::env::var("LOCALAPPDATA")
@@ -27,16 +29,17 @@ pub fn get_crash_log_path() -> PathBuf {
                 format!("{}\\AppData\\Local", userprofile)
             });
         
-        PathBuf::from(local_appdata)
-            .join("Marvel")
-            .join("Saved")
-            .join("Crashes")
+        PathBuf::from(local_appdata).join(&profile.crash_dir)
     }
     
     #[cfg(target_os = "linux")]
     {
-        // Marvel Rivals on Linux runs via Steam Proton
-        // Crash logs are in the Proton prefix for the game (AppID: 2767030)
+        // On Linux the game runs via Steam Proton
+        // Crash logs are in the Proton prefix for the game's app id
+        let local_appdata = format!(
+            "steamapps/compatdata/{}/pfx/drive_c/users/steamuser/AppData/Local",
+            profile.steam_app_id.unwrap_or_default()
+        );
         if let Some(home) = dirs::home_dir() {
             // Try common Steam library locations
             let steam_paths = [
@@ -46,34 +49,35 @@ pub fn get_crash_log_path() -> PathBuf {
             ];
             
             for steam_path in &steam_paths {
-                let crash_path = steam_path
-                    .join("steamapps/compatdata/2767030/pfx/drive_c/users/steamuser/AppData/Local/Marvel/Saved/Crashes");
+                let crash_path = steam_path.join(&local_appdata).join(&profile.crash_dir);
                 if crash_path.exists() {
                     return crash_path;
                 }
             }
             
             // Default to first path even if it doesn't exist yet
-            return steam_paths[0]
-                .join("steamapps/compatdata/2767030/pfx/drive_c/users/steamuser/AppData/Local/Marvel/Saved/Crashes");
+            return steam_paths[0].join(&local_appdata).join(&profile.crash_dir);
         }
         
         // Fallback if home dir not found
         PathBuf::from("/home")
             .join(std::env::var("USER").unwrap_or_else(|_| "user".to_string()))
-            .join(".steam/steam/steamapps/compatdata/2767030/pfx/drive_c/users/steamuser/AppData/Local/Marvel/Saved/Crashes")
+            .join(".steam/steam")
+            .join(&local_appdata)
+            .join(&profile.crash_dir)
     }
     
     #[cfg(target_os = "macos")]
     {
-        // macOS - Marvel Rivals may run via CrossOver or similar
+        // macOS - the game may run via CrossOver or similar
         // TODO: Determine actual path when macOS support is added
         if let Some(home) = dirs::home_dir() {
-            home.join("Library/Application Support/Marvel/Saved/Crashes")
+            home.join("Library/Application Support").join(&profile.crash_dir)
         } else {
             PathBuf::from("/Users")
                 .join(std::env::var("USER").unwrap_or_else(|_| "user".to_string()))
-                .join("Library/Application Support/Marvel/Saved/Crashes")
+                .join("Library/Application Support")
+                .join(&profile.crash_dir)
         }
     }
     
@@ -0,0 +1,278 @@
+// Game profiles - everything that ties the mod manager to a specific UE game
+//
+// The active profile is read from game_profile.json in the app directory. Fields that are
+// missing from the file fall back to the built-in Marvel Rivals profile, so an empty or
+// partial file only overrides what it mentions.
+use log::{info, warn};
+use repak::utils::AesKey;
+use repak::{PakBuilder, PakReader, Version};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, RwLock};
+use uasset_toolkit::package::PackageVersion;
+
+use crate::utils::app_dir;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameProfile {
+    /// Display name used in logs and messages
+    pub name: String,
+    /// Used for steam://run launches and the Proton prefix on Linux
+    pub steam_app_id: Option<u32>,
+    /// AES keys as hex or base64. The first one encrypts paks we write; all of them are tried when reading
+    pub aes_keys: Vec<String>,
+    /// Pak version written when repacking
+    #[serde(serialize_with = "serialize_version", deserialize_with = "deserialize_version")]
+    pub pak_version: Version,
+    /// Mount point for paks and IoStore containers we create
+    pub mount_point: String,
+    /// Object versions the game's unversioned packages are read and patched with natively
+    pub package_version: PackageVersion,
+    /// Folder name under steamapps/common
+    pub install_dir: String,
+    /// Path of the mods folder relative to the game root
+    pub mods_dir: String,
+    /// Executable name of the running game, compared case-insensitively
+    pub process_name: String,
+    /// Crash report folder relative to the game's LocalAppData folder
+    pub crash_dir: String,
+}
+
+impl Default for GameProfile {
+    fn default() -> Self {
+        Self::marvel_rivals()
+    }
+}
+
+impl GameProfile {
+    pub fn marvel_rivals() -> Self {
+        Self {
+            name: "Marvel Rivals".to_string(),
+            steam_app_id: Some(2767030),
+            aes_keys: vec!["0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74".to_string()],
+            pak_version: Version::V11,
+            mount_point: "../../../".to_string(),
+            package_version: PackageVersion::UE5_3,
+            install_dir: "MarvelRivals".to_string(),
+            mods_dir: "MarvelGame/Marvel/Content/Paks/~mods".to_string(),
+            process_name: "Marvel-Win64-Shipping.exe".to_string(),
+            crash_dir: "Marvel/Saved/Crashes".to_string(),
+        }
+    }
+
+    /// Parsed AES keys, skipping any that aren't valid 256-bit keys
+    pub fn keys(&self) -> Vec<AesKey> {
+        self.aes_keys
+            .iter()
+            .filter_map(|key| match AesKey::from_str(key.trim()) {
+                Ok(key) => Some(key),
+                Err(_) => {
+                    warn!("Ignoring invalid AES key in game profile '{}'", self.name);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Rejects profiles with keys that would silently be skipped
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(key) = self.aes_keys.iter().find(|key| AesKey::from_str(key.trim()).is_err()) {
+            return Err(format!("Invalid AES key '{}' in game profile '{}'", key, self.name));
+        }
+        Ok(())
+    }
+
+    /// Key used to encrypt paks and IoStore containers we write
+    pub fn primary_key(&self) -> Option<AesKey> {
+        self.keys().into_iter().next()
+    }
+
+    /// The primary key as a hex string, for UAssetTool, which doesn't accept base64 keys
+    pub fn primary_key_hex(&self) -> Option<String> {
+        self.aes_keys
+            .iter()
+            .map(|key| key.trim())
+            .find(|key| AesKey::from_str(key).is_ok())
+            .and_then(key_bytes)
+            .map(hex::encode_upper)
+    }
+
+    /// PakBuilder preconfigured with the primary key
+    pub fn pak_builder(&self) -> PakBuilder {
+        match self.primary_key() {
+            Some(key) => PakBuilder::new().key(key.0),
+            None => PakBuilder::new(),
+        }
+    }
+
+    /// Open a pak, trying each configured key in turn
+    pub fn open_pak<R: Read + Seek>(&self, reader: &mut R) -> Result<PakReader, repak::Error> {
+        let keys = self.keys();
+        if keys.is_empty() {
+            return PakBuilder::new().reader(reader);
+        }
+        let mut last_error = None;
+        for key in keys {
+            match PakBuilder::new().key(key.0).reader(reader) {
+                Ok(pak) => return Ok(pak),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap())
+    }
+
+    /// Game root for a mods folder laid out as `mods_dir`, e.g. ~mods -> Paks -> Content -> Marvel -> MarvelGame -> MarvelRivals
+    #[allow(dead_code)]
+    pub fn game_root(&self, mods_path: &Path) -> Option<PathBuf> {
+        let depth = Path::new(&self.mods_dir).components().count();
+        mods_path.ancestors().nth(depth).map(Path::to_path_buf)
+    }
+
+    /// Mods folder inside a game root
+    #[allow(dead_code)]
+    pub fn mods_path(&self, game_root: &Path) -> PathBuf {
+        game_root.join(&self.mods_dir)
+    }
+
+    #[allow(dead_code)]
+    pub fn steam_run_url(&self) -> Option<String> {
+        self.steam_app_id.map(|id| format!("steam://run/{}", id))
+    }
+
+    #[allow(dead_code)]
+    pub fn is_game_process(&self, name: &str) -> bool {
+        name.eq_ignore_ascii_case(&self.process_name)
+    }
+}
+
+fn serialize_version<S: Serializer>(version: &Version, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&version.to_string())
+}
+
+fn deserialize_version<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Version, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    Version::from_str(&s).map_err(|_| serde::de::Error::custom(format!("unknown pak version '{}'", s)))
+}
+
+pub fn profile_path() -> PathBuf {
+    app_dir().join("game_profile.json")
+}
+
+pub fn load_from(path: &Path) -> Result<GameProfile, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read game profile {}: {}", path.display(), e))?;
+    let profile: GameProfile = serde_json::from_str(&text)
+        .map_err(|e| format!("Invalid game profile {}: {}", path.display(), e))?;
+    profile.validate()?;
+    Ok(profile)
+}
+
+/// Profile from the app directory, or the built-in one if there is none
+fn load() -> GameProfile {
+    let path = profile_path();
+    if !path.exists() {
+        return GameProfile::default();
+    }
+    match load_from(&path) {
+        Ok(profile) => {
+            info!("Loaded game profile '{}' from {}", profile.name, path.display());
+            profile
+        }
+        Err(e) => {
+            warn!("{}; using the built-in profile", e);
+            GameProfile::default()
+        }
+    }
+}
+
+static ACTIVE: LazyLock<RwLock<Arc<GameProfile>>> = LazyLock::new(|| RwLock::new(Arc::new(load())));
+
+pub fn active() -> Arc<GameProfile> {
+    ACTIVE.read().unwrap().clone()
+}
+
+pub fn set_active(profile: GameProfile) {
+    *ACTIVE.write().unwrap() = Arc::new(profile);
+}
+
+/// Write the profile to the app directory and make it the active one
+#[allow(dead_code)]
+pub fn save(profile: GameProfile) -> Result<(), String> {
+    profile.validate()?;
+    let path = profile_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(&profile).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write game profile: {}", e))?;
+    set_active(profile);
+    Ok(())
+}
+
+/// Raw bytes of a key in either of the notations `AesKey::from_str` accepts
+fn key_bytes(key: &str) -> Option<Vec<u8>> {
+    use base64::{engine::general_purpose, Engine as _};
+    hex::decode(key.strip_prefix("0x").unwrap_or(key))
+        .ok()
+        .or_else(|| general_purpose::STANDARD_NO_PAD.decode(key.trim_end_matches('=')).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_profile_falls_back_to_defaults() {
+        let profile: GameProfile = serde_json::from_str(r#"{ "name": "Other", "steam_app_id": 1, "pak_version": "V9" }"#).unwrap();
+        assert_eq!(profile.name, "Other");
+        assert_eq!(profile.pak_version, Version::V9);
+        assert_eq!(profile.steam_run_url().as_deref(), Some("steam://run/1"));
+        assert_eq!(profile.mount_point, GameProfile::marvel_rivals().mount_point);
+        assert_eq!(profile.package_version, PackageVersion::UE5_3);
+
+        let ue5_4: GameProfile = serde_json::from_str(r#"{ "package_version": { "ue4": 522, "ue5": 1012 } }"#).unwrap();
+        assert_eq!(ue5_4.package_version, PackageVersion::UE5_4);
+
+        assert!(serde_json::from_str::<GameProfile>(r#"{ "pak_version": "V99" }"#).is_err());
+    }
+
+    #[test]
+    fn game_root_follows_mods_layout() {
+        let profile = GameProfile::marvel_rivals();
+        let root = Path::new("/steam/steamapps/common/MarvelRivals");
+        let mods = profile.mods_path(root);
+        assert_eq!(profile.game_root(&mods).as_deref(), Some(root));
+
+        let other = GameProfile { mods_dir: "Game/Content/Paks/~mods".to_string(), ..profile };
+        assert_eq!(other.game_root(Path::new("/g/Game/Content/Paks/~mods")).as_deref(), Some(Path::new("/g")));
+    }
+
+    #[test]
+    fn keys_are_tried_in_order() {
+        let mut profile = GameProfile::marvel_rivals();
+        assert_eq!(profile.keys().len(), 1);
+        assert!(profile.primary_key_hex().is_some());
+
+        profile.aes_keys.insert(0, "not a key".to_string());
+        assert_eq!(profile.keys().len(), 1);
+        assert!(profile.validate().is_err());
+        assert_eq!(profile.primary_key_hex(), Some(GameProfile::marvel_rivals().aes_keys[0].clone()));
+    }
+
+    #[test]
+    fn base64_keys_are_handed_to_the_toolkit_as_hex() {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let hex_key = GameProfile::marvel_rivals().aes_keys[0].clone();
+        let encoded = general_purpose::STANDARD.encode(hex::decode(&hex_key).unwrap());
+        let profile = GameProfile { aes_keys: vec![encoded], ..GameProfile::marvel_rivals() };
+        assert!(profile.validate().is_ok());
+        assert_eq!(profile.primary_key_hex(), Some(hex_key));
+
+        let prefixed = GameProfile { aes_keys: vec![format!("0x{}", GameProfile::marvel_rivals().aes_keys[0].to_lowercase())], ..profile };
+        assert_eq!(prefixed.primary_key_hex(), Some(GameProfile::marvel_rivals().aes_keys[0].clone()));
+    }
+}
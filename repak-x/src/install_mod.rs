@@ -1,19 +1,19 @@
 pub mod install_mod_logic;
 
-use crate::install_mod::install_mod_logic::archives::{extract_zip, extract_rar, extract_7z};
+use crate::game_profile;
+use crate::install_mod::install_mod_logic::archives::{archive_kind, archive_origin, extract_archive_nested, is_secondary_volume, ExtractGuard};
 use crate::uasset_detection::{detect_texture_files, detect_static_mesh_files};
 use crate::utils::{collect_files, get_current_pak_characteristics};
 use crate::utoc_utils::read_utoc;
 use log::{debug, error};
-use repak::utils::AesKey;
 use repak::Compression::Oodle;
 use repak::{Compression, PakReader};
 use std::fs::File;
 use std::io::BufReader;
 use std::path::PathBuf;
-use std::str::FromStr;
-use std::sync::LazyLock;
-use tempfile::tempdir;
+use std::sync::Arc;
+use tempfile::{tempdir, TempDir};
+use uasset_toolkit::{CancellationToken, RequestOptions};
 use walkdir::WalkDir;
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -55,6 +55,13 @@ pub struct InstallableMod {
     /// Enable obfuscation (encrypts IoStore with game's AES key to block extraction tools like FModel)
     #[serde(default)]
     pub obfuscate: bool,
+    /// Archives the mod was unpacked from, outermost first (empty if it wasn't in an archive)
+    #[serde(default)]
+    pub archive_origin: Vec<String>,
+    /// Folder the archive was extracted to; `mod_path` points into it, and it is deleted once
+    /// the last mod extracted from the archive is dropped, after the install committed or rolled back
+    #[serde(skip)]
+    pub extracted_dir: Option<Arc<TempDir>>,
 }
 
 impl Default for InstallableMod {
@@ -84,6 +91,8 @@ impl Default for InstallableMod {
             install_subfolder: String::new(),
             parallel_processing: false,
             obfuscate: false,
+            archive_origin: Vec::new(),
+            extracted_dir: None,
         }
     }
 }
@@ -100,12 +109,7 @@ pub fn contains_uasset_files(files: &[String]) -> bool {
     })
 }
 
-pub static AES_KEY: LazyLock<AesKey> = LazyLock::new(|| {
-    AesKey::from_str("0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74")
-        .expect("Unable to initialise AES_KEY")
-});
-
-fn find_mods_from_archive(path: &str) -> Vec<InstallableMod> {
+fn find_mods_from_archive(path: &str, options: &RequestOptions) -> Vec<InstallableMod> {
     let mut new_mods = Vec::<InstallableMod>::new();
     let mut processed_mods = std::collections::HashSet::new();
     let mut found_pak_files = false;
@@ -143,10 +147,7 @@ fn find_mods_from_archive(path: &str) -> Vec<InstallableMod> {
                 let has_uassets = contains_uasset_files(&files);
 
                 // Try to open PAK for reader (optional - may fail for obfuscated mods)
-                let reader = repak::PakBuilder::new()
-                    .key(AES_KEY.clone().0)
-                    .reader(&mut BufReader::new(File::open(&file_path).unwrap()))
-                    .ok();
+                let reader = game_profile::active().open_pak(&mut BufReader::new(File::open(file_path).unwrap())).ok();
 
                 let installable_mod = InstallableMod {
                     mod_name: mod_base_name,
@@ -155,7 +156,7 @@ fn find_mods_from_archive(path: &str) -> Vec<InstallableMod> {
                     is_dir: false,
                     reader,
                     mod_path: file_path.to_path_buf(),
-                    mount_point: "../../../".to_string(),
+                    mount_point: game_profile::active().mount_point.clone(),
                     path_hash_seed: "00000000".to_string(),
                     total_files: len,
                     iostore: true,  // Mark as iostore so it gets copied directly
@@ -170,9 +171,7 @@ fn find_mods_from_archive(path: &str) -> Vec<InstallableMod> {
             }
             // This is a standalone .pak file
             else {
-                let builder = repak::PakBuilder::new()
-                    .key(AES_KEY.clone().0)
-                    .reader(&mut BufReader::new(File::open(file_path).unwrap()));
+                let builder = game_profile::active().open_pak(&mut BufReader::new(File::open(file_path).unwrap()));
 
                 if let Ok(builder) = builder {
                     let files = builder.files();
@@ -184,8 +183,8 @@ fn find_mods_from_archive(path: &str) -> Vec<InstallableMod> {
                     let is_audio_or_movie = modtype.contains("Audio") || modtype.contains("Movies");
                     
                     // Auto-detect texture files (mesh patching is handled automatically by UAssetTool)
-                    let auto_fix_textures = detect_texture_files(&files);
-                    let auto_fix_static_mesh = detect_static_mesh_files(&files);
+                    let auto_fix_textures = detect_texture_files(&files, options);
+                    let auto_fix_static_mesh = detect_static_mesh_files(&files, options);
 
                     let installable_mod = InstallableMod {
                         mod_name: mod_base_name,
@@ -196,7 +195,7 @@ fn find_mods_from_archive(path: &str) -> Vec<InstallableMod> {
                         is_dir: false,
                         reader: Some(builder),
                         mod_path: file_path.to_path_buf(),
-                        mount_point: "../../../".to_string(),
+                        mount_point: game_profile::active().mount_point.clone(),
                         path_hash_seed: "00000000".to_string(),
                         total_files: len,
                         iostore: false,
@@ -262,8 +261,8 @@ fn find_mods_from_archive(path: &str) -> Vec<InstallableMod> {
                         let is_audio_or_movies = modtype.contains("Audio") || modtype.contains("Movies");
                         
                         // Auto-detect texture files (mesh patching is handled automatically by UAssetTool)
-                        let auto_fix_textures = detect_texture_files(&file_strings);
-                        let auto_fix_static_mesh = detect_static_mesh_files(&file_strings);
+                        let auto_fix_textures = detect_texture_files(&file_strings, options);
+                        let auto_fix_static_mesh = detect_static_mesh_files(&file_strings, options);
                         
                         let installable_mod = InstallableMod {
                             mod_name,
@@ -274,7 +273,7 @@ fn find_mods_from_archive(path: &str) -> Vec<InstallableMod> {
                             is_dir: true,  // Mark as directory so it uses convert_to_iostore_directory
                             reader: None,
                             mod_path: entry_path,
-                            mount_point: "../../../".to_string(),
+                            mount_point: game_profile::active().mount_point.clone(),
                             path_hash_seed: "00000000".to_string(),
                             total_files: content_files.len(),
                             iostore: false,
@@ -319,8 +318,8 @@ fn find_mods_from_archive(path: &str) -> Vec<InstallableMod> {
                     let modtype = get_current_pak_characteristics(file_strings.clone());
                     let has_uassets = contains_uasset_files(&file_strings);
                     let is_audio_or_movies = modtype.contains("Audio") || modtype.contains("Movies");
-                    let auto_fix_textures = detect_texture_files(&file_strings);
-                    let auto_fix_static_mesh = detect_static_mesh_files(&file_strings);
+                    let auto_fix_textures = detect_texture_files(&file_strings, options);
+                    let auto_fix_static_mesh = detect_static_mesh_files(&file_strings, options);
                     
                     let installable_mod = InstallableMod {
                         mod_name,
@@ -331,7 +330,7 @@ fn find_mods_from_archive(path: &str) -> Vec<InstallableMod> {
                         is_dir: true,
                         reader: None,
                         mod_path: archive_root.to_path_buf(),
-                        mount_point: "../../../".to_string(),
+                        mount_point: game_profile::active().mount_point.clone(),
                         path_hash_seed: "00000000".to_string(),
                         total_files: content_files.len(),
                         iostore: false,
@@ -351,14 +350,19 @@ fn find_mods_from_archive(path: &str) -> Vec<InstallableMod> {
     new_mods
 }
 
-fn map_to_mods_internal(paths: &[PathBuf]) -> Vec<InstallableMod> {
+fn map_to_mods_internal(paths: &[PathBuf], cancel: &CancellationToken) -> Vec<InstallableMod> {
+    // Detection requests are aborted with the rest of the batch
+    let options = RequestOptions { timeout: None, cancel: Some(cancel.clone()) };
     let mut extensible_vec: Vec<InstallableMod> = Vec::new();
     let mut installable_mods = paths
         .iter()
+        // Later volumes of a split archive are read through the first one
+        .filter(|path| !is_secondary_volume(path))
+        .take_while(|_| !cancel.is_cancelled())
         .map(|path| {
             let is_dir = path.clone().is_dir();
             let extension = path.extension().unwrap_or_default();
-            let is_archive = extension == "zip" || extension == "rar" || extension == "7z";
+            let is_archive = !is_dir && archive_kind(path).is_some();
             
             // Check if this is an IoStore package (has .utoc and .ucas companions)
             let is_iostore = if extension == "pak" {
@@ -389,14 +393,9 @@ fn map_to_mods_internal(paths: &[PathBuf]) -> Vec<InstallableMod> {
                     has_uassets = contains_uasset_files(&files);
                     
                     // Try to open PAK for reader (optional - may fail for obfuscated mods)
-                    pak = repak::PakBuilder::new()
-                        .key(AES_KEY.clone().0)
-                        .reader(&mut BufReader::new(File::open(path.clone()).unwrap()))
-                        .ok();
+                    pak = game_profile::active().open_pak(&mut BufReader::new(File::open(path.clone()).unwrap())).ok();
                 } else {
-                    let builder = repak::PakBuilder::new()
-                        .key(AES_KEY.clone().0)
-                        .reader(&mut BufReader::new(File::open(path.clone()).unwrap()));
+                    let builder = game_profile::active().open_pak(&mut BufReader::new(File::open(path.clone()).unwrap()));
                     match builder {
                         Ok(builder) => {
                             pak = Some(builder.clone());
@@ -408,8 +407,8 @@ fn map_to_mods_internal(paths: &[PathBuf]) -> Vec<InstallableMod> {
                             has_uassets = contains_uasset_files(&files);
                             
                             // Auto-detect texture files (mesh patching is handled automatically by UAssetTool)
-                            auto_fix_textures = detect_texture_files(&files);
-                            auto_fix_static_mesh = detect_static_mesh_files(&files);
+                            auto_fix_textures = detect_texture_files(&files, &options);
+                            auto_fix_static_mesh = detect_static_mesh_files(&files, &options);
                         }
                         Err(e) => {
                             error!("Error reading pak file: {}", e);
@@ -431,31 +430,33 @@ fn map_to_mods_internal(paths: &[PathBuf]) -> Vec<InstallableMod> {
                 has_uassets = contains_uasset_files(&files);
                 
                 // Auto-detect texture files (mesh patching is handled automatically by UAssetTool)
-                auto_fix_textures = detect_texture_files(&files);
-                auto_fix_static_mesh = detect_static_mesh_files(&files);
+                auto_fix_textures = detect_texture_files(&files, &options);
+                auto_fix_static_mesh = detect_static_mesh_files(&files, &options);
             }
 
             if is_archive {
                 modtype = "Archive".to_string();
-                let tempdir = tempdir()
-                    .unwrap()
-                    .path()
-                    .as_os_str()
-                    .to_str()
-                    .unwrap()
-                    .to_string();
+                // Kept until the install is done; mod paths point into it
+                let tempdir = Arc::new(tempdir().map_err(repak::Error::Io)?);
 
-                if extension == "zip" {
-                    extract_zip(path.to_str().unwrap(), &tempdir).expect("Unable to extract zip archive")
-                } else if extension == "rar" {
-                    extract_rar(path.to_str().unwrap(), &tempdir).expect("Unable to extract rar archive")
-                } else if extension == "7z" {
-                    extract_7z(path.to_str().unwrap(), &tempdir).expect("Unable to extract 7z archive")
+                // Unpacks archives inside the archive too (zip holding a 7z, split volumes, tarballs)
+                let mut guard = ExtractGuard::default().with_cancel(cancel.clone());
+                match extract_archive_nested(path, tempdir.path(), &mut guard) {
+                    Ok(extracted) => {
+                        // Now find pak files / iostore mods and turn them into installable mods
+                        let mut new_mods = find_mods_from_archive(tempdir.path().to_str().unwrap(), &options);
+                        for new_mod in &mut new_mods {
+                            new_mod.archive_origin = archive_origin(&extracted, &new_mod.mod_path);
+                            new_mod.extracted_dir = Some(tempdir.clone());
+                            debug!("{} came from {}", new_mod.mod_name, new_mod.archive_origin.join(" > "));
+                        }
+                        extensible_vec.append(&mut new_mods);
+                    }
+                    Err(e) => {
+                        // The half-extracted download is removed with `tempdir`
+                        error!("Unable to extract archive {}: {}", path.display(), e);
+                    }
                 }
-
-                // Now find pak files / iostore mods and turn them into installable mods
-                let mut new_mods = find_mods_from_archive(&tempdir);
-                extensible_vec.append(&mut new_mods);
             }
 
             // Determine if we should repak this mod
@@ -472,7 +473,7 @@ fn map_to_mods_internal(paths: &[PathBuf]) -> Vec<InstallableMod> {
                 is_dir,
                 reader: pak,
                 mod_path: path.clone(),
-                mount_point: "../../../".to_string(),
+                mount_point: game_profile::active().mount_point.clone(),
                 path_hash_seed: "00000000".to_string(),
                 total_files: len,
                 iostore: is_iostore,  // Mark as IoStore package
@@ -492,10 +493,17 @@ fn map_to_mods_internal(paths: &[PathBuf]) -> Vec<InstallableMod> {
 }
 
 pub fn map_paths_to_mods(paths: &[PathBuf]) -> Vec<InstallableMod> {
-    let installable_mods = map_to_mods_internal(paths);
+    let installable_mods = map_to_mods_internal(paths, &CancellationToken::new());
     installable_mods
 }
 
+/// `map_paths_to_mods`, giving up on the remaining paths (and any archive being extracted) once
+/// `cancel` is triggered
+#[allow(dead_code)]
+pub fn map_paths_to_mods_cancellable(paths: &[PathBuf], cancel: &CancellationToken) -> Vec<InstallableMod> {
+    map_to_mods_internal(paths, cancel)
+}
+
 // Egui-specific function - stubbed out for Tauri
 #[allow(dead_code)]
 pub fn map_dropped_file_to_mods(_dropped_files: &[PathBuf]) -> Vec<InstallableMod> {
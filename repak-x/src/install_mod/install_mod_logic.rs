@@ -1,14 +1,21 @@
+pub mod archive_reader;
 pub mod archives;
 pub mod iotoc;
 pub mod pak_files;
+pub mod plan;
+pub mod progress;
+pub mod transaction;
 
 use crate::install_mod::InstallableMod;
 use iotoc::convert_to_iostore_directory;
 use log::{error, info, warn};
 use pak_files::create_repak_from_pak;
+use progress::{check_cancelled, error_chain, AppliedFix, InstallEvent, InstallOutcome, InstallStage, InstallStatus, CANCELLED};
+use transaction::{cleanup_stale_staging, InstallTransaction};
 use std::path::{Path, PathBuf};
 use std::fs;
-use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::time::Instant;
+use uasset_toolkit::CancellationToken;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use dirs;
@@ -16,7 +23,7 @@ use serde_json;
 use regex_lite::Regex;
 
 /// Recursively copy a directory and all its contents to a destination
-fn copy_dir_recursive(src: &Path, dst: &Path) -> std::io::Result<()> {
+fn copy_dir_recursive(src: &Path, dst: &Path, cancel: &CancellationToken) -> std::io::Result<()> {
     if !dst.exists() {
         fs::create_dir_all(dst)?;
     }
@@ -28,8 +35,9 @@ fn copy_dir_recursive(src: &Path, dst: &Path) -> std::io::Result<()> {
         let dst_path = dst.join(entry.file_name());
         
         if file_type.is_dir() {
-            copy_dir_recursive(&src_path, &dst_path)?;
+            copy_dir_recursive(&src_path, &dst_path, cancel)?;
         } else {
+            check_cancelled(cancel)?;
             fs::copy(&src_path, &dst_path)?;
         }
     }
@@ -100,137 +108,504 @@ pub fn record_installed_tags(base_name: &str, tags: &Vec<String>) {
     let _ = fs::write(&path, serde_json::to_string_pretty(&map).unwrap());
 }
 
+/// Final base names for a batch: each enabled mod of a type gets one more 9 than the previous one
+/// so mods installed together keep their relative load order
+pub fn install_base_names(mods: &[InstallableMod]) -> Vec<String> {
+    let mut type_tracker: HashMap<String, usize> = HashMap::new();
+    mods.iter()
+        .map(|installable_mod| {
+            let min_nines = if installable_mod.enabled {
+                let count = type_tracker.entry(installable_mod.mod_type.clone()).or_insert(0);
+                let n = 7 + *count;
+                *count += 1;
+                n
+            } else {
+                7
+            };
+            normalize_mod_base_name(&installable_mod.mod_name, min_nines)
+        })
+        .collect()
+}
+
+/// Output folder for a mod: the mods folder, or the subfolder it was asked to go into
+pub fn install_output_directory(installable_mod: &InstallableMod, mod_directory: &Path) -> PathBuf {
+    if installable_mod.install_subfolder.is_empty() {
+        mod_directory.to_path_buf()
+    } else {
+        mod_directory.join(&installable_mod.install_subfolder)
+    }
+}
+
+/// Install `mods` into `mod_directory`, reporting each stage through `on_event`.
+/// Returns one outcome per mod, in order; disabled mods are skipped. Cancelling `cancel` stops the
+/// current mod at its next step (including a running UAssetTool request), discards its staged
+/// output and skips the rest.
 pub fn install_mods_in_viewport(
     mods: &mut [InstallableMod],
     mod_directory: &Path,
-    installed_mods_ptr: &AtomicI32,
-    stop_thread: &AtomicBool,
-) {
-    let mut type_tracker: HashMap<String, usize> = HashMap::new();
+    on_event: &dyn Fn(InstallEvent),
+    cancel: &CancellationToken,
+) -> Vec<InstallOutcome> {
+    cleanup_stale_staging(mod_directory);
+
+    // Ensure naming suffix consistency up-front for all flows
+    let names = install_base_names(mods);
+    for (installable_mod, name) in mods.iter_mut().zip(names) {
+        installable_mod.mod_name = name;
+    }
+
+    let total = mods.len();
+    on_event(InstallEvent::Started { total });
+    let mut outcomes = Vec::with_capacity(total);
 
-    for installable_mod in mods.iter_mut() {
-        let min_nines = if installable_mod.enabled {
-             let count = type_tracker.entry(installable_mod.mod_type.clone()).or_insert(0);
-             let n = 7 + *count;
-             *count += 1;
-             n
+    for (index, installable_mod) in mods.iter_mut().enumerate() {
+        let outcome = if !installable_mod.enabled {
+            InstallOutcome::skipped(&installable_mod.mod_name, installable_mod.mod_path.clone(), "Mod is disabled")
+        } else if cancel.is_cancelled() {
+            InstallOutcome::skipped(&installable_mod.mod_name, installable_mod.mod_path.clone(), CANCELLED)
         } else {
-             7
+            let stage = |stage: InstallStage| {
+                on_event(InstallEvent::Stage {
+                    index,
+                    total,
+                    mod_name: installable_mod.mod_name.clone(),
+                    stage,
+                })
+            };
+            install_one(installable_mod, mod_directory, &stage, cancel)
         };
 
-        // Ensure naming suffix consistency up-front for all flows
-        installable_mod.mod_name = normalize_mod_base_name(&installable_mod.mod_name, min_nines);
-        
-        if !installable_mod.enabled {
-            continue;
-        }
-        
-        if stop_thread.load(Ordering::SeqCst) {
-            warn!("Stopping thread");
-            break;
+        match outcome.status {
+            InstallStatus::Installed => info!("Installed mod: {}", outcome.mod_name),
+            InstallStatus::Skipped => {}
+            InstallStatus::Failed => error!(
+                "Failed to install {}, nothing was changed: {}",
+                outcome.mod_name,
+                outcome.error_message().unwrap_or_default()
+            ),
         }
+        on_event(InstallEvent::Finished { index, total, outcome: Box::new(outcome.clone()) });
+        outcomes.push(outcome);
+    }
 
-        // Determine the actual output directory (base + subfolder if specified)
-        let output_directory = if installable_mod.install_subfolder.is_empty() {
-            mod_directory.to_path_buf()
-        } else {
-            let subfolder_path = mod_directory.join(&installable_mod.install_subfolder);
-            // Create the subfolder if it doesn't exist
-            if !subfolder_path.exists() {
-                if let Err(e) = fs::create_dir_all(&subfolder_path) {
-                    error!("Failed to create subfolder '{}': {}", installable_mod.install_subfolder, e);
-                    continue;
-                }
-                info!("Created install subfolder: {}", subfolder_path.display());
-            }
-            subfolder_path
-        };
+    let count = |status: InstallStatus| outcomes.iter().filter(|o| o.status == status).count();
+    on_event(InstallEvent::Done {
+        installed: count(InstallStatus::Installed),
+        skipped: count(InstallStatus::Skipped),
+        failed: count(InstallStatus::Failed),
+    });
+    outcomes
+}
 
-        if installable_mod.iostore {
-            // copy the iostore files
-            let pak_path = installable_mod.mod_path.with_extension("pak");
-            let utoc_path = installable_mod.mod_path.with_extension("utoc");
-            let ucas_path = installable_mod.mod_path.with_extension("ucas");
-
-            // Ensure output names follow suffix rule
-            let base = normalize_mod_base_name(&installable_mod.mod_name, 7);
-            let dests = vec![
-                (pak_path, format!("{}.pak", base)),
-                (utoc_path, format!("{}.utoc", base)),
-                (ucas_path, format!("{}.ucas", base)),
-            ];
+/// Error chain with `context` in front of the error's own messages
+fn with_context(context: impl Into<String>, error: &dyn std::error::Error) -> Vec<String> {
+    let mut chain = vec![context.into()];
+    chain.extend(error_chain(error));
+    chain
+}
 
-            for (src, dest_name) in dests {
-                if let Err(e) = std::fs::copy(&src, output_directory.join(&dest_name)) {
-                    error!("Unable to copy file {:?}: {:?}", src, e);
-                }
-            }
+fn install_one(
+    installable_mod: &InstallableMod,
+    mod_directory: &Path,
+    stage: &dyn Fn(InstallStage),
+    cancel: &CancellationToken,
+) -> InstallOutcome {
+    let started = Instant::now();
+    let mut outcome = InstallOutcome {
+        mod_name: installable_mod.mod_name.clone(),
+        source: installable_mod.mod_path.clone(),
+        status: InstallStatus::Failed,
+        installed_files: Vec::new(),
+        skipped_reason: None,
+        error_chain: Vec::new(),
+        duration_ms: 0,
+        fixes_applied: Vec::new(),
+    };
+
+    match stage_and_commit(installable_mod, mod_directory, stage, cancel) {
+        Ok((installed_files, fixes)) => {
             // Record tags for pickup by main app
-            record_installed_tags(&base, &installable_mod.custom_tags);
-            continue;
+            record_installed_tags(&normalize_mod_base_name(&installable_mod.mod_name, 7), &installable_mod.custom_tags);
+            outcome.status = InstallStatus::Installed;
+            outcome.installed_files = installed_files;
+            outcome.fixes_applied = fixes;
         }
+        // The staging folder is gone by now, so a cancelled mod leaves nothing behind
+        Err(_) if cancel.is_cancelled() => {
+            warn!("Cancelled install of {}", installable_mod.mod_name);
+            outcome.status = InstallStatus::Skipped;
+            outcome.skipped_reason = Some(CANCELLED.to_string());
+        }
+        Err(chain) => outcome.error_chain = chain,
+    }
+    outcome.duration_ms = started.elapsed().as_millis() as u64;
+    outcome
+}
 
-        if installable_mod.repak {
-            if let Err(e) = create_repak_from_pak(
-                installable_mod,
-                output_directory.clone(),
-                installed_mods_ptr,
-            ) {
-                error!("Failed to create repak from pak: {}", e);
-            } else {
-                let base = normalize_mod_base_name(&installable_mod.mod_name, 7);
-                record_installed_tags(&base, &installable_mod.custom_tags);
-            }
+fn stage_and_commit(
+    installable_mod: &InstallableMod,
+    mod_directory: &Path,
+    stage: &dyn Fn(InstallStage),
+    cancel: &CancellationToken,
+) -> Result<(Vec<PathBuf>, Vec<AppliedFix>), Vec<String>> {
+    let check_cancelled = || check_cancelled(cancel).map_err(|e| vec![e.to_string()]);
+    // Determine the actual output directory (base + subfolder if specified)
+    let output_directory = install_output_directory(installable_mod, mod_directory);
+    // Create the subfolder if it doesn't exist
+    if !output_directory.exists() {
+        fs::create_dir_all(&output_directory).map_err(|e| {
+            with_context(format!("Failed to create subfolder '{}'", installable_mod.install_subfolder), &e)
+        })?;
+        info!("Created install subfolder: {}", output_directory.display());
+    }
+
+    // Everything is written to a staging folder first and only moved in once it checks out
+    let transaction = InstallTransaction::begin(&output_directory).map_err(|e| {
+        with_context(format!("Failed to create staging folder in {}", output_directory.display()), &e)
+    })?;
+    let staging_dir = transaction.staging_dir().to_path_buf();
+    // Ensure output names follow suffix rule
+    let base = normalize_mod_base_name(&installable_mod.mod_name, 7);
+
+    let fixes = if installable_mod.iostore {
+        // copy the iostore files
+        stage(InstallStage::Copy);
+        for ext in ["pak", "utoc", "ucas"] {
+            check_cancelled()?;
+            let src = installable_mod.mod_path.with_extension(ext);
+            fs::copy(&src, staging_dir.join(format!("{}.{}", base, ext)))
+                .map_err(|e| with_context(format!("Unable to copy file {:?}", src), &e))?;
         }
+        Vec::new()
+    } else if installable_mod.is_dir {
+        // Copy source directory to temp dir to avoid modifying original files
+        stage(InstallStage::Copy);
+        let temp_dir = tempfile::tempdir().map_err(|e| with_context("Failed to create temp directory", &e))?;
+        let temp_path = temp_dir.path().to_path_buf();
+        copy_dir_recursive(&installable_mod.mod_path, &temp_path, cancel)
+            .map_err(|e| with_context("Failed to copy mod files to temp directory", &e))?;
+        info!("Copied mod files to temp directory for processing");
+        // temp_dir is automatically cleaned up when it goes out of scope
+        convert_to_iostore_directory(installable_mod, staging_dir.clone(), temp_path, stage, cancel)
+            .map_err(|e| with_context("Failed to convert mod", &e))?
+    } else if installable_mod.repak {
+        create_repak_from_pak(installable_mod, staging_dir.clone(), stage, cancel)
+            .map_err(|e| with_context("Failed to create repak from pak", &e))?
+    } else {
+        // just move files to the correct location
+        info!(
+            "Copying mod instead of repacking: {}",
+            installable_mod.mod_name
+        );
+        stage(InstallStage::Copy);
+        fs::copy(&installable_mod.mod_path, staging_dir.join(format!("{}.pak", base)))
+            .map_err(|e| with_context(format!("Unable to copy file {:?}", installable_mod.mod_path), &e))?;
+        Vec::new()
+    };
 
-        // This shit shouldnt even be possible why do I still have this in the codebase???
-        if !installable_mod.repak && !installable_mod.is_dir {
-            // just move files to the correct location
-            info!(
-                "Copying mod instead of repacking: {}",
-                installable_mod.mod_name
-            );
-            let base = normalize_mod_base_name(&installable_mod.mod_name, 7);
-            std::fs::copy(&installable_mod.mod_path, output_directory.join(format!("{}.pak", &base)))
-            .unwrap();
-            record_installed_tags(&base, &installable_mod.custom_tags);
-            installed_mods_ptr.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-            continue;
+    // Last point where a cancel can still back out cleanly
+    check_cancelled()?;
+    stage(InstallStage::Commit);
+    let installed = transaction.commit(&base).map_err(|e| vec![e])?;
+    Ok((installed, fixes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uasset_detection::{detect_static_mesh_files, detect_texture_files};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use uasset_toolkit::mock::MockToolkit;
+    use uasset_toolkit::{RequestOptions, UAssetRequest};
+
+    /// The toolkit backend is process-global, so tests that swap it must not overlap
+    static TOOLKIT_LOCK: Mutex<()> = Mutex::new(());
+
+    fn with_mock<R>(mock: MockToolkit, test: impl FnOnce(&MockToolkit) -> R) -> R {
+        let _guard = TOOLKIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mock = Arc::new(mock);
+        uasset_toolkit::set_global_toolkit(mock.clone());
+        test(&mock)
+    }
+
+    /// Stands in for `create_mod_iostore` by writing placeholder container files next to `output_path`
+    fn write_iostore(request: &UAssetRequest) -> Result<serde_json::Value, String> {
+        let UAssetRequest::CreateModIoStore { output_path, input_dir, .. } = request else {
+            return Err("unexpected request".to_string());
+        };
+        let mut files = vec![];
+        crate::utils::collect_files(&mut files, Path::new(input_dir)).map_err(|e| e.to_string())?;
+        for ext in ["utoc", "ucas"] {
+            fs::write(format!("{output_path}.{ext}"), ext.as_bytes()).map_err(|e| e.to_string())?;
         }
+        let pak = fs::File::create(format!("{output_path}.pak")).map_err(|e| e.to_string())?;
+        let mut writer = repak::PakBuilder::new().writer(pak, repak::Version::V11, "../../../".to_string(), None);
+        writer.write_file("Marvel/Content/Marvel/T_Hero.uasset", false, b"stub").map_err(|e| e.to_string())?;
+        writer.write_index().map_err(|e| e.to_string())?;
+        Ok(json!({
+            "utoc_path": format!("{output_path}.utoc"),
+            "ucas_path": format!("{output_path}.ucas"),
+            "pak_path": format!("{output_path}.pak"),
+            "converted_count": files.iter().filter(|f| f.extension().is_some_and(|e| e == "uasset")).count(),
+            "file_count": files.len(),
+        }))
+    }
 
-        if installable_mod.is_dir {
-            // Copy source directory to temp dir to avoid modifying original files
-            let temp_dir = match tempfile::tempdir() {
-                Ok(dir) => dir,
-                Err(e) => {
-                    error!("Failed to create temp directory: {}", e);
-                    continue;
-                }
-            };
-            let temp_path = temp_dir.path().to_path_buf();
-            
-            // Copy all files from source to temp
-            let source_path = PathBuf::from(&installable_mod.mod_path);
-            if let Err(e) = copy_dir_recursive(&source_path, &temp_path) {
-                error!("Failed to copy mod files to temp directory: {}", e);
-                continue;
-            }
-            info!("Copied mod files to temp directory for processing");
-            
-            let res = convert_to_iostore_directory(
-                installable_mod,
-                output_directory.clone(),
-                temp_path,
-                installed_mods_ptr,
+    /// Loose mod folder with one texture (uasset/uexp/ubulk) and one mesh (uasset/uexp)
+    fn loose_mod(root: &Path) -> PathBuf {
+        let content = root.join("Hero").join("Marvel").join("Content").join("Marvel");
+        fs::create_dir_all(&content).unwrap();
+        for file in ["T_Hero.uasset", "T_Hero.uexp", "T_Hero.ubulk", "SM_Hero.uasset", "SM_Hero.uexp"] {
+            fs::write(content.join(file), file.as_bytes()).unwrap();
+        }
+        root.join("Hero")
+    }
+
+    fn installable(mod_path: PathBuf, mod_type: &str) -> InstallableMod {
+        InstallableMod {
+            mod_name: "Hero".to_string(),
+            mod_type: mod_type.to_string(),
+            is_dir: true,
+            mod_path,
+            mount_point: "../../../".to_string(),
+            path_hash_seed: "0".to_string(),
+            compression: repak::Compression::Zlib,
+            ..Default::default()
+        }
+    }
+
+    fn install(mods: &mut [InstallableMod], mod_dir: &Path) -> Vec<InstallOutcome> {
+        install_mods_in_viewport(mods, mod_dir, &|_| {}, &CancellationToken::new())
+    }
+
+    fn installed_files(mod_dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(mod_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn loose_mod_is_converted_to_iostore() {
+        let source = tempfile::tempdir().unwrap();
+        let mods_dir = tempfile::tempdir().unwrap();
+        let mock = MockToolkit::new()
+            .respond("batch_strip_mipmaps_native", json!({
+                "success_count": 1,
+                "results": [{ "path": "T_Hero.uasset", "success": true }],
+            }))
+            .on("create_mod_iostore", write_iostore)
+            .respond("list_iostore_files", json!({ "package_count": 2, "files": ["T_Hero.uasset", "SM_Hero.uasset"] }));
+
+        with_mock(mock, |mock| {
+            let mut mods = [InstallableMod {
+                fix_textures: true,
+                ..installable(loose_mod(source.path()), "Character")
+            }];
+            let [outcome] = install(&mut mods, mods_dir.path()).try_into().unwrap();
+            assert_eq!(outcome.status, InstallStatus::Installed);
+            assert_eq!(outcome.fixes_applied, vec![AppliedFix::Textures { textures: 1 }]);
+            assert_eq!(outcome.installed_files.len(), 3);
+
+            let base = normalize_mod_base_name("Hero", 7);
+            assert_eq!(mods[0].mod_name, base);
+            assert_eq!(
+                installed_files(mods_dir.path()),
+                vec![format!("{base}.pak"), format!("{base}.ucas"), format!("{base}.utoc")]
             );
-            // temp_dir is automatically cleaned up when it goes out of scope
-            if let Err(e) = res {
-                error!("Failed to create repak from pak: {}", e);
-            } else {
-                info!("Installed mod: {}", installable_mod.mod_name);
+
+            // Only the texture with a .ubulk is sent for mipmap stripping
+            let strips = mock.requests_for("batch_strip_mipmaps_native");
+            let [UAssetRequest::BatchStripMipmapsNative { file_paths, .. }] = strips.as_slice() else {
+                panic!("expected one strip request, got {strips:?}");
+            };
+            assert_eq!(file_paths.len(), 1);
+            assert!(file_paths[0].ends_with("T_Hero.uasset"));
+
+            // The conversion runs on a copy, never on the user's folder
+            let creates = mock.requests_for("create_mod_iostore");
+            let [UAssetRequest::CreateModIoStore { output_path, input_dir, mount_point, compress, .. }] = creates.as_slice() else {
+                panic!("expected one create_mod_iostore request, got {creates:?}");
+            };
+            // Written to a staging folder inside the mods folder, then moved in
+            let output_path = Path::new(output_path);
+            assert_eq!(output_path.parent().and_then(Path::parent), Some(mods_dir.path()));
+            assert_eq!(output_path.file_name().unwrap().to_string_lossy(), base);
+            assert!(!Path::new(input_dir).starts_with(source.path()));
+            assert_eq!(mount_point.as_deref(), Some("../../../"));
+            assert_eq!(*compress, Some(true));
+
+            // The staged .utoc is checked before it is moved in
+            assert_eq!(mock.requests_for("list_iostore_files").len(), 1);
+        });
+    }
+
+    #[test]
+    fn unreadable_iostore_output_is_rolled_back() {
+        let source = tempfile::tempdir().unwrap();
+        let mods_dir = tempfile::tempdir().unwrap();
+        let mock = MockToolkit::new()
+            .on("create_mod_iostore", write_iostore)
+            .fail("list_iostore_files", "Invalid TOC header");
+
+        with_mock(mock, |_| {
+            let mut mods = [installable(loose_mod(source.path()), "Character")];
+            let [outcome] = install(&mut mods, mods_dir.path()).try_into().unwrap();
+            assert_eq!(outcome.status, InstallStatus::Failed);
+            assert!(outcome.installed_files.is_empty());
+            assert!(installed_files(mods_dir.path()).is_empty());
+        });
+    }
+
+    #[test]
+    fn failed_iostore_conversion_installs_nothing() {
+        let source = tempfile::tempdir().unwrap();
+        let mods_dir = tempfile::tempdir().unwrap();
+        let mock = MockToolkit::new().fail("create_mod_iostore", "Unhandled exception: usmap mismatch");
+
+        with_mock(mock, |mock| {
+            let mut mods = [installable(loose_mod(source.path()), "Character")];
+            let [outcome] = install(&mut mods, mods_dir.path()).try_into().unwrap();
+            assert_eq!(outcome.status, InstallStatus::Failed);
+            // Our context first, the toolkit's message last
+            assert_eq!(outcome.error_chain.first().map(String::as_str), Some("Failed to convert mod"));
+            assert!(outcome.error_chain.last().unwrap().contains("usmap mismatch"));
+            assert!(installed_files(mods_dir.path()).is_empty());
+            assert!(mock.requests_for("batch_strip_mipmaps_native").is_empty());
+            assert_eq!(mock.requests_for("create_mod_iostore").len(), 1);
+        });
+    }
+
+    #[test]
+    fn cancelling_mid_conversion_discards_the_mod_and_skips_the_rest() {
+        let source = tempfile::tempdir().unwrap();
+        let mods_dir = tempfile::tempdir().unwrap();
+        let cancel = CancellationToken::new();
+        let cancel_in_tool = cancel.clone();
+        // The user hits cancel while UAssetTool is writing the container
+        let mock = MockToolkit::new().on("create_mod_iostore", move |request| {
+            cancel_in_tool.cancel();
+            write_iostore(request)
+        });
+
+        with_mock(mock, |mock| {
+            let mut mods = [
+                installable(loose_mod(source.path()), "Character"),
+                installable(loose_mod(source.path()), "Character"),
+            ];
+            let outcomes = install_mods_in_viewport(&mut mods, mods_dir.path(), &|_| {}, &cancel);
+
+            assert_eq!(outcomes.len(), 2);
+            for outcome in &outcomes {
+                assert_eq!(outcome.status, InstallStatus::Skipped);
+                assert_eq!(outcome.skipped_reason.as_deref(), Some(CANCELLED));
             }
+            assert_eq!(mock.requests_for("create_mod_iostore").len(), 1);
+            // Neither the staged container nor the staging folder is left behind
+            assert!(installed_files(mods_dir.path()).is_empty());
+        });
+    }
+
+    #[test]
+    fn archive_extraction_lives_until_the_mods_are_dropped() {
+        let source = tempfile::tempdir().unwrap();
+        let mods_dir = tempfile::tempdir().unwrap();
+        let folder = loose_mod(source.path());
+        let archive = source.path().join("Hero.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+        for entry in walkdir::WalkDir::new(&folder).into_iter().filter_map(|e| e.ok()).filter(|e| e.file_type().is_file()) {
+            let name = entry.path().strip_prefix(source.path()).unwrap().to_string_lossy().replace('\\', "/");
+            writer.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+            std::io::Write::write_all(&mut writer, &fs::read(entry.path()).unwrap()).unwrap();
         }
+        writer.finish().unwrap();
+        let mock = MockToolkit::new()
+            .on("create_mod_iostore", write_iostore)
+            .respond("list_iostore_files", json!({ "package_count": 2, "files": ["T_Hero.uasset", "SM_Hero.uasset"] }));
+
+        with_mock(mock, |_| {
+            let mut mods = crate::install_mod::map_paths_to_mods(&[archive]);
+            let [hero] = mods.as_slice() else {
+                panic!("expected one mod from the archive, got {mods:?}");
+            };
+            let extracted = hero.extracted_dir.as_ref().unwrap().path().to_path_buf();
+            assert!(hero.mod_path.starts_with(&extracted));
+
+            let [outcome] = install(&mut mods, mods_dir.path()).try_into().unwrap();
+            assert_eq!(outcome.status, InstallStatus::Installed);
+            assert!(extracted.exists());
+
+            drop(mods);
+            assert!(!extracted.exists());
+        });
+    }
+
+    #[test]
+    fn audio_mods_are_packed_without_the_toolkit() {
+        let source = tempfile::tempdir().unwrap();
+        let mods_dir = tempfile::tempdir().unwrap();
+
+        with_mock(MockToolkit::new(), |mock| {
+            let mut mods = [installable(loose_mod(source.path()), "Audio")];
+            let [outcome] = install(&mut mods, mods_dir.path()).try_into().unwrap();
+            assert_eq!(outcome.status, InstallStatus::Installed);
+            assert!(outcome.fixes_applied.is_empty());
+
+            let base = normalize_mod_base_name("Hero", 7);
+            assert_eq!(installed_files(mods_dir.path()), vec![format!("{base}.pak")]);
+            assert!(mock.requests().is_empty());
+
+            let pak_path = mods_dir.path().join(format!("{base}.pak"));
+            let mut reader = std::io::BufReader::new(fs::File::open(pak_path).unwrap());
+            let pak = crate::game_profile::active().open_pak(&mut reader).unwrap();
+            let mut files = pak.files();
+            files.sort();
+            assert_eq!(
+                files,
+                [
+                    "Marvel/Content/Marvel/SM_Hero.uasset",
+                    "Marvel/Content/Marvel/SM_Hero.uexp",
+                    "Marvel/Content/Marvel/T_Hero.uasset",
+                    "Marvel/Content/Marvel/T_Hero.ubulk",
+                    "Marvel/Content/Marvel/T_Hero.uexp",
+                    // The file list repak_dir writes into every pak
+                    "chunknames",
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn detection_uses_the_toolkit_backend() {
+        let files: Vec<String> = ["Content/T_Hero.uasset", "Content/T_Hero.ubulk", "Content/SM_Hero.uasset"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let mock = MockToolkit::new()
+            .respond("batch_detect_texture", json!(true))
+            .fail("batch_detect_static_mesh", "no usmap");
+
+        with_mock(mock, |mock| {
+            let options = RequestOptions::default();
+            assert!(detect_texture_files(&files, &options));
+            // Tool errors are reported as "not detected"
+            assert!(!detect_static_mesh_files(&files, &options));
+
+            let textures = mock.requests_for("batch_detect_texture");
+            let [UAssetRequest::BatchDetectTexture { file_paths }] = textures.as_slice() else {
+                panic!("expected one texture detection request, got {textures:?}");
+            };
+            assert_eq!(file_paths, &vec!["Content/T_Hero.uasset".to_string()]);
+
+            // A cancelled batch stops asking the tool
+            let cancel = CancellationToken::new();
+            cancel.cancel();
+            let cancelled = RequestOptions { timeout: None, cancel: Some(cancel) };
+            assert!(!detect_texture_files(&files, &cancelled));
+            assert_eq!(mock.requests_for("batch_detect_texture").len(), 1);
+        });
     }
-    // set i32 to -255 magic value to indicate mod installation is done
-    AtomicI32::store(installed_mods_ptr, -255, Ordering::SeqCst);
 }
@@ -0,0 +1,482 @@
+// Random access into zip and 7z archives, so drag-and-drop detection can read a mod's .pak
+// index or .utoc straight out of the download instead of unpacking all of it first
+use crate::game_profile;
+use crate::install_mod::install_mod_logic::archives::{
+    archive_kind, is_secondary_volume, split_volumes, ArchiveError, ArchiveKind, ExtractGuard, VolumeReader,
+};
+use crate::utoc_utils::read_utoc;
+use repak::PakReader;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use zip::{CompressionMethod, ZipArchive};
+
+/// Largest entry `read` will load into memory; .utoc files and single assets are far below this
+const MAX_READ: u64 = 256 * 1024 * 1024;
+
+/// How much of the end of a compressed entry `open_seekable` keeps; enough for the index of a
+/// pak with tens of thousands of files
+const MAX_TAIL: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub size: u64,
+}
+
+enum Inner {
+    Zip(ZipArchive<VolumeReader>),
+    SevenZip(Box<sevenz_rust2::ArchiveReader<VolumeReader>>),
+}
+
+pub struct ArchiveReader {
+    volumes: Vec<PathBuf>,
+    inner: Inner,
+    entries: Vec<ArchiveEntry>,
+}
+
+impl ArchiveReader {
+    /// Open a zip or 7z archive (split or not). Rar and tarballs have no usable index, so they
+    /// return NotAnArchive and still go through extract_archive.
+    pub fn open(path: &Path) -> Result<Self, ArchiveError> {
+        let volumes = split_volumes(path);
+        let not_indexed = || ArchiveError::NotAnArchive(path.to_path_buf());
+        match archive_kind(path).ok_or_else(not_indexed)? {
+            ArchiveKind::Zip => {
+                let mut archive = ZipArchive::new(VolumeReader::open(&volumes)?)?;
+                let mut entries = Vec::new();
+                for i in 0..archive.len() {
+                    if let Some(file) = archive.by_index_raw(i).ok().filter(|f| f.is_file()) {
+                        entries.push(ArchiveEntry { name: file.name().to_string(), size: file.size() });
+                    }
+                }
+                Ok(Self { volumes, inner: Inner::Zip(archive), entries })
+            }
+            ArchiveKind::SevenZip => {
+                let reader = sevenz_rust2::ArchiveReader::new(VolumeReader::open(&volumes)?, sevenz_rust2::Password::empty())
+                    .map_err(|e| ArchiveError::Format(e.to_string()))?;
+                let entries = reader
+                    .archive()
+                    .files
+                    .iter()
+                    .filter(|f| !f.is_directory())
+                    .map(|f| ArchiveEntry { name: f.name().to_string(), size: f.size() })
+                    .collect();
+                Ok(Self { volumes, inner: Inner::SevenZip(Box::new(reader)), entries })
+            }
+            _ => Err(not_indexed()),
+        }
+    }
+
+    /// Files in the archive (directories are left out)
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    fn entry(&self, name: &str) -> Result<&ArchiveEntry, ArchiveError> {
+        self.entries
+            .iter()
+            .find(|e| e.name == name)
+            .ok_or_else(|| ArchiveError::Format(format!("'{}' is not in the archive", name)))
+    }
+
+    /// Read one small file fully into memory
+    pub fn read(&mut self, name: &str) -> Result<Vec<u8>, ArchiveError> {
+        let size = self.entry(name)?.size;
+        if size > MAX_READ {
+            return Err(ArchiveError::TooLarge { limit: MAX_READ });
+        }
+        match &mut self.inner {
+            Inner::Zip(archive) => {
+                let mut data = Vec::with_capacity(size as usize);
+                archive.by_name(name)?.take(MAX_READ).read_to_end(&mut data)?;
+                Ok(data)
+            }
+            Inner::SevenZip(reader) => reader.read_file(name).map_err(|e| ArchiveError::Format(e.to_string())),
+        }
+    }
+
+    /// Seekable view of one file. Stored zip entries are read in place, so opening a pak's
+    /// index only touches its last few KB. Anything compressed can only be read front to back, so
+    /// it is decompressed once in memory, keeping just its last MAX_TAIL bytes where a pak's index
+    /// lives; nothing is written to disk.
+    pub fn open_seekable(&mut self, name: &str) -> Result<EntryReader, ArchiveError> {
+        if let Inner::Zip(archive) = &mut self.inner {
+            let file = archive.by_name(name)?;
+            if file.compression() == CompressionMethod::Stored && !file.encrypted() {
+                let (start, len) = (file.data_start(), file.size());
+                drop(file);
+                return Ok(EntryReader::Window { source: VolumeReader::open(&self.volumes)?, start, len, pos: 0 });
+            }
+        }
+        self.capture(name, &[], MAX_TAIL)
+    }
+
+    /// Decompress `name` front to back, keeping the bytes in each of `ranges` (start, end) and its
+    /// last `tail` bytes. What is decompressed counts against the ExtractGuard limits, so a bomb
+    /// is stopped as it would be when extracting.
+    pub fn capture(&mut self, name: &str, ranges: &[(u64, u64)], tail: u64) -> Result<EntryReader, ArchiveError> {
+        let mut guard = ExtractGuard::default();
+        guard.start_archive(self.volumes.iter().filter_map(|v| v.metadata().ok()).map(|m| m.len()).sum());
+        let mut captured = Captured::new(ranges, tail);
+        match &mut self.inner {
+            Inner::Zip(archive) => {
+                let mut file = archive.by_name(name)?;
+                let compressed = file.compressed_size();
+                captured.read_from(name, &mut file, Some(compressed), &mut guard)?;
+            }
+            Inner::SevenZip(reader) => {
+                // sevenz_rust2 only takes its own error type back from the callback, so ours is stashed here
+                let mut failure: Option<ArchiveError> = None;
+                let mut found = false;
+                let result = reader.for_each_entries(|entry, data| {
+                    if entry.name() != name {
+                        return Ok(true);
+                    }
+                    found = true;
+                    match captured.read_from(name, data, None, &mut guard) {
+                        Ok(()) => Ok(false),
+                        Err(e) => {
+                            failure = Some(e);
+                            Err(sevenz_rust2::Error::Other("read stopped".into()))
+                        }
+                    }
+                });
+                match (failure, result) {
+                    (Some(e), _) => return Err(e),
+                    (None, Err(e)) => return Err(ArchiveError::Format(e.to_string())),
+                    (None, Ok(())) => {}
+                }
+                if !found {
+                    return Err(ArchiveError::Format(format!("'{}' is not in the archive", name)));
+                }
+            }
+        }
+        Ok(EntryReader::Captured(captured))
+    }
+}
+
+/// The parts of a compressed entry kept while reading it front to back
+pub struct Captured {
+    /// (offset in the entry, bytes); the last part is the entry's tail
+    parts: Vec<(u64, Vec<u8>)>,
+    ranges: Vec<(u64, u64)>,
+    tail: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl Captured {
+    fn new(ranges: &[(u64, u64)], tail: u64) -> Self {
+        let parts = ranges.iter().map(|&(start, _)| (start, Vec::new())).chain([(0, Vec::new())]).collect();
+        Self { parts, ranges: ranges.to_vec(), tail, len: 0, pos: 0 }
+    }
+
+    fn read_from(&mut self, name: &str, data: &mut dyn Read, compressed: Option<u64>, guard: &mut ExtractGuard) -> Result<(), ArchiveError> {
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = data.read(&mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            let (chunk, start) = (&buf[..n], self.len);
+            self.len += n as u64;
+            guard.add(name, self.len, n as u64, compressed)?;
+
+            for (&(from, to), (_, part)) in self.ranges.iter().zip(&mut self.parts) {
+                let (lo, hi) = (from.max(start), to.min(self.len));
+                if lo < hi {
+                    part.extend_from_slice(&chunk[(lo - start) as usize..(hi - start) as usize]);
+                }
+            }
+            let (tail_start, tail) = self.parts.last_mut().expect("tail part");
+            tail.extend_from_slice(chunk);
+            // Trimmed in batches so the buffer isn't shifted on every chunk
+            let excess = tail.len() as u64 - self.tail.min(tail.len() as u64);
+            if excess > self.tail.max(1024 * 1024) {
+                tail.drain(..excess as usize);
+                *tail_start += excess;
+            }
+        }
+    }
+}
+
+/// A file inside an archive, readable and seekable like a regular file. Reading a part of a
+/// compressed entry that wasn't kept fails.
+pub enum EntryReader {
+    Window { source: VolumeReader, start: u64, len: u64, pos: u64 },
+    Captured(Captured),
+}
+
+impl Read for EntryReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            EntryReader::Window { source, start, len, pos } => {
+                let remaining = len.saturating_sub(*pos);
+                if remaining == 0 {
+                    return Ok(0);
+                }
+                let max = buf.len().min(remaining as usize);
+                source.seek(SeekFrom::Start(*start + *pos))?;
+                let n = source.read(&mut buf[..max])?;
+                *pos += n as u64;
+                Ok(n)
+            }
+            EntryReader::Captured(captured) => {
+                if captured.pos >= captured.len {
+                    return Ok(0);
+                }
+                let pos = captured.pos;
+                let part = captured
+                    .parts
+                    .iter()
+                    .find(|(start, data)| *start <= pos && pos < *start + data.len() as u64);
+                let Some((start, data)) = part else {
+                    return Err(io::Error::other("this part of the compressed archive entry wasn't kept"));
+                };
+                let available = &data[(pos - start) as usize..];
+                let n = buf.len().min(available.len());
+                buf[..n].copy_from_slice(&available[..n]);
+                captured.pos += n as u64;
+                Ok(n)
+            }
+        }
+    }
+}
+
+impl Seek for EntryReader {
+    fn seek(&mut self, from: SeekFrom) -> io::Result<u64> {
+        let (len, pos) = match self {
+            EntryReader::Window { len, pos, .. } => (*len, pos),
+            EntryReader::Captured(captured) => (captured.len, &mut captured.pos),
+        };
+        let new_pos = match from {
+            SeekFrom::Start(p) => p as i64,
+            SeekFrom::End(p) => len as i64 + p,
+            SeekFrom::Current(p) => *pos as i64 + p,
+        };
+        if new_pos < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before start of entry"));
+        }
+        *pos = new_pos as u64;
+        Ok(*pos)
+    }
+}
+
+fn has_ext(name: &str, ext: &str) -> bool {
+    Path::new(name).extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case(ext))
+}
+
+/// What drag-and-drop detection needs from an archive holding a single mod
+#[allow(dead_code)]
+pub struct ArchivePreview {
+    reader: ArchiveReader,
+    /// The mod's .pak, when it opens with our keys (obfuscated IoStore paks may not), and its
+    /// name in the archive
+    pak: Option<(PakReader, EntryReader, String)>,
+    pub is_iostore: bool,
+    /// No .pak, just loose content files (installed as a folder mod)
+    pub is_loose: bool,
+    /// Asset paths from the pak index or .utoc, or the loose files' paths in the archive
+    pub files: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl ArchivePreview {
+    /// Ok(None) when the archive can't be classified without extracting it: rar/tar, several
+    /// paks, archives inside the archive, or nothing recognisable
+    pub fn open(path: &Path) -> Result<Option<Self>, ArchiveError> {
+        let mut reader = match ArchiveReader::open(path) {
+            Ok(reader) => reader,
+            Err(ArchiveError::NotAnArchive(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let names: Vec<String> = reader.entries().iter().map(|e| e.name.clone()).collect();
+        if names.iter().any(|n| archive_kind(Path::new(n)).is_some() || is_secondary_volume(Path::new(n))) {
+            return Ok(None);
+        }
+
+        let paks: Vec<&String> = names.iter().filter(|n| has_ext(n, "pak")).collect();
+        let pak_name = match paks.as_slice() {
+            [] => {
+                let files: Vec<String> = names
+                    .iter()
+                    .filter(|n| ["uasset", "uexp", "ubulk", "bnk", "wem"].iter().any(|ext| has_ext(n, ext)))
+                    .cloned()
+                    .collect();
+                if files.is_empty() {
+                    return Ok(None);
+                }
+                return Ok(Some(Self { reader, pak: None, is_iostore: false, is_loose: true, files }));
+            }
+            [pak] => pak.to_string(),
+            _ => return Ok(None),
+        };
+
+        let stem = &pak_name[..pak_name.len() - ".pak".len()];
+        let sibling = |ext: &str| names.iter().find(|n| n.eq_ignore_ascii_case(&format!("{}.{}", stem, ext))).cloned();
+        let (utoc, ucas) = (sibling("utoc"), sibling("ucas"));
+        let is_iostore = utoc.is_some() && ucas.is_some();
+
+        let pak = reader
+            .open_seekable(&pak_name)
+            .ok()
+            .and_then(|mut entry| game_profile::active().open_pak(&mut entry).ok().map(|pak| (pak, entry, pak_name.clone())));
+
+        let files = match (&utoc, &pak) {
+            // UAssetTool lists IoStore containers from the .utoc alone
+            (Some(utoc), _) if is_iostore => {
+                let dir = tempfile::tempdir()?;
+                let utoc_path = dir.path().join(Path::new(utoc).file_name().unwrap_or_default());
+                std::fs::write(&utoc_path, reader.read(utoc)?)?;
+                read_utoc(&utoc_path).into_iter().map(|entry| entry.file_path).collect()
+            }
+            (_, Some((pak, ..))) => pak.files(),
+            _ => return Ok(None),
+        };
+        if files.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self { reader, pak, is_iostore, is_loose: false, files }))
+    }
+
+    /// Write the given assets to `dir` under their file names (keeping .uasset/.uexp pairs next to
+    /// each other) and return the paths of the .uasset ones
+    pub fn extract_samples(&mut self, names: &[&String], dir: &Path) -> Vec<String> {
+        // Only the tail of a compressed pak was kept, so its samples are read in one more pass
+        if let Some((pak, entry @ EntryReader::Captured(_), pak_name)) = &mut self.pak {
+            let ranges: Vec<(u64, u64)> = names
+                .iter()
+                .filter_map(|name| pak.get_file_entry(name).ok())
+                .map(|e| {
+                    let blocks = e.blocks.as_ref().map_or(0, |b| b.len() as u32);
+                    let header = repak::entry::Entry::get_serialized_size(pak.version(), e.compression_slot, blocks);
+                    (e.offset, e.offset + header + e.compressed)
+                })
+                .collect();
+            match self.reader.capture(pak_name, &ranges, MAX_TAIL) {
+                Ok(captured) => *entry = captured,
+                Err(e) => log::warn!("Could not read samples from {}: {}", pak_name, e),
+            }
+        }
+
+        let mut uassets = Vec::new();
+        for name in names {
+            let data = match &mut self.pak {
+                Some((pak, entry, _)) => pak.get(name, entry).ok(),
+                None => self.reader.read(name).ok(),
+            };
+            let Some(data) = data else { continue };
+            let dest = dir.join(Path::new(name.as_str()).file_name().unwrap_or_default());
+            if std::fs::write(&dest, data).is_ok() && has_ext(name, "uasset") {
+                uassets.push(dest.to_string_lossy().to_string());
+            }
+        }
+        uassets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+
+    fn pak_bytes(files: &[&str]) -> Vec<u8> {
+        let mut data = io::Cursor::new(Vec::new());
+        let mut writer = game_profile::active().pak_builder().writer(
+            &mut data,
+            repak::Version::V11,
+            "../../../".to_string(),
+            None,
+        );
+        for file in files {
+            writer.write_file(file, false, file.as_bytes()).unwrap();
+        }
+        writer.write_index().unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn stored_pak_is_read_in_place_and_deflated_pak_is_captured() {
+        let dir = tempfile::tempdir().unwrap();
+        let pak = pak_bytes(&["Marvel/Content/Marvel/T_Hero.uasset", "Marvel/Content/Marvel/T_Hero.uexp"]);
+
+        for method in [CompressionMethod::Stored, CompressionMethod::Deflated] {
+            let archive = dir.path().join("Hero.zip");
+            let mut writer = zip::ZipWriter::new(File::create(&archive).unwrap());
+            let options = zip::write::SimpleFileOptions::default().compression_method(method);
+            writer.start_file("Readme.txt", options).unwrap();
+            writer.write_all(b"hi").unwrap();
+            writer.start_file("Hero/Hero_P.pak", options).unwrap();
+            writer.write_all(&pak).unwrap();
+            writer.finish().unwrap();
+
+            let mut reader = ArchiveReader::open(&archive).unwrap();
+            assert_eq!(reader.entries().len(), 2);
+            assert_eq!(reader.read("Readme.txt").unwrap(), b"hi");
+            let entry = reader.open_seekable("Hero/Hero_P.pak").unwrap();
+            assert_eq!(matches!(entry, EntryReader::Window { .. }), method == CompressionMethod::Stored);
+            // Parts of a compressed entry picked out in one pass read back like the entry itself
+            let mut part = reader.capture("Hero/Hero_P.pak", &[(4, 10)], 16).unwrap();
+            let mut data = [0u8; 6];
+            part.seek(SeekFrom::Start(4)).unwrap();
+            part.read_exact(&mut data).unwrap();
+            assert_eq!(data, pak[4..10]);
+            let mut tail = Vec::new();
+            part.seek(SeekFrom::End(-16)).unwrap();
+            part.read_to_end(&mut tail).unwrap();
+            assert_eq!(tail, pak[pak.len() - 16..]);
+
+            let mut preview = ArchivePreview::open(&archive).unwrap().expect("single pak archive");
+            assert!(!preview.is_iostore && !preview.is_loose);
+            assert_eq!(preview.files.len(), 2);
+
+            let samples_dir = tempfile::tempdir().unwrap();
+            let files = preview.files.clone();
+            let names: Vec<&String> = files.iter().collect();
+            let uassets = preview.extract_samples(&names, samples_dir.path());
+            assert_eq!(uassets.len(), 1);
+            assert_eq!(std::fs::read(&uassets[0]).unwrap(), b"Marvel/Content/Marvel/T_Hero.uasset");
+        }
+    }
+
+    #[test]
+    fn compressed_entries_count_against_the_extract_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("Bomb.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        writer.start_file("Bomb_P.pak", options).unwrap();
+        writer.write_all(&vec![0u8; 32 * 1024 * 1024]).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = ArchiveReader::open(&archive).unwrap();
+        assert!(matches!(reader.open_seekable("Bomb_P.pak"), Err(ArchiveError::CompressionRatio { .. })));
+    }
+
+    #[test]
+    fn loose_files_and_ambiguous_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let write_zip = |name: &str, files: &[&str]| {
+            let path = dir.path().join(name);
+            let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+            for file in files {
+                writer.start_file(*file, zip::write::SimpleFileOptions::default()).unwrap();
+                writer.write_all(b"data").unwrap();
+            }
+            writer.finish().unwrap();
+            path
+        };
+
+        let loose = write_zip("Loose.zip", &["Mod/Marvel/Content/A.uasset", "Mod/Marvel/Content/A.uexp", "Mod/readme.md"]);
+        let preview = ArchivePreview::open(&loose).unwrap().expect("loose mod");
+        assert!(preview.is_loose);
+        assert_eq!(preview.files.len(), 2);
+
+        // These need a full extraction to sort out
+        let two_paks = write_zip("Two.zip", &["A.pak", "B.pak"]);
+        assert!(ArchivePreview::open(&two_paks).unwrap().is_none());
+        let nested = write_zip("Nested.zip", &["Inner.7z"]);
+        assert!(ArchivePreview::open(&nested).unwrap().is_none());
+    }
+}
@@ -1,56 +1,921 @@
-pub fn extract_zip(zip_path: &str, output_dir: &str) -> io::Result<()> {
-    let file = File::open(zip_path)?;
-    let mut archive = ZipArchive::new(file)?;
+use std::fs::File;
+use std::io::{self, Read, Seek};
+use unrar::Archive;
+use std::path::{Component, Path, PathBuf};
+use uasset_toolkit::CancellationToken;
+use zip::ZipArchive;
+
+/// Why an archive couldn't be extracted. The limit and path variants mean the archive was
+/// refused on purpose; the message is meant to be shown to the user as-is.
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(io::Error),
+    /// Corrupt or unreadable archive
+    Format(String),
+    NotAnArchive(PathBuf),
+    /// Entry with an absolute path or `..` that would land outside the output folder
+    UnsafePath(String),
+    /// Symlink pointing outside the output folder
+    UnsafeLink { entry: String, target: String },
+    TooLarge { limit: u64 },
+    TooManyEntries { limit: usize },
+    /// Entry (or whole archive) expands far more than real mod files do - likely a zip bomb
+    CompressionRatio { entry: String, limit: u64 },
+    /// The install was cancelled while extracting
+    Cancelled,
+}
+
+impl ArchiveError {
+    /// The archive was refused by a safety check rather than failing to read
+    pub fn is_rejected(&self) -> bool {
+        !matches!(
+            self,
+            ArchiveError::Io(_) | ArchiveError::Format(_) | ArchiveError::NotAnArchive(_) | ArchiveError::Cancelled
+        )
+    }
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveError::Io(e) => write!(f, "{}", e),
+            ArchiveError::Format(msg) => write!(f, "Invalid archive: {}", msg),
+            ArchiveError::NotAnArchive(path) => write!(f, "Not a supported archive: {}", path.display()),
+            ArchiveError::UnsafePath(entry) => write!(f, "Archive entry '{}' points outside the extraction folder", entry),
+            ArchiveError::UnsafeLink { entry, target } => {
+                write!(f, "Archive entry '{}' links to '{}', outside the extraction folder", entry, target)
+            }
+            ArchiveError::TooLarge { limit } => {
+                write!(f, "Archive expands to more than {} MB", limit / (1024 * 1024))
+            }
+            ArchiveError::TooManyEntries { limit } => write!(f, "Archive has more than {} entries", limit),
+            ArchiveError::CompressionRatio { entry, limit } => {
+                write!(f, "'{}' expands more than {}x its compressed size", entry, limit)
+            }
+            ArchiveError::Cancelled => write!(f, "Extraction was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+impl From<io::Error> for ArchiveError {
+    fn from(e: io::Error) -> Self {
+        ArchiveError::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for ArchiveError {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(e) => ArchiveError::Io(e),
+            e => ArchiveError::Format(e.to_string()),
+        }
+    }
+}
+
+/// Caps on what one extraction (including nested archives) may write
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimits {
+    pub max_total_size: u64,
+    pub max_entries: usize,
+    /// Uncompressed / compressed size, per entry where the format records it and per archive
+    pub max_ratio: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_total_size: 16 * 1024 * 1024 * 1024,
+            max_entries: 50_000,
+            max_ratio: 200,
+        }
+    }
+}
+
+/// Output below this size is never treated as a bomb, so small well-compressed files are fine
+const RATIO_GRACE: u64 = 16 * 1024 * 1024;
+
+/// Tracks what has been written so far against ExtractLimits
+pub struct ExtractGuard {
+    limits: ExtractLimits,
+    entries: usize,
+    total: u64,
+    archive_size: u64,
+    archive_written: u64,
+    cancel: Option<CancellationToken>,
+}
+
+impl ExtractGuard {
+    pub fn new(limits: ExtractLimits) -> Self {
+        Self { limits, entries: 0, total: 0, archive_size: 0, archive_written: 0, cancel: None }
+    }
+
+    /// Stop with ArchiveError::Cancelled at the next entry or chunk once `cancel` is triggered
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    fn check_cancelled(&self) -> Result<(), ArchiveError> {
+        match &self.cancel {
+            Some(cancel) if cancel.is_cancelled() => Err(ArchiveError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// Start counting the archive-wide ratio for a new archive of `size` bytes on disk
+    pub(super) fn start_archive(&mut self, size: u64) {
+        self.archive_size = size;
+        self.archive_written = 0;
+    }
+
+    fn entry(&mut self) -> Result<(), ArchiveError> {
+        self.check_cancelled()?;
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            return Err(ArchiveError::TooManyEntries { limit: self.limits.max_entries });
+        }
+        Ok(())
+    }
+
+    /// Count `len` bytes written for `entry` (compressed size `compressed`, if known)
+    pub(super) fn add(&mut self, entry: &str, written: u64, len: u64, compressed: Option<u64>) -> Result<(), ArchiveError> {
+        self.check_cancelled()?;
+        self.total += len;
+        self.archive_written += len;
+        if self.total > self.limits.max_total_size {
+            return Err(ArchiveError::TooLarge { limit: self.limits.max_total_size });
+        }
+        let too_dense = |out: u64, packed: u64| out > RATIO_GRACE && out / packed.max(1) > self.limits.max_ratio;
+        if compressed.is_some_and(|packed| too_dense(written, packed)) || too_dense(self.archive_written, self.archive_size) {
+            return Err(ArchiveError::CompressionRatio { entry: entry.to_string(), limit: self.limits.max_ratio });
+        }
+        Ok(())
+    }
+
+    /// Copy an entry's data, stopping as soon as a limit is crossed rather than trusting the
+    /// sizes in the archive's headers
+    fn copy(&mut self, entry: &str, reader: &mut dyn Read, out_path: &Path, compressed: Option<u64>) -> Result<(), ArchiveError> {
+        let mut out = io::BufWriter::new(File::create(out_path)?);
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut written = 0u64;
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            io::Write::write_all(&mut out, &buf[..n])?;
+            written += n as u64;
+            self.add(entry, written, n as u64, compressed)?;
+        }
+        io::Write::flush(&mut out)?;
+        Ok(())
+    }
+}
+
+impl Default for ExtractGuard {
+    fn default() -> Self {
+        Self::new(ExtractLimits::default())
+    }
+}
+
+/// Where `name` goes inside `output_dir`. Absolute paths, drive letters and `..` are refused
+/// instead of being silently rewritten, since they only show up in crafted archives. A `:`
+/// anywhere is refused too: on Windows `Mod/C:evil.pak` would put a drive prefix mid-path,
+/// which replaces the output folder when pushed, and `file:stream` names an alternate data stream.
+fn safe_path(output_dir: &Path, name: &str) -> Result<PathBuf, ArchiveError> {
+    let normalized = name.replace('\\', "/");
+    if normalized.starts_with('/') || normalized.contains(':') {
+        return Err(ArchiveError::UnsafePath(name.to_string()));
+    }
+    let mut path = output_dir.to_path_buf();
+    for component in Path::new(&normalized).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return Err(ArchiveError::UnsafePath(name.to_string())),
+        }
+    }
+    Ok(path)
+}
+
+/// Links aren't recreated, but one pointing outside the output marks the archive as hostile
+fn check_link(entry: &str, target: &str) -> Result<(), ArchiveError> {
+    let unsafe_link = || ArchiveError::UnsafeLink { entry: entry.to_string(), target: target.to_string() };
+    let target = target.replace('\\', "/");
+    if target.starts_with('/') || target.contains(':') {
+        return Err(unsafe_link());
+    }
+    // Resolve relative to the folder holding the link
+    let mut depth = entry.replace('\\', "/").split('/').filter(|p| !p.is_empty() && *p != ".").count() as i64 - 1;
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => depth -= 1,
+            _ => depth += 1,
+        }
+        if depth < 0 {
+            return Err(unsafe_link());
+        }
+    }
+    Ok(())
+}
+
+fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) => std::fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+}
+
+pub fn extract_zip<R: Read + Seek>(reader: R, output_dir: &Path, guard: &mut ExtractGuard) -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(reader)?;
 
     for i in 0..archive.len() {
+        guard.entry()?;
         let mut file = archive.by_index(i)?;
-        let outpath = Path::new(output_dir).join(file.mangled_name());
+        let name = file.name().to_string();
+        let outpath = safe_path(output_dir, &name)?;
 
-        if file.name().ends_with('/') {
+        if file.is_symlink() {
+            let mut target = String::new();
+            (&mut file).take(4096).read_to_string(&mut target)?;
+            check_link(&name, &target)?;
+            log::debug!("Skipping symlink {} in {}", name, output_dir.display());
+        } else if file.is_dir() {
             std::fs::create_dir_all(&outpath)?;
         } else {
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    std::fs::create_dir_all(p)?;
-                }
-            }
-            let mut outfile = File::create(&outpath)?;
-            io::copy(&mut file, &mut outfile)?;
+            create_parent(&outpath)?;
+            let compressed = file.compressed_size();
+            guard.copy(&name, &mut file, &outpath, Some(compressed))?;
         }
     }
 
     Ok(())
 }
 
-use std::fs::File;
-use std::io;
-use unrar::Archive;
-use std::path::Path;
-use zip::ZipArchive;
+pub fn extract_rar(rar_path: &Path, output_dir: &Path, guard: &mut ExtractGuard) -> Result<(), ArchiveError> {
+    const S_IFMT: u32 = 0o170000;
+    const S_IFLNK: u32 = 0o120000;
+    const REPARSE_POINT: u32 = 0x400;
+    let rar_error = |e: unrar::error::UnrarError| ArchiveError::Format(e.to_string());
 
-pub fn extract_rar(rar_path: &str, output_dir: &str) -> Result<(), unrar::error::UnrarError> {
-    let output_dir = Path::new(output_dir);
-    let mut archive =
-        Archive::new(rar_path)
-            .open_for_processing()?;
-    while let Some(header) = archive.read_header()? {
-        let filename = header.entry().filename.clone();
-        archive = if header.entry().is_file() {
-            header.extract_to(output_dir.join(filename))?
+    let mut archive = Archive::new(rar_path).open_for_processing().map_err(rar_error)?;
+    while let Some(header) = archive.read_header().map_err(rar_error)? {
+        let entry = header.entry();
+        let name = entry.filename.to_string_lossy().to_string();
+        let attr = entry.file_attr;
+        let size = entry.unpacked_size;
+        let is_file = entry.is_file();
+        guard.entry()?;
+        let outpath = safe_path(output_dir, &name)?;
+
+        archive = if attr & S_IFMT == S_IFLNK || attr & REPARSE_POINT != 0 {
+            let (target, next) = header.read().map_err(rar_error)?;
+            check_link(&name, &String::from_utf8_lossy(&target))?;
+            next
+        } else if is_file {
+            // unrar writes the file itself, so the declared size is checked up front
+            if guard.total.saturating_add(size) > guard.limits.max_total_size {
+                return Err(ArchiveError::TooLarge { limit: guard.limits.max_total_size });
+            }
+            let next = header.extract_to(&outpath).map_err(rar_error)?;
+            let metadata = std::fs::symlink_metadata(&outpath)?;
+            if metadata.file_type().is_symlink() {
+                // Link flagged in a way we didn't recognise; links are never kept
+                std::fs::remove_file(&outpath)?;
+            } else {
+                guard.add(&name, metadata.len(), metadata.len(), None)?;
+            }
+            next
         } else {
-            header.skip()?
+            header.skip().map_err(rar_error)?
         };
     }
     Ok(())
 }
 
-pub fn extract_7z(archive_path: &str, output_dir: &str) -> io::Result<()> {
-    let output_path = Path::new(output_dir);
-    std::fs::create_dir_all(output_path)?;
-    
-    // Use sevenz_rust2's decompress_file utility for simple extraction
-    sevenz_rust2::decompress_file(archive_path, output_path)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Failed to extract 7z archive: {}", e)))?;
-    
+pub fn extract_7z<R: Read + Seek>(reader: R, output_dir: &Path, guard: &mut ExtractGuard) -> Result<(), ArchiveError> {
+    // sevenz_rust2 only takes its own error type back from the callback, so ours is stashed here
+    let mut failure: Option<ArchiveError> = None;
+    let result = sevenz_rust2::decompress_with_extract_fn(reader, output_dir, |entry, data, _| {
+        let result = (|| {
+            guard.entry()?;
+            let outpath = safe_path(output_dir, entry.name())?;
+            if entry.is_directory() {
+                std::fs::create_dir_all(&outpath)?;
+            } else {
+                create_parent(&outpath)?;
+                guard.copy(entry.name(), data, &outpath, None)?;
+            }
+            Ok(())
+        })();
+        match result {
+            Ok(()) => Ok(true),
+            Err(e) => {
+                failure = Some(e);
+                Err(sevenz_rust2::Error::Other("extraction stopped".into()))
+            }
+        }
+    });
+    match (failure, result) {
+        (Some(e), _) => Err(e),
+        (None, Err(e)) => Err(ArchiveError::Format(format!("Failed to extract 7z archive: {}", e))),
+        (None, Ok(())) => Ok(()),
+    }
+}
+
+/// How many archives deep we unpack (a zip holding a 7z holding a rar is depth 3)
+pub const MAX_ARCHIVE_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Rar,
+    SevenZip,
+    Tar,
+    TarGz,
+}
+
+/// Archive type of `path`, or None if it isn't an archive or is a later volume of a split set
+/// (those are read through the first volume)
+pub fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if is_secondary_volume(path) {
+        return None;
+    }
+    let name = name.strip_suffix(".001").unwrap_or(&name);
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".7z") {
+        Some(ArchiveKind::SevenZip)
+    } else if name.ends_with(".rar") {
+        Some(ArchiveKind::Rar)
+    } else {
+        None
+    }
+}
+
+/// `.part2.rar`, `.r00`, `.7z.002`, `.zip.002`, ... - anything but the first volume of a split archive
+pub fn is_secondary_volume(path: &Path) -> bool {
+    let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_lowercase()) else {
+        return false;
+    };
+    // name.part<N>.rar
+    if let Some(stem) = name.strip_suffix(".rar") {
+        if let Some((_, part)) = stem.rsplit_once(".part") {
+            return part.parse::<u32>().is_ok_and(|n| n > 1);
+        }
+        return false;
+    }
+    // Old style RAR volumes: name.r00, name.r01, ...
+    if let Some((_, ext)) = name.rsplit_once('.') {
+        if ext.len() == 3 && ext.starts_with('r') && ext[1..].chars().all(|c| c.is_ascii_digit()) {
+            return true;
+        }
+    }
+    // name.7z.<NNN> / name.zip.<NNN>
+    numbered_volume(&name).is_some_and(|(_, n)| n > 1)
+}
+
+/// Splits `mod.7z.003` into (`mod.7z`, 3)
+fn numbered_volume(name: &str) -> Option<(&str, u32)> {
+    let (base, number) = name.rsplit_once('.')?;
+    if number.len() != 3 || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if !(base.ends_with(".7z") || base.ends_with(".zip")) {
+        return None;
+    }
+    Some((base, number.parse().ok()?))
+}
+
+/// All volumes of a `.001` split set, in order, stopping at the first gap
+pub fn split_volumes(first: &Path) -> Vec<PathBuf> {
+    let name = first.file_name().unwrap().to_string_lossy().to_string();
+    let Some(base) = name.strip_suffix(".001") else {
+        return vec![first.to_path_buf()];
+    };
+    (1..)
+        .map(|n| first.with_file_name(format!("{}.{:03}", base, n)))
+        .take_while(|p| p.exists())
+        .collect()
+}
+
+/// Reads a split archive's volumes as one continuous file
+pub struct VolumeReader {
+    files: Vec<(File, u64)>,
+    len: u64,
+    pos: u64,
+}
+
+impl VolumeReader {
+    pub fn open(volumes: &[PathBuf]) -> io::Result<Self> {
+        let mut files = Vec::new();
+        let mut len = 0;
+        for path in volumes {
+            let file = File::open(path)?;
+            let size = file.metadata()?.len();
+            files.push((file, size));
+            len += size;
+        }
+        Ok(Self { files, len, pos: 0 })
+    }
+}
+
+impl io::Read for VolumeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut offset = self.pos;
+        for (file, size) in &mut self.files {
+            if offset < *size {
+                file.seek(io::SeekFrom::Start(offset))?;
+                let max = buf.len().min((*size - offset) as usize);
+                let n = file.read(&mut buf[..max])?;
+                self.pos += n as u64;
+                return Ok(n);
+            }
+            offset -= *size;
+        }
+        Ok(0)
+    }
+}
+
+impl io::Seek for VolumeReader {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            io::SeekFrom::Start(p) => p as i64,
+            io::SeekFrom::End(p) => self.len as i64 + p,
+            io::SeekFrom::Current(p) => self.pos as i64 + p,
+        };
+        if new_pos < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before start of archive"));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
+
+/// Combined size of `path` and its sibling volumes, for the archive-wide compression ratio
+fn volume_set_size(path: &Path) -> u64 {
+    let size = |p: &Path| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let stem = archive_stem(&name).to_lowercase();
+    let Some(dir) = path.parent() else {
+        return size(path);
+    };
+    let siblings: u64 = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| is_secondary_volume(p))
+        .filter(|p| {
+            let other = p.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+            // Old style .rNN volumes aren't covered by archive_stem
+            let other = match other.rsplit_once('.') {
+                Some((base, ext)) if ext.starts_with('r') && ext[1..].chars().all(|c| c.is_ascii_digit()) => base.to_string(),
+                _ => archive_stem(&other),
+            };
+            other == stem
+        })
+        .map(|p| size(&p))
+        .sum();
+    size(path) + siblings
+}
+
+/// Extract one archive (any supported kind, split or not) into `output_dir`, counting what it
+/// writes against `guard`
+pub fn extract_archive(path: &Path, output_dir: &Path, guard: &mut ExtractGuard) -> Result<(), ArchiveError> {
+    let kind = archive_kind(path).ok_or_else(|| ArchiveError::NotAnArchive(path.to_path_buf()))?;
+    std::fs::create_dir_all(output_dir)?;
+    let volumes = split_volumes(path);
+    guard.start_archive(volume_set_size(path));
+
+    match kind {
+        ArchiveKind::Zip if volumes.len() > 1 => extract_zip(VolumeReader::open(&volumes)?, output_dir, guard),
+        ArchiveKind::Zip => extract_zip(File::open(path)?, output_dir, guard),
+        ArchiveKind::SevenZip if volumes.len() > 1 => extract_7z(VolumeReader::open(&volumes)?, output_dir, guard),
+        ArchiveKind::SevenZip => extract_7z(File::open(path)?, output_dir, guard),
+        // unrar follows .partN.rar / .rNN volumes on its own when given the first one
+        ArchiveKind::Rar => extract_rar(path, output_dir, guard),
+        ArchiveKind::Tar => extract_tar(io::BufReader::new(File::open(path)?), output_dir, guard),
+        ArchiveKind::TarGz => extract_tar(
+            flate2::read::MultiGzDecoder::new(io::BufReader::new(File::open(path)?)),
+            output_dir,
+            guard,
+        ),
+    }
+}
+
+/// Minimal tar reader: regular files and directories, ustar prefixes, GNU long names and
+/// pax path records. Links and devices are skipped.
+pub fn extract_tar<R: io::Read>(mut reader: R, output_dir: &Path, guard: &mut ExtractGuard) -> Result<(), ArchiveError> {
+    fn field(bytes: &[u8]) -> String {
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..end]).to_string()
+    }
+    fn size(bytes: &[u8]) -> Result<u64, ArchiveError> {
+        // GNU base-256 for sizes that don't fit in octal
+        if bytes[0] & 0x80 != 0 {
+            return Ok(bytes[1..].iter().fold(0u64, |acc, &b| (acc << 8) | b as u64));
+        }
+        let text = field(bytes);
+        let text = text.trim_matches(|c: char| c == ' ' || c == '\0');
+        if text.is_empty() {
+            return Ok(0);
+        }
+        u64::from_str_radix(text, 8).map_err(|_| ArchiveError::Format(format!("Bad tar size field '{}'", text)))
+    }
+    fn read_data<R: io::Read>(reader: &mut R, len: u64) -> Result<Vec<u8>, ArchiveError> {
+        // Long names and pax records are small; anything bigger is garbage
+        if len > 1024 * 1024 {
+            return Err(ArchiveError::Format("Oversized tar extended header".to_string()));
+        }
+        let mut data = Vec::new();
+        reader.take(len).read_to_end(&mut data)?;
+        if (data.len() as u64) < len {
+            return Err(ArchiveError::Format("Truncated tar archive".to_string()));
+        }
+        Ok(data)
+    }
+    fn skip<R: io::Read>(reader: &mut R, len: u64) -> io::Result<()> {
+        io::copy(&mut reader.take(len), &mut io::sink())?;
+        Ok(())
+    }
+    fn padding(len: u64) -> u64 {
+        (512 - len % 512) % 512
+    }
+
+    let mut header = [0u8; 512];
+    let mut next_name: Option<String> = None;
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            // Some writers leave out the closing zero blocks
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+
+        let len = size(&header[124..136])?;
+        let type_flag = header[156];
+        let name = match next_name.take() {
+            Some(name) => name,
+            None => {
+                let name = field(&header[0..100]);
+                let prefix = if &header[257..262] == b"ustar" { field(&header[345..500]) } else { String::new() };
+                if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) }
+            }
+        };
+
+        match type_flag {
+            // GNU long name for the next entry
+            b'L' => {
+                let data = read_data(&mut reader, len)?;
+                next_name = Some(field(&data));
+                skip(&mut reader, padding(len))?;
+                continue;
+            }
+            // pax extended header; only the path is of interest
+            b'x' => {
+                let data = read_data(&mut reader, len)?;
+                next_name = String::from_utf8_lossy(&data)
+                    .lines()
+                    .filter_map(|record| record.split_once(' ').map(|(_, kv)| kv))
+                    .find_map(|kv| kv.strip_prefix("path=").map(|p| p.to_string()));
+                skip(&mut reader, padding(len))?;
+                continue;
+            }
+            _ => {}
+        }
+
+        guard.entry()?;
+        let out_path = safe_path(output_dir, &name)?;
+
+        match type_flag {
+            b'0' | 0 | b'7' => {
+                create_parent(&out_path)?;
+                let mut data = (&mut reader).take(len);
+                guard.copy(&name, &mut data, &out_path, None)?;
+                if data.limit() > 0 {
+                    return Err(ArchiveError::Format("Truncated tar archive".to_string()));
+                }
+            }
+            b'5' => {
+                std::fs::create_dir_all(&out_path)?;
+                skip(&mut reader, len)?;
+            }
+            // Hard and symbolic links; the target is in the header
+            b'1' | b'2' => {
+                check_link(&name, &field(&header[157..257]))?;
+                skip(&mut reader, len)?;
+            }
+            _ => skip(&mut reader, len)?,
+        }
+        skip(&mut reader, padding(len))?;
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// A folder produced by extract_archive_nested and the chain of archives it came from
+#[derive(Debug, Clone)]
+pub struct ExtractedArchive {
+    pub dir: PathBuf,
+    /// Archive file names, outermost first
+    pub origin: Vec<String>,
+}
+
+/// Extract `path` into `output_dir`, then keep extracting archives found inside it (each into a
+/// folder named after it, next to it) up to MAX_ARCHIVE_DEPTH levels. `guard` covers the whole
+/// tree, so a bomb can't hide behind a layer of nesting.
+pub fn extract_archive_nested(path: &Path, output_dir: &Path, guard: &mut ExtractGuard) -> Result<Vec<ExtractedArchive>, ArchiveError> {
+    extract_archive(path, output_dir, guard)?;
+    let origin = vec![path.file_name().unwrap_or_default().to_string_lossy().to_string()];
+    let mut extracted = vec![ExtractedArchive { dir: output_dir.to_path_buf(), origin }];
+
+    let mut level = 0;
+    while level < extracted.len() {
+        let parent = extracted[level].clone();
+        level += 1;
+
+        let inner: Vec<PathBuf> = walkdir::WalkDir::new(&parent.dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file() && archive_kind(e.path()).is_some())
+            // Archives inside a nested folder belong to that folder's level
+            .filter(|e| !extracted.iter().any(|x| x.dir != parent.dir && x.dir.starts_with(&parent.dir) && e.path().starts_with(&x.dir)))
+            .map(|e| e.into_path())
+            .collect();
+
+        for archive in inner {
+            let name = archive.file_name().unwrap().to_string_lossy().to_string();
+            if parent.origin.len() >= MAX_ARCHIVE_DEPTH {
+                log::warn!("Not extracting {}: archives nested more than {} deep", name, MAX_ARCHIVE_DEPTH);
+                continue;
+            }
+            let dir = unique_dir(&archive.with_file_name(archive_stem(&name)));
+            match extract_archive(&archive, &dir, guard) {
+                Ok(()) => {
+                    let mut origin = parent.origin.clone();
+                    origin.push(name);
+                    extracted.push(ExtractedArchive { dir, origin });
+                }
+                // A hostile inner archive taints the whole download
+                Err(e) if e.is_rejected() || matches!(e, ArchiveError::Cancelled) => return Err(e),
+                Err(e) => log::warn!("Failed to extract nested archive {}: {}", name, e),
+            }
+        }
+    }
+    Ok(extracted)
+}
+
+/// "Mod.part1.rar" -> "Mod", "Mod.tar.gz" -> "Mod", "Mod.7z.001" -> "Mod"
+fn archive_stem(name: &str) -> String {
+    let lower = name.to_lowercase();
+    let mut end = lower.len();
+    for suffix in [".001", ".gz", ".tgz", ".tar", ".zip", ".7z", ".rar"] {
+        if lower[..end].ends_with(suffix) {
+            end -= suffix.len();
+        }
+    }
+    if let Some(idx) = lower[..end].rfind(".part") {
+        if lower[idx + 5..end].chars().all(|c| c.is_ascii_digit()) {
+            end = idx;
+        }
+    }
+    if end == 0 { name.to_string() } else { name[..end].to_string() }
+}
+
+fn unique_dir(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    (2..)
+        .map(|n| path.with_file_name(format!("{} ({})", name, n)))
+        .find(|p| !p.exists())
+        .unwrap()
+}
+
+/// The archive chain of the deepest extracted folder containing `path`
+pub fn archive_origin(extracted: &[ExtractedArchive], path: &Path) -> Vec<String> {
+    extracted
+        .iter()
+        .filter(|x| path.starts_with(&x.dir))
+        .max_by_key(|x| x.dir.components().count())
+        .map(|x| x.origin.clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    fn tar_entry(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+        header[156] = b'0';
+        let mut entry = header.to_vec();
+        entry.extend_from_slice(data);
+        entry.resize(entry.len().div_ceil(512) * 512, 0);
+        entry
+    }
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut writer = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in files {
+            writer.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn volumes_and_kinds_are_recognised() {
+        assert_eq!(archive_kind(Path::new("Mod.tar.gz")), Some(ArchiveKind::TarGz));
+        assert_eq!(archive_kind(Path::new("Mod.7z.001")), Some(ArchiveKind::SevenZip));
+        assert_eq!(archive_kind(Path::new("Mod.part1.rar")), Some(ArchiveKind::Rar));
+        assert_eq!(archive_kind(Path::new("Mod.part2.rar")), None);
+        assert_eq!(archive_kind(Path::new("Mod.pak")), None);
+
+        for secondary in ["Mod.part2.rar", "Mod.r00", "Mod.7z.002", "Mod.zip.010"] {
+            assert!(is_secondary_volume(Path::new(secondary)), "{secondary}");
+        }
+        for first in ["Mod.part1.rar", "Mod.rar", "Mod.zip.001", "Mod.pak"] {
+            assert!(!is_secondary_volume(Path::new(first)), "{first}");
+        }
+        assert_eq!(archive_stem("Mod.part1.rar"), "Mod");
+        assert_eq!(archive_stem("Mod.tar.gz"), "Mod");
+        assert_eq!(archive_stem("Mod.7z.001"), "Mod");
+    }
+
+    #[test]
+    fn tarballs_extract_and_refuse_escaping_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let tgz = |entries: &[(&str, &[u8])]| {
+            let mut tar: Vec<u8> = entries.iter().flat_map(|(name, data)| tar_entry(name, data)).collect();
+            tar.extend([0u8; 1024]);
+            let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            gz.write_all(&tar).unwrap();
+            gz.finish().unwrap()
+        };
+
+        let archive = dir.path().join("Mod.tgz");
+        fs::write(&archive, tgz(&[("Mod/Marvel/Content/A.uasset", b"asset")])).unwrap();
+        let out = dir.path().join("out");
+        extract_archive(&archive, &out, &mut ExtractGuard::default()).unwrap();
+        assert_eq!(fs::read(out.join("Mod/Marvel/Content/A.uasset")).unwrap(), b"asset");
+
+        let evil = dir.path().join("Evil.tgz");
+        fs::write(&evil, tgz(&[("../../evil.txt", b"nope")])).unwrap();
+        let err = extract_archive(&evil, &dir.path().join("evil_out"), &mut ExtractGuard::default()).unwrap_err();
+        assert!(matches!(err, ArchiveError::UnsafePath(_)), "{err}");
+        assert!(!dir.path().join("evil.txt").exists());
+
+        fs::write(&evil, tgz(&[("Mod/C:evil.pak", b"nope")])).unwrap();
+        let err = extract_archive(&evil, &dir.path().join("drive_out"), &mut ExtractGuard::default()).unwrap_err();
+        assert!(matches!(err, ArchiveError::UnsafePath(_)), "{err}");
+    }
+
+    #[test]
+    fn seven_zip_entries_naming_a_stream_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("Mod")).unwrap();
+        fs::write(src.join("Mod").join("A.pak:stream"), b"nope").unwrap();
+        let archive = dir.path().join("Evil.7z");
+        sevenz_rust2::compress_to_path(&src, &archive).unwrap();
+
+        let err = extract_archive(&archive, &dir.path().join("out"), &mut ExtractGuard::default()).unwrap_err();
+        assert!(matches!(err, ArchiveError::UnsafePath(_)), "{err}");
+    }
+
+    #[test]
+    fn zip_slip_entries_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let names = [
+            "../evil.pak",
+            "/etc/evil.pak",
+            "C:\\Windows\\evil.pak",
+            "Mod/../../evil.pak",
+            // A drive prefix mid-path replaces the output folder when pushed on Windows
+            "Mod/C:evil.pak",
+            "Mod\\C:\\x",
+            // Alternate data stream
+            "Mod/A.pak:stream",
+        ];
+        for name in names {
+            let archive = dir.path().join("Evil.zip");
+            write_zip(&archive, &[("Mod/A.pak", b"ok"), (name, b"nope")]);
+            let err = extract_archive(&archive, &dir.path().join("out"), &mut ExtractGuard::default()).unwrap_err();
+            assert!(matches!(err, ArchiveError::UnsafePath(_)), "{name}: {err}");
+            assert!(err.is_rejected());
+        }
+        assert!(!dir.path().join("evil.pak").exists());
+    }
+
+    #[test]
+    fn symlinks_escaping_the_output_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("Links.zip");
+        let options = zip::write::SimpleFileOptions::default();
+
+        let mut writer = zip::ZipWriter::new(File::create(&archive).unwrap());
+        writer.add_symlink("Mod/inside", "../Mod/A.pak", options).unwrap();
+        writer.finish().unwrap();
+        extract_archive(&archive, &dir.path().join("ok"), &mut ExtractGuard::default()).unwrap();
+        // Links are never recreated
+        assert!(!dir.path().join("ok/Mod/inside").exists());
+
+        let mut writer = zip::ZipWriter::new(File::create(&archive).unwrap());
+        writer.add_symlink("Mod/escape", "../../../home", options).unwrap();
+        writer.finish().unwrap();
+        let err = extract_archive(&archive, &dir.path().join("bad"), &mut ExtractGuard::default()).unwrap_err();
+        assert!(matches!(err, ArchiveError::UnsafeLink { .. }), "{err}");
+
+        assert!(check_link("a/b", "/etc/passwd").is_err());
+        assert!(check_link("a/b", "c/../../../x").is_err());
+        assert!(check_link("a/b/c", "../d").is_ok());
+    }
+
+    #[test]
+    fn bombs_and_oversized_archives_are_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // 64 MB of zeros deflates to a few dozen KB
+        let bomb = dir.path().join("Bomb.zip");
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let mut writer = zip::ZipWriter::new(File::create(&bomb).unwrap());
+        writer.start_file("zeros.pak", options).unwrap();
+        let chunk = vec![0u8; 1024 * 1024];
+        for _ in 0..64 {
+            writer.write_all(&chunk).unwrap();
+        }
+        writer.finish().unwrap();
+        let err = extract_archive(&bomb, &dir.path().join("bomb"), &mut ExtractGuard::default()).unwrap_err();
+        assert!(matches!(err, ArchiveError::CompressionRatio { .. }), "{err}");
+
+        let small = dir.path().join("Small.zip");
+        write_zip(&small, &[("A.pak", &[1u8; 100]), ("B.pak", &[2u8; 100]), ("C.pak", &[3u8; 100])]);
+
+        let limits = ExtractLimits { max_entries: 2, ..Default::default() };
+        let err = extract_zip(File::open(&small).unwrap(), &dir.path().join("count"), &mut ExtractGuard::new(limits)).unwrap_err();
+        assert!(matches!(err, ArchiveError::TooManyEntries { limit: 2 }), "{err}");
+
+        let limits = ExtractLimits { max_total_size: 250, ..Default::default() };
+        let err = extract_zip(File::open(&small).unwrap(), &dir.path().join("size"), &mut ExtractGuard::new(limits)).unwrap_err();
+        assert!(matches!(err, ArchiveError::TooLarge { limit: 250 }), "{err}");
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let mut guard = ExtractGuard::default().with_cancel(cancel);
+        let err = extract_zip(File::open(&small).unwrap(), &dir.path().join("cancel"), &mut guard).unwrap_err();
+        assert!(matches!(err, ArchiveError::Cancelled) && !err.is_rejected(), "{err}");
+    }
+
+    #[test]
+    fn split_zip_is_read_across_volumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let whole = dir.path().join("whole.zip");
+        write_zip(&whole, &[("Mod/A.pak", &[7u8; 3000])]);
+
+        let bytes = fs::read(&whole).unwrap();
+        let (first, second) = bytes.split_at(bytes.len() / 2);
+        fs::write(dir.path().join("Mod.zip.001"), first).unwrap();
+        fs::write(dir.path().join("Mod.zip.002"), second).unwrap();
+
+        let out = dir.path().join("out");
+        extract_archive(&dir.path().join("Mod.zip.001"), &out, &mut ExtractGuard::default()).unwrap();
+        assert_eq!(fs::read(out.join("Mod/A.pak")).unwrap(), vec![7u8; 3000]);
+    }
+
+    #[test]
+    fn nested_archives_record_their_origin() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner_src = dir.path().join("inner_src");
+        fs::create_dir_all(&inner_src).unwrap();
+        fs::write(inner_src.join("Skin.pak"), b"pak").unwrap();
+        let inner = dir.path().join("Skins.7z");
+        sevenz_rust2::compress_to_path(&inner_src, &inner).unwrap();
+
+        let outer = dir.path().join("Pack.zip");
+        write_zip(&outer, &[("Readme.txt", b"hi"), ("Skins.7z", &fs::read(&inner).unwrap())]);
+
+        let out = dir.path().join("out");
+        let extracted = extract_archive_nested(&outer, &out, &mut ExtractGuard::default()).unwrap();
+        let skin = out.join("Skins").join("Skin.pak");
+        assert_eq!(fs::read(&skin).unwrap(), b"pak");
+        assert_eq!(archive_origin(&extracted, &skin), vec!["Pack.zip", "Skins.7z"]);
+        assert_eq!(archive_origin(&extracted, &out.join("Readme.txt")), vec!["Pack.zip"]);
+    }
+}
@@ -1,37 +1,63 @@
 #![allow(dead_code)]
+use crate::game_profile;
 use crate::install_mod::install_mod_logic::pak_files::repak_dir;
+use crate::install_mod::install_mod_logic::progress::{cancelled, check_cancelled, AppliedFix, InstallStage};
 use crate::install_mod::InstallableMod;
 use crate::uasset_api_integration::batch_convert_textures_to_inline_with_parallel;
 use crate::utils::collect_files;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::AtomicI32;
 use log::{debug, error, warn, info};
 use std::process::Command;
 use serde::{Deserialize, Serialize};
+use uasset_toolkit::package::{self, PackageHeader};
+use uasset_toolkit::{CancellationToken, RequestOptions};
 
 // Windows-specific: Hide CMD windows when spawning processes
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 
+/// Full path of the mod's USMAP in the roaming Usmap folder; an absolute `usmap_path` is used as is
+fn usmap_file(pak: &InstallableMod) -> Option<String> {
+    if pak.usmap_path.is_empty() {
+        return None;
+    }
+    let usmap_file = dirs::config_dir()
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join("Repak-X")
+        .join("Usmap")
+        .join(&pak.usmap_path);
+    if usmap_file.exists() {
+        Some(usmap_file.to_string_lossy().to_string())
+    } else {
+        warn!("USmap file not found in roaming folder: {}", usmap_file.display());
+        None
+    }
+}
+
 pub fn convert_to_iostore_directory(
     pak: &InstallableMod,
     mod_dir: PathBuf,
     to_pak_dir: PathBuf,
-    packed_files_count: &AtomicI32,
-) -> Result<(), repak::Error> {
+    stage: &dyn Fn(InstallStage),
+    cancel: &CancellationToken,
+) -> Result<Vec<AppliedFix>, repak::Error> {
     let mod_type = pak.mod_type.clone();
+    let mut fixes = Vec::new();
+    check_cancelled(cancel)?;
     
     // Check for force_legacy_pak flag - skip IoStore conversion entirely
     if pak.force_legacy_pak {
         info!("Force Legacy PAK enabled for '{}'. Skipping IoStore conversion.", pak.mod_name);
-        repak_dir(pak, to_pak_dir, mod_dir, packed_files_count)?;
-        return Ok(());
+        stage(InstallStage::Convert);
+        repak_dir(pak, to_pak_dir, mod_dir, cancel)?;
+        return Ok(fixes);
     }
     
     if mod_type == "Audio" || mod_type == "Movies" {
         debug!("{} mod detected. Not creating iostore packages",mod_type);
-        repak_dir(pak, to_pak_dir, mod_dir, packed_files_count)?;
-        return Ok(());
+        stage(InstallStage::Convert);
+        repak_dir(pak, to_pak_dir, mod_dir, cancel)?;
+        return Ok(fixes);
     }
 
 
@@ -46,34 +72,23 @@ pub fn convert_to_iostore_directory(
 
     // Static Mesh SerializeSize fix
     if pak.fix_serialsize_header {
+        stage(InstallStage::FixSerializeSize);
         info!("╔══════════════════════════════════════════════════════════╗");
         info!("║  STATIC MESH SERIALIZESIZE FIX - STARTING                ║");
         info!("╚══════════════════════════════════════════════════════════╝");
         
         // Check for usmap file (required for unversioned assets) - stored in roaming folder
-        let usmap_full_path = if !pak.usmap_path.is_empty() {
-            // Construct full path to Usmap folder in roaming directory
-            let usmap_dir = dirs::config_dir()
-                .unwrap_or_else(|| std::path::PathBuf::from("."))
-                .join("Repak-X")
-                .join("Usmap");
-            let usmap_file = usmap_dir.join(&pak.usmap_path);
-            if usmap_file.exists() {
-                Some(usmap_file.to_string_lossy().to_string())
-            } else {
-                warn!("USmap file not found in roaming folder: {}", usmap_file.display());
-                None
-            }
-        } else {
+        let usmap_full_path = usmap_file(pak);
+        if pak.usmap_path.is_empty() {
             warn!("No usmap file specified - detection may be limited for unversioned assets");
-            None
-        };
+        }
         
         let usmap_path = usmap_full_path.as_deref();
         
-        match process_static_mesh_serializesize(&to_pak_dir, usmap_path) {
+        match process_static_mesh_serializesize(&to_pak_dir, usmap_path, cancel) {
             Ok(fixed_count) => {
                 if fixed_count > 0 {
+                    fixes.push(AppliedFix::SerializeSize { meshes: fixed_count });
                     info!("✓ Fixed SerializeSize for {} Static Mesh(es)", fixed_count);
                     info!("   Proceeding with IoStore conversion...");
                 } else {
@@ -81,6 +96,7 @@ pub fn convert_to_iostore_directory(
                 }
             }
             Err(e) => {
+                check_cancelled(cancel)?;
                 error!("✗ Static Mesh SerializeSize fix failed: {}", e);
                 return Err(repak::Error::Io(std::io::Error::new(
                     std::io::ErrorKind::Other,
@@ -96,6 +112,7 @@ pub fn convert_to_iostore_directory(
     // This modifies the .uasset to clear DataResources and embeds mip data in export.Extras
     // Uses batch processing for much better performance (single UAssetTool process call)
     let processed_textures: std::collections::HashSet<String> = if pak.fix_textures {
+        stage(InstallStage::FixTextures);
         info!("Texture fix enabled for mod: {}", pak.mod_name);
         
         // Collect all .uasset files that have corresponding .ubulk files (textures needing conversion)
@@ -113,10 +130,10 @@ pub fn convert_to_iostore_directory(
         } else {
             info!("Found {} textures with .ubulk files - batch processing (parallel={})", texture_paths.len(), pak.parallel_processing);
             
-            // Log USMAP_PATH status for debugging
-            match std::env::var("USMAP_PATH") {
-                Ok(usmap) => info!("[Texture] USMAP_PATH is set: {}", usmap),
-                Err(_) => warn!("[Texture] USMAP_PATH is NOT set - texture parsing may fail!"),
+            let usmap_full_path = usmap_file(pak);
+            match &usmap_full_path {
+                Some(usmap) => info!("[Texture] Using USMAP: {}", usmap),
+                None => warn!("[Texture] No USMAP available - texture parsing may fail!"),
             }
             
             // Log first few texture paths for debugging
@@ -125,10 +142,13 @@ pub fn convert_to_iostore_directory(
             }
             
             // Use batch processing for all textures at once with parallel option
-            match batch_convert_textures_to_inline_with_parallel(&texture_paths, pak.parallel_processing) {
+            match batch_convert_textures_to_inline_with_parallel(&texture_paths, usmap_full_path.as_deref(), pak.parallel_processing, Some(cancel)) {
                 Ok((success_count, skip_count, error_count, processed_names)) => {
                     info!("Batch texture conversion complete: {} stripped, {} skipped, {} errors", 
                           success_count, skip_count, error_count);
+                    if success_count > 0 {
+                        fixes.push(AppliedFix::Textures { textures: success_count });
+                    }
                     
                     // Convert processed names to HashSet
                     processed_names.into_iter().collect()
@@ -142,6 +162,8 @@ pub fn convert_to_iostore_directory(
     } else {
         std::collections::HashSet::new()
     };
+    // A cancelled texture batch is reported like any other failure above
+    check_cancelled(cancel)?;
 
     // Filter out temporary/backup files that should NOT be included in the IoStore package
     // This includes: .bak files (mesh patch backups), .temp files, patched_files cache,
@@ -219,25 +241,17 @@ pub fn convert_to_iostore_directory(
     let output_base = mod_dir.join(&pak.mod_name);
     
     // Get usmap path if available
-    let usmap_full_path = if !pak.usmap_path.is_empty() {
-        let usmap_dir = dirs::config_dir()
-            .unwrap_or_else(|| std::path::PathBuf::from("."))
-            .join("Repak-X")
-            .join("Usmap");
-        let usmap_file = usmap_dir.join(&pak.usmap_path);
-        if usmap_file.exists() {
-            Some(usmap_file.to_string_lossy().to_string())
-        } else {
-            None
-        }
-    } else {
-        None
-    };
+    let usmap_full_path = usmap_file(pak);
     
+    stage(InstallStage::Convert);
     info!("Converting to IoStore using UAssetTool...");
     info!("  Input directory: {}", to_pak_dir.display());
     info!("  Output base: {}", output_base.display());
     
+    let profile = game_profile::active();
+    // Cancelling stops the UAssetTool worker; whatever it wrote is left in the staging folder
+    let options = RequestOptions { timeout: None, cancel: Some(cancel.clone()) };
+
     // parallel_processing toggle: false=50% threads, true=75% threads
     let result = uasset_toolkit::create_mod_iostore(
         &output_base.to_string_lossy(),
@@ -245,13 +259,19 @@ pub fn convert_to_iostore_directory(
         usmap_full_path.as_deref(),
         Some(&pak.mount_point),
         Some(true), // Enable compression
-        None, // Use default AES key
+        profile.primary_key_hex().as_deref(),
         pak.parallel_processing, // Toggle: false=50%, true=75% CPU threads
         pak.obfuscate, // Encrypt with game's AES key to block FModel extraction
-    ).map_err(|e| repak::Error::Io(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        format!("IoStore conversion failed: {}", e),
-    )))?;
+        &options,
+    ).map_err(|e| {
+        if cancel.is_cancelled() {
+            return repak::Error::Io(cancelled());
+        }
+        for line in e.stderr() {
+            error!("  [UAssetTool] {}", line);
+        }
+        repak::Error::Io(std::io::Error::other(format!("IoStore conversion failed: {}", e)))
+    })?;
     
     info!("IoStore conversion complete:");
     info!("  UTOC: {}", result.utoc_path);
@@ -259,8 +279,7 @@ pub fn convert_to_iostore_directory(
     info!("  PAK:  {}", result.pak_path);
     info!("  Converted {} assets ({} files)", result.converted_count, result.file_count);
 
-    packed_files_count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-    Ok(())
+    Ok(fixes)
 }
 
 /// Process texture files for NoMipmaps fix.
@@ -298,6 +317,20 @@ struct SerializeSizeFixResult {
     message: String,
     fixed_count: Option<usize>,
     asset_type: Option<String>,
+    #[serde(default)]
+    fixes: Vec<SerializeSizeFix>,
+}
+
+/// One export whose SerializeSize the tool recalculated
+#[derive(Debug, Deserialize, Serialize)]
+struct SerializeSizeFix {
+    old_size: i64,
+    new_size: i64,
+    /// Index into the export map, when the tool reports it
+    #[serde(default)]
+    export_index: Option<usize>,
+    #[serde(default)]
+    export_name: Option<String>,
 }
 
 /// Find the UAssetTool - searches multiple locations
@@ -446,56 +479,75 @@ fn fix_static_mesh_serializesize(uasset_path: &Path, usmap_path: Option<&str>) -
         return Ok(0);
     }
 
-    // Apply binary patches to the .uasset header ONLY
+    // Patch the export map of the .uasset header ONLY
     // The C# tool calculates correct sizes from .uexp, Rust patches the .uasset header
-    info!("   Applying binary patches to .uasset header...");
-    let fixes_json: serde_json::Value = serde_json::from_str(&stdout)?;
-    if let Some(fixes_array) = fixes_json.get("fixes").and_then(|f| f.as_array()) {
-        for fix in fixes_array {
-            let old_size = fix.get("old_size").and_then(|v| v.as_i64()).ok_or("Missing old_size")?;
-            let new_size = fix.get("new_size").and_then(|v| v.as_i64()).ok_or("Missing new_size")?;
-            apply_binary_patch(uasset_path, old_size, new_size)?;
-        }
-    }
+    info!("   Patching SerialSize in the .uasset export map...");
+    apply_serial_size_fixes(uasset_path, &result.fixes)?;
     
     Ok(fixed_count)
 }
 
-/// Apply a binary patch to replace old SerializeSize with new SerializeSize
-/// This preserves the exact file structure that retoc expects
-fn apply_binary_patch(uasset_path: &Path, old_size: i64, new_size: i64) -> Result<(), Box<dyn std::error::Error>> {
-    // Read the entire file
+/// Write each fix into the `SerialSize` field of its export map entry.
+/// This preserves the exact file structure that retoc expects; the file is only
+/// written once every fix has been applied and verified.
+fn apply_serial_size_fixes(uasset_path: &Path, fixes: &[SerializeSizeFix]) -> Result<(), Box<dyn std::error::Error>> {
     let mut uasset_data = std::fs::read(uasset_path)?;
-    
-    // Search for the old SerializeSize value in the binary
-    let search_bytes = old_size.to_le_bytes();
-    let mut found_offset = None;
-    
-    for i in 0..uasset_data.len().saturating_sub(8) {
-        if &uasset_data[i..i+8] == &search_bytes {
-            found_offset = Some(i);
-            info!("      Found old SerializeSize {} at offset {}", old_size, i);
-            break;
+    let version = game_profile::active().package_version;
+    let header = PackageHeader::parse(&uasset_data, version)?;
+
+    let mut patched = 0;
+    for fix in fixes {
+        let index = find_fixed_export(&header, fix)?;
+        let export = &header.exports[index];
+        if export.serial_size == fix.new_size as u64 {
+            debug!("      {} already has SerialSize {}", export.object_name, fix.new_size);
+            continue;
         }
+        let new_size = u64::try_from(fix.new_size).map_err(|_| format!("Invalid new SerialSize {}", fix.new_size))?;
+        package::patch_serial_size(&mut uasset_data, version, index, new_size)?;
+        info!("      Patched SerialSize of {}: {} → {}", export.object_name, fix.old_size, fix.new_size);
+        patched += 1;
+    }
+
+    if patched > 0 {
+        std::fs::write(uasset_path, &uasset_data)?;
     }
-    
-    let offset = found_offset.ok_or(format!("Could not find old SerializeSize {} in file", old_size))?;
-    
-    // Patch the 8 bytes at that offset with the new value
-    let new_bytes = new_size.to_le_bytes();
-    uasset_data[offset..offset+8].copy_from_slice(&new_bytes);
-    
-    // Write the file back
-    std::fs::write(uasset_path, &uasset_data)?;
-    
-    info!("      Patched SerializeSize: {} → {} at offset {}", old_size, new_size, offset);
-    
     Ok(())
 }
 
+/// Index of the export a fix applies to: the one the tool named, otherwise the only
+/// export whose current SerialSize is the old size
+fn find_fixed_export(header: &PackageHeader, fix: &SerializeSizeFix) -> Result<usize, String> {
+    if let Some(index) = fix.export_index {
+        return match header.exports.get(index) {
+            Some(_) => Ok(index),
+            None => Err(format!("Export index {} out of range ({} exports)", index, header.exports.len())),
+        };
+    }
+    if let Some(name) = &fix.export_name {
+        return header
+            .exports
+            .iter()
+            .position(|e| &e.object_name == name)
+            .ok_or_else(|| format!("No export named {}", name));
+    }
+
+    let mut candidates = header
+        .exports
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.serial_size as i64 == fix.old_size || e.serial_size as i64 == fix.new_size)
+        .map(|(i, _)| i);
+    match (candidates.next(), candidates.next()) {
+        (Some(index), None) => Ok(index),
+        (None, _) => Err(format!("No export has SerialSize {}", fix.old_size)),
+        (Some(_), Some(_)) => Err(format!("Several exports have SerialSize {}; can't tell which to patch", fix.old_size)),
+    }
+}
+
 /// Process Static Mesh .uasset files in a directory - fix SerializeSize for Static Meshes ONLY
 /// Uses UAssetAPI to detect asset type before processing
-fn process_static_mesh_serializesize(dir: &Path, usmap_path: Option<&str>) -> Result<usize, Box<dyn std::error::Error>> {
+fn process_static_mesh_serializesize(dir: &Path, usmap_path: Option<&str>, cancel: &CancellationToken) -> Result<usize, Box<dyn std::error::Error>> {
     let mut total_fixed = 0;
     let mut uasset_files = Vec::new();
 
@@ -514,6 +566,7 @@ fn process_static_mesh_serializesize(dir: &Path, usmap_path: Option<&str>) -> Re
     // Filter to only Static Mesh files using UAssetAPI detection
     let mut static_mesh_files = Vec::new();
     for uasset_file in &uasset_files {
+        check_cancelled(cancel)?;
         let filename = uasset_file.file_name().unwrap_or_default().to_string_lossy();
         
         // Detect asset type using UAssetAPI
@@ -541,6 +594,7 @@ fn process_static_mesh_serializesize(dir: &Path, usmap_path: Option<&str>) -> Re
 
     // Process only Static Mesh files
     for uasset_file in &static_mesh_files {
+        check_cancelled(cancel)?;
         let filename = uasset_file.file_name().unwrap_or_default().to_string_lossy();
         info!("🔧 Processing Static Mesh: {}", filename);
         
@@ -1,25 +1,28 @@
-use crate::install_mod::{InstallableMod, AES_KEY};
+use crate::game_profile;
+use crate::install_mod::InstallableMod;
 use crate::utils::collect_files;
 use log::{debug, info, error};
 use path_clean::PathClean;
 use path_slash::PathExt;
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
-use repak::Version;
 use std::fs;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::AtomicI32;
 use tempfile::tempdir;
 
 use super::iotoc::{convert_to_iostore_directory, process_texture_files};
+use super::progress::{check_cancelled, AppliedFix, InstallStage};
+use uasset_toolkit::CancellationToken;
 
-pub fn extract_pak_to_dir(pak: &InstallableMod, install_dir: PathBuf) -> Result<(), repak::Error> {
+/// Unpack every file of `pak` into `install_dir`. Stops between files once `cancel` is triggered.
+pub fn extract_pak_to_dir(pak: &InstallableMod, install_dir: PathBuf, cancel: &CancellationToken) -> Result<(), repak::Error> {
     let pak_reader = pak.clone().reader.clone().unwrap();
 
     let mount_point = PathBuf::from(pak_reader.mount_point());
-    let prefix = Path::new("../../../");
+    let profile = game_profile::active();
+    let prefix = Path::new(&profile.mount_point);
 
     struct UnpakEntry {
         entry_path: String,
@@ -60,6 +63,9 @@ pub fn extract_pak_to_dir(pak: &InstallableMod, install_dir: PathBuf) -> Result<
         .collect::<Result<Vec<_>, _>>()?;
 
     entries.par_iter().for_each(|entry| {
+        if cancel.is_cancelled() {
+            return;
+        }
         log::debug!("Unpacking: {}", entry.entry_path);
         fs::create_dir_all(&entry.out_dir).unwrap();
         let mut reader = BufReader::new(File::open(&pak.mod_path).unwrap());
@@ -72,6 +78,7 @@ pub fn extract_pak_to_dir(pak: &InstallableMod, install_dir: PathBuf) -> Result<
             .unwrap();
         log::info!("Unpacked: {:?}", entry.out_path);
     });
+    check_cancelled(cancel)?;
     Ok(())
 }
 
@@ -79,21 +86,22 @@ pub fn extract_pak_to_dir(pak: &InstallableMod, install_dir: PathBuf) -> Result<
 pub fn create_repak_from_pak(
     pak: &InstallableMod,
     mod_dir: PathBuf,
-    packed_files_count: &AtomicI32,
-) -> Result<(), repak::Error> {
+    stage: &dyn Fn(InstallStage),
+    cancel: &CancellationToken,
+) -> Result<Vec<AppliedFix>, repak::Error> {
     // extract the pak first into a temporary dir
     let temp_dir = tempdir().map_err(repak::Error::Io)?;
     let temp_path = temp_dir.path(); // Get the path of the temporary directory
 
-    extract_pak_to_dir(pak, temp_path.to_path_buf())?;
+    stage(InstallStage::Extract);
+    extract_pak_to_dir(pak, temp_path.to_path_buf(), cancel)?;
     convert_to_iostore_directory(
         pak,
         mod_dir.clone(),
         temp_path.to_path_buf(),
-        packed_files_count,
-    )?;
-    // repak_dir(pak, PathBuf::from(temp_path), mod_dir,packed_files_count)?;
-    Ok(())
+        stage,
+        cancel,
+    )
 }
 
 // leaving this here for legacy reasons
@@ -101,7 +109,7 @@ pub fn repak_dir(
     pak: &InstallableMod,
     to_pak_dir: PathBuf,
     mod_dir: PathBuf,
-    installed_mods_ptr: &AtomicI32,
+    cancel: &CancellationToken,
 ) -> Result<(), repak::Error> {
     let mut pak_name = pak.mod_name.clone();
     pak_name.push_str(".pak");
@@ -167,13 +175,13 @@ pub fn repak_dir(
 
     paths.sort();
 
-    let builder = repak::PakBuilder::new()
-        .compression(vec![pak.compression])
-        .key(AES_KEY.clone().0);
+    let profile = game_profile::active();
+    let builder = profile.pak_builder()
+        .compression(vec![pak.compression]);
 
     let mut pak_writer = builder.writer(
         BufWriter::new(output_file),
-        Version::V11,
+        profile.pak_version,
         pak.mount_point.clone(),
         Some(pak.path_hash_seed.parse().unwrap()),
     );
@@ -182,6 +190,7 @@ pub fn repak_dir(
     let partial_entry = paths
         .par_iter()
         .map(|p| {
+            check_cancelled(cancel)?;
             let rel = &p
                 .strip_prefix(to_pak_dir.clone())
                 .expect("file not in input directory")
@@ -191,15 +200,15 @@ pub fn repak_dir(
             let entry = entry_builder
                 .build_entry(true, std::fs::read(p).expect("WTF"), rel)
                 .expect("Failed to build entry");
-            (rel.to_string(), entry)
+            Ok((rel.to_string(), entry))
         })
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, repak::Error>>()?;
 
     let mut rel_paths = vec![];
     for (path, entry) in partial_entry {
+        check_cancelled(cancel)?;
         debug!("Writing: {}", path);
         pak_writer.write_entry(path.clone(), entry)?;
-        installed_mods_ptr.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
         rel_paths.push(path);
     }
 
@@ -0,0 +1,303 @@
+// Dry run of install_mods_in_viewport: works out what an install would do without writing anything
+use crate::game_profile;
+use crate::install_mod::install_mod_logic::{install_base_names, install_output_directory};
+use crate::install_mod::InstallableMod;
+use crate::mod_ops::{mod_priority, scan_mods};
+use crate::utils::{asset_key, collect_files};
+use crate::utoc_utils::read_utoc;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallMethod {
+    /// Already an IoStore mod; the .pak/.utoc/.ucas are copied as-is
+    CopyIoStore,
+    /// Packed into a new IoStore container by UAssetTool
+    ConvertToIoStore,
+    /// Packed into a legacy .pak (Force Legacy PAK, audio and movie mods)
+    LegacyPak,
+    /// The .pak is copied as-is
+    CopyPak,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedConflict {
+    pub installed_mod: PathBuf,
+    pub installed_priority: usize,
+    pub overlapping_files: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedMod {
+    pub source: PathBuf,
+    /// Final base name, including the priority nines and _P
+    pub base_name: String,
+    pub target_dir: PathBuf,
+    pub subfolder: String,
+    pub output_files: Vec<String>,
+    pub method: InstallMethod,
+    pub priority: usize,
+    pub fix_textures: bool,
+    pub fix_serialize_size: bool,
+    /// An installed mod with the same file name will be replaced
+    pub replaces_existing: bool,
+    pub conflicts: Vec<PlannedConflict>,
+    /// Size of the source files; compressed IoStore output usually comes out smaller
+    pub estimated_size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InstallPlan {
+    pub mods: Vec<PlannedMod>,
+    /// Disabled mods that would be skipped
+    pub skipped: Vec<PathBuf>,
+    pub estimated_size: u64,
+}
+
+pub fn plan_install(mods: &[InstallableMod], mod_directory: &Path) -> InstallPlan {
+    let installed = installed_mod_files(mod_directory);
+    let mut plan = InstallPlan::default();
+
+    for (installable_mod, base_name) in mods.iter().zip(install_base_names(mods)) {
+        if !installable_mod.enabled {
+            plan.skipped.push(installable_mod.mod_path.clone());
+            continue;
+        }
+
+        let method = install_method(installable_mod);
+        let output_files: Vec<String> = match method {
+            InstallMethod::CopyIoStore | InstallMethod::ConvertToIoStore => {
+                ["pak", "utoc", "ucas"].iter().map(|ext| format!("{}.{}", base_name, ext)).collect()
+            }
+            InstallMethod::LegacyPak | InstallMethod::CopyPak => vec![format!("{}.pak", base_name)],
+        };
+        let target_dir = install_output_directory(installable_mod, mod_directory);
+        let replaces_existing = output_files.iter().any(|f| target_dir.join(f).exists());
+
+        let files: HashSet<String> = source_files(installable_mod).iter().map(|f| asset_key(f)).collect();
+        let conflicts = installed
+            .iter()
+            // The mod being replaced doesn't conflict with its own new version
+            .filter(|(path, _, _)| !output_files.iter().any(|f| *path == target_dir.join(f)))
+            .filter_map(|(path, priority, installed_files)| {
+                let overlapping_files = installed_files.intersection(&files).count();
+                (overlapping_files > 0).then(|| PlannedConflict {
+                    installed_mod: path.clone(),
+                    installed_priority: *priority,
+                    overlapping_files,
+                })
+            })
+            .collect();
+
+        let estimated_size = source_size(installable_mod);
+        plan.estimated_size += estimated_size;
+        plan.mods.push(PlannedMod {
+            source: installable_mod.mod_path.clone(),
+            priority: mod_priority(&base_name),
+            base_name,
+            target_dir,
+            subfolder: installable_mod.install_subfolder.clone(),
+            output_files,
+            method,
+            fix_textures: installable_mod.fix_textures && method == InstallMethod::ConvertToIoStore,
+            fix_serialize_size: installable_mod.fix_serialsize_header && method == InstallMethod::ConvertToIoStore,
+            replaces_existing,
+            conflicts,
+            estimated_size,
+        });
+    }
+    plan
+}
+
+/// Mirrors the branches in install_mods_in_viewport and convert_to_iostore_directory
+fn install_method(installable_mod: &InstallableMod) -> InstallMethod {
+    if installable_mod.iostore {
+        InstallMethod::CopyIoStore
+    } else if installable_mod.is_dir || installable_mod.repak {
+        if installable_mod.force_legacy_pak
+            || installable_mod.mod_type == "Audio"
+            || installable_mod.mod_type == "Movies"
+        {
+            InstallMethod::LegacyPak
+        } else {
+            InstallMethod::ConvertToIoStore
+        }
+    } else {
+        InstallMethod::CopyPak
+    }
+}
+
+fn source_files(installable_mod: &InstallableMod) -> Vec<String> {
+    if installable_mod.iostore {
+        return read_utoc(&installable_mod.mod_path.with_extension("utoc"))
+            .into_iter()
+            .map(|entry| entry.file_path)
+            .collect();
+    }
+    if installable_mod.is_dir {
+        let mut paths = vec![];
+        let _ = collect_files(&mut paths, &installable_mod.mod_path);
+        return paths
+            .iter()
+            .filter_map(|p| p.strip_prefix(&installable_mod.mod_path).ok())
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+    }
+    if let Some(reader) = &installable_mod.reader {
+        return reader.files();
+    }
+    File::open(&installable_mod.mod_path)
+        .ok()
+        .and_then(|file| game_profile::active().open_pak(&mut BufReader::new(file)).ok())
+        .map(|pak| pak.files())
+        .unwrap_or_default()
+}
+
+fn source_size(installable_mod: &InstallableMod) -> u64 {
+    let size = |path: &Path| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if installable_mod.iostore {
+        ["pak", "utoc", "ucas"]
+            .iter()
+            .map(|ext| size(&installable_mod.mod_path.with_extension(ext)))
+            .sum()
+    } else if installable_mod.is_dir {
+        let mut paths = vec![];
+        let _ = collect_files(&mut paths, &installable_mod.mod_path);
+        paths.iter().map(|p| size(p)).sum()
+    } else {
+        size(&installable_mod.mod_path)
+    }
+}
+
+/// Files of every enabled installed mod, keyed by asset
+fn installed_mod_files(mod_directory: &Path) -> Vec<(PathBuf, usize, HashSet<String>)> {
+    if !mod_directory.exists() {
+        return Vec::new();
+    }
+    scan_mods(mod_directory)
+        .into_iter()
+        .filter(|m| m.enabled)
+        .map(|m| {
+            let utoc_path = m.path.with_extension("utoc");
+            let files: Vec<String> = if utoc_path.exists() {
+                read_utoc(&utoc_path).into_iter().map(|entry| entry.file_path).collect()
+            } else {
+                File::open(&m.path)
+                    .ok()
+                    .and_then(|file| game_profile::active().open_pak(&mut BufReader::new(file)).ok())
+                    .map(|pak| pak.files())
+                    .unwrap_or_default()
+            };
+            (m.path, m.priority, files.iter().map(|f| asset_key(f)).collect())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write_pak(path: &Path, files: &[&str]) {
+        let mut writer = repak::PakBuilder::new().writer(
+            std::io::BufWriter::new(File::create(path).unwrap()),
+            repak::Version::V11,
+            "../../../".to_string(),
+            None,
+        );
+        for file in files {
+            writer.write_file(file, false, b"data").unwrap();
+        }
+        writer.write_index().unwrap();
+    }
+
+    #[test]
+    fn asset_keys_match_across_containers() {
+        assert_eq!(asset_key("Marvel/Content/Marvel/Characters/1050/T_Hero.uasset"), "/game/marvel/characters/1050/t_hero");
+        assert_eq!(asset_key("/Game/Marvel/Characters/1050/T_Hero"), "/game/marvel/characters/1050/t_hero");
+        assert_eq!(asset_key("Hero\\Marvel\\Content\\Marvel\\Characters\\1050\\T_Hero.uexp"), "/game/marvel/characters/1050/t_hero");
+    }
+
+    #[test]
+    fn plan_reports_names_methods_and_conflicts_without_writing() {
+        let source = tempfile::tempdir().unwrap();
+        let mods_dir = tempfile::tempdir().unwrap();
+
+        let content = source.path().join("Hero").join("Marvel").join("Content").join("Marvel");
+        fs::create_dir_all(&content).unwrap();
+        fs::write(content.join("T_Hero.uasset"), b"1234").unwrap();
+        fs::write(content.join("T_Hero.uexp"), b"5678").unwrap();
+
+        let legacy = source.path().join("Voice.pak");
+        write_pak(&legacy, &["Marvel/Content/Audio/Voice.bnk"]);
+
+        write_pak(
+            &mods_dir.path().join("Other_9999999_P.pak"),
+            &["Marvel/Content/Marvel/T_Hero.uasset", "Marvel/Content/Marvel/T_Hero.uexp"],
+        );
+
+        let mods = [
+            InstallableMod {
+                mod_name: "Hero".to_string(),
+                mod_type: "Character".to_string(),
+                is_dir: true,
+                fix_textures: true,
+                fix_serialsize_header: true,
+                mod_path: source.path().join("Hero"),
+                ..Default::default()
+            },
+            InstallableMod {
+                mod_name: "Voice".to_string(),
+                mod_type: "Audio".to_string(),
+                repak: true,
+                fix_textures: true,
+                install_subfolder: "Audio".to_string(),
+                mod_path: legacy.clone(),
+                ..Default::default()
+            },
+            InstallableMod {
+                mod_name: "Off".to_string(),
+                enabled: false,
+                mod_path: source.path().join("Off.pak"),
+                ..Default::default()
+            },
+        ];
+
+        let listing = || {
+            let mut paths: Vec<_> = fs::read_dir(mods_dir.path()).unwrap().map(|e| e.unwrap().path()).collect();
+            paths.sort();
+            paths
+        };
+        let before = listing();
+        let plan = plan_install(&mods, mods_dir.path());
+
+        let [hero, voice] = plan.mods.as_slice() else {
+            panic!("expected two planned mods, got {:?}", plan.mods);
+        };
+        assert_eq!(hero.base_name, "Hero_9999999_P");
+        assert_eq!(hero.method, InstallMethod::ConvertToIoStore);
+        assert_eq!(hero.output_files, vec!["Hero_9999999_P.pak", "Hero_9999999_P.utoc", "Hero_9999999_P.ucas"]);
+        assert!(hero.fix_textures && hero.fix_serialize_size);
+        assert_eq!(hero.estimated_size, 8);
+        let [conflict] = hero.conflicts.as_slice() else {
+            panic!("expected one conflict, got {:?}", hero.conflicts);
+        };
+        assert_eq!(conflict.installed_mod, mods_dir.path().join("Other_9999999_P.pak"));
+        assert_eq!(conflict.overlapping_files, 1);
+
+        assert_eq!(voice.method, InstallMethod::LegacyPak);
+        assert_eq!(voice.target_dir, mods_dir.path().join("Audio"));
+        // Fixes only run on IoStore conversions
+        assert!(!voice.fix_textures);
+        assert!(voice.conflicts.is_empty());
+
+        assert_eq!(plan.skipped, vec![source.path().join("Off.pak")]);
+        assert_eq!(plan.estimated_size, hero.estimated_size + voice.estimated_size);
+
+        assert_eq!(listing(), before);
+    }
+}
@@ -0,0 +1,114 @@
+// What install_mods_in_viewport reports: typed progress events while it runs and one
+// InstallOutcome per mod when it's done
+use serde::Serialize;
+use std::path::PathBuf;
+use uasset_toolkit::CancellationToken;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallStage {
+    /// Unpacking an archive or a .pak to work on its files
+    Extract,
+    /// Working out the mod type and which fixes apply
+    #[allow(dead_code)]
+    Detect,
+    FixSerializeSize,
+    FixTextures,
+    /// Building the IoStore container or legacy .pak
+    Convert,
+    /// Copying files that need no conversion
+    Copy,
+    /// Checking the staged output and moving it into the mods folder
+    Commit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallStatus {
+    Installed,
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "fix", rename_all = "snake_case")]
+pub enum AppliedFix {
+    SerializeSize { meshes: usize },
+    Textures { textures: usize },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallOutcome {
+    pub mod_name: String,
+    pub source: PathBuf,
+    pub status: InstallStatus,
+    pub installed_files: Vec<PathBuf>,
+    pub skipped_reason: Option<String>,
+    /// Outermost context first, root cause last
+    pub error_chain: Vec<String>,
+    pub duration_ms: u64,
+    pub fixes_applied: Vec<AppliedFix>,
+}
+
+impl InstallOutcome {
+    pub fn skipped(mod_name: &str, source: PathBuf, reason: &str) -> Self {
+        Self {
+            mod_name: mod_name.to_string(),
+            source,
+            status: InstallStatus::Skipped,
+            installed_files: Vec::new(),
+            skipped_reason: Some(reason.to_string()),
+            error_chain: Vec::new(),
+            duration_ms: 0,
+            fixes_applied: Vec::new(),
+        }
+    }
+
+    /// The error chain as one line, for logs and toasts
+    pub fn error_message(&self) -> Option<String> {
+        (!self.error_chain.is_empty()).then(|| self.error_chain.join(": "))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum InstallEvent {
+    /// Before the batch starts, while dropped paths are extracted and detected
+    #[allow(dead_code)]
+    Preparing { path: PathBuf, stage: InstallStage },
+    Started { total: usize },
+    Stage { index: usize, total: usize, mod_name: String, stage: InstallStage },
+    Finished { index: usize, total: usize, outcome: Box<InstallOutcome> },
+    Done { installed: usize, skipped: usize, failed: usize },
+}
+
+/// `error` and its sources, outermost first
+pub fn error_chain(error: &dyn std::error::Error) -> Vec<String> {
+    let mut chain = vec![error.to_string()];
+    let mut source = error.source();
+    while let Some(e) = source {
+        let message = e.to_string();
+        // Wrappers often repeat their source's message verbatim
+        if !chain.last().is_some_and(|last| last.ends_with(&message)) {
+            chain.push(message);
+        }
+        source = e.source();
+    }
+    chain
+}
+
+/// Skip reason of mods cut short or never started because the batch was cancelled
+pub const CANCELLED: &str = "Installation was cancelled";
+
+/// Error returned by install steps stopped because the batch was cancelled
+pub fn cancelled() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Interrupted, CANCELLED)
+}
+
+/// Fail with `cancelled()` once `cancel` has been triggered
+pub fn check_cancelled(cancel: &CancellationToken) -> std::io::Result<()> {
+    if cancel.is_cancelled() {
+        return Err(cancelled());
+    }
+    Ok(())
+}
@@ -0,0 +1,267 @@
+// Staged installs: a mod's output files are written to a temp folder inside the destination
+// folder (so the final move is a same-volume rename), validated, and only then moved into
+// place. Anything that goes wrong leaves the mods folder exactly as it was. Mod scans skip
+// staging folders (see mod_ops::in_install_staging), and each holds a lock on its owner file
+// for as long as its install runs, so only folders of installs that died are cleaned up.
+use crate::game_profile;
+use crate::utoc_utils::try_read_utoc;
+use log::{info, warn};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+/// Prefix of staging folders, used to clean up after a crashed install
+pub const STAGING_PREFIX: &str = ".repakx-staging-";
+
+/// Locked by the install using a staging folder
+const OWNER_FILE: &str = ".owner";
+
+/// Staging folders without an owner file are only cleaned up once they are this old, since the
+/// install that made one may not have created its owner file yet
+const UNOWNED_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// Every mod file an install of `base` can produce. Installing replaces all of them, so a legacy
+/// pak doesn't keep an IoStore install's .utoc/.ucas around, or the other way round.
+const OUTPUT_EXTENSIONS: [&str; 3] = ["pak", "utoc", "ucas"];
+
+pub struct InstallTransaction {
+    // Declared before `staging` so the lock is released before the folder is deleted
+    _owner: File,
+    staging: TempDir,
+    output_directory: PathBuf,
+}
+
+impl InstallTransaction {
+    pub fn begin(output_directory: &Path) -> std::io::Result<Self> {
+        let staging = tempfile::Builder::new()
+            .prefix(STAGING_PREFIX)
+            .tempdir_in(output_directory)?;
+        let owner = File::create(staging.path().join(OWNER_FILE))?;
+        owner.lock()?;
+        Ok(Self {
+            _owner: owner,
+            staging,
+            output_directory: output_directory.to_path_buf(),
+        })
+    }
+
+    /// Where the install should write its output files
+    pub fn staging_dir(&self) -> &Path {
+        self.staging.path()
+    }
+
+    /// Check the staged output for `base` and move it into the output directory.
+    /// Returns the installed paths. On error nothing in the output directory is changed.
+    pub fn commit(self, base: &str) -> Result<Vec<PathBuf>, String> {
+        let staged = validate_staged(self.staging_dir(), base)?;
+
+        // Files being replaced are parked in the staging folder so they can be put back
+        let backup_dir = self.staging_dir().join("backup");
+        fs::create_dir(&backup_dir).map_err(|e| format!("Failed to create backup folder: {}", e))?;
+
+        let mut replaced: Vec<(PathBuf, PathBuf)> = Vec::new();
+        let mut installed: Vec<PathBuf> = Vec::new();
+
+        // Files of the previous install that this one doesn't produce go too
+        for ext in OUTPUT_EXTENSIONS {
+            let name = format!("{}.{}", base, ext);
+            let dest = self.output_directory.join(&name);
+            if !dest.exists() || staged.iter().any(|f| f.file_name() == Some(name.as_ref())) {
+                continue;
+            }
+            let backup = backup_dir.join(&name);
+            if let Err(e) = fs::rename(&dest, &backup) {
+                rollback(&installed, &replaced);
+                return Err(format!("Failed to remove the old {}: {}", name, e));
+            }
+            replaced.push((backup, dest));
+        }
+
+        for file in &staged {
+            let name = file.file_name().unwrap();
+            let dest = self.output_directory.join(name);
+            let result = (|| {
+                if dest.exists() {
+                    let backup = backup_dir.join(name);
+                    fs::rename(&dest, &backup)?;
+                    replaced.push((backup, dest.clone()));
+                }
+                fs::rename(file, &dest)
+            })();
+            if let Err(e) = result {
+                rollback(&installed, &replaced);
+                return Err(format!("Failed to move {} into place: {}", name.to_string_lossy(), e));
+            }
+            installed.push(dest);
+        }
+
+        info!("Committed {} file(s) for {}", installed.len(), base);
+        Ok(installed)
+    }
+}
+
+fn rollback(installed: &[PathBuf], replaced: &[(PathBuf, PathBuf)]) {
+    for path in installed {
+        if let Err(e) = fs::remove_file(path) {
+            warn!("Rollback: failed to remove {}: {}", path.display(), e);
+        }
+    }
+    for (backup, original) in replaced {
+        if let Err(e) = fs::rename(backup, original) {
+            warn!("Rollback: failed to restore {}: {}", original.display(), e);
+        }
+    }
+}
+
+/// A staged install is either a lone .pak or a complete .pak/.utoc/.ucas triple
+fn validate_staged(staging: &Path, base: &str) -> Result<Vec<PathBuf>, String> {
+    let pak = staging.join(format!("{}.pak", base));
+    let utoc = staging.join(format!("{}.utoc", base));
+    let ucas = staging.join(format!("{}.ucas", base));
+
+    if !pak.is_file() {
+        return Err(format!("Install produced no {}.pak", base));
+    }
+
+    if utoc.exists() || ucas.exists() {
+        for path in [&utoc, &ucas] {
+            if !path.is_file() {
+                return Err(format!("Incomplete IoStore output: missing {}", path.file_name().unwrap().to_string_lossy()));
+            }
+        }
+        if fs::metadata(&ucas).map(|m| m.len()).unwrap_or(0) == 0 {
+            return Err(format!("{}.ucas is empty", base));
+        }
+        // The .pak of an obfuscated container may not open with our keys; the .utoc always lists
+        try_read_utoc(&utoc).map_err(|e| format!("{}.utoc is not readable: {}", base, e))?;
+        return Ok(vec![pak, utoc, ucas]);
+    }
+
+    let file = File::open(&pak).map_err(|e| format!("Failed to open {}.pak: {}", base, e))?;
+    game_profile::active()
+        .open_pak(&mut BufReader::new(file))
+        .map_err(|e| format!("{}.pak is not readable: {}", base, e))?;
+    Ok(vec![pak])
+}
+
+/// Whether the install that made staging folder `dir` is gone: nobody holds the lock on its
+/// owner file, or it has none and is old
+fn abandoned(dir: &Path) -> bool {
+    match File::open(dir.join(OWNER_FILE)) {
+        Ok(owner) => owner.try_lock().is_ok(),
+        Err(_) => fs::metadata(dir)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > UNOWNED_GRACE),
+    }
+}
+
+/// Remove staging folders left behind by an install that was killed mid-way. Folders of
+/// installs still running, in this process or another, are left alone.
+pub fn cleanup_stale_staging(mod_directory: &Path) {
+    for entry in walkdir::WalkDir::new(mod_directory)
+        .max_depth(2)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_dir())
+    {
+        if entry.file_name().to_string_lossy().starts_with(STAGING_PREFIX) && abandoned(entry.path()) {
+            match fs::remove_dir_all(entry.path()) {
+                Ok(()) => info!("Removed stale install staging folder {}", entry.path().display()),
+                Err(e) => warn!("Failed to remove stale staging folder {}: {}", entry.path().display(), e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_pak(path: &Path) {
+        let mut writer = repak::PakBuilder::new().writer(
+            std::io::BufWriter::new(File::create(path).unwrap()),
+            repak::Version::V11,
+            "../../../".to_string(),
+            None,
+        );
+        writer.write_file("Marvel/Content/A.uasset", false, b"data").unwrap();
+        writer.write_index().unwrap();
+    }
+
+    #[test]
+    fn commit_moves_a_valid_pak_and_replaces_the_old_one() {
+        let mods_dir = tempfile::tempdir().unwrap();
+        fs::write(mods_dir.path().join("Hero_9999999_P.pak"), b"old").unwrap();
+
+        let transaction = InstallTransaction::begin(mods_dir.path()).unwrap();
+        write_pak(&transaction.staging_dir().join("Hero_9999999_P.pak"));
+        let installed = transaction.commit("Hero_9999999_P").unwrap();
+
+        assert_eq!(installed, vec![mods_dir.path().join("Hero_9999999_P.pak")]);
+        assert_ne!(fs::read(&installed[0]).unwrap(), b"old");
+        // Only the installed pak is left; the staging folder is gone
+        assert_eq!(fs::read_dir(mods_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn invalid_output_leaves_the_mods_folder_untouched() {
+        let mods_dir = tempfile::tempdir().unwrap();
+        fs::write(mods_dir.path().join("Hero_9999999_P.pak"), b"old").unwrap();
+
+        // Truncated pak
+        let transaction = InstallTransaction::begin(mods_dir.path()).unwrap();
+        fs::write(transaction.staging_dir().join("Hero_9999999_P.pak"), b"garbage").unwrap();
+        assert!(transaction.commit("Hero_9999999_P").is_err());
+
+        // Half-written IoStore triple
+        let transaction = InstallTransaction::begin(mods_dir.path()).unwrap();
+        write_pak(&transaction.staging_dir().join("Hero_9999999_P.pak"));
+        fs::write(transaction.staging_dir().join("Hero_9999999_P.utoc"), b"toc").unwrap();
+        let err = transaction.commit("Hero_9999999_P").unwrap_err();
+        assert!(err.contains("missing Hero_9999999_P.ucas"), "{err}");
+
+        let names: Vec<_> = fs::read_dir(mods_dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, vec!["Hero_9999999_P.pak"]);
+        assert_eq!(fs::read(mods_dir.path().join("Hero_9999999_P.pak")).unwrap(), b"old");
+    }
+
+    #[test]
+    fn switching_between_iostore_and_legacy_pak_removes_the_old_files() {
+        let mods_dir = tempfile::tempdir().unwrap();
+        for ext in ["pak", "utoc", "ucas"] {
+            fs::write(mods_dir.path().join(format!("Hero_9999999_P.{}", ext)), b"old").unwrap();
+        }
+
+        let transaction = InstallTransaction::begin(mods_dir.path()).unwrap();
+        write_pak(&transaction.staging_dir().join("Hero_9999999_P.pak"));
+        transaction.commit("Hero_9999999_P").unwrap();
+        let names: Vec<_> = fs::read_dir(mods_dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, vec!["Hero_9999999_P.pak"]);
+    }
+
+    #[test]
+    fn only_abandoned_staging_folders_are_removed() {
+        let mods_dir = tempfile::tempdir().unwrap();
+        let skins = mods_dir.path().join("Skins");
+        fs::create_dir_all(&skins).unwrap();
+        let running = InstallTransaction::begin(&skins).unwrap();
+
+        let crashed = skins.join(format!("{}abc", STAGING_PREFIX));
+        fs::create_dir_all(&crashed).unwrap();
+        fs::write(crashed.join(OWNER_FILE), b"").unwrap();
+        fs::write(crashed.join("Half_9999999_P.ucas"), b"").unwrap();
+        // Just created, its install hasn't locked it yet
+        let starting = skins.join(format!("{}def", STAGING_PREFIX));
+        fs::create_dir_all(&starting).unwrap();
+
+        cleanup_stale_staging(mods_dir.path());
+        assert!(!crashed.exists());
+        assert!(starting.exists());
+        assert!(running.staging_dir().exists());
+        assert!(skins.exists());
+    }
+}
@@ -0,0 +1,434 @@
+// Load order - mod priorities worked out from rules instead of set one mod at a time
+//
+// Rules say that one mod loads after another ("A after B"), that every mod of one category goes
+// above every mod of another, or pin a mod to a fixed priority. A mod that loads after another
+// wins wherever both replace the same files, so it gets the higher priority, i.e. the lower
+// number (see mod_ops::mod_priority). Mods stay at their current priority unless a rule moves
+// them, and are moved only as far as the rules need. Each mod's placement comes with the reasons
+// it ended up there, and a set of rules that can't all hold is refused with the ones that clash.
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::mod_ops::{self, ScannedMod};
+use crate::mod_profiles;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LoadOrderRule {
+    /// `mod_key` loads after `after`, so it wins wherever both replace the same files
+    After { mod_key: String, after: String },
+    /// Every mod in category `upper` goes above every mod in category `lower`. Mods in both
+    /// categories are left out of the rule.
+    CategoryAbove { upper: String, lower: String },
+    /// `mod_key` always gets `priority`
+    Pin { mod_key: String, priority: usize },
+}
+
+impl fmt::Display for LoadOrderRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::After { mod_key, after } => write!(f, "{} loads after {}", mod_key, after),
+            Self::CategoryAbove { upper, lower } => write!(f, "{} above {}", upper, lower),
+            Self::Pin { mod_key, priority } => write!(f, "{} pinned at priority {}", mod_key, priority),
+        }
+    }
+}
+
+/// An installed mod as the solver sees it
+#[derive(Debug, Clone)]
+pub struct OrderedMod {
+    pub path: PathBuf,
+    /// See mod_profiles::mod_key
+    pub key: String,
+    pub priority: usize,
+    /// Detected category and custom tags, matched case-insensitively by category rules
+    pub categories: Vec<String>,
+}
+
+impl OrderedMod {
+    pub fn from_scanned(scanned: &ScannedMod, categories: Vec<String>) -> Option<Self> {
+        Some(Self {
+            key: mod_profiles::mod_key(&scanned.path)?,
+            path: scanned.path.clone(),
+            priority: scanned.priority,
+            categories,
+        })
+    }
+
+    fn in_category(&self, category: &str) -> bool {
+        self.categories.iter().any(|c| c.eq_ignore_ascii_case(category))
+    }
+}
+
+/// Where one mod ends up and why
+#[derive(Debug, Clone, Serialize)]
+pub struct Placement {
+    pub key: String,
+    pub from: PathBuf,
+    /// Same as `from` when the priority doesn't change
+    pub to: PathBuf,
+    pub old_priority: usize,
+    pub priority: usize,
+    /// Why the mod landed at `priority`, the deciding reason first
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct LoadOrderPlan {
+    /// Every mod, highest priority first
+    pub placements: Vec<Placement>,
+    /// Rules that don't match any installed mod
+    pub unused_rules: Vec<LoadOrderRule>,
+}
+
+impl LoadOrderPlan {
+    /// Mods whose priority changes
+    pub fn changes(&self) -> impl Iterator<Item = &Placement> {
+        self.placements.iter().filter(|p| p.from != p.to)
+    }
+}
+
+/// `winner` has to end up above `loser` because of rule `rule`
+#[derive(Clone, Copy)]
+struct Edge {
+    winner: usize,
+    loser: usize,
+    rule: usize,
+}
+
+/// Lowest priority number a mod can take, set by the pinned mod `pinned` it has to stay above,
+/// reached through `via` (`None` when the mod is pinned itself)
+#[derive(Clone, Copy)]
+struct Ceiling {
+    limit: i64,
+    pinned: Option<usize>,
+    via: Option<Edge>,
+}
+
+fn mods_with_key<'a>(mods: &'a [OrderedMod], key: &'a str) -> impl Iterator<Item = usize> + 'a {
+    mods.iter().enumerate().filter(move |(_, m)| m.key == key).map(|(i, _)| i)
+}
+
+/// Work out the priority of every mod in `mods` under `rules`
+pub fn solve(mods: &[OrderedMod], rules: &[LoadOrderRule]) -> Result<LoadOrderPlan, String> {
+    let n = mods.len();
+    let mut pins: Vec<Option<usize>> = vec![None; n];
+    let mut edges = Vec::new();
+    let mut unused_rules = Vec::new();
+
+    for (r, rule) in rules.iter().enumerate() {
+        let before = edges.len();
+        let mut pinned = false;
+        match rule {
+            LoadOrderRule::After { mod_key, after } => {
+                for winner in mods_with_key(mods, mod_key) {
+                    for loser in mods_with_key(mods, after).filter(|&l| l != winner) {
+                        edges.push(Edge { winner, loser, rule: r });
+                    }
+                }
+            }
+            LoadOrderRule::CategoryAbove { upper, lower } => {
+                let only_in = |m: &OrderedMod, wanted: &str, other: &str| m.in_category(wanted) && !m.in_category(other);
+                for winner in (0..n).filter(|&i| only_in(&mods[i], upper, lower)) {
+                    for loser in (0..n).filter(|&i| only_in(&mods[i], lower, upper)) {
+                        edges.push(Edge { winner, loser, rule: r });
+                    }
+                }
+            }
+            LoadOrderRule::Pin { mod_key, priority } => {
+                for i in mods_with_key(mods, mod_key) {
+                    if let Some(other) = pins[i].filter(|p| p != priority) {
+                        return Err(format!("{} is pinned at both priority {} and {}", mod_key, other, priority));
+                    }
+                    pins[i] = Some(*priority);
+                    pinned = true;
+                }
+            }
+        }
+        if !pinned && edges.len() == before {
+            unused_rules.push(rule.clone());
+        }
+    }
+
+    let mut above: Vec<Vec<Edge>> = vec![Vec::new(); n];
+    let mut below: Vec<Vec<Edge>> = vec![Vec::new(); n];
+    for edge in &edges {
+        above[edge.loser].push(*edge);
+        below[edge.winner].push(*edge);
+    }
+    let order = topological_order(mods, rules, &above, &below)?;
+
+    // The lowest number each mod can take and still leave room for the pinned mods below it
+    let mut ceilings = vec![Ceiling { limit: i64::MAX, pinned: None, via: None }; n];
+    for &v in order.iter().rev() {
+        if let Some(pin) = pins[v] {
+            ceilings[v] = Ceiling { limit: pin as i64, pinned: Some(v), via: None };
+        }
+        for edge in &below[v] {
+            let loser = ceilings[edge.loser];
+            if loser.pinned.is_some() && loser.limit - 1 < ceilings[v].limit {
+                ceilings[v] = Ceiling { limit: loser.limit - 1, pinned: loser.pinned, via: Some(*edge) };
+            }
+        }
+    }
+
+    let mut priority = vec![0usize; n];
+    let mut reasons: Vec<Vec<String>> = vec![Vec::new(); n];
+    for &v in &order {
+        let current = mods[v].priority;
+        let ceiling = ceilings[v];
+        let (floor, floor_edge) = above[v]
+            .iter()
+            .map(|e| (priority[e.winner] + 1, Some(*e)))
+            .max_by_key(|(p, _)| *p)
+            .unwrap_or((0, None));
+
+        let required = pins[v].unwrap_or(floor);
+        if required as i64 > ceiling.limit {
+            // Only pins put a limit on the ceiling
+            let pinned = ceiling.pinned.unwrap_or(v);
+            let limit = pins[pinned].unwrap_or_default();
+            return Err(match pins[v] {
+                Some(pin) if pinned != v => format!(
+                    "{} is pinned at priority {}, which doesn't leave it above {} (pinned at priority {})",
+                    mods[v].key, pin, mods[pinned].key, limit
+                ),
+                _ => format!("There is no room for {} above {}, which is pinned at priority {}", mods[v].key, mods[pinned].key, limit),
+            });
+        }
+
+        let (value, deciding) = match (pins[v], floor_edge, ceiling.via) {
+            (Some(pin), _, _) => (pin, format!("Pinned at priority {}", pin)),
+            (None, Some(edge), _) if current < floor => {
+                let deciding = format!(
+                    "Moved down from priority {} to {} to stay below {} ({})",
+                    current, floor, mods[edge.winner].key, rules[edge.rule]
+                );
+                (floor, deciding)
+            }
+            (None, _, Some(edge)) if current as i64 > ceiling.limit => {
+                let value = ceiling.limit as usize;
+                let deciding = format!(
+                    "Moved up from priority {} to {} to stay above {} ({})",
+                    current, value, mods[edge.loser].key, rules[edge.rule]
+                );
+                (value, deciding)
+            }
+            _ => (current, format!("Stays at priority {}", current)),
+        };
+        priority[v] = value;
+        reasons[v].push(deciding);
+    }
+
+    // Every rule that bears on a mod, after the deciding reason
+    for v in 0..n {
+        let mut seen = HashSet::new();
+        let related = above[v].iter().map(|e| (e.rule, false)).chain(below[v].iter().map(|e| (e.rule, true)));
+        for (rule, wins) in related {
+            if !seen.insert(rule) {
+                continue;
+            }
+            reasons[v].push(match (&rules[rule], wins) {
+                (LoadOrderRule::After { after, .. }, true) => format!("Loads after {}", after),
+                (LoadOrderRule::After { mod_key, .. }, false) => format!("Loads before {}", mod_key),
+                (LoadOrderRule::CategoryAbove { upper, lower }, true) => format!("In {}, which goes above {}", upper, lower),
+                (LoadOrderRule::CategoryAbove { upper, lower }, false) => format!("In {}, which goes below {}", lower, upper),
+                (LoadOrderRule::Pin { .. }, _) => continue,
+            });
+        }
+    }
+
+    let mut placements: Vec<Placement> = mods
+        .iter()
+        .zip(priority)
+        .zip(reasons)
+        .map(|((m, priority), reasons)| Placement {
+            key: m.key.clone(),
+            from: m.path.clone(),
+            to: target_path(&m.path, m.priority, priority),
+            old_priority: m.priority,
+            priority,
+            reasons,
+        })
+        .collect();
+    placements.sort_by(|a, b| a.priority.cmp(&b.priority).then_with(|| a.key.cmp(&b.key)));
+    Ok(LoadOrderPlan { placements, unused_rules })
+}
+
+/// Mods ordered so every mod comes after all mods that have to stay above it, or the rules that
+/// form a loop
+fn topological_order(mods: &[OrderedMod], rules: &[LoadOrderRule], above: &[Vec<Edge>], below: &[Vec<Edge>]) -> Result<Vec<usize>, String> {
+    let mut waiting: Vec<usize> = above.iter().map(Vec::len).collect();
+    let mut ready: VecDeque<usize> = (0..mods.len()).filter(|&i| waiting[i] == 0).collect();
+    let mut order = Vec::with_capacity(mods.len());
+    while let Some(v) = ready.pop_front() {
+        order.push(v);
+        for edge in &below[v] {
+            waiting[edge.loser] -= 1;
+            if waiting[edge.loser] == 0 {
+                ready.push_back(edge.loser);
+            }
+        }
+    }
+    if order.len() == mods.len() {
+        return Ok(order);
+    }
+
+    // Every mod left over still waits on another left-over mod, so following those back from
+    // any of them runs into a loop
+    let mut trail = Vec::new();
+    let mut visited = vec![None; mods.len()];
+    let mut v = (0..mods.len()).find(|&i| waiting[i] > 0).unwrap();
+    while visited[v].is_none() {
+        visited[v] = Some(trail.len());
+        let edge = above[v].iter().find(|e| waiting[e.winner] > 0).copied().unwrap();
+        trail.push(edge);
+        v = edge.winner;
+    }
+    let cycle: Vec<String> = trail[visited[v].unwrap()..]
+        .iter()
+        .rev()
+        .map(|e| format!("{} above {} ({})", mods[e.winner].key, mods[e.loser].key, rules[e.rule]))
+        .collect();
+    Err(format!("The load order rules contradict each other: {}", cycle.join(", ")))
+}
+
+/// Path of the mod at `path` once moved from priority `from` to `to`
+fn target_path(path: &Path, from: usize, to: usize) -> PathBuf {
+    if from == to {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("pak");
+    path.with_file_name(format!("{}.{}", mod_ops::priority_stem(stem, to), extension))
+}
+
+/// Installed mods of `mods_dir`, with the categories `categories` gives each
+pub fn installed(mods_dir: &Path, mut categories: impl FnMut(&ScannedMod) -> Vec<String>) -> Vec<OrderedMod> {
+    mod_ops::scan_mods(mods_dir)
+        .iter()
+        .filter_map(|scanned| OrderedMod::from_scanned(scanned, categories(scanned)))
+        .collect()
+}
+
+/// Add a rule making `winner` load after `loser`, dropping any rule that says the opposite
+pub fn prefer(rules: &mut Vec<LoadOrderRule>, winner: &str, loser: &str) {
+    rules.retain(|r| !matches!(r, LoadOrderRule::After { mod_key, after } if mod_key == loser && after == winner));
+    let rule = LoadOrderRule::After { mod_key: winner.to_string(), after: loser.to_string() };
+    if !rules.contains(&rule) {
+        rules.push(rule);
+    }
+}
+
+/// Rename every mod `plan` moves. Either every rename happens or none does.
+pub fn apply(plan: &LoadOrderPlan) -> Result<usize, String> {
+    let moves: Vec<(PathBuf, PathBuf)> = plan.changes().flat_map(|p| mod_profiles::file_moves(&p.from, &p.to)).collect();
+    mod_profiles::move_all(&moves)?;
+    let changed = plan.changes().count();
+    info!("[LoadOrder] Moved {} mod(s)", changed);
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn installed_mod(key: &str, priority: usize, categories: &[&str]) -> OrderedMod {
+        OrderedMod {
+            path: PathBuf::from(format!("{}.pak", mod_ops::priority_stem(key, priority))),
+            key: key.to_string(),
+            priority,
+            categories: categories.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    fn after(mod_key: &str, after: &str) -> LoadOrderRule {
+        LoadOrderRule::After { mod_key: mod_key.to_string(), after: after.to_string() }
+    }
+
+    fn priorities(plan: &LoadOrderPlan) -> Vec<(&str, usize)> {
+        plan.placements.iter().map(|p| (p.key.as_str(), p.priority)).collect()
+    }
+
+    #[test]
+    fn rules_move_mods_only_as_far_as_needed() {
+        let mods = [
+            installed_mod("Ui", 1, &[]),
+            installed_mod("Skin", 1, &["Mesh"]),
+            installed_mod("Sounds", 4, &["audio"]),
+            installed_mod("Untouched", 3, &[]),
+        ];
+        let rules = [
+            after("Ui", "Skin"),
+            LoadOrderRule::CategoryAbove { upper: "Mesh".to_string(), lower: "Audio".to_string() },
+            LoadOrderRule::Pin { mod_key: "Ui".to_string(), priority: 0 },
+            after("Gone", "Skin"),
+        ];
+
+        let plan = solve(&mods, &rules).unwrap();
+        assert_eq!(priorities(&plan), [("Ui", 0), ("Skin", 1), ("Untouched", 3), ("Sounds", 4)]);
+        assert_eq!(plan.changes().map(|p| p.key.as_str()).collect::<Vec<_>>(), ["Ui"]);
+        assert_eq!(plan.unused_rules, [after("Gone", "Skin")]);
+
+        let skin = &plan.placements[1];
+        assert_eq!(skin.reasons, ["Stays at priority 1", "Loads before Ui", "In Mesh, which goes above Audio"]);
+    }
+
+    #[test]
+    fn mods_move_down_below_winners_and_up_above_pinned_losers() {
+        let mods = [installed_mod("Base", 2, &[]), installed_mod("Patch", 1, &[]), installed_mod("Last", 5, &[])];
+        let rules = [
+            after("Base", "Patch"),
+            after("Patch", "Last"),
+            LoadOrderRule::Pin { mod_key: "Last".to_string(), priority: 3 },
+        ];
+
+        let plan = solve(&mods, &rules).unwrap();
+        assert_eq!(priorities(&plan), [("Base", 1), ("Patch", 2), ("Last", 3)]);
+        assert_eq!(plan.placements[1].reasons[0], "Moved down from priority 1 to 2 to stay below Base (Base loads after Patch)");
+    }
+
+    #[test]
+    fn pinning_below_a_chain_moves_it_up() {
+        let mods = [installed_mod("Top", 4, &[]), installed_mod("Bottom", 9, &[])];
+        let rules = [after("Top", "Bottom"), LoadOrderRule::Pin { mod_key: "Bottom".to_string(), priority: 2 }];
+
+        let plan = solve(&mods, &rules).unwrap();
+        assert_eq!(priorities(&plan), [("Top", 1), ("Bottom", 2)]);
+        assert_eq!(plan.placements[0].reasons[0], "Moved up from priority 4 to 1 to stay above Bottom (Top loads after Bottom)");
+    }
+
+    #[test]
+    fn contradicting_rules_are_refused_with_the_loop() {
+        let mods = [installed_mod("A", 1, &["Skin"]), installed_mod("B", 1, &["Audio"])];
+        let looping = [
+            after("A", "B"),
+            LoadOrderRule::CategoryAbove { upper: "Audio".to_string(), lower: "Skin".to_string() },
+        ];
+        let err = solve(&mods, &looping).unwrap_err();
+        assert!(err.contains("A above B (A loads after B)"), "{}", err);
+        assert!(err.contains("B above A (Audio above Skin)"), "{}", err);
+
+        let no_room = [after("A", "B"), LoadOrderRule::Pin { mod_key: "B".to_string(), priority: 0 }];
+        assert!(solve(&mods, &no_room).unwrap_err().contains("no room for A above B"));
+    }
+
+    #[test]
+    fn applying_renames_mods_with_their_iostore_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["Hero_9999999_P.pak", "Hero_9999999_P.utoc", "Hero_9999999_P.ucas", "Skin_9999999_P.bak_repak"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+        let mods = installed(dir.path(), |_| Vec::new());
+
+        let plan = solve(&mods, &[after("Skin", "Hero")]).unwrap();
+        assert_eq!(apply(&plan).unwrap(), 1);
+        for name in ["Hero_99999999_P.pak", "Hero_99999999_P.utoc", "Hero_99999999_P.ucas", "Skin_9999999_P.bak_repak"] {
+            assert!(dir.path().join(name).exists(), "{}", name);
+        }
+    }
+}
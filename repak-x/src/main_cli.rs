@@ -0,0 +1,279 @@
+// Headless front end for scripting mod builds.
+// Drives the same install_mod / mod_ops code as the Tauri app and prints JSON on stdout.
+
+mod game_profile;
+mod install_mod;
+mod mod_ops;
+mod uasset_detection;
+mod uasset_api_integration;
+mod utils;
+mod utoc_utils;
+mod character_data;
+
+use clap::{Parser, Subcommand};
+use install_mod::install_mod_logic::install_mods_in_viewport;
+use install_mod::install_mod_logic::progress::{InstallEvent, InstallOutcome};
+use install_mod::install_mod_logic::plan::plan_install;
+use install_mod::map_paths_to_mods;
+use serde::Serialize;
+use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
+use std::path::{Path, PathBuf};
+use utils::{app_dir, usmap_dir};
+
+#[derive(Parser)]
+#[command(name = "repak-x-cli", version, about = "Install and manage game mods without the GUI")]
+struct Cli {
+    /// Mods directory (~mods). Defaults to the game path saved by the app
+    #[arg(long, global = true)]
+    mods_dir: Option<PathBuf>,
+
+    /// USMAP mappings to use. Defaults to the one imported in the app
+    #[arg(long, global = true)]
+    usmap: Option<PathBuf>,
+
+    /// Game profile JSON to use instead of the one saved by the app
+    #[arg(long, global = true)]
+    game_profile: Option<PathBuf>,
+
+    /// Pretty-print the JSON output
+    #[arg(long, global = true)]
+    pretty: bool,
+
+    /// Log progress to stderr
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Install mods from .pak files, IoStore files, folders or archives
+    Install {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Mod name to use instead of the one derived from the file (single mod only)
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        fix_textures: bool,
+        #[arg(long)]
+        fix_serialize_size: bool,
+        /// Repack .pak mods instead of copying them
+        #[arg(long)]
+        to_repak: bool,
+        /// Keep the legacy PAK format instead of converting to IoStore
+        #[arg(long)]
+        force_legacy: bool,
+        /// Subfolder of the mods directory to install into
+        #[arg(long, default_value = "")]
+        subfolder: String,
+        #[arg(long)]
+        parallel: bool,
+        #[arg(long)]
+        obfuscate: bool,
+        /// Print what would be installed without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List installed mods
+    List,
+    /// Enable or disable a mod
+    Toggle { mod_path: PathBuf },
+    /// Set a mod's load priority (0 = highest)
+    Priority { mod_path: PathBuf, priority: usize },
+    /// Report enabled mods at the same priority that replace the same files
+    Clashes,
+    /// Recompress mods that aren't using Oodle
+    Recompress {
+        /// Encoder preset: fast, default or max
+        #[arg(long, default_value = "default")]
+        mode: String,
+    },
+    /// Extract a mod's assets into DEST/<mod name>
+    Extract { mod_path: PathBuf, dest: PathBuf },
+}
+
+#[derive(Serialize)]
+struct InstalledMod {
+    mod_type: String,
+    /// Archives the mod was extracted from, outermost first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    archive_origin: Vec<String>,
+    #[serde(flatten)]
+    outcome: InstallOutcome,
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let level = if cli.verbose { log::LevelFilter::Info } else { log::LevelFilter::Warn };
+    let _ = TermLogger::init(level, Config::default(), TerminalMode::Stderr, ColorChoice::Auto);
+
+    let output = run(&cli);
+    let json = match &output {
+        Ok(value) => value.clone(),
+        Err(e) => serde_json::json!({ "error": e }),
+    };
+    let text = if cli.pretty {
+        serde_json::to_string_pretty(&json)
+    } else {
+        serde_json::to_string(&json)
+    };
+    println!("{}", text.unwrap_or_default());
+
+    if output.is_err() {
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: &Cli) -> Result<serde_json::Value, String> {
+    if let Some(path) = &cli.game_profile {
+        game_profile::set_active(game_profile::load_from(path)?);
+    }
+    let mods_dir = resolve_mods_dir(cli.mods_dir.as_deref())?;
+
+    // Installs take the mappings per mod; extraction cleanup picks them up from the environment
+    let usmap = resolve_usmap(cli.usmap.as_deref())?;
+    if let Some(usmap) = &usmap {
+        std::env::set_var("USMAP_PATH", usmap);
+    }
+
+    let value = match &cli.command {
+        Command::Install {
+            paths,
+            name,
+            fix_textures,
+            fix_serialize_size,
+            to_repak,
+            force_legacy,
+            subfolder,
+            parallel,
+            obfuscate,
+            dry_run,
+        } => {
+            let mut mods = map_paths_to_mods(paths);
+            if mods.is_empty() {
+                return Err("No valid mods found to install".to_string());
+            }
+            if name.is_some() && mods.len() > 1 {
+                return Err("--name can only be used when installing a single mod".to_string());
+            }
+
+            for installable in &mut mods {
+                if let Some(name) = name {
+                    installable.mod_name = name.clone();
+                }
+                installable.fix_textures = *fix_textures;
+                installable.fix_serialsize_header = *fix_serialize_size;
+                installable.repak = *to_repak;
+                installable.force_legacy_pak = *force_legacy;
+                installable.install_subfolder = subfolder.clone();
+                installable.usmap_path = usmap.as_ref()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                installable.parallel_processing = *parallel;
+                installable.obfuscate = *obfuscate;
+            }
+
+            if *dry_run {
+                return to_json(&plan_install(&mods, &mods_dir));
+            }
+
+            std::fs::create_dir_all(&mods_dir)
+                .map_err(|e| format!("Failed to create mods directory: {}", e))?;
+
+            let cancel = uasset_toolkit::CancellationToken::new();
+            let outcomes = install_mods_in_viewport(&mut mods, &mods_dir, &|event| {
+                if let InstallEvent::Stage { index, total, mod_name, stage } = event {
+                    log::info!("[{}/{}] {}: {:?}", index + 1, total, mod_name, stage);
+                }
+            }, &cancel);
+
+            let installed: Vec<InstalledMod> = mods.iter()
+                .zip(outcomes)
+                .map(|(m, outcome)| InstalledMod {
+                    mod_type: m.mod_type.clone(),
+                    archive_origin: m.archive_origin.clone(),
+                    outcome,
+                })
+                .collect();
+            to_json(&installed)?
+        }
+        Command::List => {
+            if !mods_dir.exists() {
+                return Err(format!("Game path does not exist: {}", mods_dir.display()));
+            }
+            to_json(&mod_ops::scan_mods(&mods_dir))?
+        }
+        Command::Toggle { mod_path } => {
+            let enabled = mod_ops::toggle_mod(&resolve_mod(&mods_dir, mod_path))?;
+            serde_json::json!({ "enabled": enabled })
+        }
+        Command::Priority { mod_path, priority } => {
+            let new_path = mod_ops::set_mod_priority(&resolve_mod(&mods_dir, mod_path), *priority)?;
+            serde_json::json!({ "path": new_path, "priority": priority })
+        }
+        Command::Clashes => to_json(&mod_ops::find_mod_clashes(&mods_dir, &mut mod_ops::Disk)?)?,
+        Command::Recompress { mode } => {
+            let oodle_settings = mod_ops::oodle_settings_for_mode(Some(mode))?;
+            let result = mod_ops::recompress_mods(&mods_dir, &mut mod_ops::Disk, oodle_settings, |current, total, status| {
+                log::info!("[{}/{}] {}", current, total, status);
+            })?;
+            to_json(&result)?
+        }
+        Command::Extract { mod_path, dest } => {
+            let file_count = mod_ops::extract_mod_assets(&resolve_mod(&mods_dir, mod_path), dest)?;
+            serde_json::json!({ "extracted": file_count })
+        }
+    };
+    Ok(value)
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<serde_json::Value, String> {
+    serde_json::to_value(value).map_err(|e| e.to_string())
+}
+
+/// Mod paths may be given relative to the mods directory
+fn resolve_mod(mods_dir: &Path, mod_path: &Path) -> PathBuf {
+    if mod_path.exists() {
+        mod_path.to_path_buf()
+    } else {
+        mods_dir.join(mod_path)
+    }
+}
+
+/// `--mods-dir`, or the game path saved in the app's state.json
+fn resolve_mods_dir(mods_dir: Option<&Path>) -> Result<PathBuf, String> {
+    if let Some(dir) = mods_dir {
+        return Ok(dir.to_path_buf());
+    }
+    let state_path = app_dir().join("state.json");
+    let state: serde_json::Value = std::fs::read_to_string(&state_path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default();
+    state.get("game_path")
+        .and_then(|p| p.as_str())
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| format!("No mods directory configured in {}; pass --mods-dir", state_path.display()))
+}
+
+/// `--usmap`, or the first USMAP in the app's Usmap folder (the same one the app picks on startup)
+fn resolve_usmap(usmap: Option<&Path>) -> Result<Option<PathBuf>, String> {
+    if let Some(usmap) = usmap {
+        if !usmap.exists() {
+            return Err(format!("USMAP file not found: {}", usmap.display()));
+        }
+        return Ok(Some(usmap.to_path_buf()));
+    }
+    let found = std::fs::read_dir(usmap_dir())
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .find(|p| p.extension().and_then(|s| s.to_str()) == Some("usmap"));
+    Ok(found)
+}
@@ -3,7 +3,16 @@
 
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod asset_conflicts;
 mod install_mod;
+mod game_profile;
+mod load_order;
+mod mod_ops;
+mod mod_identity;
+mod mod_index;
+mod mod_library;
+mod mod_profiles;
+mod mod_versions;
 mod uasset_detection;
 mod uasset_api_integration;
 mod utils;
@@ -21,14 +30,16 @@ mod toast_events;
 mod discord_presence;
 
 use uasset_detection::detect_texture_files_async;
+use uasset_toolkit::usmap::{Usmap, UsmapHeader};
 use log::{info, warn, error};
 use serde::{Deserialize, Serialize};
 use simplelog::{ColorChoice, CombinedLogger, Config, TermLogger, TerminalMode, WriteLogger};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use tauri::{Emitter, Listener, Manager, State, Window};
-use utils::find_marvel_rivals;
+use utils::{app_dir, find_game_root, get_usmap_full_path, usmap_dir};
 use walkdir::WalkDir;
 use regex_lite::Regex;
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
@@ -81,6 +92,15 @@ struct AppState {
     /// Enable obfuscation (encrypts IoStore with game's AES key to block FModel extraction)
     #[serde(default)]
     obfuscate: bool,
+    /// Saved loadouts of enabled mods, priorities and folders
+    #[serde(default)]
+    profiles: Vec<mod_profiles::ModProfile>,
+    /// Name of the profile last applied
+    #[serde(default)]
+    active_profile: Option<String>,
+    /// Rules the load order is worked out from
+    #[serde(default)]
+    load_order_rules: Vec<load_order::LoadOrderRule>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -117,8 +137,12 @@ struct RootFolderInfo {
     subfolder_count: usize,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 struct ModMetadata {
+    /// Stable ID of the mod (see mod_identity); `None` in state saved before mods had IDs
+    #[serde(default)]
+    id: Option<String>,
+    /// Where the mod was last seen
     path: PathBuf,
     custom_name: Option<String>,
     folder_id: Option<String>,
@@ -126,6 +150,130 @@ struct ModMetadata {
     custom_tags: Vec<String>,
 }
 
+/// Whether `a` and `b` name the same mod file, ignoring whether it's enabled or disabled
+fn same_mod_path(a: &Path, b: &Path) -> bool {
+    a == b || a.with_extension("pak") == b.with_extension("pak")
+}
+
+/// Whether the mod file at `path` is still there, enabled or disabled
+fn mod_file_exists(path: &Path) -> bool {
+    ["pak", "bak_repak", "pak_disabled"].iter().any(|ext| path.with_extension(ext).exists())
+}
+
+impl AppState {
+    /// Metadata of the mod with `id` at `path`: the entry with that ID and path, or else one with
+    /// that ID whose mod left its path, as it was renamed or moved. `listed` maps the paths of
+    /// known mods (as .pak) to their IDs; other paths count as left when nothing is there.
+    /// Copies of the same mod share an ID but each keep their own entry.
+    fn metadata_by_id(
+        &self,
+        id: Option<&str>,
+        path: &Path,
+        taken: &HashSet<usize>,
+        listed: &HashMap<PathBuf, Option<&str>>,
+    ) -> Option<usize> {
+        let id = id?;
+        let candidates = || {
+            self.mod_metadata.iter().enumerate().filter(move |(i, m)| !taken.contains(i) && m.id.as_deref() == Some(id))
+        };
+        let left = |m: &ModMetadata| match listed.get(&m.path.with_extension("pak")) {
+            Some(current) => *current != Some(id),
+            None => !mod_file_exists(&m.path),
+        };
+        candidates()
+            .find(|(_, m)| same_mod_path(&m.path, path))
+            .or_else(|| candidates().find(|(_, m)| left(m)))
+            .map(|(i, _)| i)
+    }
+
+    /// Metadata last seen at `path` under another ID (or none): the mod was rewritten in place,
+    /// e.g. recompressed or patched, or the entry predates IDs. Entries whose ID belongs to one of
+    /// the `live` mods stay with that mod.
+    fn metadata_by_path(&self, path: &Path, taken: &HashSet<usize>, live: &HashSet<&str>) -> Option<usize> {
+        self.mod_metadata
+            .iter()
+            .enumerate()
+            .find(|(i, m)| {
+                !taken.contains(i)
+                    && same_mod_path(&m.path, path)
+                    && !m.id.as_deref().is_some_and(|id| live.contains(id))
+            })
+            .map(|(i, _)| i)
+    }
+
+    /// Point metadata entry `index` at the mod with `id` at `path`
+    fn rekey_metadata(&mut self, index: usize, id: Option<&str>, path: &Path) {
+        let metadata = &mut self.mod_metadata[index];
+        if id.is_some() {
+            metadata.id = id.map(str::to_string);
+        }
+        metadata.path = path.to_path_buf();
+    }
+
+    /// Position of the metadata of the mod with `id` at `path`, pointing the entry at both
+    fn metadata_index(&mut self, id: Option<&str>, path: &Path) -> Option<usize> {
+        let none = HashSet::new();
+        let index = self
+            .metadata_by_id(id, path, &none, &HashMap::new())
+            .or_else(|| self.metadata_by_path(path, &none, &HashSet::new()))?;
+        self.rekey_metadata(index, id, path);
+        Some(index)
+    }
+
+    /// Metadata of the mod with `id` (see mod_identity) at `path`, created empty if it has none
+    /// yet. The ID is worked out by the caller since hashing a large mod must not hold the lock.
+    fn metadata_entry(&mut self, id: Option<String>, path: &Path) -> &mut ModMetadata {
+        let index = match self.metadata_index(id.as_deref(), path) {
+            Some(index) => index,
+            None => {
+                self.mod_metadata.push(ModMetadata {
+                    id,
+                    path: path.to_path_buf(),
+                    custom_name: None,
+                    folder_id: None,
+                    custom_tags: Vec::new(),
+                });
+                self.mod_metadata.len() - 1
+            }
+        };
+        &mut self.mod_metadata[index]
+    }
+
+    /// Match every mod in `paths` (with the IDs from mod_identity::mod_ids) to its metadata,
+    /// migrating path-keyed entries and following renames and moves. Saves if anything changed.
+    fn sync_mod_metadata(&mut self, paths: &[PathBuf], ids: &[Option<String>]) -> Vec<Option<usize>> {
+        let before = self.mod_metadata.clone();
+        let live: HashSet<&str> = ids.iter().flatten().map(String::as_str).collect();
+        let listed: HashMap<PathBuf, Option<&str>> =
+            paths.iter().zip(ids).map(|(path, id)| (path.with_extension("pak"), id.as_deref())).collect();
+        let mut taken = HashSet::new();
+        let mut indices = vec![None; paths.len()];
+        // Every mod claims its entry by ID before any is matched by path, so a mod that moved
+        // keeps its metadata even when another mod now sits where it was
+        for (slot, (path, id)) in indices.iter_mut().zip(paths.iter().zip(ids)) {
+            *slot = self.metadata_by_id(id.as_deref(), path, &taken, &listed);
+            taken.extend(*slot);
+        }
+        for (slot, path) in indices.iter_mut().zip(paths) {
+            if slot.is_none() {
+                *slot = self.metadata_by_path(path, &taken, &live);
+                taken.extend(*slot);
+            }
+        }
+        for ((path, id), index) in paths.iter().zip(ids).zip(&indices) {
+            if let Some(index) = *index {
+                self.rekey_metadata(index, id.as_deref(), path);
+            }
+        }
+        if self.mod_metadata != before {
+            if let Err(e) = save_state(self) {
+                warn!("Failed to save mod metadata: {}", e);
+            }
+        }
+        indices
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 struct ModEntry {
     path: PathBuf,
@@ -273,12 +421,26 @@ async fn set_game_path(path: String, state: State<'_, Arc<Mutex<AppState>>>) ->
     Ok(())
 }
 
+/// Get the active game profile (AES keys, pak version, install layout, ...)
+#[tauri::command]
+async fn get_game_profile() -> Result<game_profile::GameProfile, String> {
+    Ok((*game_profile::active()).clone())
+}
+
+/// Save a game profile to the app directory and switch to it
+#[tauri::command]
+async fn set_game_profile(profile: game_profile::GameProfile) -> Result<(), String> {
+    info!("Switching to game profile '{}'", profile.name);
+    game_profile::save(profile)
+}
+
 #[tauri::command]
 async fn auto_detect_game_path(state: State<'_, Arc<Mutex<AppState>>>, window: Window) -> Result<String, String> {
-    match find_marvel_rivals() {
+    let profile = game_profile::active();
+    match find_game_root() {
         Some(game_root) => {
             // game_path should be the ~mods directory (matching egui behavior)
-            let mods_path = game_root.join("~mods");
+            let mods_path = profile.mods_path(&game_root);
             
             // Create ~mods directory if it doesn't exist
             if !mods_path.exists() {
@@ -302,7 +464,7 @@ async fn auto_detect_game_path(state: State<'_, Arc<Mutex<AppState>>>, window: W
             Ok(mods_path.to_string_lossy().to_string())
         }
         None => {
-            let error_msg = "Could not auto-detect Marvel Rivals installation".to_string();
+            let error_msg = format!("Could not auto-detect {} installation", profile.name);
             toast_events::emit_game_path_failed(&window, &error_msg);
             Err(error_msg)
         }
@@ -332,9 +494,15 @@ async fn start_file_watcher(
     let watcher_result = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
         match res {
             Ok(event) => {
+                if event.need_rescan() {
+                    mod_index::lost_track();
+                }
                 // We only care about Create, Remove, Rename, and Modify events (files and directories)
                 match event.kind {
                     EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(_) => {
+                         // The index follows every change, only the UI refresh is debounced
+                         mod_index::changed(&event.paths);
+
                          // Debounce: only emit if 500ms have passed since last event
                          let mut last_time = last_event_time.lock().unwrap();
                          let now = std::time::Instant::now();
@@ -350,7 +518,10 @@ async fn start_file_watcher(
                     _ => {}
                 }
             },
-            Err(e) => error!("Watch error: {:?}", e),
+            Err(e) => {
+                error!("Watch error: {:?}", e);
+                mod_index::lost_track();
+            }
         }
     });
 
@@ -362,6 +533,7 @@ async fn start_file_watcher(
             }
             info!("Started watching game path: {:?}", game_path);
             *watcher_guard = Some(watcher);
+            mod_index::lock().watch(&game_path);
             Ok(())
         },
         Err(e) => {
@@ -373,8 +545,7 @@ async fn start_file_watcher(
 
 #[tauri::command]
 async fn get_pak_files(state: State<'_, Arc<Mutex<AppState>>>) -> Result<Vec<ModEntry>, String> {
-    let state = state.lock().unwrap();
-    let game_path = &state.game_path;
+    let game_path = state.lock().unwrap().game_path.clone();
     
     info!("Loading mods from: {}", game_path.display());
     
@@ -384,108 +555,32 @@ async fn get_pak_files(state: State<'_, Arc<Mutex<AppState>>>) -> Result<Vec<Mod
     }
 
     // game_path IS the ~mods directory (matching egui behavior)
-    let mut mods = Vec::new();
-    
-    // Scan root ~mods directory and all subdirectories recursively (no depth limit)
-    for entry in WalkDir::new(&game_path)
+    let scanned_mods = mod_index::lock().mods(&game_path);
+    // Hashing new mods can take a moment, so IDs are worked out before taking the state lock
+    let paths: Vec<PathBuf> = scanned_mods.iter().map(|m| m.path.clone()).collect();
+    let ids = mod_index::ids(&paths);
+
+    let mut state = state.lock().unwrap();
+    let indices = state.sync_mod_metadata(&paths, &ids);
+    let mods: Vec<ModEntry> = scanned_mods
         .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let path = entry.path();
-        
-        // Skip directories themselves
-        if path.is_dir() {
-            continue;
-        }
-        
-        let ext = path.extension().and_then(|s| s.to_str());
-        
-        // Check for .pak, .bak_repak, and .pak_disabled files
-        if ext == Some("pak") || ext == Some("bak_repak") || ext == Some("pak_disabled") {
-            let is_enabled = ext == Some("pak");
-            
-            // Determine which folder this mod is in
-            let root_folder_name = game_path.file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("~mods")
-                .to_string();
-            
-            // Determine folder_id based on relative path from game_path
-            let folder_id = if let Some(parent) = path.parent() {
-                if parent == game_path {
-                    // Mod is directly in root - use root folder name (e.g., "~mods")
-                    Some(root_folder_name)
-                } else {
-                    // Mod is in a subfolder - use relative path from game_path as ID
-                    parent.strip_prefix(game_path)
-                        .map(|p| p.to_string_lossy().replace('\\', "/"))
-                        .ok()
-                }
-            } else {
-                Some(root_folder_name)
-            };
-            
-            info!("Found PAK file: {} (enabled: {}, folder: {:?})", path.display(), is_enabled, folder_id);
-            
-            let metadata = state.mod_metadata.iter()
-                .find(|m| {
-                    m.path == path || 
-                    m.path.with_extension("pak") == path || 
-                    m.path.with_extension("bak_repak") == path ||
-                    m.path.with_extension("pak_disabled") == path
-                });
-            
-            let ucas_path = path.with_extension("ucas");
-            let file_size = if ucas_path.exists() {
-                std::fs::metadata(&ucas_path)
-                    .map(|m| m.len())
-                    .unwrap_or(0)
-            } else {
-                std::fs::metadata(path)
-                    .map(|m| m.len())
-                    .unwrap_or(0)
-            };
-            
-            // Calculate priority
-            // Priority 0 = "!" prefix (highest priority)
-            // Priority 1-N = 7-N+6 nines displayed as 1-based (7 nines → Priority 1, 8 nines → Priority 2, etc.)
-            let mut priority = 0;
-            let file_stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
-            
-            // Check for "!" prefix (highest priority)
-            if file_stem.starts_with("!") {
-                priority = 0; // Highest priority
-            } else if file_stem.ends_with("_P") {
-                let base_no_p = file_stem.strip_suffix("_P").unwrap();
-                // Check for _999... suffix
-                let re_nums = Regex::new(r"_(\d+)$").unwrap();
-                if let Some(caps) = re_nums.captures(base_no_p) {
-                    let nums = &caps[1];
-                    // Verify they are all 9s
-                    if nums.chars().all(|c| c == '9') {
-                        let actual_nines = nums.len();
-                        // Convert actual nines count to UI priority (1-based)
-                        // 7 nines → Priority 1, 8 nines → Priority 2, etc.
-                        if actual_nines >= 7 {
-                            priority = actual_nines - 6;
-                        }
-                    }
-                }
-            }
+        .zip(indices)
+        .map(|(scanned, index)| {
+            let metadata = index.map(|i| &state.mod_metadata[i]);
             
-            mods.push(ModEntry {
-                path: path.to_path_buf(),
-                enabled: is_enabled,
+            ModEntry {
                 custom_name: metadata.and_then(|m| m.custom_name.clone()),
-                folder_id,
                 custom_tags: metadata.map(|m| m.custom_tags.clone()).unwrap_or_default(),
-                file_size,
-                priority,
+                path: scanned.path,
+                enabled: scanned.enabled,
+                folder_id: scanned.folder_id,
+                file_size: scanned.file_size,
+                priority: scanned.priority,
                 character_name: None,
                 skin_name: None,
-            });
-        }
-    }
+            }
+        })
+        .collect();
 
     info!("Found {} mod(s)", mods.len());
     Ok(mods)
@@ -493,78 +588,7 @@ async fn get_pak_files(state: State<'_, Arc<Mutex<AppState>>>) -> Result<Vec<Mod
 
 #[tauri::command]
 async fn set_mod_priority(mod_path: String, priority: usize) -> Result<(), String> {
-    let path = PathBuf::from(&mod_path);
-    if !path.exists() {
-         return Err("Mod file does not exist".to_string());
-    }
-    
-    let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
-    let stem = path.file_stem().and_then(|s| s.to_str()).ok_or("Invalid filename")?;
-    
-    // Strip leading "!" if present (highest priority marker)
-    let stem_no_exclaim = stem.strip_prefix("!").unwrap_or(stem);
-    
-    // 1. Strip _P if present
-    let base_no_p = if stem_no_exclaim.ends_with("_P") {
-        stem_no_exclaim.strip_suffix("_P").unwrap()
-    } else {
-        stem_no_exclaim
-    };
-    
-    // 2. Strip _999... if present
-    let re = Regex::new(r"^(.*)_(\d+)$").unwrap();
-    let clean_base = if let Some(caps) = re.captures(base_no_p) {
-        let prefix = &caps[1];
-        let numbers = &caps[2];
-        if numbers.chars().all(|c| c == '9') {
-            prefix.to_string()
-        } else {
-            base_no_p.to_string()
-        }
-    } else {
-        base_no_p.to_string()
-    };
-    
-    // 3. Construct new name with new priority
-    // Priority 0 = "!" prefix (highest priority) with minimum 7 nines
-    // Priority 1-N = 7-N+6 nines (1→7 nines, 2→8 nines, etc.)
-    let new_stem = if priority == 0 {
-        // Highest priority: use "!" prefix with minimum 7 nines
-        let min_nines = "9".repeat(7);
-        format!("!{}_{}_P", clean_base, min_nines)
-    } else {
-        // Convert UI priority (1-based) to actual nines count (7-based)
-        // Remove "!" prefix if present (since priority > 0)
-        let actual_nines = priority + 6; // Priority 1 → 7 nines, Priority 2 → 8 nines, etc.
-        let new_nines = "9".repeat(actual_nines);
-        format!("{}_{}_P", clean_base, new_nines)
-    };
-    
-    let new_filename = format!("{}.{}", new_stem, extension);
-    let new_path = path.with_file_name(&new_filename);
-    
-    if new_path == path {
-        return Ok(()); // No change
-    }
-
-    if new_path.exists() {
-        return Err("A mod with this priority already exists".to_string());
-    }
-    
-    // Rename main file
-    std::fs::rename(&path, &new_path).map_err(|e| format!("Failed to rename mod: {}", e))?;
-    
-    // Rename associated files (.utoc, .ucas)
-    let exts = ["utoc", "ucas"];
-    for ext in exts {
-        let old_f = path.with_extension(ext);
-        if old_f.exists() {
-             let new_f = new_path.with_extension(ext);
-             let _ = std::fs::rename(old_f, new_f);
-        }
-    }
-    
-    Ok(())
+    mod_ops::set_mod_priority(Path::new(&mod_path), priority).map(|_| ())
 }
 
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
@@ -572,6 +596,8 @@ struct InstallableModInfo {
     mod_name: String,
     mod_type: String,
     is_dir: bool,
+    /// Loose content files installed as a folder mod, whether dropped as a folder or in an archive
+    is_loose: bool,
     path: String,
     auto_fix_texture: bool,
     auto_fix_serialize_size: bool,
@@ -581,6 +607,24 @@ struct InstallableModInfo {
     contains_uassets: bool,
 }
 
+/// Assets worth handing to UAssetAPI for detection: SK_, SM_ and T_ .uasset/.uexp files,
+/// limited to 40 (20 uasset + 20 uexp pairs)
+fn detection_samples(files: &[String]) -> Vec<&String> {
+    files.iter()
+        .filter(|f| {
+            let lower = f.to_lowercase();
+            (lower.ends_with(".uasset") || lower.ends_with(".uexp")) &&
+            if let Some(filename) = std::path::Path::new(f).file_name().and_then(|n| n.to_str()) {
+                let fname_lower = filename.to_lowercase();
+                fname_lower.starts_with("sk_") || fname_lower.starts_with("sm_") || fname_lower.starts_with("t_")
+            } else {
+                false
+            }
+        })
+        .take(40)
+        .collect()
+}
+
 #[tauri::command]
 async fn parse_dropped_files(
     paths: Vec<String>,
@@ -588,9 +632,6 @@ async fn parse_dropped_files(
     window: Window
 ) -> Result<Vec<InstallableModInfo>, String> {
     use crate::utils::get_current_pak_characteristics;
-    use repak::PakBuilder;
-    use repak::utils::AesKey;
-    use std::str::FromStr;
     use std::fs::File;
     use std::io::BufReader;
     
@@ -622,9 +663,15 @@ async fn parse_dropped_files(
     let mut mods = Vec::new();
     
     // Filter out .utoc and .ucas files - they will be handled with their .pak file
+    // Later volumes of split archives are read through the first volume
+    use crate::install_mod::install_mod_logic::archives::{archive_kind, extract_archive_nested, is_secondary_volume, ExtractGuard};
+    use crate::install_mod::install_mod_logic::archive_reader::ArchivePreview;
     let filtered_paths: Vec<String> = paths.into_iter()
         .filter(|p| {
             let path = PathBuf::from(p);
+            if is_secondary_volume(&path) {
+                return false;
+            }
             if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
                 ext != "utoc" && ext != "ucas"
             } else {
@@ -771,26 +818,80 @@ async fn parse_dropped_files(
             // Get file extension
             let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
             
-            // Check if it's an archive file (zip, rar, 7z)
-            if ext == "zip" || ext == "rar" || ext == "7z" {
-                use crate::install_mod::install_mod_logic::archives::{extract_zip, extract_rar, extract_7z};
+            // Check if it's an archive file (zip, rar, 7z, tarball, split volumes)
+            if let Some(kind) = archive_kind(&path) {
                 use walkdir::WalkDir;
                 
-                let _ = window.emit("install_log", format!("[Detection] Archive detected: {} ({})", mod_name, ext));
+                let _ = window.emit("install_log", format!("[Detection] Archive detected: {} ({:?})", mod_name, kind));
+                
+                // Zip and 7z holding a single mod can be classified from the pak index, .utoc or
+                // file list alone, without unpacking a possibly multi-GB download
+                match ArchivePreview::open(&path) {
+                    Ok(Some(mut preview)) => {
+                        let _ = window.emit("install_log", format!("[Detection] Read {} file entries from archive without extracting", preview.files.len()));
+                        if preview.is_iostore {
+                            let _ = window.emit("install_log", format!("[Detection] IoStore package detected in archive: {}", mod_name));
+                        }
+                        
+                        let files = preview.files.clone();
+                        let mod_type = if preview.is_loose {
+                            get_current_pak_characteristics(files.clone())
+                        } else {
+                            crate::utils::get_pak_characteristics_detailed(files.clone()).mod_type
+                        };
+                        let _ = window.emit("install_log", format!("[Detection] Detected mod type: {}", mod_type));
+                        
+                        // Only scan for textures - mesh fixes are automatic in ZenConverter
+                        let has_ubulk = files.iter().any(|f| f.to_lowercase().ends_with(".ubulk"));
+                        let has_texture = match tempfile::tempdir() {
+                            Ok(samples_dir) if has_ubulk => {
+                                let mut samples = preview.extract_samples(&detection_samples(&files), samples_dir.path());
+                                let _ = window.emit("install_log", format!("[Detection] Extracted {} uasset files for UAssetAPI", samples.len()));
+                                if samples.is_empty() {
+                                    false
+                                } else {
+                                    samples.push("dummy.ubulk".to_string()); // Signal that .ubulk exists
+                                    detect_texture_files_async(&samples).await
+                                }
+                            }
+                            _ => false,
+                        };
+                        let summary = format!("[Detection] Archive results: texture={} (mesh fixes automatic)", has_texture);
+                        info!("{}", summary);
+                        let _ = window.emit("install_log", &summary);
+                        
+                        mods.push(InstallableModInfo {
+                            mod_name,
+                            mod_type,
+                            is_dir: false,
+                            is_loose: preview.is_loose,
+                            path: path_str,
+                            auto_fix_texture: has_texture,
+                            auto_fix_serialize_size: false, // Mesh fixes are automatic
+                            auto_to_repak: !preview.is_loose && !preview.is_iostore,
+                            contains_uassets: crate::install_mod::contains_uasset_files(&files),
+                        });
+                        continue;
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("[Detection] Could not read {} without extracting: {}", mod_name, e),
+                }
                 
-                // Extract archive to temp directory for analysis
+                // Extract archive (and any archives inside it) to temp directory for analysis
                 let temp_dir = tempfile::tempdir().ok();
                 if let Some(ref temp) = temp_dir {
                     let temp_path = temp.path().to_str().unwrap();
                     
-                    // Extract based on type
-                    let extract_result = if ext == "zip" {
-                        extract_zip(path.to_str().unwrap(), temp_path)
-                    } else if ext == "rar" {
-                        extract_rar(path.to_str().unwrap(), temp_path).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-                    } else {
-                        extract_7z(path.to_str().unwrap(), temp_path)
-                    };
+                    let extract_result = extract_archive_nested(&path, temp.path(), &mut ExtractGuard::default());
+                    
+                    if let Err(e) = &extract_result {
+                        error!("[Detection] Failed to extract {}: {}", mod_name, e);
+                        let _ = window.emit("install_log", format!("[Detection] ERROR: {}: {}", mod_name, e));
+                        // Don't offer anything from an archive that tried to escape or blow up
+                        if e.is_rejected() {
+                            continue;
+                        }
+                    }
                     
                     if extract_result.is_ok() {
                         let _ = window.emit("install_log", "[Detection] Archive extracted successfully");
@@ -845,10 +946,8 @@ async fn parse_dropped_files(
                                     .collect();
                                 if utoc_files.is_empty() { None } else { Some(utoc_files) }
                             } else if let Ok(file) = File::open(entry_path) {
-                                if let Ok(aes_key) = AesKey::from_str("0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74") {
-                                    let mut reader = BufReader::new(file);
-                                    PakBuilder::new().key(aes_key.0).reader(&mut reader).ok().map(|pak| pak.files())
-                                } else { None }
+                                let mut reader = BufReader::new(file);
+                                game_profile::active().open_pak(&mut reader).ok().map(|pak| pak.files())
                             } else { None };
                             
                             if let Some(files) = files {
@@ -861,27 +960,14 @@ async fn parse_dropped_files(
                                         let _ = window.emit("install_log", format!("[Detection] Detected mod type: {}", mod_type));
                                         
                                         // Get files to extract (both .uasset and .uexp needed by UAssetAPI)
-                                        // Prioritize SK_, SM_, T_ files for detection
-                                        let files_to_extract: Vec<&String> = files.iter()
-                                            .filter(|f| {
-                                                let lower = f.to_lowercase();
-                                                (lower.ends_with(".uasset") || lower.ends_with(".uexp")) &&
-                                                if let Some(filename) = std::path::Path::new(f).file_name().and_then(|n| n.to_str()) {
-                                                    let fname_lower = filename.to_lowercase();
-                                                    fname_lower.starts_with("sk_") || fname_lower.starts_with("sm_") || fname_lower.starts_with("t_")
-                                                } else {
-                                                    false
-                                                }
-                                            })
-                                            .take(40)  // Limit to 40 files (20 uasset + 20 uexp pairs)
-                                            .collect();
+                                        let files_to_extract = detection_samples(&files);
                                         
                                         let _ = window.emit("install_log", format!("[Detection] Extracting {} files from archive PAK for analysis...", files_to_extract.len()));
                                         
                                         // Extract to temp directory for UAssetAPI analysis
                                         let mut extracted_paths: Vec<String> = Vec::new();
                                         let uasset_temp_dir = tempfile::tempdir().ok();
-                                        let aes_key_for_extraction = AesKey::from_str("0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74").unwrap();
+                                        let profile = game_profile::active();
                                         
                                         if let Some(ref uasset_temp) = uasset_temp_dir {
                                             use rayon::prelude::*;
@@ -894,7 +980,7 @@ async fn parse_dropped_files(
                                             files_to_extract.par_iter().for_each(|internal_path| {
                                                 if let Ok(file) = File::open(&pak_path) {
                                                     let mut reader = BufReader::new(file);
-                                                    if let Ok(pak) = PakBuilder::new().key(aes_key_for_extraction.0.clone()).reader(&mut reader) {
+                                                    if let Ok(pak) = profile.open_pak(&mut reader) {
                                                         // Use just the filename to preserve .uasset/.uexp pairing
                                                         let filename = std::path::Path::new(internal_path.as_str())
                                                             .file_name()
@@ -950,6 +1036,7 @@ async fn parse_dropped_files(
                                             mod_name,
                                             mod_type,
                                             is_dir: false,
+                                            is_loose: false,
                                             path: path_str,
                                             auto_fix_texture: has_texture,
                                             auto_fix_serialize_size: false, // Mesh fixes are automatic
@@ -1007,7 +1094,8 @@ async fn parse_dropped_files(
                                     return Ok(vec![InstallableModInfo {
                                         mod_name,
                                         mod_type,
-                                        is_dir: true,
+                                        is_dir: false,
+                                        is_loose: true,
                                         path: path_str,
                                         auto_fix_texture: has_texture,
                                         auto_fix_serialize_size: false, // Mesh fixes are automatic
@@ -1036,24 +1124,21 @@ async fn parse_dropped_files(
                 // For IoStore, read from utoc directly (works with obfuscated mods);
                 // otherwise open PAK with AES key
                 let mod_type = {
-                    let files_and_key: Option<(Vec<String>, Option<repak::utils::AesKey>)> = if is_iostore {
+                    let files: Option<Vec<String>> = if is_iostore {
                         use crate::utoc_utils::read_utoc;
                         let _ = window.emit("install_log", "[Detection] Reading IoStore .utoc file for accurate file list");
                         let utoc_files: Vec<String> = read_utoc(&utoc_path)
                             .iter()
                             .map(|entry| entry.file_path.clone())
                             .collect();
-                        if utoc_files.is_empty() { None } else { Some((utoc_files, None)) }
+                        if utoc_files.is_empty() { None } else { Some(utoc_files) }
                     } else if let Ok(file) = File::open(&path) {
-                        if let Ok(aes_key) = AesKey::from_str("0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74") {
-                            let aes_key_for_extraction = aes_key.clone();
-                            let mut reader = BufReader::new(file);
-                            PakBuilder::new().key(aes_key.0).reader(&mut reader).ok().map(|pak| (pak.files(), Some(aes_key_for_extraction)))
-                        } else { None }
+                        let mut reader = BufReader::new(file);
+                        game_profile::active().open_pak(&mut reader).ok().map(|pak| pak.files())
                     } else { None };
                     
-                    if let Some((files, aes_key_opt)) = files_and_key {
-                        let aes_key_for_extraction = aes_key_opt.unwrap_or_else(|| AesKey::from_str("0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74").unwrap());
+                    if let Some(files) = files {
+                        let profile = game_profile::active();
                             
                             // Use detailed characteristics (same as get_mod_details)
                             use crate::utils::get_pak_characteristics_detailed;
@@ -1096,7 +1181,7 @@ async fn parse_dropped_files(
                                     // Each thread opens its own file handle
                                     if let Ok(file) = File::open(&pak_path) {
                                         let mut reader = BufReader::new(file);
-                                        if let Ok(pak) = PakBuilder::new().key(aes_key_for_extraction.0.clone()).reader(&mut reader) {
+                                        if let Ok(pak) = profile.open_pak(&mut reader) {
                                             // Sanitize filename for filesystem
                                             let safe_name = internal_path.replace("/", "_").replace("\\", "_");
                                             let dest_path = uasset_temp.path().join(&safe_name);
@@ -1149,6 +1234,7 @@ async fn parse_dropped_files(
                                 mod_name,
                                 mod_type,
                                 is_dir: false,
+                                is_loose: false,
                                 path: path_str,
                                 auto_fix_texture: has_texture,
                                 auto_fix_serialize_size: false, // Mesh fixes are automatic
@@ -1176,6 +1262,7 @@ async fn parse_dropped_files(
             mod_name,
             mod_type,
             is_dir: path.is_dir(),
+            is_loose: path.is_dir(),
             path: path_str,
             auto_fix_texture,
             auto_fix_serialize_size,
@@ -1187,7 +1274,7 @@ async fn parse_dropped_files(
     Ok(mods)
 }
 
-#[derive(serde::Deserialize)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 struct ModToInstall {
     path: String,
     #[serde(rename = "customName")]
@@ -1308,7 +1395,7 @@ async fn quick_organize(
     state: State<'_, Arc<Mutex<AppState>>>,
     window: Window,
 ) -> Result<i32, String> {
-    use crate::install_mod::install_mod_logic::archives::{extract_zip, extract_rar, extract_7z};
+    use crate::install_mod::install_mod_logic::archives::{archive_kind, extract_archive_nested, is_secondary_volume, ExtractGuard};
     use walkdir::WalkDir;
     
     let state_guard = state.lock().unwrap();
@@ -1396,22 +1483,20 @@ async fn quick_organize(
         
         let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
         
+        // Later volumes of a split archive are extracted through the first one
+        if is_secondary_volume(&path) {
+            continue;
+        }
+        
         // Handle archives - extract and copy contents preserving subfolder structure
-        if ext == "zip" || ext == "rar" || ext == "7z" {
+        if archive_kind(&path).is_some() {
             let _ = window.emit("install_log", format!("[QuickOrganize] Extracting archive: {}", path.file_name().unwrap_or_default().to_string_lossy()));
             
             let temp_dir = tempfile::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?;
             let temp_path = temp_dir.path();
-            let temp_path_str = temp_path.to_str().unwrap();
             
-            // Extract archive
-            let extract_result = if ext == "zip" {
-                extract_zip(path.to_str().unwrap(), temp_path_str)
-            } else if ext == "rar" {
-                extract_rar(path.to_str().unwrap(), temp_path_str).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-            } else {
-                extract_7z(path.to_str().unwrap(), temp_path_str)
-            };
+            // Extract archive, including any archives nested inside it
+            let extract_result = extract_archive_nested(&path, temp_path, &mut ExtractGuard::default());
             
             if let Err(e) = extract_result {
                 error!("[QuickOrganize] Failed to extract archive: {}", e);
@@ -1585,60 +1670,255 @@ async fn quick_organize(
     Ok(copied_count)
 }
 
+/// Apply the options of one queued entry from the install dialog, and the app-wide settings,
+/// to the mods it produced
+fn apply_install_settings(
+    installable_mods: &mut [crate::install_mod::InstallableMod],
+    mod_to_install: &ModToInstall,
+    settings: &InstallSettings,
+) {
+    let single = installable_mods.len() == 1;
+    for installable in installable_mods.iter_mut() {
+        // Apply custom name if provided; an archive holding several mods keeps their own names
+        if let Some(ref custom) = mod_to_install.custom_name {
+            if !custom.is_empty() && single {
+                installable.mod_name = custom.clone();
+            }
+        }
+
+        // Apply fix settings (mesh patching is handled automatically by UAssetTool)
+        installable.fix_textures = mod_to_install.fix_texture;
+        installable.fix_serialsize_header = mod_to_install.fix_serialize_size;
+        installable.repak = mod_to_install.to_repak;
+        installable.force_legacy_pak = mod_to_install.force_legacy;
+        installable.install_subfolder = mod_to_install.install_subfolder.clone();
+        installable.usmap_path = settings.usmap_filename.clone();
+        // Apply parallel processing setting from app state
+        installable.parallel_processing = settings.parallel_processing;
+        // Apply obfuscation setting from app state
+        installable.obfuscate = settings.obfuscate;
+    }
+}
+
+/// Map each queued entry to installable mods with its options applied. Returns the mods and,
+/// for each, the index of the entry it came from. Stops early once `cancel` is triggered.
+fn map_queued_mods(
+    mods: &[ModToInstall],
+    settings: &InstallSettings,
+    cancel: &uasset_toolkit::CancellationToken,
+) -> (Vec<crate::install_mod::InstallableMod>, Vec<usize>) {
+    let mut installable_mods = Vec::new();
+    let mut owners = Vec::new();
+    for (owner, queued) in mods.iter().enumerate() {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let mut mapped = crate::install_mod::map_paths_to_mods_cancellable(&[PathBuf::from(&queued.path)], cancel);
+        apply_install_settings(&mut mapped, queued, settings);
+        owners.extend(std::iter::repeat_n(owner, mapped.len()));
+        installable_mods.extend(mapped);
+    }
+    (installable_mods, owners)
+}
+
+/// App settings an install batch runs with, taken when it is queued
+#[derive(Clone, Serialize, Deserialize)]
+struct InstallSettings {
+    mod_directory: PathBuf,
+    usmap_filename: String,
+    parallel_processing: bool,
+    obfuscate: bool,
+}
+
+impl InstallSettings {
+    fn from_state(state: &AppState) -> Self {
+        Self {
+            mod_directory: state.game_path.clone(),
+            usmap_filename: state.usmap_path.clone(),
+            parallel_processing: state.parallel_processing,
+            obfuscate: state.obfuscate,
+        }
+    }
+}
+
+/// Preview what install_mods would do with the same input, without touching the mods folder:
+/// final file names, target folders, IoStore conversion, fixes, conflicts and estimated size
 #[tauri::command]
-async fn install_mods(
+async fn plan_install(
     mods: Vec<ModToInstall>,
-    window: Window,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<(), String> {
-    use std::sync::atomic::{AtomicI32, AtomicBool};
-    use std::sync::Arc as StdArc;
+) -> Result<crate::install_mod::install_mod_logic::plan::InstallPlan, String> {
+    let settings = InstallSettings::from_state(&state.lock().unwrap());
 
-    let state_guard = state.lock().unwrap();
-    let mod_directory = state_guard.game_path.clone();
-    let usmap_filename = state_guard.usmap_path.clone();
-    let parallel_processing = state_guard.parallel_processing;
-    let obfuscate = state_guard.obfuscate;
-    drop(state_guard);
+    let (installable_mods, _) = map_queued_mods(&mods, &settings, &uasset_toolkit::CancellationToken::new());
+    if installable_mods.is_empty() {
+        return Err("No valid mods found to install".to_string());
+    }
+
+    Ok(crate::install_mod::install_mod_logic::plan::plan_install(&installable_mods, &settings.mod_directory))
+}
+
+/// Tokens of the install batches that are running, by batch id; triggered by cancel_install
+static INSTALL_BATCHES: Mutex<BTreeMap<String, uasset_toolkit::CancellationToken>> = Mutex::new(BTreeMap::new());
+
+/// An install batch, kept on disk while it runs so it can be resumed if the app is closed
+/// or crashes part-way through
+#[derive(Serialize, Deserialize)]
+struct PendingInstall {
+    batch: String,
+    mods: Vec<ModToInstall>,
+    /// Paths of `mods` whose installs have all finished
+    completed: Vec<String>,
+    /// Settings the batch was queued with, reused when it is resumed
+    settings: InstallSettings,
+}
+
+impl PendingInstall {
+    fn dir() -> PathBuf {
+        app_dir().join("pending_installs")
+    }
+
+    fn path(batch: &str) -> PathBuf {
+        Self::dir().join(format!("{}.json", batch))
+    }
 
-    // Propagate USMAP path to UAssetTool via environment for UAssetAPI-based processing (from roaming folder)
-    if !usmap_filename.is_empty() {
-        if let Some(usmap_full_path) = get_usmap_full_path(&usmap_filename) {
-            std::env::set_var("USMAP_PATH", &usmap_full_path);
-            info!(
-                "Set USMAP_PATH for UAssetTool: {}",
-                usmap_full_path.display()
-            );
+    fn load(batch: &str) -> Option<Self> {
+        let file = File::open(Self::path(batch)).ok()?;
+        serde_json::from_reader(file).ok()
+    }
+
+    /// Every batch with a journal on disk, running or not
+    fn load_all() -> Vec<Self> {
+        let Ok(entries) = std::fs::read_dir(Self::dir()) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let file = File::open(entry.path()).ok()?;
+                serde_json::from_reader(file).ok()
+            })
+            .collect()
+    }
+
+    fn save(&self) {
+        let result = std::fs::create_dir_all(Self::dir())
+            .and_then(|_| File::create(Self::path(&self.batch)))
+            .and_then(|file| serde_json::to_writer_pretty(file, self).map_err(std::io::Error::from));
+        if let Err(e) = result {
+            warn!("Failed to save pending install {}: {}", self.batch, e);
+        }
+    }
+
+    /// Remove this batch's journal, leaving other batches alone
+    fn clear(&self) {
+        let _ = std::fs::remove_file(Self::path(&self.batch));
+    }
+
+    /// Rewrite the journal with only the mods that weren't installed, or remove it if there are none
+    fn settle(&mut self) {
+        self.mods.retain(|m| !self.completed.contains(&m.path));
+        self.completed.clear();
+        if self.mods.is_empty() {
+            self.clear();
         } else {
-            let expected_path = usmap_dir().join(&usmap_filename);
-            error!(
-                "USMAP file not found at expected path for UAssetTool: {}",
-                expected_path.display()
-            );
+            self.save();
         }
     }
 
+    /// Mods not installed yet whose source still exists
+    fn remaining(&self) -> Vec<ModToInstall> {
+        self.mods.iter()
+            .filter(|m| !self.completed.contains(&m.path) && Path::new(&m.path).exists())
+            .cloned()
+            .collect()
+    }
+}
+
+/// Mods left over from an install batch that was interrupted by closing or crashing the app
+#[derive(Serialize)]
+struct InterruptedInstall {
+    batch: String,
+    mods: Vec<ModToInstall>,
+}
+
+fn new_batch_id() -> String {
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or_default();
+    let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    format!("{:x}-{}", nanos, count)
+}
+
+/// Start installing a new batch; returns its id, which cancel_install takes
+#[tauri::command]
+async fn install_mods(
+    mods: Vec<ModToInstall>,
+    window: Window,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<String, String> {
+    let settings = InstallSettings::from_state(&state.lock().unwrap());
+    let batch = new_batch_id();
+    start_install(batch.clone(), mods, settings, window)?;
+    Ok(batch)
+}
+
+/// Run an install batch on a background thread, journaling it under `batch` as it goes
+fn start_install(
+    batch: String,
+    mods: Vec<ModToInstall>,
+    settings: InstallSettings,
+    window: Window,
+) -> Result<(), String> {
+    use crate::install_mod::install_mod_logic::progress::CANCELLED;
+
+    // The USMAP reaches UAssetTool through each mod's install options (usmap_path)
+    let mod_directory = settings.mod_directory.clone();
+
     if !mod_directory.exists() {
         std::fs::create_dir_all(&mod_directory)
             .map_err(|e| format!("Failed to create mods directory: {}", e))?;
     }
 
-    // Convert paths to properly initialized InstallableMods
-    use crate::install_mod::map_paths_to_mods;
+    use crate::install_mod::install_mod_logic::archives::archive_kind;
+    use crate::install_mod::install_mod_logic::progress::{InstallEvent, InstallStage};
 
-    let paths: Vec<PathBuf> = mods.iter().map(|m| PathBuf::from(&m.path)).collect();
+    let cancel = uasset_toolkit::CancellationToken::new();
+    INSTALL_BATCHES.lock().unwrap().insert(batch.clone(), cancel.clone());
+    let finish_batch = {
+        let batch = batch.clone();
+        move || {
+            INSTALL_BATCHES.lock().unwrap().remove(&batch);
+        }
+    };
+    let mut journal = PendingInstall { batch: batch.clone(), mods: mods.clone(), completed: Vec::new(), settings: settings.clone() };
+    journal.save();
 
     // Log the paths we're trying to install
-    for p in &paths {
+    for m in &mods {
+        let p = PathBuf::from(&m.path);
         info!("[Install] Processing path: {}", p.display());
         let _ = window.emit("install_log", format!("[Install] Processing path: {}", p.display()));
+        let stage = if archive_kind(&p).is_some() { InstallStage::Extract } else { InstallStage::Detect };
+        let _ = window.emit("install_event", InstallEvent::Preparing { path: p, stage });
     }
 
-    let mut installable_mods = map_paths_to_mods(&paths);
+    // Convert paths to properly initialized InstallableMods, each tied to the entry it came from
+    let (mut installable_mods, owners) = map_queued_mods(&mods, &settings, &cancel);
+
+    if cancel.is_cancelled() {
+        info!("[Install] Cancelled while extracting");
+        let _ = window.emit("install_log", "[Install] Installation cancelled");
+        journal.settle();
+        finish_batch();
+        window.emit("install_complete", Vec::<()>::new()).ok();
+        return Ok(());
+    }
 
     // Check if we actually have mods to install
     if installable_mods.is_empty() {
-        error!("[Install] No valid mods found from {} input path(s)", paths.len());
+        journal.clear();
+        finish_batch();
+        error!("[Install] No valid mods found from {} input path(s)", mods.len());
         let _ = window.emit("install_log", "ERROR: No valid mods found to install!");
         let _ = window.emit("install_log", "Possible causes:");
         let _ = window.emit("install_log", "  - PAK file couldn't be read (wrong AES key or corrupted)");
@@ -1649,43 +1929,36 @@ async fn install_mods(
         return Err(error_msg.to_string());
     }
 
-    // Apply user settings to each mod
-    for (idx, mod_to_install) in mods.iter().enumerate() {
-        if let Some(installable) = installable_mods.get_mut(idx) {
-            // Apply custom name if provided
-            if let Some(ref custom) = mod_to_install.custom_name {
-                if !custom.is_empty() {
-                    installable.mod_name = custom.clone();
-                }
-            }
-
-            // Apply fix settings (mesh patching is handled automatically by UAssetTool)
-            installable.fix_textures = mod_to_install.fix_texture;
-            installable.fix_serialsize_header = mod_to_install.fix_serialize_size;
-            installable.repak = mod_to_install.to_repak;
-            installable.force_legacy_pak = mod_to_install.force_legacy;
-            installable.install_subfolder = mod_to_install.install_subfolder.clone();
-            installable.usmap_path = usmap_filename.clone();
-            // Apply parallel processing setting from app state
-            installable.parallel_processing = parallel_processing;
-            // Apply obfuscation setting from app state
-            installable.obfuscate = obfuscate;
-        }
-    }
-
+    // How each mod is recorded in its version history: where it came from and how it was installed
+    let versions: Vec<mod_versions::ModVersion> = installable_mods.iter()
+        .map(|m| mod_versions::ModVersion {
+            source: Some(m.archive_origin.first().cloned().unwrap_or_else(|| m.mod_path.display().to_string())),
+            custom_tags: m.custom_tags.clone(),
+            folder_id: Some(m.install_subfolder.clone()).filter(|f| !f.is_empty()),
+            install_options: Some(mod_versions::InstallOptions::from(m)),
+            ..Default::default()
+        })
+        .collect();
+    let mut pending_per_entry = vec![0usize; mods.len()];
+    for owner in &owners {
+        pending_per_entry[*owner] += 1;
+    }
+    // Queued entries that produced no mod have nothing left to do
+    journal.completed = mods.iter()
+        .zip(&pending_per_entry)
+        .filter(|(_, pending)| **pending == 0)
+        .map(|(m, _)| m.path.clone())
+        .collect();
+    journal.save();
+    let journal = std::cell::RefCell::new((journal, pending_per_entry));
+    
     // Use existing installation logic
-    let installed_counter = StdArc::new(AtomicI32::new(0));
-    let stop_flag = StdArc::new(AtomicBool::new(false));
-
-    let total = installable_mods.len() as i32;
-    let counter_clone = installed_counter.clone();
-    let _stop_clone = stop_flag.clone();
-    let window_clone = window.clone();
     
     // Spawn installation thread
     let window_for_logs = window.clone();
     std::thread::spawn(move || {
         use std::panic;
+        use crate::install_mod::install_mod_logic::progress::{InstallEvent, InstallStatus};
         
         let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
             window_for_logs.emit("install_log", "Starting installation...").ok();
@@ -1699,31 +1972,61 @@ async fn install_mods(
                 window_for_logs.emit("install_log", format!("  - Force Legacy PAK: {}", imod.force_legacy_pak)).ok();
             }
             
-            window_for_logs.emit("install_log", "Calling installation logic...").ok();
             window_for_logs.emit("install_log", format!("Mod directory: {}", mod_directory.display())).ok();
             
             use crate::install_mod::install_mod_logic::install_mods_in_viewport;
             
-            window_for_logs.emit("install_log", "Entering install_mods_in_viewport...").ok();
-            
-            // Log each mod's path before processing
-            for (idx, m) in installable_mods.iter().enumerate() {
-                window_for_logs.emit("install_log", format!("  Mod {} path exists: {}", idx, m.mod_path.exists())).ok();
-                window_for_logs.emit("install_log", format!("  Mod {} path: {}", idx, m.mod_path.display())).ok();
-            }
-            
+            let on_event = |event: InstallEvent| {
+                window_for_logs.emit("install_event", &event).ok();
+                if let InstallEvent::Finished { index, total, outcome } = &event {
+                    let progress = (*index + 1) as f32 / *total as f32 * 100.0;
+                    window_for_logs.emit("install_progress", progress).ok();
+                    if let (InstallStatus::Failed, Some(message)) = (outcome.status, outcome.error_message()) {
+                        window_for_logs.emit("install_log", format!("ERROR: {}: {}", outcome.mod_name, message)).ok();
+                    }
+                    if outcome.status == InstallStatus::Installed {
+                        add_to_library(outcome, versions.get(*index).cloned().unwrap_or_default(), &mod_directory);
+                    }
+
+                    // Failed and cancelled mods stay queued for a resume
+                    let done = outcome.status == InstallStatus::Installed
+                        || (outcome.status == InstallStatus::Skipped && outcome.skipped_reason.as_deref() != Some(CANCELLED));
+                    if let (true, Some(&owner)) = (done, owners.get(*index)) {
+                        let (journal, pending) = &mut *journal.borrow_mut();
+                        pending[owner] -= 1;
+                        if pending[owner] == 0 {
+                            journal.completed.push(journal.mods[owner].path.clone());
+                            journal.save();
+                        }
+                    }
+                }
+            };
             install_mods_in_viewport(
                 &mut installable_mods,
                 &mod_directory,
-                &installed_counter,
-                &stop_flag,
-            );
-            window_for_logs.emit("install_log", "Exited install_mods_in_viewport").ok();
+                &on_event,
+                &cancel,
+            )
         }));
         
+        finish_batch();
         match result {
-            Ok(_) => {
-                window_for_logs.emit("install_log", "Installation completed successfully!").ok();
+            Ok(outcomes) => {
+                // Only the failed and cancelled mods are left to resume
+                journal.borrow_mut().0.settle();
+                if outcomes.iter().any(|o| o.status == InstallStatus::Installed) {
+                    let state = window_for_logs.app_handle().state::<Arc<Mutex<AppState>>>();
+                    if let Some(plan) = reapply_load_order(&state, &window_for_logs) {
+                        window_for_logs.emit("install_log", format!("Moved {} mod(s) to follow the load order rules", plan.changes().count())).ok();
+                    }
+                }
+                let failed = outcomes.iter().filter(|o| o.status == InstallStatus::Failed).count();
+                if failed == 0 {
+                    window_for_logs.emit("install_log", "Installation completed successfully!").ok();
+                } else {
+                    window_for_logs.emit("install_log", format!("Installation finished, {} mod(s) failed", failed)).ok();
+                }
+                window_for_logs.emit("install_complete", &outcomes).ok();
             }
             Err(e) => {
                 let msg = if let Some(s) = e.downcast_ref::<&str>() {
@@ -1736,35 +2039,359 @@ async fn install_mods(
                 window_for_logs.emit("install_log", &msg).ok();
                 toast_events::emit_installation_failed(&window_for_logs, &msg);
                 error!("Installation thread panicked!");
+                window_for_logs.emit("install_complete", Vec::<()>::new()).ok();
             }
         }
     });
     
-    // Monitor progress
-    std::thread::spawn(move || {
-        loop {
-            let current = counter_clone.load(std::sync::atomic::Ordering::SeqCst);
-            if current == -255 {
-                window_clone.emit("install_complete", ()).ok();
-                break;
-            }
-            let progress = (current as f32 / total as f32) * 100.0;
-            window_clone.emit("install_progress", progress).ok();
-            std::thread::sleep(std::time::Duration::from_millis(100));
-        }
-    });
-    
     Ok(())
 }
 
+/// Cancel an install batch, or every running batch when no id is given. The mod being
+/// installed is rolled back, the rest are skipped.
 #[tauri::command]
-async fn delete_mod(path: String, window: Window) -> Result<(), String> {
-    let path_buf = PathBuf::from(&path);
-    log::info!("delete_mod called with path: {}", path);
-    
-    // Determine the actual file to delete - check both .pak and .bak_repak variants
-    let (actual_path, is_disabled) = if path_buf.exists() {
-        (path_buf.clone(), path.ends_with(".bak_repak"))
+async fn cancel_install(batch_id: Option<String>, window: Window) -> Result<(), String> {
+    let batches = INSTALL_BATCHES.lock().unwrap();
+    let tokens: Vec<_> = match &batch_id {
+        Some(batch) => batches.get(batch).into_iter().collect(),
+        None => batches.values().collect(),
+    };
+    if tokens.is_empty() {
+        info!("[Install] cancel_install called with no matching install running");
+    } else {
+        info!("[Install] Cancellation requested for {} batch(es)", tokens.len());
+        let _ = window.emit("install_log", "[Install] Cancelling installation...");
+        tokens.iter().for_each(|cancel| cancel.cancel());
+    }
+    Ok(())
+}
+
+/// Install batches that were interrupted by closing or crashing the app, with what is left of them
+#[tauri::command]
+async fn get_pending_install() -> Result<Vec<InterruptedInstall>, String> {
+    let running = INSTALL_BATCHES.lock().unwrap();
+    Ok(PendingInstall::load_all().into_iter()
+        .filter(|pending| !running.contains_key(&pending.batch))
+        .map(|pending| InterruptedInstall { mods: pending.remaining(), batch: pending.batch })
+        .filter(|interrupted| !interrupted.mods.is_empty())
+        .collect())
+}
+
+/// Install what is left of an interrupted batch, with the settings it was queued with
+#[tauri::command]
+async fn resume_install(batch_id: String, window: Window) -> Result<(), String> {
+    if INSTALL_BATCHES.lock().unwrap().contains_key(&batch_id) {
+        return Err("That installation is still running".to_string());
+    }
+    let Some(pending) = PendingInstall::load(&batch_id) else {
+        return Err("No interrupted installation to resume".to_string());
+    };
+    let remaining = pending.remaining();
+    if remaining.is_empty() {
+        pending.clear();
+        return Err("No interrupted installation to resume".to_string());
+    }
+    info!("[Install] Resuming interrupted installation of {} mod(s)", remaining.len());
+    let _ = window.emit("install_log", format!("[Install] Resuming {} mod(s) from an interrupted installation", remaining.len()));
+    start_install(batch_id, remaining, pending.settings, window)
+}
+
+#[tauri::command]
+async fn discard_pending_install(batch_id: String) -> Result<(), String> {
+    if let Some(pending) = PendingInstall::load(&batch_id) {
+        pending.clear();
+    }
+    Ok(())
+}
+
+/// Keep a copy of a freshly installed mod in the library, track its files as deployed and
+/// record it as the mod's newest version. The install itself already succeeded, so failures
+/// are only logged.
+fn add_to_library(
+    outcome: &crate::install_mod::install_mod_logic::progress::InstallOutcome,
+    version: mod_versions::ModVersion,
+    mods_dir: &Path,
+) {
+    if let Err(e) = keep_version(&outcome.mod_name, &outcome.installed_files, version, mods_dir, true) {
+        warn!("[Library] Failed to add {} to the library: {}", outcome.mod_name, e);
+    }
+}
+
+/// Import `files` into the library and record them as the newest version of their mod in
+/// `mods_dir`. With `adopt` the files are also tracked as deployed from the new entry.
+fn keep_version(
+    name: &str,
+    files: &[PathBuf],
+    mut version: mod_versions::ModVersion,
+    mods_dir: &Path,
+    adopt: bool,
+) -> std::io::Result<String> {
+    let library = mod_library::ModLibrary::open_default()?;
+    let entry = if adopt {
+        library.adopt(name, files, version.source.clone())?
+    } else {
+        library.import(name, files, version.source.clone())?
+    };
+    let key = files.iter()
+        .find(|f| f.extension().is_some_and(|e| e == "pak" || e == "bak_repak" || e == "pak_disabled"))
+        .and_then(|f| mod_versions::history_key(mods_dir, f))
+        .unwrap_or_else(|| name.to_string());
+    version.entry_id = entry.id.clone();
+    version.recorded = mod_versions::now();
+    mod_versions::record(&library, &key, version)?;
+    Ok(entry.id)
+}
+
+/// Existing files of the mod at `mod_path`: the .pak (or its disabled form) and any .utoc/.ucas
+fn mod_files(mod_path: &Path) -> Vec<PathBuf> {
+    let dir = mod_path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let stem = mod_path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+    std::iter::once(mod_path.to_path_buf())
+        .chain(["ucas", "utoc"].iter().flat_map(|ext| {
+            let companion = format!("{}.{}", stem, ext);
+            [dir.join(&companion), dir.join(format!("{}.bak_repak", companion))]
+        }))
+        .filter(|p| p.exists())
+        .collect()
+}
+
+// ============================================================================
+// MOD VERSIONS
+// ============================================================================
+
+#[derive(Serialize)]
+struct ModVersionInfo {
+    #[serde(flatten)]
+    version: mod_versions::ModVersion,
+    /// Whether this is the version currently in the mods folder
+    current: bool,
+    file_size: u64,
+}
+
+/// Kept versions of the mod at `mod_path`, newest first
+#[tauri::command]
+async fn list_mod_versions(
+    mod_path: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<ModVersionInfo>, String> {
+    let path = PathBuf::from(&mod_path);
+    let mods_dir = state.lock().unwrap().game_path.clone();
+    let key = mod_versions::history_key(&mods_dir, &path).ok_or("Invalid mod path")?;
+    let library = mod_library::ModLibrary::open_default().map_err(|e| e.to_string())?;
+    let current = library.deployed_entry(&path).map_err(|e| e.to_string())?;
+
+    let versions = mod_versions::versions(&library, &key).map_err(|e| e.to_string())?;
+    Ok(versions.into_iter()
+        .map(|version| {
+            let file_size = library.entry(&version.entry_id).ok().flatten()
+                .map(|entry| entry.files.iter().map(|f| f.size).sum())
+                .unwrap_or(0);
+            ModVersionInfo {
+                current: current.as_deref() == Some(version.entry_id.as_str()),
+                version,
+                file_size,
+            }
+        })
+        .collect())
+}
+
+/// Replace the mod at `mod_path` with one of its kept versions, keeping the current one in
+/// the history. Restores the version's name, tags and folder and keeps the enabled state; the
+/// restored version becomes the newest, so later updates use the options it was installed
+/// with. Returns the path of the restored mod.
+#[tauri::command]
+async fn rollback_mod(
+    mod_path: String,
+    entry_id: String,
+    window: Window,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<String, String> {
+    let path = PathBuf::from(&mod_path);
+    if !path.exists() {
+        return Err(format!("Mod not found: {}", mod_path));
+    }
+    let mods_dir = state.lock().unwrap().game_path.clone();
+    let key = mod_versions::history_key(&mods_dir, &path).ok_or("Invalid mod path")?;
+    let mod_name = mod_profiles::mod_key(&path).ok_or("Invalid mod path")?;
+    let was_disabled = path.extension().is_some_and(|e| e != "pak");
+    let library = mod_library::ModLibrary::open_default().map_err(|e| e.to_string())?;
+
+    let history = mod_versions::versions(&library, &key).map_err(|e| e.to_string())?;
+    let Some(target) = history.iter().find(|v| v.entry_id == entry_id).cloned() else {
+        return Err(format!("No kept version {} of {}", entry_id, key));
+    };
+
+    // Make sure the version being replaced can be rolled forward to again
+    let current_files = mod_files(&path);
+    let current_id = mod_identity::mod_id(&path);
+    let (custom_name, custom_tags, folder_id) = {
+        let mut state = state.lock().unwrap();
+        let index = state.metadata_index(current_id.as_deref(), &path);
+        let metadata = index.map(|i| &state.mod_metadata[i]);
+        (
+            metadata.and_then(|m| m.custom_name.clone()),
+            metadata.map(|m| m.custom_tags.clone()).unwrap_or_default(),
+            metadata.and_then(|m| m.folder_id.clone()),
+        )
+    };
+    let current = library.import(&mod_name, &current_files, None).map_err(|e| e.to_string())?;
+    mod_versions::record_if_missing(&library, &key, mod_versions::ModVersion {
+        entry_id: current.id.clone(),
+        recorded: mod_versions::now(),
+        custom_name: custom_name.clone(),
+        custom_tags,
+        folder_id,
+        ..Default::default()
+    }).map_err(|e| e.to_string())?;
+    if current.id == target.entry_id {
+        return Ok(mod_path);
+    }
+
+    // Deploy first so a failure leaves the current version in place
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let deployed = library.deploy(&target.entry_id, &dir)
+        .map_err(|e| format!("Failed to restore version: {}", e))?;
+    for old in current_files.iter().filter(|f| !deployed.contains(f)) {
+        if let Err(e) = std::fs::remove_file(old) {
+            warn!("[Versions] Failed to remove {}: {}", old.display(), e);
+        }
+    }
+
+    let mut new_path = deployed.iter()
+        .find(|p| p.extension().is_some_and(|e| e == "pak"))
+        .cloned()
+        .ok_or("Restored version has no .pak file")?;
+    if was_disabled {
+        mod_ops::toggle_mod(&new_path)?;
+        new_path = new_path.with_extension("bak_repak");
+    }
+
+    {
+        let new_id = mod_identity::mod_id(&new_path);
+        let mut state = state.lock().unwrap();
+        if let Some(index) = state.metadata_index(current_id.as_deref(), &path) {
+            state.mod_metadata.remove(index);
+        }
+        if target.custom_name.is_some() || !target.custom_tags.is_empty() || target.folder_id.is_some() {
+            state.mod_metadata.push(ModMetadata {
+                id: new_id,
+                path: new_path.clone(),
+                custom_name: target.custom_name.clone(),
+                folder_id: target.folder_id.clone(),
+                custom_tags: target.custom_tags.clone(),
+            });
+        }
+        save_state(&state).map_err(|e| e.to_string())?;
+    }
+    if let Err(e) = mod_versions::record(&library, &key, mod_versions::ModVersion { recorded: mod_versions::now(), ..target.clone() }) {
+        warn!("[Versions] Failed to mark {} as the current version of {}: {}", target.entry_id, key, e);
+    }
+
+    let name = target.custom_name.or(custom_name).unwrap_or(mod_name);
+    toast_events::emit_success(&window, "Mod Rolled Back", format!("Restored an earlier version of {}", name));
+    Ok(new_path.to_string_lossy().to_string())
+}
+
+/// Files added, removed and changed between two kept versions
+#[tauri::command]
+async fn diff_mod_versions(from_entry: String, to_entry: String) -> Result<mod_versions::VersionDiff, String> {
+    let library = mod_library::ModLibrary::open_default().map_err(|e| e.to_string())?;
+    mod_versions::diff(&library, &from_entry, &to_entry)
+}
+
+// ============================================================================
+// MOD LIBRARY
+// ============================================================================
+
+#[tauri::command]
+async fn get_library_entries() -> Result<Vec<mod_library::LibraryEntry>, String> {
+    mod_library::ModLibrary::open_default()
+        .and_then(|library| library.entries())
+        .map_err(|e| format!("Failed to read mod library: {}", e))
+}
+
+/// Put a library entry into the mods folder, in `subfolder` if given
+#[tauri::command]
+async fn deploy_library_entry(
+    entry_id: String,
+    subfolder: Option<String>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<String>, String> {
+    let game_path = state.lock().unwrap().game_path.clone();
+    if game_path.as_os_str().is_empty() {
+        return Err("Game path not set".to_string());
+    }
+    let dir = match subfolder.as_deref().filter(|s| !s.is_empty()) {
+        Some(sub) => game_path.join(sub),
+        None => game_path,
+    };
+    let deployed = mod_library::ModLibrary::open_default()
+        .and_then(|library| library.deploy(&entry_id, &dir))
+        .map_err(|e| format!("Failed to deploy mod: {}", e))?;
+    Ok(deployed.iter().map(|p| p.to_string_lossy().to_string()).collect())
+}
+
+/// Make the library-managed mods in the mods folder exactly `entries`, each an entry id and
+/// an optional subfolder. Mods that weren't installed through the library are left alone.
+#[tauri::command]
+async fn deploy_library_set(
+    entries: Vec<(String, Option<String>)>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<mod_library::DeployReport, String> {
+    let game_path = state.lock().unwrap().game_path.clone();
+    if game_path.as_os_str().is_empty() {
+        return Err("Game path not set".to_string());
+    }
+    let wanted: Vec<(String, PathBuf)> = entries.into_iter()
+        .map(|(id, sub)| {
+            let dir = match sub.filter(|s| !s.is_empty()) {
+                Some(sub) => game_path.join(sub),
+                None => game_path.clone(),
+            };
+            (id, dir)
+        })
+        .collect();
+    mod_library::ModLibrary::open_default()
+        .and_then(|library| library.deploy_set(&wanted))
+        .map_err(|e| format!("Failed to deploy mods: {}", e))
+}
+
+/// Library entry a mod in the mods folder was installed from, if any
+#[tauri::command]
+async fn get_library_entry_for_mod(mod_path: String) -> Result<Option<String>, String> {
+    mod_library::ModLibrary::open_default()
+        .and_then(|library| library.deployed_entry(Path::new(&mod_path)))
+        .map_err(|e| format!("Failed to read mod library: {}", e))
+}
+
+/// Take a library entry's files out of the mods folder, keeping it in the library
+#[tauri::command]
+async fn retract_library_entry(entry_id: String) -> Result<Vec<String>, String> {
+    let (removed, kept) = mod_library::ModLibrary::open_default()
+        .and_then(|library| library.retract(&entry_id))
+        .map_err(|e| format!("Failed to remove mod files: {}", e))?;
+    if !kept.is_empty() {
+        warn!("[Library] Kept {} modified file(s) of {}", kept.len(), entry_id);
+    }
+    Ok(removed.iter().map(|p| p.to_string_lossy().to_string()).collect())
+}
+
+/// Delete a library entry and the stored files nothing else uses
+#[tauri::command]
+async fn remove_library_entry(entry_id: String) -> Result<usize, String> {
+    mod_library::ModLibrary::open_default()
+        .and_then(|library| library.remove_entry(&entry_id))
+        .map_err(|e| format!("Failed to remove library entry: {}", e))
+}
+
+#[tauri::command]
+async fn delete_mod(path: String, window: Window) -> Result<(), String> {
+    let path_buf = PathBuf::from(&path);
+    log::info!("delete_mod called with path: {}", path);
+    
+    // Determine the actual file to delete - check both .pak and .bak_repak variants
+    let (actual_path, is_disabled) = if path_buf.exists() {
+        (path_buf.clone(), path.ends_with(".bak_repak"))
     } else if path.ends_with(".pak") {
         // The .pak file doesn't exist, check if there's a disabled version (.bak_repak)
         let disabled_path = PathBuf::from(format!("{}.bak_repak", path.trim_end_matches(".pak")));
@@ -1925,15 +2552,11 @@ async fn update_mod(
     info!("Preserved install subfolder: {}", install_subfolder);
     
     // Get the old mod's custom name and tags from metadata
+    let old_id = mod_identity::mod_id(&actual_old_path);
     let (old_custom_name, old_custom_tags, old_folder_id) = {
-        let state_guard = state.lock().unwrap();
-        let metadata = state_guard.mod_metadata.iter()
-            .find(|m| {
-                m.path == actual_old_path || 
-                m.path.with_extension("pak") == actual_old_path ||
-                m.path.with_extension("bak_repak") == actual_old_path ||
-                m.path.with_extension("pak_disabled") == actual_old_path
-            });
+        let mut state_guard = state.lock().unwrap();
+        let index = state_guard.metadata_index(old_id.as_deref(), &actual_old_path);
+        let metadata = index.map(|i| &state_guard.mod_metadata[i]);
         
         match metadata {
             Some(m) => (m.custom_name.clone(), m.custom_tags.clone(), m.folder_id.clone()),
@@ -1959,6 +2582,24 @@ async fn update_mod(
     // Step 2: Delete the old mod files
     // ========================================================================
     
+    // Keep the old version in the library so the update can be rolled back, and install the
+    // new one the way the old one was
+    let old_version = mod_versions::ModVersion {
+        custom_name: old_custom_name.clone(),
+        custom_tags: old_custom_tags.clone(),
+        folder_id: old_folder_id.clone(),
+        ..Default::default()
+    };
+    let old_options = match keep_version(&old_mod_name, &mod_files(&actual_old_path), old_version, &game_path, false) {
+        Ok(entry_id) => mod_versions::history_key(&game_path, &actual_old_path)
+            .zip(mod_library::ModLibrary::open_default().ok())
+            .and_then(|(key, library)| mod_versions::install_options(&library, &key, &entry_id).ok().flatten()),
+        Err(e) => {
+            warn!("[Library] Failed to keep the old version of {}: {}", old_mod_name, e);
+            None
+        }
+    };
+    
     info!("Deleting old mod files...");
     
     // Delete main file
@@ -2016,9 +2657,8 @@ async fn update_mod(
     };
     
     // Use the existing install_mods logic
-    use std::sync::atomic::{AtomicI32, AtomicBool};
-    use std::sync::Arc as StdArc;
     use crate::install_mod::map_paths_to_mods;
+    use crate::install_mod::install_mod_logic::progress::InstallStatus;
     
     let state_guard = state.lock().unwrap();
     let mod_directory = state_guard.game_path.clone();
@@ -2026,13 +2666,6 @@ async fn update_mod(
     let obfuscate = state_guard.obfuscate;
     drop(state_guard);
     
-    // Set USMAP path
-    if !usmap_filename.is_empty() {
-        if let Some(usmap_full_path) = get_usmap_full_path(&usmap_filename) {
-            std::env::set_var("USMAP_PATH", &usmap_full_path);
-        }
-    }
-    
     let paths = vec![new_source.clone()];
     let mut installable_mods = map_paths_to_mods(&paths);
     
@@ -2048,11 +2681,22 @@ async fn update_mod(
         installable.install_subfolder = install_subfolder.clone();
         installable.usmap_path = usmap_filename;
         installable.obfuscate = obfuscate;
+        if let Some(options) = &old_options {
+            options.apply(installable);
+        }
     }
     
+    let new_version = installable_mods.first().map(|m| mod_versions::ModVersion {
+        source: Some(new_mod_source.clone()),
+        custom_name: old_custom_name.clone(),
+        custom_tags: old_custom_tags.clone(),
+        folder_id: old_folder_id.clone(),
+        install_options: Some(mod_versions::InstallOptions::from(m)),
+        ..Default::default()
+    }).unwrap_or_default();
+    
     // Install synchronously for update operation (we need to know the result)
-    let installed_counter = StdArc::new(AtomicI32::new(0));
-    let stop_flag = StdArc::new(AtomicBool::new(false));
+    let cancel = uasset_toolkit::CancellationToken::new();
     
     let window_clone = window.clone();
     window_clone.emit("install_log", format!("[Update] Replacing mod: {}", old_mod_name)).ok();
@@ -2060,24 +2704,44 @@ async fn update_mod(
     
     use crate::install_mod::install_mod_logic::install_mods_in_viewport;
     
-    install_mods_in_viewport(
+    let outcomes = install_mods_in_viewport(
         &mut installable_mods,
         &mod_directory,
-        &installed_counter,
-        &stop_flag,
+        &|event| { window_clone.emit("install_event", &event).ok(); },
+        &cancel,
     );
+    window.emit("install_complete", &outcomes).ok();
+    
+    let Some(outcome) = outcomes.into_iter().next() else {
+        let err = "Failed to install new mod";
+        toast_events::emit_installation_failed(&window, err);
+        return Err(err.to_string());
+    };
+    if outcome.status != InstallStatus::Installed {
+        let err = outcome.error_message()
+            .or(outcome.skipped_reason)
+            .unwrap_or_else(|| "Failed to install new mod".to_string());
+        toast_events::emit_installation_failed(&window, &err);
+        return Err(err);
+    }
+    add_to_library(&outcome, new_version, &mod_directory);
     
     // ========================================================================
     // Step 4: Apply preserved metadata to the new mod
     // ========================================================================
     
     // Determine the new mod's path
-    let new_mod_filename = format!("{}_9999999_P.pak", mod_name);
-    let new_mod_path = if install_subfolder.is_empty() {
-        mod_directory.join(&new_mod_filename)
-    } else {
-        mod_directory.join(&install_subfolder).join(&new_mod_filename)
-    };
+    let new_mod_path = outcome.installed_files.iter()
+        .find(|p| p.extension().is_some_and(|e| e == "pak"))
+        .cloned()
+        .unwrap_or_else(|| {
+            let new_mod_filename = format!("{}_9999999_P.pak", mod_name);
+            if install_subfolder.is_empty() {
+                mod_directory.join(&new_mod_filename)
+            } else {
+                mod_directory.join(&install_subfolder).join(&new_mod_filename)
+            }
+        });
     
     info!("Expected new mod path: {:?}", new_mod_path);
     
@@ -2094,8 +2758,6 @@ async fn update_mod(
     
     // Update metadata with preserved tags and folder assignment
     if !old_custom_tags.is_empty() || old_folder_id.is_some() || old_custom_name.is_some() {
-        let mut state_guard = state.lock().unwrap();
-        
         // Find or create metadata entry for the new mod
         let new_path_for_metadata = if was_disabled {
             PathBuf::from(format!("{}.bak_repak", 
@@ -2103,16 +2765,17 @@ async fn update_mod(
         } else {
             new_mod_path.clone()
         };
+        let new_id = mod_identity::mod_id(&new_path_for_metadata);
+        let mut state_guard = state.lock().unwrap();
         
         // Remove old metadata entry if it exists
-        state_guard.mod_metadata.retain(|m| {
-            m.path != actual_old_path && 
-            m.path != old_path &&
-            m.path.with_extension("pak") != actual_old_path
-        });
+        if let Some(index) = state_guard.metadata_index(old_id.as_deref(), &actual_old_path) {
+            state_guard.mod_metadata.remove(index);
+        }
         
         // Add new metadata entry with preserved data
         state_guard.mod_metadata.push(ModMetadata {
+            id: new_id,
             path: new_path_for_metadata.clone(),
             custom_name: if preserve_name { old_custom_name } else { Some(mod_name.clone()) },
             folder_id: old_folder_id,
@@ -2125,9 +2788,16 @@ async fn update_mod(
         }
     }
     
+    // The new version was installed at the top priority
+    let mut new_mod_path = new_mod_path;
+    if let Some(plan) = reapply_load_order(&state, &window) {
+        if let Some(moved) = plan.changes().find(|p| p.from.with_extension("pak") == new_mod_path) {
+            new_mod_path = moved.to.with_extension("pak");
+        }
+    }
+    
     // Emit success event
     toast_events::emit_success(&window, "Mod Updated", format!("Successfully replaced mod: {}", mod_name));
-    window.emit("install_complete", ()).ok();
     
     info!("update_mod completed successfully");
     
@@ -2473,6 +3143,7 @@ async fn get_folders(state: State<'_, Arc<Mutex<AppState>>>) -> Result<Vec<ModFo
     for entry in WalkDir::new(game_path)
         .min_depth(1)
         .into_iter()
+        .filter_entry(|e| !mod_ops::in_install_staging(Path::new(e.file_name())))
         .filter_map(|e| e.ok()) 
     {
         let path = entry.path();
@@ -2769,27 +3440,308 @@ async fn assign_mod_to_folder(
     Ok(())
 }
 
+// ============================================================================
+// MOD PROFILES
+// ============================================================================
+
+#[derive(Serialize)]
+struct ProfilesInfo {
+    profiles: Vec<mod_profiles::ModProfile>,
+    active: Option<String>,
+}
+
+#[tauri::command]
+async fn get_profiles(state: State<'_, Arc<Mutex<AppState>>>) -> Result<ProfilesInfo, String> {
+    let state = state.lock().unwrap();
+    Ok(ProfilesInfo {
+        profiles: state.profiles.clone(),
+        active: state.active_profile.clone(),
+    })
+}
+
+/// Save the current mods folder as profile `name`, replacing a profile of the same name
+#[tauri::command]
+async fn save_profile(name: String, state: State<'_, Arc<Mutex<AppState>>>) -> Result<mod_profiles::ModProfile, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Profile name cannot be empty".to_string());
+    }
+    let mut state = state.lock().unwrap();
+    if !state.game_path.exists() {
+        return Err(format!("Game path does not exist: {}", state.game_path.display()));
+    }
+
+    let library = mod_library::ModLibrary::open_default().ok();
+    let profile = mod_profiles::capture(&name, &state.game_path, library.as_ref());
+    info!("[Profiles] Saved '{}' with {} mod(s)", name, profile.mods.len());
+    state.profiles.retain(|p| p.name != name);
+    state.profiles.push(profile.clone());
+    state.active_profile = Some(name);
+    save_state(&state).map_err(|e| e.to_string())?;
+    Ok(profile)
+}
+
+#[tauri::command]
+async fn delete_profile(name: String, state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    state.profiles.retain(|p| p.name != name);
+    if state.active_profile.as_deref() == Some(name.as_str()) {
+        state.active_profile = None;
+    }
+    save_state(&state).map_err(|e| e.to_string())
+}
+
+/// What switching to profile `name` would change, without changing anything
+#[tauri::command]
+async fn preview_profile(name: String, state: State<'_, Arc<Mutex<AppState>>>) -> Result<mod_profiles::ProfileReport, String> {
+    let state = state.lock().unwrap();
+    let profile = state.profiles.iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| format!("No profile named {}", name))?;
+    Ok(mod_profiles::plan(profile, &state.game_path))
+}
+
+/// Switch the mods folder to profile `name`. Either every rename happens or none does.
+#[tauri::command]
+async fn apply_profile(
+    name: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    window: Window,
+) -> Result<mod_profiles::ProfileReport, String> {
+    let mut state = state.lock().unwrap();
+    let Some(profile) = state.profiles.iter().find(|p| p.name == name).cloned() else {
+        return Err(format!("No profile named {}", name));
+    };
+
+    // Give path-keyed metadata its mod's ID while the paths still match
+    let game_path = state.game_path.clone();
+    let mod_paths = |dir: &Path| -> Vec<PathBuf> { mod_ops::scan_mods(dir).into_iter().map(|m| m.path).collect() };
+    let before = mod_paths(&game_path);
+    state.sync_mod_metadata(&before, &mod_identity::mod_ids(&before));
+
+    let library = mod_library::ModLibrary::open_default().ok();
+    let report = mod_profiles::apply(&profile, &game_path, library.as_ref())
+        .inspect_err(|e| toast_events::emit_profile_failed(&window, e))?;
+
+    let after = mod_paths(&game_path);
+    state.sync_mod_metadata(&after, &mod_identity::mod_ids(&after));
+    state.active_profile = Some(name.clone());
+    save_state(&state).map_err(|e| e.to_string())?;
+
+    let mut summary = format!("{} mod(s) changed", report.changes.len());
+    if !report.missing.is_empty() {
+        summary.push_str(&format!(", {} missing", report.missing.len()));
+    }
+    toast_events::emit_success(&window, format!("Switched to {}", name), summary);
+    Ok(report)
+}
+
+// ============================================================================
+// LOAD ORDER
+// ============================================================================
+
+/// Category and additional categories detected from the files in the mod at `path`
+fn detected_categories(path: &Path) -> Vec<String> {
+    let characteristics = match mod_index::characteristics(path) {
+        Ok(characteristics) => characteristics,
+        Err(e) => {
+            warn!("[LoadOrder] Couldn't read {}: {}", path.display(), e);
+            return Vec::new();
+        }
+    };
+    std::iter::once(characteristics.category).chain(characteristics.additional_categories).collect()
+}
+
+/// Work out the load order of the mods folder under `rules`. The state is only locked to read
+/// the game path and match metadata; hashing and reading the mods happens without it.
+fn plan_load_order(state: &Mutex<AppState>, rules: &[load_order::LoadOrderRule]) -> Result<load_order::LoadOrderPlan, String> {
+    let game_path = state.lock().unwrap().game_path.clone();
+    if !game_path.exists() {
+        return Err(format!("Game path does not exist: {}", game_path.display()));
+    }
+    // Reading every mod's files is only worth it when a rule goes by category
+    let by_category = rules.iter().any(|r| matches!(r, load_order::LoadOrderRule::CategoryAbove { .. }));
+
+    let paths: Vec<PathBuf> = mod_ops::scan_mods(&game_path).into_iter().map(|m| m.path).collect();
+    let ids = mod_identity::mod_ids(&paths);
+    let tags: std::collections::HashMap<PathBuf, Vec<String>> = {
+        let mut state = state.lock().unwrap();
+        let indices = state.sync_mod_metadata(&paths, &ids);
+        paths.into_iter()
+            .zip(indices)
+            .filter_map(|(path, index)| Some((path, state.mod_metadata[index?].custom_tags.clone())))
+            .collect()
+    };
+
+    let mods = load_order::installed(&game_path, |scanned| {
+        let mut categories = tags.get(&scanned.path).cloned().unwrap_or_default();
+        if by_category {
+            categories.extend(detected_categories(&scanned.path));
+        }
+        categories
+    });
+    load_order::solve(&mods, rules)
+}
+
+/// Plan the load order under `rules` and rename the mods accordingly, then match metadata to
+/// the new file names
+fn apply_load_order_rules(state: &Mutex<AppState>, rules: &[load_order::LoadOrderRule]) -> Result<load_order::LoadOrderPlan, String> {
+    let plan = plan_load_order(state, rules)?;
+    load_order::apply(&plan)?;
+
+    let game_path = state.lock().unwrap().game_path.clone();
+    let after: Vec<PathBuf> = mod_ops::scan_mods(&game_path).into_iter().map(|m| m.path).collect();
+    let ids = mod_identity::mod_ids(&after);
+    state.lock().unwrap().sync_mod_metadata(&after, &ids);
+    Ok(plan)
+}
+
+/// New mods are installed at the top priority; move them to where the saved rules put them.
+/// Returns the plan if any mod was renamed.
+fn reapply_load_order(state: &Mutex<AppState>, window: &Window) -> Option<load_order::LoadOrderPlan> {
+    let rules = state.lock().unwrap().load_order_rules.clone();
+    if rules.is_empty() {
+        return None;
+    }
+    match apply_load_order_rules(state, &rules) {
+        Ok(plan) if plan.changes().next().is_some() => {
+            info!("[LoadOrder] Moved {} mod(s) to keep the load order rules", plan.changes().count());
+            Some(plan)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            warn!("[LoadOrder] Couldn't apply the load order rules after installing: {}", e);
+            toast_events::emit_load_order_failed(window, &e);
+            None
+        }
+    }
+}
+
+#[tauri::command]
+async fn get_load_order_rules(state: State<'_, Arc<Mutex<AppState>>>) -> Result<Vec<load_order::LoadOrderRule>, String> {
+    Ok(state.lock().unwrap().load_order_rules.clone())
+}
+
+#[tauri::command]
+async fn set_load_order_rules(
+    rules: Vec<load_order::LoadOrderRule>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    state.load_order_rules = rules;
+    save_state(&state).map_err(|e| e.to_string())
+}
+
+/// Where the saved rules would put every mod and why, without renaming anything
+#[tauri::command]
+async fn preview_load_order(state: State<'_, Arc<Mutex<AppState>>>) -> Result<load_order::LoadOrderPlan, String> {
+    let rules = state.lock().unwrap().load_order_rules.clone();
+    plan_load_order(&state, &rules)
+}
+
+/// Rename mods to the priorities the saved rules give them. Either every rename happens or none does.
+#[tauri::command]
+async fn apply_load_order(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    window: Window,
+) -> Result<load_order::LoadOrderPlan, String> {
+    let rules = state.lock().unwrap().load_order_rules.clone();
+    let plan = apply_load_order_rules(&state, &rules)
+        .inspect_err(|e| toast_events::emit_load_order_failed(&window, e))?;
+
+    toast_events::emit_success(&window, "Load Order Applied", format!("{} mod(s) moved", plan.changes().count()));
+    Ok(plan)
+}
+
+// ============================================================================
+// ASSET CONFLICTS
+// ============================================================================
+
+/// Every asset more than one enabled mod replaces, with the mod that wins it
+#[tauri::command]
+async fn get_asset_conflicts(state: State<'_, Arc<Mutex<AppState>>>) -> Result<asset_conflicts::ConflictReport, String> {
+    let game_path = state.lock().unwrap().game_path.clone();
+    asset_conflicts::scan(&game_path, &mut mod_index::Shared)
+}
+
+/// Settle a conflict in favour of `winner_path` with a load-order rule, then apply the load order.
+/// The rule is only saved once the load order has been applied with it.
+#[tauri::command]
+async fn prefer_mod(
+    winner_path: String,
+    loser_path: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    window: Window,
+) -> Result<load_order::LoadOrderPlan, String> {
+    let winner = mod_profiles::mod_key(Path::new(&winner_path)).ok_or("Invalid mod path")?;
+    let loser = mod_profiles::mod_key(Path::new(&loser_path)).ok_or("Invalid mod path")?;
+    if winner == loser {
+        return Err(format!("Both mods are named {}; rename one of them first", winner));
+    }
+
+    let mut rules = state.lock().unwrap().load_order_rules.clone();
+    load_order::prefer(&mut rules, &winner, &loser);
+    let plan = apply_load_order_rules(&state, &rules)
+        .inspect_err(|e| toast_events::emit_load_order_failed(&window, e))?;
+
+    // Added to the rules as they are now, in case they were edited in the meantime
+    let mut state = state.lock().unwrap();
+    load_order::prefer(&mut state.load_order_rules, &winner, &loser);
+    save_state(&state).map_err(|e| e.to_string())?;
+
+    toast_events::emit_success(&window, "Load Order Applied", format!("{} now loads after {}", winner, loser));
+    Ok(plan)
+}
+
+/// Move `assets` out of the mod at `mod_path` into a new mod of their own. The mod as it was
+/// is kept as a version in the library so the split can be rolled back.
+#[tauri::command]
+async fn split_conflicting_assets(
+    mod_path: String,
+    assets: Vec<String>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    window: Window,
+) -> Result<String, String> {
+    let path = PathBuf::from(&mod_path);
+    let (usmap, obfuscate, mods_dir) = {
+        let state = state.lock().unwrap();
+        (get_usmap_full_path(&state.usmap_path), state.obfuscate, state.game_path.clone())
+    };
+
+    let name = mod_profiles::mod_key(&path).ok_or("Invalid mod path")?;
+    if let Err(e) = keep_version(&name, &mod_files(&path), mod_versions::ModVersion::default(), &mods_dir, false) {
+        warn!("[Library] Failed to keep {} before splitting it: {}", name, e);
+    }
+
+    let split = asset_conflicts::split_out(&path, &assets, usmap.as_deref(), obfuscate)
+        .inspect_err(|e| toast_events::emit_split_failed(&window, e))?;
+
+    // The mod's contents changed; its metadata is matched by path and takes on the new ID
+    let new_id = mod_identity::mod_id(&path);
+    let mut state = state.lock().unwrap();
+    if state.metadata_index(new_id.as_deref(), &path).is_some() {
+        save_state(&state).map_err(|e| e.to_string())?;
+    }
+
+    toast_events::emit_success(&window, "Assets Split Out", format!("{} asset(s) moved to {}", assets.len(),
+        split.file_name().unwrap_or_default().to_string_lossy()));
+    Ok(split.to_string_lossy().to_string())
+}
+
 #[tauri::command]
 async fn add_custom_tag(
     mod_path: String,
     tag: String,
     state: State<'_, Arc<Mutex<AppState>>>,
 ) -> Result<(), String> {
-    let mut state = state.lock().unwrap();
     let path = PathBuf::from(&mod_path);
+    let id = mod_identity::mod_id(&path);
+    let mut state = state.lock().unwrap();
     
     // Find or create mod metadata
-    if let Some(metadata) = state.mod_metadata.iter_mut().find(|m| m.path == path) {
-        if !metadata.custom_tags.contains(&tag) {
-            metadata.custom_tags.push(tag);
-        }
-    } else {
-        state.mod_metadata.push(ModMetadata {
-            path,
-            custom_name: None,
-            folder_id: None,
-            custom_tags: vec![tag],
-        });
+    let metadata = state.metadata_entry(id, &path);
+    if !metadata.custom_tags.contains(&tag) {
+        metadata.custom_tags.push(tag);
     }
     
     save_state(&state).map_err(|e| e.to_string())?;
@@ -2802,11 +3754,12 @@ async fn remove_custom_tag(
     tag: String,
     state: State<'_, Arc<Mutex<AppState>>>,
 ) -> Result<(), String> {
-    let mut state = state.lock().unwrap();
     let path = PathBuf::from(&mod_path);
+    let id = mod_identity::mod_id(&path);
+    let mut state = state.lock().unwrap();
 
-    if let Some(metadata) = state.mod_metadata.iter_mut().find(|m| m.path == path) {
-        metadata.custom_tags.retain(|t| t != &tag);
+    if let Some(index) = state.metadata_index(id.as_deref(), &path) {
+        state.mod_metadata[index].custom_tags.retain(|t| t != &tag);
     }
 
     save_state(&state).map_err(|e| e.to_string())?;
@@ -2825,6 +3778,9 @@ async fn remove_custom_tag(
 /// - Deletes ALL existing .usmap files in the roaming Usmap folder before copying
 /// - Copies the new file to `%APPDATA%/Repak-X/Usmap/`
 /// - Only one USMAP file should exist at a time
+/// - Rejects files without a valid usmap header, leaving the current USMAP in place. The
+///   payload is only decoded when the mappings are loaded, so Oodle compressed files can be
+///   imported without Oodle.
 #[tauri::command]
 async fn copy_usmap_to_folder(source_path: String) -> Result<String, String> {
     let source = PathBuf::from(&source_path);
@@ -2833,6 +3789,17 @@ async fn copy_usmap_to_folder(source_path: String) -> Result<String, String> {
         return Err("Source file does not exist".to_string());
     }
     
+    // Validate before touching the existing USMAP
+    let header = UsmapHeader::read(&source)
+        .map_err(|e| format!("Invalid USMAP file: {}", e))?;
+    info!(
+        "Validated USMAP {}: {:?}, {:?} compression, engine {}",
+        source.display(),
+        header.version,
+        header.compression,
+        describe_usmap_engine(header.package_version, header.net_cl)
+    );
+    
     // Get the Usmap directory in roaming folder
     let usmap_folder = usmap_dir();
     std::fs::create_dir_all(&usmap_folder)
@@ -2912,6 +3879,10 @@ async fn list_usmap_files() -> Result<Vec<String>, String> {
     for entry in entries.filter_map(|e| e.ok()) {
         let path = entry.path();
         if path.extension().and_then(|s| s.to_str()) == Some("usmap") {
+            if let Err(e) = UsmapHeader::read(&path) {
+                warn!("Ignoring invalid USMAP file {:?}: {}", path, e);
+                continue;
+            }
             if let Some(filename) = path.file_name().and_then(|s| s.to_str()) {
                 files.push(filename.to_string());
             }
@@ -2921,6 +3892,48 @@ async fn list_usmap_files() -> Result<Vec<String>, String> {
     Ok(files)
 }
 
+#[derive(Clone, Serialize)]
+struct UsmapInfo {
+    file_name: String,
+    /// Usmap layout revision
+    version: String,
+    compression: String,
+    /// Object versions and changelist the mappings were dumped from, if recorded
+    engine: String,
+    schema_count: usize,
+    enum_count: usize,
+}
+
+/// Human-readable engine version of a usmap, or "unversioned"
+fn describe_usmap_engine(package_version: Option<uasset_toolkit::package::PackageVersion>, net_cl: Option<u32>) -> String {
+    match (package_version, net_cl) {
+        (Some(version), Some(cl)) => format!("UE4 {} / UE5 {} (CL {})", version.ue4, version.ue5, cl),
+        (Some(version), None) => format!("UE4 {} / UE5 {}", version.ue4, version.ue5),
+        _ => "unversioned".to_string(),
+    }
+}
+
+/// Load a USMAP in the roaming Usmap folder and report what it contains.
+/// 
+/// # Arguments
+/// * `usmap_filename` - Filename of the USMAP in the Usmap folder
+#[tauri::command]
+async fn get_usmap_info(usmap_filename: String) -> Result<UsmapInfo, String> {
+    let path = get_usmap_full_path(&usmap_filename)
+        .ok_or_else(|| format!("USMAP file not found: {}", usmap_filename))?;
+    let usmap = Usmap::read(&path)
+        .map_err(|e| format!("Invalid USMAP file: {}", e))?;
+    
+    Ok(UsmapInfo {
+        file_name: usmap_filename,
+        version: format!("{:?}", usmap.version),
+        compression: format!("{:?}", usmap.compression),
+        engine: describe_usmap_engine(usmap.package_version, usmap.net_cl),
+        schema_count: usmap.schema_count(),
+        enum_count: usmap.enums.len(),
+    })
+}
+
 /// Get the currently active USMAP file by reading from filesystem.
 /// This reads the actual files in the Usmap folder, not the saved state.
 /// 
@@ -2997,40 +4010,15 @@ async fn get_all_tags(state: State<'_, Arc<Mutex<AppState>>>) -> Result<Vec<Stri
 
 #[tauri::command]
 async fn toggle_mod(mod_path: String, window: Window) -> Result<bool, String> {
-    let path = PathBuf::from(&mod_path);
-    
-    if !path.exists() {
-        let error_msg = "Mod file does not exist".to_string();
-        toast_events::emit_toggle_failed(&window, &error_msg);
-        return Err(error_msg);
-    }
-    
-    // Check current state
-    let is_enabled = path.extension().and_then(|s| s.to_str()) == Some("pak");
-    
-    // Toggle by renaming
-    let new_path = if is_enabled {
-        path.with_extension("bak_repak")
-    } else {
-        path.with_extension("pak")
-    };
-    
-    if let Err(e) = std::fs::rename(&path, &new_path) {
-        let error_msg = format!("Failed to toggle mod: {}", e);
-        toast_events::emit_toggle_failed(&window, &error_msg);
-        return Err(error_msg);
-    }
-    
-    Ok(!is_enabled)
+    mod_ops::toggle_mod(Path::new(&mod_path)).inspect_err(|error_msg| {
+        toast_events::emit_toggle_failed(&window, error_msg);
+    })
 }
 
 #[tauri::command]
 async fn extract_pak_to_destination(mod_path: String, dest_path: String) -> Result<(), String> {
     use crate::install_mod::install_mod_logic::pak_files::extract_pak_to_dir;
     use crate::install_mod::InstallableMod;
-    use repak::PakBuilder;
-    use repak::utils::AesKey;
-    use std::str::FromStr;
     use std::io::BufReader;
     
     let pak_path = PathBuf::from(&mod_path);
@@ -3046,13 +4034,9 @@ async fn extract_pak_to_destination(mod_path: String, dest_path: String) -> Resu
     
     // Open PAK
     let file = File::open(&pak_path).map_err(|e| e.to_string())?;
-    let aes_key = AesKey::from_str("0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74")
-        .map_err(|e| e.to_string())?;
-        
     let mut reader = BufReader::new(file);
-    let pak_reader = PakBuilder::new()
-        .key(aes_key.0)
-        .reader(&mut reader)
+    let pak_reader = game_profile::active()
+        .open_pak(&mut reader)
         .map_err(|e| e.to_string())?;
         
     let installable_mod = InstallableMod {
@@ -3063,77 +4047,11 @@ async fn extract_pak_to_destination(mod_path: String, dest_path: String) -> Resu
         ..Default::default()
     };
     
-    extract_pak_to_dir(&installable_mod, to_create).map_err(|e| e.to_string())?;
+    extract_pak_to_dir(&installable_mod, to_create, &uasset_toolkit::CancellationToken::new()).map_err(|e| e.to_string())?;
     
     Ok(())
 }
 
-/// Cleanup .ubulk files for textures that have inline data.
-/// This is called after extraction to remove unnecessary .ubulk files
-/// that were pulled from the base game but aren't needed because the
-/// mod's textures have been patched to use inline data.
-async fn cleanup_ubulk_for_inline_textures(output_dir: &PathBuf) {
-    use walkdir::WalkDir;
-    use uasset_toolkit::get_global_toolkit;
-    
-    // Find all .uasset files - UAssetTool will detect which are textures
-    let uasset_files: Vec<String> = WalkDir::new(output_dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| {
-            let path = e.path();
-            if let Some(ext) = path.extension() {
-                return ext.to_string_lossy().to_lowercase() == "uasset";
-            }
-            false
-        })
-        .map(|e| e.path().to_string_lossy().to_string())
-        .collect();
-    
-    if uasset_files.is_empty() {
-        return;
-    }
-    
-    log::info!("[Extraction] Checking {} uasset files for textures with inline data...", uasset_files.len());
-    
-    // Use UAssetToolkit to check which are textures with inline data
-    // The batch_has_inline_texture_data function internally checks asset type
-    match get_global_toolkit() {
-        Ok(toolkit) => {
-            // Get USMAP path from environment
-            let usmap_path = std::env::var("USMAP_PATH").ok();
-            
-            match toolkit.batch_has_inline_texture_data(&uasset_files, usmap_path.as_deref()) {
-                Ok(inline_files) => {
-                    log::info!("[Extraction] Found {} textures with inline data", inline_files.len());
-                    
-                    // Delete .ubulk files for textures with inline data
-                    let mut deleted_count = 0;
-                    for uasset_path in inline_files {
-                        let ubulk_path = uasset_path.replace(".uasset", ".ubulk");
-                        if std::path::Path::new(&ubulk_path).exists() {
-                            if let Ok(_) = std::fs::remove_file(&ubulk_path) {
-                                deleted_count += 1;
-                                log::debug!("[Extraction] Deleted unnecessary .ubulk: {}", ubulk_path);
-                            }
-                        }
-                    }
-                    
-                    if deleted_count > 0 {
-                        log::info!("[Extraction] Cleaned up {} unnecessary .ubulk files", deleted_count);
-                    }
-                }
-                Err(e) => {
-                    log::warn!("[Extraction] Failed to check inline texture data: {}", e);
-                }
-            }
-        }
-        Err(e) => {
-            log::warn!("[Extraction] UAssetToolkit unavailable for cleanup: {}", e);
-        }
-    }
-}
-
 /// Extract assets from a mod file (PAK or IoStore) to a destination directory.
 /// Automatically detects the mod type and uses the appropriate extraction method.
 /// Handles disabled mods (.bak_repak extension) by treating them as PAK files.
@@ -3143,184 +4061,21 @@ async fn cleanup_ubulk_for_inline_textures(output_dir: &PathBuf) {
 /// * `dest_path` - Destination directory for extracted files
 /// 
 /// # Returns
-/// Number of files extracted
-#[tauri::command]
-async fn extract_mod_assets(mod_path: String, dest_path: String, state: State<'_, Arc<Mutex<AppState>>>) -> Result<usize, String> {
-    // Set USMAP_PATH from AppState so UAssetTool can load mappings
-    {
-        let state_guard = state.lock().unwrap();
-        let usmap_filename = state_guard.usmap_path.clone();
-        drop(state_guard);
-        if !usmap_filename.is_empty() {
-            if let Some(usmap_full_path) = get_usmap_full_path(&usmap_filename) {
-                std::env::set_var("USMAP_PATH", &usmap_full_path);
-            }
-        }
-    }
-    extract_mod_assets_inner(mod_path, dest_path).await
-}
-
-async fn extract_mod_assets_inner(mod_path: String, dest_path: String) -> Result<usize, String> {
-    let mut path = PathBuf::from(&mod_path);
-    if !path.exists() {
-        return Err(format!("File not found: {}", mod_path));
-    }
-    
-    // Check if this is a PAK file with a corresponding .utoc file (IoStore mod)
-    // IoStore mods have both .pak (with just chunknames) and .utoc/.ucas (actual data)
-    let extension = path.extension()
-        .map(|e| e.to_string_lossy().to_lowercase())
-        .unwrap_or_default();
-    
-    if extension == "pak" || extension == "bak_repak" {
-        // Check if there's a .utoc file with the same name - if so, use IoStore extraction
-        // Handle .bak_repak (disabled mod) by stripping .bak_repak then .pak to get base name
-        let base_path = if extension == "bak_repak" {
-            let name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
-            let base_name = name.trim_end_matches(".bak_repak").trim_end_matches(".pak");
-            path.parent().map(|p| p.join(base_name)).unwrap_or_else(|| PathBuf::from(base_name))
-        } else {
-            path.with_extension("")
-        };
-        let utoc_path = base_path.with_extension("utoc");
-        if utoc_path.exists() {
-            log::info!("Detected IoStore mod (has .utoc alongside .pak/.bak_repak), using IoStore extraction");
-            path = utoc_path;
-        }
-    }
-    
-    let dest_dir = PathBuf::from(&dest_path);
-    
-    // Get mod name - handle .bak_repak extension specially
-    let file_name = path.file_name()
-        .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| "extracted".to_string());
-    
-    // Strip .bak_repak or other extensions to get clean mod name
-    let mod_name = if file_name.ends_with(".bak_repak") {
-        // Remove .bak_repak and then .pak to get the base name
-        file_name.trim_end_matches(".bak_repak")
-            .trim_end_matches(".pak")
-            .to_string()
-    } else {
-        path.file_stem()
-            .map(|s| s.to_string_lossy().to_string())
-            .unwrap_or_else(|| "extracted".to_string())
-    };
-    
-    let output_dir = dest_dir.join(&mod_name);
-    
-    // Create output directory
-    std::fs::create_dir_all(&output_dir).map_err(|e| e.to_string())?;
-    
-    // Re-get extension after potential path change
-    let extension = path.extension()
-        .map(|e| e.to_string_lossy().to_lowercase())
-        .unwrap_or_default();
-    
-    match extension.as_str() {
-        "utoc" => {
-            // IoStore extraction using UAssetTool
-            log::info!("Starting IoStore extraction from {:?} to {:?}", path, output_dir);
-            let file_count = uasset_toolkit::extract_iostore(
-                &path.to_string_lossy(),
-                &output_dir.to_string_lossy(),
-                None, // Use default AES key
-            ).map_err(|e| {
-                log::error!("IoStore extraction failed: {}", e);
-                format!("Failed to extract IoStore: {}", e)
-            })?;
-            
-            log::info!("Extracted {} files from IoStore to {:?}", file_count, output_dir);
-            
-            // Post-extraction cleanup: Remove .ubulk files for textures with inline data
-            cleanup_ubulk_for_inline_textures(&output_dir).await;
-            
-            Ok(file_count)
-        }
-        "pak" => {
-            // PAK extraction
-            use crate::install_mod::install_mod_logic::pak_files::extract_pak_to_dir;
-            use crate::install_mod::InstallableMod;
-            use repak::PakBuilder;
-            use repak::utils::AesKey;
-            use std::str::FromStr;
-            use std::io::BufReader;
-            
-            let file = File::open(&path).map_err(|e| e.to_string())?;
-            let aes_key = AesKey::from_str("0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74")
-                .map_err(|e| e.to_string())?;
-            
-            let mut reader = BufReader::new(file);
-            let pak_reader = PakBuilder::new()
-                .key(aes_key.0)
-                .reader(&mut reader)
-                .map_err(|e| e.to_string())?;
-            
-            let file_count = pak_reader.files().len();
-            
-            let installable_mod = InstallableMod {
-                mod_name: mod_name.clone(),
-                mod_type: "".to_string(),
-                reader: Some(pak_reader),
-                mod_path: path.clone(),
-                ..Default::default()
-            };
-            
-            extract_pak_to_dir(&installable_mod, output_dir.clone()).map_err(|e| e.to_string())?;
-            
-            log::info!("Extracted {} files from PAK to {:?}", file_count, output_dir);
-            Ok(file_count)
-        }
-        "ucas" => {
-            // User selected .ucas, find the corresponding .utoc
-            let utoc_path = path.with_extension("utoc");
-            if !utoc_path.exists() {
-                return Err(format!("Cannot find .utoc file for: {}", mod_path));
+/// Number of files extracted
+#[tauri::command]
+async fn extract_mod_assets(mod_path: String, dest_path: String, state: State<'_, Arc<Mutex<AppState>>>) -> Result<usize, String> {
+    // Set USMAP_PATH from AppState so UAssetTool can load mappings
+    {
+        let state_guard = state.lock().unwrap();
+        let usmap_filename = state_guard.usmap_path.clone();
+        drop(state_guard);
+        if !usmap_filename.is_empty() {
+            if let Some(usmap_full_path) = get_usmap_full_path(&usmap_filename) {
+                std::env::set_var("USMAP_PATH", &usmap_full_path);
             }
-            
-            // Recursively call with the .utoc path
-            let utoc_str = utoc_path.to_string_lossy().to_string();
-            Box::pin(extract_mod_assets_inner(utoc_str, dest_path)).await
-        }
-        "bak_repak" => {
-            // Disabled PAK file - extract it as a regular PAK
-            use crate::install_mod::install_mod_logic::pak_files::extract_pak_to_dir;
-            use crate::install_mod::InstallableMod;
-            use repak::PakBuilder;
-            use repak::utils::AesKey;
-            use std::str::FromStr;
-            use std::io::BufReader;
-            
-            let file = File::open(&path).map_err(|e| e.to_string())?;
-            let aes_key = AesKey::from_str("0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74")
-                .map_err(|e| e.to_string())?;
-            
-            let mut reader = BufReader::new(file);
-            let pak_reader = PakBuilder::new()
-                .key(aes_key.0)
-                .reader(&mut reader)
-                .map_err(|e| e.to_string())?;
-            
-            let file_count = pak_reader.files().len();
-            
-            let installable_mod = InstallableMod {
-                mod_name: mod_name.clone(),
-                mod_type: "".to_string(),
-                reader: Some(pak_reader),
-                mod_path: path.clone(),
-                ..Default::default()
-            };
-            
-            extract_pak_to_dir(&installable_mod, output_dir.clone()).map_err(|e| e.to_string())?;
-            
-            log::info!("Extracted {} files from disabled PAK to {:?}", file_count, output_dir);
-            Ok(file_count)
-        }
-        _ => {
-            Err(format!("Unsupported file type: .{}. Supported: .pak, .utoc, .ucas, .bak_repak", extension))
         }
     }
+    mod_ops::extract_mod_assets(Path::new(&mod_path), Path::new(&dest_path))
 }
 
 #[tauri::command]
@@ -3339,21 +4094,20 @@ fn is_game_process_running() -> bool {
         RefreshKind::new().with_processes(ProcessRefreshKind::everything())
     );
     
-    let game_exe_name = "marvel-win64-shipping.exe";
+    let profile = game_profile::active();
     
     for (_pid, process) in s.processes() {
         // Primary method: Check exe() path (most reliable on Windows)
         if let Some(exe_path) = process.exe() {
             if let Some(file_name) = exe_path.file_name() {
-                if file_name.to_string_lossy().to_lowercase() == game_exe_name {
+                if profile.is_game_process(&file_name.to_string_lossy()) {
                     return true;
                 }
             }
         }
         
         // Fallback: Check process name() directly
-        let process_name = process.name().to_string_lossy().to_lowercase();
-        if process_name == game_exe_name {
+        if profile.is_game_process(&process.name().to_string_lossy()) {
             return true;
         }
     }
@@ -3382,15 +4136,12 @@ async fn launch_game(state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), Strin
         state.game_path.clone()
     };
     
-    // Go up 5 levels to get the actual game root
-    // ~mods -> Paks -> Content -> Marvel -> MarvelGame -> MarvelRivals (game root)
-    let game_root = mods_path
-        .parent() // Paks
-        .and_then(|p| p.parent()) // Content
-        .and_then(|p| p.parent()) // Marvel
-        .and_then(|p| p.parent()) // MarvelGame
-        .and_then(|p| p.parent()) // MarvelRivals (game root)
+    // Walk up the profile's mods layout to get the actual game root
+    let profile = game_profile::active();
+    let game_root = profile.game_root(&mods_path)
         .ok_or_else(|| "Could not determine game root directory".to_string())?;
+    let steam_url = profile.steam_run_url()
+        .ok_or_else(|| format!("No Steam app id configured for {}", profile.name))?;
     
     // Path to launch_record file (in the game root, next to MarvelRivals_Launcher.exe)
     let launch_record_path = game_root.join("launch_record");
@@ -3436,28 +4187,29 @@ async fn launch_game(state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), Strin
             .arg("&&")
             .arg("start")
             .arg("")
-            .arg("steam://run/2767030")
+            .arg(&steam_url)
             .creation_flags(CREATE_NO_WINDOW)
             .spawn()
     };
     
     #[cfg(target_os = "macos")]
     let launch_result = Command::new("open")
-        .arg("steam://run/2767030")
+        .arg(&steam_url)
         .spawn();
     
     #[cfg(target_os = "linux")]
     let launch_result = Command::new("xdg-open")
-        .arg("steam://run/2767030")
+        .arg(&steam_url)
         .spawn();
     
     // Check launch result
     match launch_result {
         Ok(_) => {
-            info!("Successfully launched Marvel Rivals via Steam");
+            info!("Successfully launched {} via Steam", profile.name);
             
             // Spawn a background task to restore the launch_record after the game starts
             let launch_record_path_clone = launch_record_path.clone();
+            let thread_profile = profile.clone();
             std::thread::spawn(move || {
                 use sysinfo::{ProcessRefreshKind, RefreshKind, System};
                 
@@ -3476,8 +4228,7 @@ async fn launch_game(state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), Strin
                     
                     let mut found = false;
                     for (_pid, process) in s.processes() {
-                        let process_name = process.name().to_string_lossy().to_lowercase();
-                        if process_name == "marvel-win64-shipping.exe" {
+                        if thread_profile.is_game_process(&process.name().to_string_lossy()) {
                             info!("Game process detected, waiting 2 more seconds before restoring launch_record");
                             std::thread::sleep(std::time::Duration::from_secs(2));
                             found = true;
@@ -3531,14 +4282,9 @@ async fn skip_launcher_patch(state: State<'_, Arc<Mutex<AppState>>>) -> Result<b
         state.game_path.clone()
     };
     
-    // Go up 5 levels to get the actual game root
-    // ~mods -> Paks -> Content -> Marvel -> MarvelGame -> MarvelRivals (game root)
-    let game_root = mods_path
-        .parent() // Paks
-        .and_then(|p| p.parent()) // Content
-        .and_then(|p| p.parent()) // Marvel
-        .and_then(|p| p.parent()) // MarvelGame
-        .and_then(|p| p.parent()) // MarvelRivals (game root)
+    // Walk up the profile's mods layout to get the actual game root
+    let profile = game_profile::active();
+    let game_root = profile.game_root(&mods_path)
         .ok_or_else(|| "Could not determine game root directory".to_string())?;
     
     // Path to launch_record file
@@ -3588,14 +4334,9 @@ async fn get_skip_launcher_status(state: State<'_, Arc<Mutex<AppState>>>) -> Res
         state.game_path.clone()
     };
     
-    // Go up 5 levels to get the actual game root
-    // ~mods -> Paks -> Content -> Marvel -> MarvelGame -> MarvelRivals (game root)
-    let game_root = mods_path
-        .parent() // Paks
-        .and_then(|p| p.parent()) // Content
-        .and_then(|p| p.parent()) // Marvel
-        .and_then(|p| p.parent()) // MarvelGame
-        .and_then(|p| p.parent()) // MarvelRivals (game root)
+    // Walk up the profile's mods layout to get the actual game root
+    let profile = game_profile::active();
+    let game_root = profile.game_root(&mods_path)
         .ok_or_else(|| "Could not determine game root directory".to_string())?;
     
     // Path to launch_record file
@@ -3715,341 +4456,27 @@ async fn deploy_lod_disabler(state: State<'_, Arc<Mutex<AppState>>>) -> Result<b
     deploy_bundled_lod_mod(&mods_path)
 }
 
-/// Result of recompression operation
-#[derive(Clone, Serialize, Deserialize)]
-struct RecompressResult {
-    total_scanned: usize,
-    already_oodle: usize,
-    recompressed: usize,
-    failed: usize,
-    skipped_iostore: usize,
-    details: Vec<RecompressDetail>,
-}
-
-#[derive(Clone, Serialize, Deserialize)]
-struct RecompressDetail {
-    mod_name: String,
-    status: String, // "already_oodle", "recompressed", "failed", "skipped_iostore"
-    original_size: u64,
-    new_size: Option<u64>,
-    error: Option<String>,
-}
-
 /// Scan all mods and recompress any that aren't using Oodle compression
 #[tauri::command]
 async fn recompress_mods(
+    mode: Option<String>,
     state: State<'_, Arc<Mutex<AppState>>>,
     window: Window,
-) -> Result<RecompressResult, String> {
-    use repak::Compression;
-    use std::io::BufReader;
+) -> Result<mod_ops::RecompressResult, String> {
+    let oodle_settings = mod_ops::oodle_settings_for_mode(mode.as_deref())?;
     
     let game_path = {
         let state = state.lock().unwrap();
         state.game_path.clone()
     };
     
-    if !game_path.exists() {
-        return Err("Game path does not exist".to_string());
-    }
-    
-    info!("Starting recompression scan in: {}", game_path.display());
-    
-    let mut result = RecompressResult {
-        total_scanned: 0,
-        already_oodle: 0,
-        recompressed: 0,
-        failed: 0,
-        skipped_iostore: 0,
-        details: Vec::new(),
-    };
-    
-    // Collect all .pak files
-    let mut pak_files: Vec<PathBuf> = Vec::new();
-    for entry in WalkDir::new(&game_path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let path = entry.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("pak") {
-            pak_files.push(path.to_path_buf());
-        }
-    }
-    
-    result.total_scanned = pak_files.len();
-    info!("Found {} PAK files to scan", pak_files.len());
-    
-    // Emit initial progress
-    let _ = window.emit("recompress_progress", serde_json::json!({
-        "current": 0,
-        "total": pak_files.len(),
-        "status": "Scanning..."
-    }));
-    
-    for (idx, pak_path) in pak_files.iter().enumerate() {
-        let mod_name = pak_path.file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("Unknown")
-            .to_string();
-        
-        // Emit progress
-        let _ = window.emit("recompress_progress", serde_json::json!({
-            "current": idx + 1,
-            "total": pak_files.len(),
-            "status": format!("Checking: {}", mod_name)
-        }));
-        
-        // Check if this is an IoStore mod (has .utoc/.ucas files)
-        let utoc_path = pak_path.with_extension("utoc");
-        let ucas_path = pak_path.with_extension("ucas");
-        
-        if utoc_path.exists() && ucas_path.exists() {
-            // IoStore mod - check if it needs recompression
-            let ucas_size = std::fs::metadata(&ucas_path).map(|m| m.len()).unwrap_or(0);
-            
-            // Check if IoStore is already compressed
-            let is_compressed = match uasset_toolkit::is_iostore_compressed(&utoc_path.to_string_lossy()) {
-                Ok(compressed) => compressed,
-                Err(e) => {
-                    warn!("Failed to check IoStore compression for {}: {}", mod_name, e);
-                    result.failed += 1;
-                    result.details.push(RecompressDetail {
-                        mod_name,
-                        status: "failed".to_string(),
-                        original_size: ucas_size,
-                        new_size: None,
-                        error: Some(format!("Failed to check compression: {}", e)),
-                    });
-                    continue;
-                }
-            };
-            
-            if is_compressed {
-                // Already compressed
-                info!("IoStore already compressed: {}", mod_name);
-                result.already_oodle += 1;
-                result.details.push(RecompressDetail {
-                    mod_name,
-                    status: "already_oodle".to_string(),
-                    original_size: ucas_size,
-                    new_size: None,
-                    error: None,
-                });
-            } else {
-                // Need to recompress IoStore
-                info!("Recompressing IoStore: {}", mod_name);
-                
-                let _ = window.emit("recompress_progress", serde_json::json!({
-                    "current": idx + 1,
-                    "total": pak_files.len(),
-                    "status": format!("Recompressing IoStore: {}", mod_name)
-                }));
-                
-                match uasset_toolkit::recompress_iostore(&utoc_path.to_string_lossy()) {
-                    Ok(_) => {
-                        let new_ucas_size = std::fs::metadata(&ucas_path).map(|m| m.len()).unwrap_or(0);
-                        info!("Successfully recompressed IoStore: {} ({} -> {} bytes)", mod_name, ucas_size, new_ucas_size);
-                        result.recompressed += 1;
-                        result.details.push(RecompressDetail {
-                            mod_name,
-                            status: "recompressed".to_string(),
-                            original_size: ucas_size,
-                            new_size: Some(new_ucas_size),
-                            error: None,
-                        });
-                    }
-                    Err(e) => {
-                        error!("Failed to recompress IoStore {}: {}", mod_name, e);
-                        result.failed += 1;
-                        result.details.push(RecompressDetail {
-                            mod_name,
-                            status: "failed".to_string(),
-                            original_size: ucas_size,
-                            new_size: None,
-                            error: Some(format!("Recompression failed: {}", e)),
-                        });
-                    }
-                }
-            }
-            continue;
-        }
-        
-        // Try to read the PAK file
-        let file = match File::open(pak_path) {
-            Ok(f) => f,
-            Err(e) => {
-                error!("Failed to open PAK file {}: {}", pak_path.display(), e);
-                result.failed += 1;
-                result.details.push(RecompressDetail {
-                    mod_name,
-                    status: "failed".to_string(),
-                    original_size: 0,
-                    new_size: None,
-                    error: Some(format!("Failed to open: {}", e)),
-                });
-                continue;
-            }
-        };
-        
-        let original_size = std::fs::metadata(pak_path).map(|m| m.len()).unwrap_or(0);
-        
-        let pak_reader = match repak::PakBuilder::new()
-            .key(install_mod::AES_KEY.clone().0)
-            .reader(&mut BufReader::new(&file))
-        {
-            Ok(reader) => reader,
-            Err(e) => {
-                error!("Failed to read PAK file {}: {}", pak_path.display(), e);
-                result.failed += 1;
-                result.details.push(RecompressDetail {
-                    mod_name,
-                    status: "failed".to_string(),
-                    original_size,
-                    new_size: None,
-                    error: Some(format!("Failed to parse PAK: {}", e)),
-                });
-                continue;
-            }
-        };
-        
-        // Check compression type
-        let compressions = pak_reader.compression();
-        let has_oodle = compressions.iter().any(|c| matches!(c, Compression::Oodle));
-        let is_uncompressed = compressions.is_empty();
-        
-        if has_oodle && !is_uncompressed {
-            // Already using Oodle compression
-            info!("Already Oodle compressed: {}", mod_name);
-            result.already_oodle += 1;
-            result.details.push(RecompressDetail {
-                mod_name,
-                status: "already_oodle".to_string(),
-                original_size,
-                new_size: None,
-                error: None,
-            });
-            continue;
-        }
-        
-        // Need to recompress this PAK
-        info!("Recompressing: {} (compression: {:?})", mod_name, compressions);
-        
-        // Emit progress for recompression
+    mod_ops::recompress_mods(&game_path, &mut mod_index::Shared, oodle_settings, |current, total, status| {
         let _ = window.emit("recompress_progress", serde_json::json!({
-            "current": idx + 1,
-            "total": pak_files.len(),
-            "status": format!("Recompressing: {}", mod_name)
+            "current": current,
+            "total": total,
+            "status": status
         }));
-        
-        // Recompress the PAK file
-        match recompress_pak_file(pak_path, &pak_reader) {
-            Ok(new_size) => {
-                info!("Successfully recompressed: {} ({} -> {} bytes)", mod_name, original_size, new_size);
-                result.recompressed += 1;
-                result.details.push(RecompressDetail {
-                    mod_name,
-                    status: "recompressed".to_string(),
-                    original_size,
-                    new_size: Some(new_size),
-                    error: None,
-                });
-            }
-            Err(e) => {
-                error!("Failed to recompress {}: {}", mod_name, e);
-                result.failed += 1;
-                result.details.push(RecompressDetail {
-                    mod_name,
-                    status: "failed".to_string(),
-                    original_size,
-                    new_size: None,
-                    error: Some(e),
-                });
-            }
-        }
-    }
-    
-    // Emit completion
-    let _ = window.emit("recompress_progress", serde_json::json!({
-        "current": pak_files.len(),
-        "total": pak_files.len(),
-        "status": "Complete"
-    }));
-    
-    info!("Recompression complete: {} scanned, {} already Oodle, {} recompressed, {} failed",
-        result.total_scanned, result.already_oodle, result.recompressed, result.failed);
-    
-    Ok(result)
-}
-
-/// Recompress a single PAK file to use Oodle compression
-fn recompress_pak_file(pak_path: &Path, pak_reader: &repak::PakReader) -> Result<u64, String> {
-    use repak::{Compression, Version};
-    use std::io::{BufReader, BufWriter};
-    use tempfile::NamedTempFile;
-    
-    // Create a temporary file for the new PAK
-    let temp_file = NamedTempFile::new()
-        .map_err(|e| format!("Failed to create temp file: {}", e))?;
-    
-    let temp_path = temp_file.path().to_path_buf();
-    
-    // Get PAK metadata
-    let mount_point = pak_reader.mount_point().to_string();
-    let path_hash_seed = pak_reader.path_hash_seed();
-    let files = pak_reader.files();
-    
-    // Create new PAK with Oodle compression
-    let output_file = File::create(&temp_path)
-        .map_err(|e| format!("Failed to create output file: {}", e))?;
-    
-    let builder = repak::PakBuilder::new()
-        .compression(vec![Compression::Oodle])
-        .key(install_mod::AES_KEY.clone().0);
-    
-    let mut pak_writer = builder.writer(
-        BufWriter::new(output_file),
-        Version::V11,
-        mount_point,
-        path_hash_seed,
-    );
-    
-    let entry_builder = pak_writer.entry_builder();
-    
-    // Read source file
-    let source_file = File::open(pak_path)
-        .map_err(|e| format!("Failed to open source PAK: {}", e))?;
-    let mut source_reader = BufReader::new(source_file);
-    
-    // Copy all entries with Oodle compression
-    for file_path in &files {
-        let data = pak_reader.get(file_path, &mut source_reader)
-            .map_err(|e| format!("Failed to read entry {}: {}", file_path, e))?;
-        
-        // Build entry with compression enabled
-        let entry = entry_builder
-            .build_entry(true, data, file_path)
-            .map_err(|e| format!("Failed to build entry {}: {}", file_path, e))?;
-        
-        pak_writer.write_entry(file_path.to_string(), entry)
-            .map_err(|e| format!("Failed to write entry {}: {}", file_path, e))?;
-    }
-    
-    // Finalize the PAK (write_index consumes pak_writer)
-    let _writer = pak_writer.write_index()
-        .map_err(|e| format!("Failed to write index: {}", e))?;
-    
-    // Get new file size
-    let new_size = std::fs::metadata(&temp_path)
-        .map(|m| m.len())
-        .unwrap_or(0);
-    
-    // Replace original file with recompressed version
-    std::fs::copy(&temp_path, pak_path)
-        .map_err(|e| format!("Failed to replace original PAK: {}", e))?;
-    
-    // Clean up temp file (it will be deleted when temp_file is dropped)
-    
-    Ok(new_size)
+    })
 }
 
 #[tauri::command]
@@ -4981,31 +5408,6 @@ async fn identify_mod_character(file_paths: Vec<String>) -> Result<Option<(Strin
 // HELPER FUNCTIONS
 // ============================================================================
 
-fn app_dir() -> PathBuf {
-    dirs::config_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join("Repak-X")
-}
-
-/// Directory for USMAP files - stored in roaming folder
-fn usmap_dir() -> PathBuf {
-    app_dir().join("Usmap")
-}
-
-/// Get the full path to a USMAP file by filename
-fn get_usmap_full_path(usmap_filename: &str) -> Option<PathBuf> {
-    if usmap_filename.is_empty() {
-        return None;
-    }
-    
-    let usmap_path = usmap_dir().join(usmap_filename);
-    if usmap_path.exists() {
-        Some(usmap_path)
-    } else {
-        None
-    }
-}
-
 /// Directory for log files - placed next to the executable for easy access
 fn log_dir() -> PathBuf {
     if let Ok(exe_path) = std::env::current_exe() {
@@ -5108,12 +5510,6 @@ struct ModDetails {
 
 #[tauri::command]
 async fn get_mod_details(mod_path: String, _detect_blueprint: Option<bool>) -> Result<ModDetails, String> {
-    use repak::PakBuilder;
-    use repak::utils::AesKey;
-    use std::str::FromStr;
-    use std::fs::File;
-    use std::io::BufReader;
-    
     let path = PathBuf::from(&mod_path);
     
     info!("Getting details for mod: {}", path.display());
@@ -5129,30 +5525,9 @@ async fn get_mod_details(mod_path: String, _detect_blueprint: Option<bool>) -> R
     utoc_path.set_extension("utoc");
     let is_iostore = utoc_path.exists();
     
-    // Get file list
-    let files: Vec<String> = if is_iostore {
-        // For IoStore, read from utoc (handles both normal and obfuscated containers)
-        use crate::utoc_utils::read_utoc;
-        read_utoc(&utoc_path)
-            .iter()
-            .map(|entry| entry.file_path.clone())
-            .collect()
-    } else {
-        // For regular PAK, open with AES key
-        let aes_key = AesKey::from_str("0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74")
-            .map_err(|e| format!("Failed to create AES key: {}", e))?;
-        
-        let file = File::open(&path)
-            .map_err(|e| format!("Failed to open PAK file: {}", e))?;
-        
-        let mut reader = BufReader::new(file);
-        let pak = PakBuilder::new()
-            .key(aes_key.0)
-            .reader(&mut reader)
-            .map_err(|e| format!("Failed to read PAK (bad AES key or corrupted file): {}", e))?;
-        
-        pak.files()
-    };
+    // Get file list and mod type from the index, which reads the mod only once
+    let files = mod_index::contents(&path)?;
+    let characteristics = mod_index::characteristics(&path)?;
     
     let file_count = files.len();
     
@@ -5163,9 +5538,6 @@ async fn get_mod_details(mod_path: String, _detect_blueprint: Option<bool>) -> R
         info!("First 10 files: {:?}", &files[..10]);
     }
     
-    // Determine mod type using the detailed function
-    use crate::utils::get_pak_characteristics_detailed;
-    let characteristics = get_pak_characteristics_detailed(files.clone());
     info!("Detected mod type: {}", characteristics.mod_type);
     info!("Character name: {}", characteristics.character_name);
     info!("Category: {}", characteristics.category);
@@ -5222,12 +5594,6 @@ async fn get_mod_details(mod_path: String, _detect_blueprint: Option<bool>) -> R
     })
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-struct ModClash {
-    file_path: String,
-    mod_paths: Vec<String>,
-}
-
 #[derive(Clone, Serialize, Deserialize)]
 struct SingleModConflict {
     conflicting_mod_path: String,
@@ -5238,212 +5604,9 @@ struct SingleModConflict {
 }
 
 #[tauri::command]
-async fn check_mod_clashes(state: State<'_, Arc<Mutex<AppState>>>) -> Result<Vec<ModClash>, String> {
-    use repak::PakBuilder;
-    use repak::utils::AesKey;
-    use std::str::FromStr;
-    use std::fs::File;
-    use std::io::BufReader;
-    use std::collections::HashMap;
-    
-    let state = state.lock().unwrap();
-    let game_path = &state.game_path;
-    
-    info!("Checking for mod clashes...");
-    
-    if !game_path.exists() {
-        return Err("Game path does not exist".to_string());
-    }
-
-    // Get AES key
-    let aes_key = AesKey::from_str("0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74")
-        .map_err(|e| format!("Failed to create AES key: {}", e))?;
-
-    // Structure to hold mod info for clash detection
-    #[derive(Clone)]
-    struct ModInfo {
-        path: PathBuf,
-        priority: usize,
-        files: Vec<String>,      // List of files inside this mod
-    }
-
-    let mut mods_info: Vec<ModInfo> = Vec::new();
-
-    // Scan all enabled mods
-    for entry in WalkDir::new(&game_path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let path = entry.path();
-
-        if path.is_dir() {
-            continue;
-        }
-
-        let ext = path.extension().and_then(|s| s.to_str());
-
-        // Only check enabled .pak files
-        if ext != Some("pak") {
-            continue;
-        }
-
-        // Calculate priority (same as get_pak_files)
-        let mut priority = 0;
-        let file_stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
-
-        // Check for "!" prefix (highest priority)
-        if file_stem.starts_with("!") {
-            priority = 0; // Highest priority
-        } else if file_stem.ends_with("_P") {
-            let base_no_p = file_stem.strip_suffix("_P").unwrap();
-            let re_nums = Regex::new(r"_(\d+)$").unwrap();
-            if let Some(caps) = re_nums.captures(base_no_p) {
-                let nums = &caps[1];
-                if nums.chars().all(|c| c == '9') {
-                    let actual_nines = nums.len();
-                    // Convert actual nines count to UI priority (1-based)
-                    if actual_nines >= 7 {
-                        priority = actual_nines - 6;
-                    }
-                }
-            }
-        }
-
-        // Open PAK file to analyze contents
-        let file = match File::open(&path) {
-            Ok(f) => f,
-            Err(e) => {
-                warn!("Failed to open PAK file {:?}: {}", path, e);
-                continue;
-            }
-        };
-
-        let mut reader = BufReader::new(file);
-        let pak = match PakBuilder::new()
-            .key(aes_key.0.clone())
-            .reader(&mut reader) {
-            Ok(p) => p,
-            Err(e) => {
-                warn!("Failed to read PAK {:?}: {}", path, e);
-                continue;
-            }
-        };
-
-        // Check if it's IoStore
-        let mut utoc_path = path.to_path_buf();
-        utoc_path.set_extension("utoc");
-        let is_iostore = utoc_path.exists();
-
-        // Get file list
-        let files: Vec<String> = if is_iostore {
-            use crate::utoc_utils::read_utoc;
-            read_utoc(&utoc_path)
-                .iter()
-                .map(|entry| entry.file_path.clone())
-                .collect()
-        } else {
-            pak.files()
-        };
-
-
-        mods_info.push(ModInfo {
-            path: path.to_path_buf(),
-            priority,
-            files,
-        });
-    }
-
-    info!("Analyzed {} enabled mods", mods_info.len());
-
-    // Don't group by character - instead, compare all mods at the same priority level
-    // Group by priority first
-    let mut by_priority: HashMap<usize, Vec<ModInfo>> = HashMap::new();
-    
-    for mod_info in mods_info {
-        by_priority.entry(mod_info.priority).or_insert_with(Vec::new).push(mod_info);
-    }
-
-    // Find clashes: same priority and overlapping files
-    let mut clashes: Vec<ModClash> = Vec::new();
-    use std::collections::HashSet;
-
-    for (priority, same_priority_mods) in by_priority {
-        if same_priority_mods.len() < 2 {
-            continue;
-        }
-
-        info!("Checking priority {} with {} mods", priority, same_priority_mods.len());
-
-        // Compare each pair of mods at this priority level
-        for i in 0..same_priority_mods.len() {
-            for j in (i + 1)..same_priority_mods.len() {
-                let mod1 = &same_priority_mods[i];
-                let mod2 = &same_priority_mods[j];
-
-                // Convert file lists to HashSets for efficient intersection
-                let files1: HashSet<&String> = mod1.files.iter().collect();
-                let files2: HashSet<&String> = mod2.files.iter().collect();
-
-                // Find overlapping files, excluding metadata files like 'patched_files'
-                let overlapping_files: Vec<String> = files1
-                    .intersection(&files2)
-                    .filter(|f| !f.ends_with("patched_files") && !f.contains("/patched_files"))
-                    .map(|s| (*s).clone())
-                    .collect();
-
-                if !overlapping_files.is_empty() {
-                    // Found a clash! These two mods modify the same files
-                    let mod_paths = vec![
-                        mod1.path.to_string_lossy().to_string(),
-                        mod2.path.to_string_lossy().to_string(),
-                    ];
-
-                    // Build a description showing which characters are affected
-                    let mut affected_characters = HashSet::new();
-                    
-                    // Extract character IDs from overlapping file paths
-                    for file_path in &overlapping_files {
-                        // Look for pattern like "Characters/1050/" or "1050/1050800/"
-                        if let Some(char_match) = file_path.split('/').find(|s| {
-                            s.len() == 4 && s.chars().all(|c| c.is_ascii_digit()) && s.starts_with("10")
-                        }) {
-                            affected_characters.insert(char_match.to_string());
-                        }
-                    }
-
-                    let character_info = if !affected_characters.is_empty() {
-                        let char_ids: Vec<String> = affected_characters.iter().cloned().collect();
-                        format!("Characters: {} - ", char_ids.join(", "))
-                    } else {
-                        String::new()
-                    };
-
-                    let clash_description = format!(
-                        "{}Priority: {} - {} overlapping file(s)",
-                        character_info,
-                        priority,
-                        overlapping_files.len()
-                    );
-
-                    info!(
-                        "Found clash between {} and {} at priority {} ({} overlapping files, characters: {:?})",
-                        mod1.path.file_name().unwrap_or_default().to_string_lossy(),
-                        mod2.path.file_name().unwrap_or_default().to_string_lossy(),
-                        priority,
-                        overlapping_files.len(),
-                        affected_characters
-                    );
-
-                    clashes.push(ModClash {
-                        file_path: clash_description,
-                        mod_paths,
-                    });
-                }
-            }
-        }
-    }
-    info!("Found {} clashes", clashes.len());
-    Ok(clashes)
+async fn check_mod_clashes(state: State<'_, Arc<Mutex<AppState>>>) -> Result<Vec<mod_ops::ModClash>, String> {
+    let game_path = state.lock().unwrap().game_path.clone();
+    mod_ops::find_mod_clashes(&game_path, &mut mod_index::Shared)
 }
 
 #[tauri::command]
@@ -5451,13 +5614,6 @@ async fn check_single_mod_conflicts(
     mod_path: String,
     state: State<'_, Arc<Mutex<AppState>>>
 ) -> Result<Vec<SingleModConflict>, String> {
-    use repak::PakBuilder;
-    use repak::utils::AesKey;
-    use std::str::FromStr;
-    use std::fs::File;
-    use std::io::BufReader;
-    use std::collections::HashSet;
-    
     let target_path = PathBuf::from(&mod_path);
     
     if !target_path.exists() {
@@ -5475,9 +5631,6 @@ async fn check_single_mod_conflicts(
     
     info!("Checking conflicts for mod: {}", target_path.display());
     
-    let aes_key = AesKey::from_str("0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74")
-        .map_err(|e| format!("Failed to create AES key: {}", e))?;
-    
     // Helper to calculate priority from filename
     fn calculate_priority(path: &Path) -> usize {
         let mut priority = 0;
@@ -5501,32 +5654,9 @@ async fn check_single_mod_conflicts(
         priority
     }
     
-    // Helper to get files from a PAK
-    fn get_pak_files(path: &Path, aes_key: &AesKey) -> Result<Vec<String>, String> {
-        let file = File::open(path).map_err(|e| format!("Failed to open PAK: {}", e))?;
-        let mut reader = BufReader::new(file);
-        let pak = PakBuilder::new()
-            .key(aes_key.0.clone())
-            .reader(&mut reader)
-            .map_err(|e| format!("Failed to read PAK: {}", e))?;
-        
-        let mut utoc_path = path.to_path_buf();
-        utoc_path.set_extension("utoc");
-        
-        if utoc_path.exists() {
-            use crate::utoc_utils::read_utoc;
-            Ok(read_utoc(&utoc_path)
-                .iter()
-                .map(|entry| entry.file_path.clone())
-                .collect())
-        } else {
-            Ok(pak.files())
-        }
-    }
-    
     // Get target mod info
     let target_priority = calculate_priority(&target_path);
-    let target_files: HashSet<String> = get_pak_files(&target_path, &aes_key)?
+    let target_files: HashSet<String> = mod_index::contents(&target_path)?
         .into_iter()
         .collect();
     
@@ -5534,20 +5664,12 @@ async fn check_single_mod_conflicts(
     
     let mut conflicts: Vec<SingleModConflict> = Vec::new();
     
-    // Scan all other enabled mods
-    for entry in WalkDir::new(&game_path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let path = entry.path();
-        
-        if path.is_dir() {
-            continue;
-        }
+    // Check all other enabled mods
+    let others = mod_index::lock().mods(&game_path);
+    for other in others {
+        let path = other.path.as_path();
         
-        // Skip non-pak files
-        let ext = path.extension().and_then(|s| s.to_str());
-        if ext != Some("pak") {
+        if !other.enabled {
             continue;
         }
         
@@ -5557,7 +5679,7 @@ async fn check_single_mod_conflicts(
         }
         
         // Get this mod's files
-        let other_files: HashSet<String> = match get_pak_files(path, &aes_key) {
+        let other_files: HashSet<String> = match mod_index::contents(path) {
             Ok(files) => files.into_iter().collect(),
             Err(e) => {
                 warn!("Failed to read mod {:?}: {}", path, e);
@@ -5908,7 +6030,7 @@ fn main() {
     }
     
     // Initialize UAssetToolkit global singleton on startup
-    // This starts the UAssetTool process once and keeps it alive for the app lifetime
+    // Worker processes are started on first use and kept alive for the app lifetime
     info!("Initializing UAssetToolkit global singleton...");
     if let Err(e) = uasset_toolkit::init_global_toolkit() {
         warn!("Failed to initialize UAssetToolkit singleton: {} - detection features may be slower", e);
@@ -6051,10 +6173,38 @@ fn main() {
             get_game_path,
             set_game_path,
             auto_detect_game_path,
+            get_game_profile,
+            set_game_profile,
             start_file_watcher,
             get_pak_files,
             parse_dropped_files,
             install_mods,
+            cancel_install,
+            get_pending_install,
+            resume_install,
+            discard_pending_install,
+            get_library_entries,
+            deploy_library_entry,
+            deploy_library_set,
+            list_mod_versions,
+            rollback_mod,
+            diff_mod_versions,
+            get_profiles,
+            save_profile,
+            delete_profile,
+            preview_profile,
+            apply_profile,
+            get_load_order_rules,
+            set_load_order_rules,
+            preview_load_order,
+            apply_load_order,
+            get_asset_conflicts,
+            prefer_mod,
+            split_conflicting_assets,
+            get_library_entry_for_mod,
+            retract_library_entry,
+            remove_library_entry,
+            plan_install,
             quick_organize,
             delete_mod,
             update_mod,
@@ -6076,6 +6226,7 @@ fn main() {
             get_usmap_path,
             get_usmap_dir_path,
             list_usmap_files,
+            get_usmap_info,
             get_current_usmap_file,
             get_current_usmap_full_path,
             delete_current_usmap,
@@ -0,0 +1,203 @@
+// Stable mod identity
+//
+// A mod's ID is derived from the contents of its .pak and, for IoStore mods, its .utoc, so it stays
+// the same when the mod is enabled or disabled, renamed, re-prioritized or moved to another folder.
+// Two copies of the very same mod share an ID. Hashing a large pak takes a while, so IDs are cached
+// in mod_ids.json together with the size and modification time of the files they were computed
+// from; a renamed or moved file keeps both, so its cached ID carries over without hashing again.
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::UNIX_EPOCH;
+
+use crate::utils::app_dir;
+
+static CACHE: LazyLock<Mutex<IdCache>> = LazyLock::new(|| Mutex::new(IdCache::load(app_dir().join("mod_ids.json"))));
+
+/// Size and modification time of one file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    size: u64,
+    modified_ns: u128,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedId {
+    id: String,
+    /// Stamps of the .pak and, if present, the .utoc the ID was computed from
+    stamps: Vec<Stamp>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct IdCache {
+    #[serde(skip)]
+    file: PathBuf,
+    #[serde(skip)]
+    dirty: bool,
+    ids: HashMap<PathBuf, CachedId>,
+}
+
+impl IdCache {
+    pub fn load(file: PathBuf) -> Self {
+        let mut cache: IdCache = File::open(&file)
+            .ok()
+            .and_then(|f| serde_json::from_reader(io::BufReader::new(f)).ok())
+            .unwrap_or_default();
+        cache.file = file;
+        cache
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        // Forget files that are gone so the cache doesn't grow forever
+        self.ids.retain(|path, _| path.exists());
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        serde_json::to_writer(io::BufWriter::new(File::create(&self.file)?), self).map_err(io::Error::from)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// ID of the mod whose .pak (or disabled .bak_repak / .pak_disabled) is at `path`
+    pub fn id_for(&mut self, path: &Path) -> io::Result<String> {
+        let files = identity_files(path);
+        let stamps = files.iter().map(|f| stamp(f)).collect::<io::Result<Vec<_>>>()?;
+
+        if let Some(cached) = self.ids.get(path).filter(|c| c.stamps == stamps) {
+            return Ok(cached.id.clone());
+        }
+
+        // A file that moved keeps its size and modification time; reuse the ID cached under its
+        // old path as long as nothing is there any more
+        let moved = self
+            .ids
+            .iter()
+            .find(|(old, cached)| cached.stamps == stamps && !old.exists())
+            .map(|(old, cached)| (old.clone(), cached.clone()));
+        let cached = match moved {
+            Some((old, cached)) => {
+                self.ids.remove(&old);
+                cached
+            }
+            None => CachedId {
+                id: hash_files(&files)?,
+                stamps,
+            },
+        };
+
+        let id = cached.id.clone();
+        self.ids.insert(path.to_path_buf(), cached);
+        self.dirty = true;
+        Ok(id)
+    }
+}
+
+/// ID of the mod at `path`, or `None` if its files can't be read
+pub fn mod_id(path: &Path) -> Option<String> {
+    mod_ids(&[path.to_path_buf()]).pop().flatten()
+}
+
+/// IDs of the mods at `paths`, in the same order. The cache is written once at the end.
+pub fn mod_ids(paths: &[PathBuf]) -> Vec<Option<String>> {
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let ids = paths
+        .iter()
+        .map(|path| match cache.id_for(path) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                warn!("Failed to identify mod {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+    if let Err(e) = cache.save() {
+        warn!("Failed to save mod ID cache: {}", e);
+    }
+    ids
+}
+
+/// The files a mod's ID is computed from: its .pak and, for IoStore mods, the .utoc. The .ucas
+/// is left out since the .utoc already holds the hashes of its chunks.
+fn identity_files(path: &Path) -> Vec<PathBuf> {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let utoc = ["utoc", "utoc.bak_repak"]
+        .iter()
+        .map(|suffix| path.with_file_name(format!("{}.{}", stem, suffix)))
+        .find(|p| p.exists());
+    std::iter::once(path.to_path_buf()).chain(utoc).collect()
+}
+
+pub fn stamp(path: &Path) -> io::Result<Stamp> {
+    let metadata = fs::metadata(path)?;
+    let modified_ns = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    Ok(Stamp { size: metadata.len(), modified_ns })
+}
+
+fn hash_files(files: &[PathBuf]) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    for file in files {
+        let mut file_hasher = Sha256::new();
+        io::copy(&mut File::open(file)?, &mut file_hasher)?;
+        hasher.update(file_hasher.finalize());
+    }
+    Ok(hex::encode(&hasher.finalize()[..16]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_survive_renames_moves_and_toggling() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = IdCache::load(dir.path().join("mod_ids.json"));
+        let pak = dir.path().join("Hero_9999999_P.pak");
+        fs::write(&pak, b"pak").unwrap();
+        fs::write(pak.with_extension("utoc"), b"utoc").unwrap();
+        let id = cache.id_for(&pak).unwrap();
+
+        let disabled = dir.path().join("Hero_9999999_P.bak_repak");
+        fs::rename(&pak, &disabled).unwrap();
+        assert_eq!(cache.id_for(&disabled).unwrap(), id);
+
+        fs::create_dir(dir.path().join("Sub")).unwrap();
+        let moved = dir.path().join("Sub/!Hero_9999999_P.pak");
+        fs::rename(&disabled, &moved).unwrap();
+        fs::rename(dir.path().join("Hero_9999999_P.utoc"), dir.path().join("Sub/!Hero_9999999_P.utoc")).unwrap();
+        assert_eq!(cache.id_for(&moved).unwrap(), id);
+
+        // Survives a restart too
+        cache.save().unwrap();
+        let mut reloaded = IdCache::load(dir.path().join("mod_ids.json"));
+        assert_eq!(reloaded.id_for(&moved).unwrap(), id);
+    }
+
+    #[test]
+    fn ids_depend_on_the_utoc_as_well_as_the_pak() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = IdCache::load(dir.path().join("mod_ids.json"));
+        for (name, utoc) in [("A", b"one"), ("B", b"two")] {
+            fs::write(dir.path().join(format!("{}.pak", name)), b"same stub pak").unwrap();
+            fs::write(dir.path().join(format!("{}.utoc", name)), utoc).unwrap();
+        }
+        fs::write(dir.path().join("C.pak"), b"same stub pak").unwrap();
+
+        let a = cache.id_for(&dir.path().join("A.pak")).unwrap();
+        let b = cache.id_for(&dir.path().join("B.pak")).unwrap();
+        let c = cache.id_for(&dir.path().join("C.pak")).unwrap();
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+    }
+}
@@ -0,0 +1,450 @@
+// Mod index - what's in the mods folder, kept between calls and across restarts
+//
+// Listing mods, checking clashes and showing details used to walk ~mods and open every pak and
+// utoc each time. The index remembers every mod with the size and modification time of its files,
+// and once asked, what it contains, its detected category and its ID. While the file watcher runs
+// (see start_file_watcher) it reports every change, and only the mods it touches are looked at
+// again; without it, each lookup compares the stamps of the files first. A mod that is renamed,
+// toggled or moved keeps its stamps, so what was known about it carries over.
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use walkdir::WalkDir;
+
+use crate::mod_identity::{self, Stamp};
+use crate::mod_ops::{self, ModSource, ScannedMod};
+use crate::utils::{app_dir, get_pak_characteristics_detailed, ModCharacteristics};
+
+static INDEX: LazyLock<Mutex<ModIndex>> =
+    LazyLock::new(|| Mutex::new(ModIndex::load(app_dir().join("mod_index.json"))));
+
+#[derive(Clone, Serialize, Deserialize)]
+struct IndexedMod {
+    scanned: ScannedMod,
+    /// Stamps of the mod file and its .utoc/.ucas
+    stamps: Vec<Stamp>,
+    id: Option<String>,
+    /// Contained files, read the first time they're asked for
+    files: Option<Vec<String>>,
+    characteristics: Option<ModCharacteristics>,
+}
+
+impl IndexedMod {
+    fn new(scanned: ScannedMod, stamps: Vec<Stamp>) -> Self {
+        Self { scanned, stamps, id: None, files: None, characteristics: None }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ModIndex {
+    #[serde(skip)]
+    file: PathBuf,
+    #[serde(skip)]
+    dirty: bool,
+    /// Set while the file watcher reports every change below `root`
+    #[serde(skip)]
+    watched: bool,
+    /// Whether `mods` holds every mod below `root`; a fresh start scans once to make sure
+    #[serde(skip)]
+    complete: bool,
+    /// Mods that went away recently, kept in case they turn up under another name
+    #[serde(skip)]
+    removed: Vec<IndexedMod>,
+    root: PathBuf,
+    mods: BTreeMap<PathBuf, IndexedMod>,
+}
+
+/// Stamps of the mod file at `path` and the .utoc/.ucas next to it
+fn mod_stamps(path: &Path) -> Vec<Stamp> {
+    std::iter::once(path.to_path_buf())
+        .chain(["utoc", "ucas"].iter().map(|ext| path.with_extension(ext)))
+        .filter_map(|file| mod_identity::stamp(&file).ok())
+        .collect()
+}
+
+/// The mod file a changed path belongs to: the path itself for a .pak or disabled mod, the mod
+/// next to it for a .utoc or .ucas
+fn mod_file(path: &Path) -> Option<PathBuf> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("pak" | "bak_repak" | "pak_disabled") => Some(path.to_path_buf()),
+        Some("utoc" | "ucas") => ["pak", "bak_repak", "pak_disabled"]
+            .iter()
+            .map(|ext| path.with_extension(ext))
+            .find(|p| p.exists()),
+        _ => None,
+    }
+}
+
+impl ModIndex {
+    pub fn load(file: PathBuf) -> Self {
+        let mut index: ModIndex = File::open(&file)
+            .ok()
+            .and_then(|f| serde_json::from_reader(io::BufReader::new(f)).ok())
+            .unwrap_or_default();
+        index.file = file;
+        index
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        serde_json::to_writer(io::BufWriter::new(File::create(&self.file)?), self).map_err(io::Error::from)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Point the index at the mods folder `root`, forgetting any other folder
+    fn set_root(&mut self, root: &Path) {
+        if self.root != root {
+            self.root = root.to_path_buf();
+            self.mods.clear();
+            self.removed.clear();
+            self.complete = false;
+            self.watched = false;
+            self.dirty = true;
+        }
+    }
+
+    /// Trust the file watcher to report every change below `root` from now on
+    pub fn watch(&mut self, root: &Path) {
+        self.set_root(root);
+        self.watched = true;
+    }
+
+    /// Every mod of the mods folder `root`
+    pub fn mods(&mut self, root: &Path) -> Vec<ScannedMod> {
+        self.set_root(root);
+        if !(self.watched && self.complete) {
+            self.rescan();
+        }
+        self.mods.values().map(|m| m.scanned.clone()).collect()
+    }
+
+    fn rescan(&mut self) {
+        let previous = std::mem::take(&mut self.mods);
+        self.removed.extend(previous.into_values());
+        for scanned in mod_ops::scan_mods(&self.root) {
+            self.insert(scanned);
+        }
+        self.removed.clear();
+        self.complete = true;
+        self.dirty = true;
+    }
+
+    /// Index `scanned`, keeping what was known about it if its files haven't changed, including
+    /// when it was known under another path before being renamed or moved
+    fn insert(&mut self, scanned: ScannedMod) {
+        let path = scanned.path.clone();
+        let stamps = mod_stamps(&path);
+        let known = match self.mods.remove(&path).filter(|m| m.stamps == stamps) {
+            Some(known) => Some(known),
+            None if stamps.is_empty() => None,
+            None => {
+                let removed = self
+                    .removed
+                    .iter()
+                    .position(|m| m.stamps == stamps && (m.scanned.path == path || !m.scanned.path.exists()));
+                match removed {
+                    Some(i) => Some(self.removed.swap_remove(i)),
+                    None => {
+                        let moved = self.mods.iter().find(|(p, m)| m.stamps == stamps && !p.exists());
+                        moved.map(|(p, _)| p.clone()).and_then(|p| self.mods.remove(&p))
+                    }
+                }
+            }
+        };
+        let entry = match known {
+            Some(mut known) => {
+                known.scanned = scanned;
+                known
+            }
+            None => IndexedMod::new(scanned, stamps),
+        };
+        self.mods.insert(path, entry);
+        self.dirty = true;
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if let Some(gone) = self.mods.remove(path) {
+            self.removed.push(gone);
+            self.dirty = true;
+        }
+    }
+
+    /// Bring the index up to date with a change the file watcher saw at `paths`
+    pub fn changed(&mut self, paths: &[PathBuf]) {
+        let root = self.root.clone();
+        for path in paths.iter().filter(|p| p.starts_with(&root)) {
+            if path.is_dir() {
+                // A folder moved in or created: index every mod inside it
+                let found: Vec<ScannedMod> = WalkDir::new(path)
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter_map(|e| mod_ops::scan_mod(&root, e.path()))
+                    .collect();
+                for scanned in found {
+                    self.insert(scanned);
+                }
+                continue;
+            }
+            match mod_file(path) {
+                Some(file) if file.exists() => {
+                    if let Some(scanned) = mod_ops::scan_mod(&root, &file) {
+                        self.insert(scanned);
+                    }
+                }
+                Some(file) => self.remove(&file),
+                // Whatever went away, mods below it went with it
+                None if !path.exists() => {
+                    let gone: Vec<PathBuf> = self.mods.keys().filter(|p| p.starts_with(path)).cloned().collect();
+                    for file in gone {
+                        self.remove(&file);
+                    }
+                }
+                None => {}
+            }
+        }
+        // Bounded, since entries only come back from here when a mod is renamed
+        let excess = self.removed.len().saturating_sub(256);
+        self.removed.drain(..excess);
+    }
+
+    /// Stop trusting the listing, e.g. after the watcher missed events; the next listing scans
+    pub fn lost_track(&mut self) {
+        self.complete = false;
+    }
+
+    /// The up-to-date entry of the mod at `path`, if it lives in the indexed folder
+    fn entry(&mut self, path: &Path) -> Option<&mut IndexedMod> {
+        if !path.starts_with(&self.root) || !path.exists() {
+            return None;
+        }
+        let stale = match self.mods.get(path) {
+            Some(known) => !self.watched && known.stamps != mod_stamps(path),
+            None => true,
+        };
+        if stale {
+            self.insert(mod_ops::scan_mod(&self.root, path)?);
+        }
+        self.mods.get_mut(path)
+    }
+
+    /// What the index knows of the mod at `path`: `Ok` with its files if they were read already,
+    /// else the stamps of its entry to store them under (see `store`), or `None` if it isn't indexed
+    fn known_contents(&mut self, path: &Path) -> Result<Vec<String>, Option<Vec<Stamp>>> {
+        match self.entry(path) {
+            Some(IndexedMod { files: Some(files), .. }) => Ok(files.clone()),
+            Some(entry) => Err(Some(entry.stamps.clone())),
+            None => Err(None),
+        }
+    }
+
+    /// The entry of the mod at `path` to store what was read from it, unless its files changed
+    /// since `stamps` were taken
+    fn store(&mut self, path: &Path, stamps: &[Stamp]) -> Option<&mut IndexedMod> {
+        let entry = self.mods.get_mut(path).filter(|m| m.stamps == stamps)?;
+        self.dirty = true;
+        Some(entry)
+    }
+}
+
+/// Files contained in the mod at `path`. The index is only locked to look them up and to store
+/// them; the mod is read with it released, so the file watcher isn't held up meanwhile.
+fn contents_in(index: &Mutex<ModIndex>, path: &Path) -> Result<Vec<String>, String> {
+    let stamps = match index.lock().unwrap_or_else(|e| e.into_inner()).known_contents(path) {
+        Ok(files) => return Ok(files),
+        Err(stamps) => stamps,
+    };
+    let files = mod_ops::mod_contents(path)?;
+    if let Some(stamps) = stamps {
+        let mut index = index.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = index.store(path, &stamps) {
+            entry.characteristics = Some(get_pak_characteristics_detailed(files.clone()));
+            entry.files = Some(files.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// IDs (see mod_identity) of the mods at `paths`, in the same order. Like `contents_in`, new
+/// mods are hashed with the index released.
+fn ids_in(index: &Mutex<ModIndex>, paths: &[PathBuf]) -> Vec<Option<String>> {
+    let known: Vec<(Option<String>, Option<Vec<Stamp>>)> = {
+        let mut index = index.lock().unwrap_or_else(|e| e.into_inner());
+        paths
+            .iter()
+            .map(|p| match index.entry(p) {
+                Some(entry) => (entry.id.clone(), Some(entry.stamps.clone())),
+                None => (None, None),
+            })
+            .collect()
+    };
+    let missing: Vec<PathBuf> =
+        paths.iter().zip(&known).filter(|(_, (id, _))| id.is_none()).map(|(p, _)| p.clone()).collect();
+    if missing.is_empty() {
+        return known.into_iter().map(|(id, _)| id).collect();
+    }
+
+    let mut found = mod_identity::mod_ids(&missing).into_iter();
+    let mut index = index.lock().unwrap_or_else(|e| e.into_inner());
+    paths
+        .iter()
+        .zip(known)
+        .map(|(path, (id, stamps))| {
+            id.or_else(|| {
+                let id = found.next().flatten();
+                if let Some(entry) = stamps.and_then(|stamps| index.store(path, &stamps)) {
+                    entry.id = id.clone();
+                }
+                id
+            })
+        })
+        .collect()
+}
+
+/// The shared index, locked for as long as this lives and saved when it's dropped
+pub struct Indexed(MutexGuard<'static, ModIndex>);
+
+pub fn lock() -> Indexed {
+    Indexed(INDEX.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+impl Deref for Indexed {
+    type Target = ModIndex;
+
+    fn deref(&self) -> &ModIndex {
+        &self.0
+    }
+}
+
+impl DerefMut for Indexed {
+    fn deref_mut(&mut self) -> &mut ModIndex {
+        &mut self.0
+    }
+}
+
+impl Drop for Indexed {
+    fn drop(&mut self) {
+        if let Err(e) = self.0.save() {
+            warn!("Failed to save mod index: {}", e);
+        }
+    }
+}
+
+pub fn contents(path: &Path) -> Result<Vec<String>, String> {
+    contents_in(&INDEX, path)
+}
+
+/// Category, heroes and mod type detected from the mod at `path`
+pub fn characteristics(path: &Path) -> Result<ModCharacteristics, String> {
+    let files = contents(path)?;
+    let known = lock().mods.get(path).and_then(|m| m.characteristics.clone());
+    Ok(known.unwrap_or_else(|| get_pak_characteristics_detailed(files)))
+}
+
+pub fn ids(paths: &[PathBuf]) -> Vec<Option<String>> {
+    ids_in(&INDEX, paths)
+}
+
+/// The shared index as a source of mods, locked only for each lookup rather than for as long as
+/// a scan takes
+pub struct Shared;
+
+impl ModSource for Shared {
+    fn mods(&mut self, mods_dir: &Path) -> Vec<ScannedMod> {
+        lock().mods(mods_dir)
+    }
+
+    fn contents(&mut self, path: &Path) -> Result<Vec<String>, String> {
+        contents(path)
+    }
+}
+
+/// Pass on what the file watcher saw. Doesn't save, since events come in bursts; the next
+/// lookup through `lock` does.
+pub fn changed(paths: &[PathBuf]) {
+    INDEX.lock().unwrap_or_else(|e| e.into_inner()).changed(paths);
+}
+
+pub fn lost_track() {
+    INDEX.lock().unwrap_or_else(|e| e.into_inner()).lost_track();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn listed(index: &mut ModIndex, root: &Path) -> Vec<String> {
+        index
+            .mods(root)
+            .into_iter()
+            .map(|m| m.path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn watched_index_follows_events_and_keeps_contents_across_renames() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("~mods");
+        write(&root.join("Hero_9999999_P.pak"), "hero");
+        write(&root.join("Hero_9999999_P.utoc"), "utoc");
+        let mut index = ModIndex::load(dir.path().join("mod_index.json"));
+        index.watch(&root);
+        assert_eq!(listed(&mut index, &root), ["Hero_9999999_P.pak"]);
+
+        // Stand in for contents read from a real pak
+        let hero = root.join("Hero_9999999_P.pak");
+        index.mods.get_mut(&hero).unwrap().files = Some(vec!["Marvel/Content/Hero.uasset".to_string()]);
+
+        let disabled = root.join("Hero_9999999_P.bak_repak");
+        mod_ops::toggle_mod(&hero).unwrap();
+        write(&root.join("Skins/Skin_9999999_P.pak"), "skin");
+        index.changed(&[hero.clone(), disabled.clone(), root.join("Skins")]);
+        assert_eq!(listed(&mut index, &root), ["Hero_9999999_P.bak_repak", "Skins/Skin_9999999_P.pak"]);
+        let shared = Mutex::new(index);
+        assert_eq!(contents_in(&shared, &disabled).unwrap(), ["Marvel/Content/Hero.uasset"]);
+        let mut index = shared.into_inner().unwrap();
+
+        fs::remove_dir_all(root.join("Skins")).unwrap();
+        index.changed(&[root.join("Skins")]);
+        assert_eq!(listed(&mut index, &root), ["Hero_9999999_P.bak_repak"]);
+
+        // What was read survives a restart, matched again by stamps
+        index.save().unwrap();
+        let mut reloaded = ModIndex::load(dir.path().join("mod_index.json"));
+        assert_eq!(listed(&mut reloaded, &root), ["Hero_9999999_P.bak_repak"]);
+        assert_eq!(contents_in(&Mutex::new(reloaded), &disabled).unwrap(), ["Marvel/Content/Hero.uasset"]);
+    }
+
+    #[test]
+    fn unwatched_index_checks_stamps() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("~mods");
+        let hero = root.join("Hero_9999999_P.pak");
+        write(&hero, "hero");
+        let mut index = ModIndex::load(dir.path().join("mod_index.json"));
+        assert_eq!(listed(&mut index, &root), ["Hero_9999999_P.pak"]);
+        index.mods.get_mut(&hero).unwrap().files = Some(vec!["stale".to_string()]);
+
+        write(&hero, "hero, updated");
+        write(&root.join("New_9999999_P.pak"), "new");
+        // Output of an install still running isn't a mod yet
+        write(&root.join(".repakx-staging-abc/Half_9999999_P.pak"), "half");
+        assert_eq!(listed(&mut index, &root), ["Hero_9999999_P.pak", "New_9999999_P.pak"]);
+        assert!(index.mods[&hero].files.is_none());
+    }
+}
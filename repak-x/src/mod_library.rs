@@ -0,0 +1,601 @@
+// Mod library - every installed mod version, stored once by content hash
+//
+// Files live under <app_dir>/Library/objects/<first two hex digits>/<sha256>. An entry lists the
+// files that make up one version of a mod (the .pak plus any .utoc/.ucas) by name and hash, and is
+// saved as entries/<id>.json where the id is the hash of that listing, so importing the same mod
+// twice gives the same entry. Files go in and out of the store as copies, never hardlinks: the
+// mods folder is rewritten in place by tools outside our control, and a shared inode would let
+// that corrupt the stored object. Every copy is checked against the hash it is stored under.
+// deployed.json remembers which files in the mods folder came from the library so they can be
+// taken out again.
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::utils::app_dir;
+
+/// Serializes read-modify-write cycles of deployed.json
+static DEPLOYMENT_LOCK: Mutex<()> = Mutex::new(());
+
+/// One file of a library entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryFile {
+    /// File name in the mods folder, always the enabled form (`.pak`, never `.bak_repak`)
+    pub name: String,
+    /// Hex SHA-256 of the contents
+    pub hash: String,
+    pub size: u64,
+}
+
+/// One version of one mod
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    pub id: String,
+    pub name: String,
+    /// Sorted by name
+    pub files: Vec<LibraryFile>,
+    /// What the mod was installed from, if known
+    pub source: Option<String>,
+    /// Seconds since the Unix epoch
+    pub added: u64,
+}
+
+/// A file in the mods folder that was materialized from, or adopted into, the library
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeployedFile {
+    pub path: PathBuf,
+    pub hash: String,
+    pub entry_id: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Deployment {
+    files: Vec<DeployedFile>,
+}
+
+/// Result of [`ModLibrary::deploy_set`]
+#[derive(Debug, Default, Serialize)]
+pub struct DeployReport {
+    pub deployed: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    /// Library files in the mods folder that were changed on disk and so were left alone
+    pub kept_modified: Vec<PathBuf>,
+}
+
+pub struct ModLibrary {
+    root: PathBuf,
+}
+
+impl ModLibrary {
+    pub fn default_root() -> PathBuf {
+        app_dir().join("Library")
+    }
+
+    pub fn open_default() -> io::Result<Self> {
+        Self::open(Self::default_root())
+    }
+
+    pub fn open(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(root.join("objects"))?;
+        fs::create_dir_all(root.join("entries"))?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.root.join("objects").join(&hash[..2]).join(hash)
+    }
+
+    fn entry_path(&self, id: &str) -> PathBuf {
+        self.root.join("entries").join(format!("{}.json", id))
+    }
+
+    fn deployment_path(&self) -> PathBuf {
+        self.root.join("deployed.json")
+    }
+
+    /// Copy the mod made of `files` into the library. Files already stored are not stored again,
+    /// and importing an identical set of files returns the existing entry.
+    pub fn import(&self, name: &str, files: &[PathBuf], source: Option<String>) -> io::Result<LibraryEntry> {
+        if files.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} has no files to import", name)));
+        }
+
+        let mut stored = files.iter().map(|file| self.store(file)).collect::<io::Result<Vec<_>>>()?;
+        stored.sort_by(|a, b| a.name.cmp(&b.name));
+        let id = entry_id(&stored);
+
+        if let Some(existing) = self.entry(&id)? {
+            return Ok(existing);
+        }
+
+        let entry = LibraryEntry {
+            id,
+            name: name.to_string(),
+            files: stored,
+            source,
+            added: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        };
+        write_json(&self.entry_path(&entry.id), &entry)?;
+        info!("[Library] Imported {} as {} ({} file(s))", entry.name, short_id(&entry.id), entry.files.len());
+        Ok(entry)
+    }
+
+    /// Import files that are already in the mods folder and track them as deployed from the
+    /// new entry, e.g. right after an install
+    pub fn adopt(&self, name: &str, files: &[PathBuf], source: Option<String>) -> io::Result<LibraryEntry> {
+        let entry = self.import(name, files, source)?;
+        self.update_deployment(|deployment| {
+            for path in files {
+                let Some(file) = entry.files.iter().find(|f| Some(f.name.as_str()) == enabled_name(path).as_deref()) else {
+                    continue;
+                };
+                deployment.files.retain(|d| &d.path != path);
+                deployment.files.push(DeployedFile {
+                    path: path.clone(),
+                    hash: file.hash.clone(),
+                    entry_id: entry.id.clone(),
+                });
+            }
+        })?;
+        Ok(entry)
+    }
+
+    /// Hash `path` and add it to the object store if it isn't there yet
+    fn store(&self, path: &Path) -> io::Result<LibraryFile> {
+        let name = enabled_name(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid file name: {}", path.display())))?;
+        let hash = hash_file(path)?;
+        let size = fs::metadata(path)?.len();
+
+        let object = self.object_path(&hash);
+        if !object.exists() {
+            fs::create_dir_all(object.parent().unwrap())?;
+            materialize(path, &object, &hash)?;
+        }
+        Ok(LibraryFile { name, hash, size })
+    }
+
+    pub fn entry(&self, id: &str) -> io::Result<Option<LibraryEntry>> {
+        let path = self.entry_path(id);
+        if !path.exists() {
+            return Ok(None);
+        }
+        read_json(&path).map(Some)
+    }
+
+    /// All entries, oldest first
+    pub fn entries(&self) -> io::Result<Vec<LibraryEntry>> {
+        let mut entries = Vec::new();
+        for dirent in fs::read_dir(self.root.join("entries"))? {
+            let path = dirent?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                match read_json::<LibraryEntry>(&path) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => warn!("[Library] Skipping unreadable entry {}: {}", path.display(), e),
+                }
+            }
+        }
+        entries.sort_by(|a, b| a.added.cmp(&b.added).then_with(|| a.name.cmp(&b.name)));
+        Ok(entries)
+    }
+
+    /// Entry a file in the mods folder was deployed from. Disabled (`.bak_repak`) copies of a
+    /// deployed `.pak` count as the same file.
+    pub fn deployed_entry(&self, path: &Path) -> io::Result<Option<String>> {
+        Ok(self
+            .load_deployment()?
+            .files
+            .into_iter()
+            .find(|d| on_disk_path(&d.path).as_deref() == Some(path) || d.path == path)
+            .map(|d| d.entry_id))
+    }
+
+    /// Whether any file in the mods folder was deployed from entry `id`
+    pub fn is_deployed(&self, id: &str) -> io::Result<bool> {
+        Ok(self.load_deployment()?.files.iter().any(|d| d.entry_id == id))
+    }
+
+    /// Put the files of entry `id` in `dir` without tracking them as deployed, e.g. to inspect
+    /// them in a temporary folder. Fails if a stored file no longer matches its hash.
+    pub fn materialize_entry(&self, id: &str, dir: &Path) -> io::Result<(LibraryEntry, Vec<PathBuf>)> {
+        let entry = self
+            .entry(id)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No library entry {}", id)))?;
+        fs::create_dir_all(dir)?;
+
+        let mut paths = Vec::new();
+        for file in &entry.files {
+            let dest = dir.join(&file.name);
+            materialize(&self.object_path(&file.hash), &dest, &file.hash)?;
+            paths.push(dest);
+        }
+        Ok((entry, paths))
+    }
+
+    /// Materialize entry `id` into `dir`, replacing files of the same name
+    pub fn deploy(&self, id: &str, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let (entry, deployed) = self.materialize_entry(id, dir)?;
+
+        self.update_deployment(|deployment| {
+            for (path, file) in deployed.iter().zip(&entry.files) {
+                deployment.files.retain(|d| &d.path != path);
+                deployment.files.push(DeployedFile {
+                    path: path.clone(),
+                    hash: file.hash.clone(),
+                    entry_id: entry.id.clone(),
+                });
+            }
+        })?;
+        info!("[Library] Deployed {} to {}", entry.name, dir.display());
+        Ok(deployed)
+    }
+
+    /// Take out files at `paths` deployed by [`ModLibrary::deploy`], e.g. when what they were
+    /// deployed for failed. Files changed since are left in place.
+    pub fn undeploy(&self, paths: &[PathBuf]) -> io::Result<()> {
+        self.update_deployment(|deployment| {
+            deployment.files.retain(|d| {
+                if !paths.contains(&d.path) {
+                    return true;
+                }
+                let _ = remove_if_unchanged(d);
+                false
+            });
+        })
+    }
+
+    /// Remove the files deployed from entry `id`. Files changed since they were deployed are
+    /// left in place and returned in the second list.
+    pub fn retract(&self, id: &str) -> io::Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+        let mut removed = Vec::new();
+        let mut kept = Vec::new();
+        self.update_deployment(|deployment| {
+            deployment.files.retain(|d| {
+                if d.entry_id != id {
+                    return true;
+                }
+                match remove_if_unchanged(d) {
+                    Ok(Some(path)) => removed.push(path),
+                    Ok(None) => {}
+                    Err(path) => kept.push(path),
+                }
+                false
+            });
+        })?;
+        Ok((removed, kept))
+    }
+
+    /// Make the library-managed part of the mods folder match `wanted`, a list of entry ids and
+    /// the folder each should be deployed to. Entries not in the list are retracted; files in the
+    /// mods folder that didn't come from the library are never touched.
+    pub fn deploy_set(&self, wanted: &[(String, PathBuf)]) -> io::Result<DeployReport> {
+        let mut report = DeployReport::default();
+        let wanted_paths: HashSet<PathBuf> = wanted
+            .iter()
+            .filter_map(|(id, dir)| self.entry(id).ok().flatten().map(|e| (e, dir)))
+            .flat_map(|(entry, dir)| entry.files.into_iter().map(move |f| dir.join(f.name)))
+            .collect();
+
+        self.update_deployment(|deployment| {
+            deployment.files.retain(|d| {
+                if wanted_paths.contains(&d.path) {
+                    return true;
+                }
+                match remove_if_unchanged(d) {
+                    Ok(Some(path)) => report.removed.push(path),
+                    Ok(None) => {}
+                    Err(path) => report.kept_modified.push(path),
+                }
+                false
+            });
+        })?;
+
+        for (id, dir) in wanted {
+            report.deployed.extend(self.deploy(id, dir)?);
+        }
+        Ok(report)
+    }
+
+    /// Delete entry `id` and any stored files no other entry uses.
+    /// Its deployed files stay in the mods folder but are no longer tracked.
+    pub fn remove_entry(&self, id: &str) -> io::Result<usize> {
+        let path = self.entry_path(id);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        self.update_deployment(|deployment| deployment.files.retain(|d| d.entry_id != id))?;
+        self.collect_garbage()
+    }
+
+    /// Delete stored files that no entry refers to. Returns how many were deleted.
+    pub fn collect_garbage(&self) -> io::Result<usize> {
+        let referenced: HashSet<String> = self
+            .entries()?
+            .into_iter()
+            .flat_map(|e| e.files.into_iter().map(|f| f.hash))
+            .collect();
+
+        let mut deleted = 0;
+        for bucket in fs::read_dir(self.root.join("objects"))? {
+            let bucket = bucket?.path();
+            if !bucket.is_dir() {
+                continue;
+            }
+            for object in fs::read_dir(&bucket)? {
+                let object = object?.path();
+                let hash = object.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                if !referenced.contains(hash) {
+                    fs::remove_file(&object)?;
+                    deleted += 1;
+                }
+            }
+        }
+        if deleted > 0 {
+            info!("[Library] Removed {} unused file(s)", deleted);
+        }
+        Ok(deleted)
+    }
+
+    fn load_deployment(&self) -> io::Result<Deployment> {
+        let path = self.deployment_path();
+        if !path.exists() {
+            return Ok(Deployment::default());
+        }
+        read_json(&path)
+    }
+
+    fn update_deployment(&self, update: impl FnOnce(&mut Deployment)) -> io::Result<()> {
+        let _guard = DEPLOYMENT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut deployment = self.load_deployment()?;
+        update(&mut deployment);
+        write_json(&self.deployment_path(), &deployment)
+    }
+}
+
+/// Name a file is stored under: its name with any disabled suffix turned back into `.pak`
+fn enabled_name(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    if let Some(base) = name.strip_suffix(".bak_repak") {
+        let has_ext = Path::new(base).extension().is_some_and(|e| e == "pak" || e == "utoc" || e == "ucas");
+        return Some(if has_ext { base.to_string() } else { format!("{}.pak", base) });
+    }
+    if let Some(base) = name.strip_suffix(".pak_disabled") {
+        return Some(format!("{}.pak", base));
+    }
+    Some(name.to_string())
+}
+
+/// Where a deployed file currently is: as deployed, or renamed to its disabled form
+fn on_disk_path(path: &Path) -> Option<PathBuf> {
+    if path.exists() {
+        return Some(path.to_path_buf());
+    }
+    let disabled = if path.extension().is_some_and(|e| e == "pak") {
+        path.with_extension("bak_repak")
+    } else {
+        PathBuf::from(format!("{}.bak_repak", path.display()))
+    };
+    disabled.exists().then_some(disabled)
+}
+
+/// Remove a deployed file unless it was changed on disk. `Ok(None)` if it is already gone,
+/// `Err` with its path if it was kept.
+fn remove_if_unchanged(deployed: &DeployedFile) -> Result<Option<PathBuf>, PathBuf> {
+    let Some(path) = on_disk_path(&deployed.path) else {
+        return Ok(None);
+    };
+    match hash_file(&path) {
+        Ok(hash) if hash == deployed.hash => match fs::remove_file(&path) {
+            Ok(()) => Ok(Some(path)),
+            Err(e) => {
+                warn!("[Library] Failed to remove {}: {}", path.display(), e);
+                Err(path)
+            }
+        },
+        _ => {
+            warn!("[Library] {} was changed since it was deployed, leaving it in place", path.display());
+            Err(path)
+        }
+    }
+}
+
+/// Copy `src` to `dest`, checking on the way that its contents hash to `hash`. Goes through a
+/// temporary name so `dest` is never left half-written, and replaced rather than written over
+/// in case it is a hardlink deployed by an older version.
+fn materialize(src: &Path, dest: &Path, hash: &str) -> io::Result<()> {
+    let tmp = PathBuf::from(format!("{}.repak_tmp", dest.display()));
+    let result = copy_hashed(src, &tmp).and_then(|copied| {
+        if copied != hash {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} doesn't match its hash {} (got {})", src.display(), short_id(hash), short_id(&copied)),
+            ));
+        }
+        fs::rename(&tmp, dest)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Copy `src` to `dest` and return the hash of what was copied
+fn copy_hashed(src: &Path, dest: &Path) -> io::Result<String> {
+    struct Tee<W> {
+        out: W,
+        hasher: Sha256,
+    }
+    impl<W: io::Write> io::Write for Tee<W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = self.out.write(buf)?;
+            self.hasher.update(&buf[..n]);
+            Ok(n)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            self.out.flush()
+        }
+    }
+
+    let mut tee = Tee { out: io::BufWriter::new(File::create(dest)?), hasher: Sha256::new() };
+    io::copy(&mut File::open(src)?, &mut tee)?;
+    io::Write::flush(&mut tee)?;
+    Ok(hex::encode(tee.hasher.finalize()))
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Entry ids hash the sorted (name, hash) listing of the entry's files
+fn entry_id(files: &[LibraryFile]) -> String {
+    let mut hasher = Sha256::new();
+    for file in files {
+        hasher.update(file.name.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(file.hash.as_bytes());
+        hasher.update(b"\n");
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn short_id(id: &str) -> &str {
+    &id[..id.len().min(12)]
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> io::Result<T> {
+    serde_json::from_reader(io::BufReader::new(File::open(path)?)).map_err(io::Error::from)
+}
+
+/// Write through a temporary file so a crash never leaves truncated JSON behind
+fn write_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    serde_json::to_writer_pretty(io::BufWriter::new(File::create(&tmp)?), value).map_err(io::Error::from)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library() -> (tempfile::TempDir, ModLibrary) {
+        let dir = tempfile::tempdir().unwrap();
+        let library = ModLibrary::open(dir.path().join("Library")).unwrap();
+        (dir, library)
+    }
+
+    fn write(dir: &Path, name: &str, contents: &[u8]) -> PathBuf {
+        fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn object_count(library: &ModLibrary) -> usize {
+        fs::read_dir(library.root.join("objects"))
+            .unwrap()
+            .map(|b| fs::read_dir(b.unwrap().path()).unwrap().count())
+            .sum()
+    }
+
+    #[test]
+    fn identical_files_are_stored_once() {
+        let (dir, library) = library();
+        let a = write(&dir.path().join("a"), "Hero_9999999_P.pak", b"same");
+        let b = write(&dir.path().join("b"), "Hero_9999999_P.pak", b"same");
+        let shared = write(&dir.path().join("b"), "Other_9999999_P.pak", b"same");
+
+        let first = library.import("Hero", &[a], None).unwrap();
+        let second = library.import("Hero again", &[b], None).unwrap();
+        library.import("Other", &[shared], None).unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(library.entries().unwrap().len(), 2);
+        assert_eq!(object_count(&library), 1);
+    }
+
+    #[test]
+    fn disabled_files_are_stored_under_their_enabled_name() {
+        let (dir, library) = library();
+        let pak = write(dir.path(), "Hero_9999999_P.bak_repak", b"pak");
+        let utoc = write(dir.path(), "Hero_9999999_P.utoc.bak_repak", b"utoc");
+
+        let entry = library.import("Hero", &[utoc, pak], None).unwrap();
+        let names: Vec<_> = entry.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["Hero_9999999_P.pak", "Hero_9999999_P.utoc"]);
+    }
+
+    #[test]
+    fn deploy_set_switches_between_versions() {
+        let (dir, library) = library();
+        let mods = dir.path().join("~mods");
+        let v1 = library.import("Hero", &[write(&dir.path().join("v1"), "Hero_9999999_P.pak", b"v1")], None).unwrap();
+        let v2 = library.import("Hero", &[write(&dir.path().join("v2"), "Hero_9999999_P.pak", b"v2")], None).unwrap();
+        let other = library.import("Other", &[write(&dir.path().join("o"), "Other_9999999_P.pak", b"o")], None).unwrap();
+        let unmanaged = write(&mods, "Manual_9999999_P.pak", b"manual");
+
+        library.deploy_set(&[(v1.id.clone(), mods.clone()), (other.id.clone(), mods.join("Sub"))]).unwrap();
+        assert_eq!(fs::read(mods.join("Hero_9999999_P.pak")).unwrap(), b"v1");
+        assert!(mods.join("Sub/Other_9999999_P.pak").exists());
+
+        let report = library.deploy_set(&[(v2.id.clone(), mods.clone())]).unwrap();
+        assert_eq!(fs::read(mods.join("Hero_9999999_P.pak")).unwrap(), b"v2");
+        assert_eq!(report.removed, vec![mods.join("Sub/Other_9999999_P.pak")]);
+        assert!(unmanaged.exists());
+        assert_eq!(library.deployed_entry(&mods.join("Hero_9999999_P.pak")).unwrap(), Some(v2.id));
+    }
+
+    #[test]
+    fn retract_keeps_files_changed_on_disk() {
+        let (dir, library) = library();
+        let mods = dir.path().join("~mods");
+        let installed = write(&mods, "Hero_9999999_P.pak", b"installed");
+        let entry = library.adopt("Hero", std::slice::from_ref(&installed), None).unwrap();
+
+        // Written in place, as recompressing does; the stored copy must not change with it
+        fs::write(&installed, b"edited").unwrap();
+        let (removed, kept) = library.retract(&entry.id).unwrap();
+        assert!(removed.is_empty());
+        assert_eq!(kept, vec![installed.clone()]);
+
+        library.deploy(&entry.id, &mods).unwrap();
+        assert_eq!(fs::read(&installed).unwrap(), b"installed");
+        fs::rename(&installed, mods.join("Hero_9999999_P.bak_repak")).unwrap();
+        let (removed, _) = library.retract(&entry.id).unwrap();
+        assert_eq!(removed, vec![mods.join("Hero_9999999_P.bak_repak")]);
+    }
+
+    #[test]
+    fn corrupted_objects_are_not_deployed() {
+        let (dir, library) = library();
+        let entry = library.import("Hero", &[write(dir.path(), "Hero_9999999_P.pak", b"good")], None).unwrap();
+        fs::write(library.object_path(&entry.files[0].hash), b"bad").unwrap();
+
+        let err = library.deploy(&entry.id, &dir.path().join("~mods")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(!dir.path().join("~mods/Hero_9999999_P.pak").exists());
+    }
+
+    #[test]
+    fn removing_an_entry_frees_only_its_own_files() {
+        let (dir, library) = library();
+        let shared = write(dir.path(), "Shared.pak", b"shared");
+        let a = library.import("A", &[shared.clone(), write(dir.path(), "A.utoc", b"a")], None).unwrap();
+        library.import("B", &[shared, write(dir.path(), "B.utoc", b"b")], None).unwrap();
+
+        assert_eq!(library.remove_entry(&a.id).unwrap(), 1);
+        assert_eq!(object_count(&library), 2);
+    }
+}
@@ -0,0 +1,779 @@
+// Operations on the installed mods folder (~mods).
+// Shared by the Tauri commands in main_tauri.rs and the repak-x-cli binary, so both
+// front ends rename, scan and rewrite mods exactly the same way.
+
+use crate::game_profile;
+use crate::install_mod::install_mod_logic::pak_files::extract_pak_to_dir;
+use crate::install_mod::install_mod_logic::transaction::STAGING_PREFIX;
+use crate::install_mod::InstallableMod;
+use crate::utoc_utils::try_read_utoc;
+use log::{error, info, warn};
+use regex_lite::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A mod file found in the mods folder
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScannedMod {
+    pub path: PathBuf,
+    pub enabled: bool,
+    /// Relative path of the containing subfolder, or the mods folder name for mods in the root
+    pub folder_id: Option<String>,
+    /// Size of the .ucas for IoStore mods, of the .pak otherwise
+    pub file_size: u64,
+    pub priority: usize,
+}
+
+/// UI priority encoded in a mod's file stem.
+/// Priority 0 = "!" prefix (highest priority)
+/// Priority 1-N = 7-N+6 nines displayed as 1-based (7 nines → Priority 1, 8 nines → Priority 2, etc.)
+pub fn mod_priority(file_stem: &str) -> usize {
+    if file_stem.starts_with('!') {
+        return 0;
+    }
+    let Some(base_no_p) = file_stem.strip_suffix("_P") else {
+        return 0;
+    };
+    // Check for _999... suffix
+    let re_nums = Regex::new(r"_(\d+)$").unwrap();
+    if let Some(caps) = re_nums.captures(base_no_p) {
+        let nums = &caps[1];
+        // Verify they are all 9s
+        if nums.chars().all(|c| c == '9') && nums.len() >= 7 {
+            return nums.len() - 6;
+        }
+    }
+    0
+}
+
+/// Whether `path` is in the staging folder of an install (see install_mod_logic::transaction),
+/// whose files aren't mods yet
+pub fn in_install_staging(path: &Path) -> bool {
+    path.components().any(|c| c.as_os_str().to_string_lossy().starts_with(STAGING_PREFIX))
+}
+
+/// Scan the mods folder and all subfolders for enabled (.pak) and disabled (.bak_repak, .pak_disabled) mods
+pub fn scan_mods(mods_dir: &Path) -> Vec<ScannedMod> {
+    WalkDir::new(mods_dir)
+        .into_iter()
+        .filter_entry(|e| !in_install_staging(Path::new(e.file_name())))
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.path().is_dir())
+        .filter_map(|e| scan_mod(mods_dir, e.path()))
+        .collect()
+}
+
+/// The mod at `path` in the mods folder `mods_dir`, or `None` if `path` isn't a mod file
+pub fn scan_mod(mods_dir: &Path, path: &Path) -> Option<ScannedMod> {
+    let ext = path.extension().and_then(|s| s.to_str());
+    if ext != Some("pak") && ext != Some("bak_repak") && ext != Some("pak_disabled") {
+        return None;
+    }
+    if in_install_staging(path.strip_prefix(mods_dir).unwrap_or(path)) {
+        return None;
+    }
+    let enabled = ext == Some("pak");
+
+    // Determine folder_id based on relative path from mods_dir
+    let folder_id = match path.parent() {
+        Some(parent) if parent != mods_dir => parent.strip_prefix(mods_dir)
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .ok(),
+        _ => Some(mods_dir.file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("~mods")
+            .to_string()),
+    };
+
+    info!("Found PAK file: {} (enabled: {}, folder: {:?})", path.display(), enabled, folder_id);
+
+    let ucas_path = path.with_extension("ucas");
+    let size_path = if ucas_path.exists() { ucas_path.as_path() } else { path };
+    let file_size = std::fs::metadata(size_path).map(|m| m.len()).unwrap_or(0);
+
+    let file_stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    Some(ScannedMod {
+        path: path.to_path_buf(),
+        enabled,
+        folder_id,
+        file_size,
+        priority: mod_priority(file_stem),
+    })
+}
+
+/// Where mod listings and contents come from: the disk itself, or an index kept up to date
+/// with it (see mod_index in the app)
+pub trait ModSource {
+    fn mods(&mut self, mods_dir: &Path) -> Vec<ScannedMod>;
+    fn contents(&mut self, path: &Path) -> Result<Vec<String>, String>;
+}
+
+/// Reads everything from the disk every time
+#[allow(dead_code)] // The app reads through its index; the CLI uses this
+pub struct Disk;
+
+impl ModSource for Disk {
+    fn mods(&mut self, mods_dir: &Path) -> Vec<ScannedMod> {
+        scan_mods(mods_dir)
+    }
+
+    fn contents(&mut self, path: &Path) -> Result<Vec<String>, String> {
+        mod_contents(path)
+    }
+}
+
+/// Enable or disable a mod by renaming it between .pak and .bak_repak.
+/// Returns whether the mod is enabled afterwards.
+pub fn toggle_mod(path: &Path) -> Result<bool, String> {
+    if !path.exists() {
+        return Err("Mod file does not exist".to_string());
+    }
+
+    let is_enabled = path.extension().and_then(|s| s.to_str()) == Some("pak");
+    let new_path = if is_enabled {
+        path.with_extension("bak_repak")
+    } else {
+        path.with_extension("pak")
+    };
+
+    std::fs::rename(path, &new_path).map_err(|e| format!("Failed to toggle mod: {}", e))?;
+    Ok(!is_enabled)
+}
+
+/// A mod's file stem without its priority markers ("!" prefix, _999..._P suffix)
+pub fn base_stem(file_stem: &str) -> String {
+    // Strip leading "!" if present (highest priority marker)
+    let stem_no_exclaim = file_stem.strip_prefix("!").unwrap_or(file_stem);
+
+    // 1. Strip _P if present
+    let base_no_p = stem_no_exclaim.strip_suffix("_P").unwrap_or(stem_no_exclaim);
+
+    // 2. Strip _999... if present
+    let re = Regex::new(r"^(.*)_(\d+)$").unwrap();
+    match re.captures(base_no_p) {
+        Some(caps) if caps[2].chars().all(|c| c == '9') => caps[1].to_string(),
+        _ => base_no_p.to_string(),
+    }
+}
+
+/// File stem encoding `priority` for the mod currently named `file_stem`
+pub fn priority_stem(file_stem: &str, priority: usize) -> String {
+    let clean_base = base_stem(file_stem);
+
+    // Priority 0 = "!" prefix (highest priority) with minimum 7 nines
+    // Priority 1-N = 7-N+6 nines (1→7 nines, 2→8 nines, etc.)
+    if priority == 0 {
+        format!("!{}_{}_P", clean_base, "9".repeat(7))
+    } else {
+        format!("{}_{}_P", clean_base, "9".repeat(priority + 6))
+    }
+}
+
+/// Rename a mod (and its .utoc/.ucas) to encode `priority`. Returns the new path.
+pub fn set_mod_priority(path: &Path, priority: usize) -> Result<PathBuf, String> {
+    if !path.exists() {
+        return Err("Mod file does not exist".to_string());
+    }
+
+    let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+    let stem = path.file_stem().and_then(|s| s.to_str()).ok_or("Invalid filename")?;
+    let new_stem = priority_stem(stem, priority);
+
+    let new_path = path.with_file_name(format!("{}.{}", new_stem, extension));
+    if new_path == path {
+        return Ok(new_path); // No change
+    }
+    if new_path.exists() {
+        return Err("A mod with this priority already exists".to_string());
+    }
+
+    // Rename main file
+    std::fs::rename(path, &new_path).map_err(|e| format!("Failed to rename mod: {}", e))?;
+
+    // Rename associated files (.utoc, .ucas)
+    for ext in ["utoc", "ucas"] {
+        let old_f = path.with_extension(ext);
+        if old_f.exists() {
+            let _ = std::fs::rename(old_f, new_path.with_extension(ext));
+        }
+    }
+
+    Ok(new_path)
+}
+
+/// Files contained in the mod at `path`. IoStore mods are listed from their .utoc without opening
+/// the .pak, whose index can't be read when the container is obfuscated.
+pub fn mod_contents(path: &Path) -> Result<Vec<String>, String> {
+    let utoc_path = path.with_extension("utoc");
+    if utoc_path.exists() {
+        return Ok(try_read_utoc(&utoc_path)?.into_iter().map(|entry| entry.file_path).collect());
+    }
+    let file = File::open(path).map_err(|e| format!("Failed to open PAK: {}", e))?;
+    let pak = game_profile::active()
+        .open_pak(&mut BufReader::new(file))
+        .map_err(|e| format!("Failed to read PAK: {}", e))?;
+    Ok(pak.files())
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ModClash {
+    pub file_path: String,
+    pub mod_paths: Vec<String>,
+}
+
+/// Find enabled mods at the same priority that replace the same files
+pub fn find_mod_clashes(mods_dir: &Path, source: &mut impl ModSource) -> Result<Vec<ModClash>, String> {
+    info!("Checking for mod clashes...");
+
+    if !mods_dir.exists() {
+        return Err("Game path does not exist".to_string());
+    }
+
+    // Structure to hold mod info for clash detection
+    struct ModInfo {
+        path: PathBuf,
+        priority: usize,
+        files: Vec<String>, // List of files inside this mod
+    }
+
+    let mut mods_info: Vec<ModInfo> = Vec::new();
+
+    // Scan all enabled mods
+    for scanned in source.mods(mods_dir).into_iter().filter(|m| m.enabled) {
+        let path = scanned.path;
+        let files = match source.contents(&path) {
+            Ok(files) => files,
+            Err(e) => {
+                warn!("Failed to read mod {:?}: {}", path, e);
+                continue;
+            }
+        };
+
+        mods_info.push(ModInfo {
+            path,
+            priority: scanned.priority,
+            files,
+        });
+    }
+
+    info!("Analyzed {} enabled mods", mods_info.len());
+
+    // Don't group by character - instead, compare all mods at the same priority level
+    let mut by_priority: HashMap<usize, Vec<ModInfo>> = HashMap::new();
+    for mod_info in mods_info {
+        by_priority.entry(mod_info.priority).or_default().push(mod_info);
+    }
+
+    // Find clashes: same priority and overlapping files
+    let mut clashes: Vec<ModClash> = Vec::new();
+
+    for (priority, same_priority_mods) in by_priority {
+        if same_priority_mods.len() < 2 {
+            continue;
+        }
+
+        info!("Checking priority {} with {} mods", priority, same_priority_mods.len());
+
+        // Compare each pair of mods at this priority level
+        for i in 0..same_priority_mods.len() {
+            for j in (i + 1)..same_priority_mods.len() {
+                let mod1 = &same_priority_mods[i];
+                let mod2 = &same_priority_mods[j];
+
+                let files1: HashSet<&String> = mod1.files.iter().collect();
+                let files2: HashSet<&String> = mod2.files.iter().collect();
+
+                // Find overlapping files, excluding metadata files like 'patched_files'
+                let overlapping_files: Vec<String> = files1
+                    .intersection(&files2)
+                    .filter(|f| !f.ends_with("patched_files") && !f.contains("/patched_files"))
+                    .map(|s| (*s).clone())
+                    .collect();
+
+                if overlapping_files.is_empty() {
+                    continue;
+                }
+
+                // Extract character IDs from overlapping file paths
+                // Look for pattern like "Characters/1050/" or "1050/1050800/"
+                let mut affected_characters = HashSet::new();
+                for file_path in &overlapping_files {
+                    if let Some(char_match) = file_path.split('/').find(|s| {
+                        s.len() == 4 && s.chars().all(|c| c.is_ascii_digit()) && s.starts_with("10")
+                    }) {
+                        affected_characters.insert(char_match.to_string());
+                    }
+                }
+
+                let character_info = if !affected_characters.is_empty() {
+                    let char_ids: Vec<String> = affected_characters.iter().cloned().collect();
+                    format!("Characters: {} - ", char_ids.join(", "))
+                } else {
+                    String::new()
+                };
+
+                info!(
+                    "Found clash between {} and {} at priority {} ({} overlapping files, characters: {:?})",
+                    mod1.path.file_name().unwrap_or_default().to_string_lossy(),
+                    mod2.path.file_name().unwrap_or_default().to_string_lossy(),
+                    priority,
+                    overlapping_files.len(),
+                    affected_characters
+                );
+
+                clashes.push(ModClash {
+                    file_path: format!(
+                        "{}Priority: {} - {} overlapping file(s)",
+                        character_info,
+                        priority,
+                        overlapping_files.len()
+                    ),
+                    mod_paths: vec![
+                        mod1.path.to_string_lossy().to_string(),
+                        mod2.path.to_string_lossy().to_string(),
+                    ],
+                });
+            }
+        }
+    }
+    info!("Found {} clashes", clashes.len());
+    Ok(clashes)
+}
+
+/// Result of recompression operation
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecompressResult {
+    pub total_scanned: usize,
+    pub already_oodle: usize,
+    pub recompressed: usize,
+    pub failed: usize,
+    pub skipped_iostore: usize,
+    pub details: Vec<RecompressDetail>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecompressDetail {
+    pub mod_name: String,
+    pub status: String, // "already_oodle", "recompressed", "failed", "skipped_iostore"
+    pub original_size: u64,
+    pub new_size: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl RecompressResult {
+    fn push(&mut self, mod_name: String, status: &str, original_size: u64, new_size: Option<u64>, error: Option<String>) {
+        match status {
+            "already_oodle" => self.already_oodle += 1,
+            "recompressed" => self.recompressed += 1,
+            "failed" => self.failed += 1,
+            _ => {}
+        }
+        self.details.push(RecompressDetail {
+            mod_name,
+            status: status.to_string(),
+            original_size,
+            new_size,
+            error,
+        });
+    }
+}
+
+/// Oodle encoder settings for a recompression mode.
+/// `fast` favours encode speed, `max` favours output size; anything else keeps the default encoder.
+pub fn oodle_settings_for_mode(mode: Option<&str>) -> Result<(repak::OodleCompressor, repak::OodleLevel), String> {
+    use repak::{OodleCompressor, OodleLevel};
+
+    match mode.unwrap_or("default") {
+        "fast" => Ok((OodleCompressor::Mermaid, OodleLevel::VeryFast)),
+        "max" => Ok((OodleCompressor::Leviathan, OodleLevel::Optimal5)),
+        "default" => Ok((OodleCompressor::Mermaid, OodleLevel::Normal)),
+        other => Err(format!("Unknown recompression mode: {}", other)),
+    }
+}
+
+/// Scan all enabled mods and recompress any that aren't using Oodle compression.
+/// `on_progress` receives (current, total, status) as mods are checked.
+pub fn recompress_mods(
+    mods_dir: &Path,
+    source: &mut impl ModSource,
+    oodle_settings: (repak::OodleCompressor, repak::OodleLevel),
+    mut on_progress: impl FnMut(usize, usize, &str),
+) -> Result<RecompressResult, String> {
+    use repak::Compression;
+
+    if !mods_dir.exists() {
+        return Err("Game path does not exist".to_string());
+    }
+
+    info!("Starting recompression scan in: {} (oodle: {:?})", mods_dir.display(), oodle_settings);
+
+    let mut result = RecompressResult {
+        total_scanned: 0,
+        already_oodle: 0,
+        recompressed: 0,
+        failed: 0,
+        skipped_iostore: 0,
+        details: Vec::new(),
+    };
+
+    // Collect all enabled .pak files
+    let pak_files: Vec<PathBuf> = source.mods(mods_dir)
+        .into_iter()
+        .filter(|m| m.enabled)
+        .map(|m| m.path)
+        .collect();
+
+    result.total_scanned = pak_files.len();
+    info!("Found {} PAK files to scan", pak_files.len());
+
+    on_progress(0, pak_files.len(), "Scanning...");
+
+    for (idx, pak_path) in pak_files.iter().enumerate() {
+        let mod_name = pak_path.file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("Unknown")
+            .to_string();
+
+        on_progress(idx + 1, pak_files.len(), &format!("Checking: {}", mod_name));
+
+        // Check if this is an IoStore mod (has .utoc/.ucas files)
+        let utoc_path = pak_path.with_extension("utoc");
+        let ucas_path = pak_path.with_extension("ucas");
+
+        if utoc_path.exists() && ucas_path.exists() {
+            let ucas_size = std::fs::metadata(&ucas_path).map(|m| m.len()).unwrap_or(0);
+
+            let is_compressed = match uasset_toolkit::is_iostore_compressed(&utoc_path.to_string_lossy()) {
+                Ok(compressed) => compressed,
+                Err(e) => {
+                    warn!("Failed to check IoStore compression for {}: {}", mod_name, e);
+                    result.push(mod_name, "failed", ucas_size, None, Some(format!("Failed to check compression: {}", e)));
+                    continue;
+                }
+            };
+
+            if is_compressed {
+                info!("IoStore already compressed: {}", mod_name);
+                result.push(mod_name, "already_oodle", ucas_size, None, None);
+                continue;
+            }
+
+            info!("Recompressing IoStore: {}", mod_name);
+            on_progress(idx + 1, pak_files.len(), &format!("Recompressing IoStore: {}", mod_name));
+
+            match uasset_toolkit::recompress_iostore(&utoc_path.to_string_lossy()) {
+                Ok(_) => {
+                    let new_ucas_size = std::fs::metadata(&ucas_path).map(|m| m.len()).unwrap_or(0);
+                    info!("Successfully recompressed IoStore: {} ({} -> {} bytes)", mod_name, ucas_size, new_ucas_size);
+                    result.push(mod_name, "recompressed", ucas_size, Some(new_ucas_size), None);
+                }
+                Err(e) => {
+                    error!("Failed to recompress IoStore {}: {}", mod_name, e);
+                    result.push(mod_name, "failed", ucas_size, None, Some(format!("Recompression failed: {}", e)));
+                }
+            }
+            continue;
+        }
+
+        // Try to read the PAK file
+        let file = match File::open(pak_path) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("Failed to open PAK file {}: {}", pak_path.display(), e);
+                result.push(mod_name, "failed", 0, None, Some(format!("Failed to open: {}", e)));
+                continue;
+            }
+        };
+
+        let original_size = std::fs::metadata(pak_path).map(|m| m.len()).unwrap_or(0);
+
+        let pak_reader = match game_profile::active().open_pak(&mut BufReader::new(&file)) {
+            Ok(reader) => reader,
+            Err(e) => {
+                error!("Failed to read PAK file {}: {}", pak_path.display(), e);
+                result.push(mod_name, "failed", original_size, None, Some(format!("Failed to parse PAK: {}", e)));
+                continue;
+            }
+        };
+
+        // Check compression type
+        let compressions = pak_reader.compression();
+        let has_oodle = compressions.iter().any(|c| matches!(c, Compression::Oodle));
+        let is_uncompressed = compressions.is_empty();
+
+        if has_oodle && !is_uncompressed {
+            info!("Already Oodle compressed: {}", mod_name);
+            result.push(mod_name, "already_oodle", original_size, None, None);
+            continue;
+        }
+
+        info!("Recompressing: {} (compression: {:?})", mod_name, compressions);
+        on_progress(idx + 1, pak_files.len(), &format!("Recompressing: {}", mod_name));
+
+        match recompress_pak_file(pak_path, &pak_reader, oodle_settings) {
+            Ok(new_size) => {
+                info!("Successfully recompressed: {} ({} -> {} bytes)", mod_name, original_size, new_size);
+                result.push(mod_name, "recompressed", original_size, Some(new_size), None);
+            }
+            Err(e) => {
+                error!("Failed to recompress {}: {}", mod_name, e);
+                result.push(mod_name, "failed", original_size, None, Some(e));
+            }
+        }
+    }
+
+    on_progress(pak_files.len(), pak_files.len(), "Complete");
+
+    info!("Recompression complete: {} scanned, {} already Oodle, {} recompressed, {} failed",
+        result.total_scanned, result.already_oodle, result.recompressed, result.failed);
+
+    Ok(result)
+}
+
+/// Recompress a single PAK file to use Oodle compression
+fn recompress_pak_file(
+    pak_path: &Path,
+    pak_reader: &repak::PakReader,
+    (oodle_compressor, oodle_level): (repak::OodleCompressor, repak::OodleLevel),
+) -> Result<u64, String> {
+    use repak::Compression;
+    use std::io::BufWriter;
+    use tempfile::NamedTempFile;
+
+    // Create a temporary file for the new PAK next to the original, so it can replace it by
+    // renaming rather than by writing over a file that may be shared (e.g. a hardlink)
+    let temp_file = NamedTempFile::new_in(pak_path.parent().unwrap_or(Path::new(".")))
+        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+
+    let temp_path = temp_file.path().to_path_buf();
+
+    // Get PAK metadata
+    let mount_point = pak_reader.mount_point().to_string();
+    let path_hash_seed = pak_reader.path_hash_seed();
+    let files = pak_reader.files();
+
+    // Create new PAK with Oodle compression
+    let output_file = File::create(&temp_path)
+        .map_err(|e| format!("Failed to create output file: {}", e))?;
+
+    let profile = game_profile::active();
+    let builder = profile.pak_builder()
+        .compression(vec![Compression::Oodle])
+        .oodle(oodle_compressor, oodle_level);
+
+    let mut pak_writer = builder.writer(
+        BufWriter::new(output_file),
+        profile.pak_version,
+        mount_point,
+        path_hash_seed,
+    );
+
+    let entry_builder = pak_writer.entry_builder();
+
+    // Read source file
+    let source_file = File::open(pak_path)
+        .map_err(|e| format!("Failed to open source PAK: {}", e))?;
+    let mut source_reader = BufReader::new(source_file);
+
+    // Copy all entries with Oodle compression
+    for file_path in &files {
+        let data = pak_reader.get(file_path, &mut source_reader)
+            .map_err(|e| format!("Failed to read entry {}: {}", file_path, e))?;
+
+        let entry = entry_builder
+            .build_entry(true, data, file_path)
+            .map_err(|e| format!("Failed to build entry {}: {}", file_path, e))?;
+
+        pak_writer.write_entry(file_path.to_string(), entry)
+            .map_err(|e| format!("Failed to write entry {}: {}", file_path, e))?;
+    }
+
+    // Finalize the PAK (write_index consumes pak_writer)
+    let _writer = pak_writer.write_index()
+        .map_err(|e| format!("Failed to write index: {}", e))?;
+
+    let new_size = std::fs::metadata(&temp_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    // Replace original file with recompressed version
+    temp_file.persist(pak_path)
+        .map_err(|e| format!("Failed to replace original PAK: {}", e))?;
+
+    Ok(new_size)
+}
+
+/// Cleanup .ubulk files for textures that have inline data.
+/// This is called after extraction to remove unnecessary .ubulk files
+/// that were pulled from the base game but aren't needed because the
+/// mod's textures have been patched to use inline data.
+fn cleanup_ubulk_for_inline_textures(output_dir: &Path) {
+    use uasset_toolkit::{get_global_toolkit, RequestOptions};
+
+    // Find all .uasset files - UAssetTool will detect which are textures
+    let uasset_files: Vec<String> = WalkDir::new(output_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.path().extension()
+                .is_some_and(|ext| ext.to_string_lossy().to_lowercase() == "uasset")
+        })
+        .map(|e| e.path().to_string_lossy().to_string())
+        .collect();
+
+    if uasset_files.is_empty() {
+        return;
+    }
+
+    info!("[Extraction] Checking {} uasset files for textures with inline data...", uasset_files.len());
+
+    let toolkit = match get_global_toolkit() {
+        Ok(toolkit) => toolkit,
+        Err(e) => {
+            warn!("[Extraction] UAssetToolkit unavailable for cleanup: {}", e);
+            return;
+        }
+    };
+
+    // Get USMAP path from environment
+    let usmap_path = std::env::var("USMAP_PATH").ok();
+
+    // The batch_has_inline_texture_data function internally checks asset type
+    match toolkit.batch_has_inline_texture_data(&uasset_files, usmap_path.as_deref(), &RequestOptions::default()) {
+        Ok(inline_files) => {
+            info!("[Extraction] Found {} textures with inline data", inline_files.len());
+
+            // Delete .ubulk files for textures with inline data
+            let mut deleted_count = 0;
+            for uasset_path in inline_files {
+                let ubulk_path = uasset_path.replace(".uasset", ".ubulk");
+                if Path::new(&ubulk_path).exists() && std::fs::remove_file(&ubulk_path).is_ok() {
+                    deleted_count += 1;
+                    log::debug!("[Extraction] Deleted unnecessary .ubulk: {}", ubulk_path);
+                }
+            }
+
+            if deleted_count > 0 {
+                info!("[Extraction] Cleaned up {} unnecessary .ubulk files", deleted_count);
+            }
+        }
+        Err(e) => {
+            warn!("[Extraction] Failed to check inline texture data: {}", e);
+        }
+    }
+}
+
+/// Extract assets from a mod file (PAK or IoStore) into `dest_dir/<mod name>`.
+/// Automatically detects the mod type and uses the appropriate extraction method.
+/// Handles disabled mods (.bak_repak extension) by treating them as PAK files.
+/// Returns the number of files extracted.
+pub fn extract_mod_assets(mod_path: &Path, dest_dir: &Path) -> Result<usize, String> {
+    let mut path = mod_path.to_path_buf();
+    if !path.exists() {
+        return Err(format!("File not found: {}", mod_path.display()));
+    }
+
+    // Check if this is a PAK file with a corresponding .utoc file (IoStore mod)
+    // IoStore mods have both .pak (with just chunknames) and .utoc/.ucas (actual data)
+    let extension = path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if extension == "pak" || extension == "bak_repak" {
+        // Handle .bak_repak (disabled mod) by stripping .bak_repak then .pak to get base name
+        let base_path = if extension == "bak_repak" {
+            let name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            let base_name = name.trim_end_matches(".bak_repak").trim_end_matches(".pak");
+            path.parent().map(|p| p.join(base_name)).unwrap_or_else(|| PathBuf::from(base_name))
+        } else {
+            path.with_extension("")
+        };
+        let utoc_path = base_path.with_extension("utoc");
+        if utoc_path.exists() {
+            info!("Detected IoStore mod (has .utoc alongside .pak/.bak_repak), using IoStore extraction");
+            path = utoc_path;
+        }
+    }
+
+    // Get mod name - handle .bak_repak extension specially
+    let file_name = path.file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "extracted".to_string());
+
+    let mod_name = if file_name.ends_with(".bak_repak") {
+        file_name.trim_end_matches(".bak_repak")
+            .trim_end_matches(".pak")
+            .to_string()
+    } else {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "extracted".to_string())
+    };
+
+    let output_dir = dest_dir.join(&mod_name);
+    std::fs::create_dir_all(&output_dir).map_err(|e| e.to_string())?;
+
+    // Re-get extension after potential path change
+    let extension = path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "utoc" => {
+            // IoStore extraction using UAssetTool
+            info!("Starting IoStore extraction from {:?} to {:?}", path, output_dir);
+            let file_count = uasset_toolkit::extract_iostore(
+                &path.to_string_lossy(),
+                &output_dir.to_string_lossy(),
+                game_profile::active().primary_key_hex().as_deref(),
+            ).map_err(|e| {
+                error!("IoStore extraction failed: {}", e);
+                format!("Failed to extract IoStore: {}", e)
+            })?;
+
+            info!("Extracted {} files from IoStore to {:?}", file_count, output_dir);
+
+            // Post-extraction cleanup: Remove .ubulk files for textures with inline data
+            cleanup_ubulk_for_inline_textures(&output_dir);
+
+            Ok(file_count)
+        }
+        // Disabled PAK files (.bak_repak) are extracted as regular PAKs
+        "pak" | "bak_repak" => {
+            let file = File::open(&path).map_err(|e| e.to_string())?;
+            let mut reader = BufReader::new(file);
+            let pak_reader = game_profile::active().open_pak(&mut reader)
+                .map_err(|e| e.to_string())?;
+
+            let file_count = pak_reader.files().len();
+
+            let installable_mod = InstallableMod {
+                mod_name: mod_name.clone(),
+                mod_type: "".to_string(),
+                reader: Some(pak_reader),
+                mod_path: path.clone(),
+                ..Default::default()
+            };
+
+            extract_pak_to_dir(&installable_mod, output_dir.clone(), &uasset_toolkit::CancellationToken::new()).map_err(|e| e.to_string())?;
+
+            info!("Extracted {} files from PAK to {:?}", file_count, output_dir);
+            Ok(file_count)
+        }
+        "ucas" => {
+            // User selected .ucas, find the corresponding .utoc
+            let utoc_path = path.with_extension("utoc");
+            if !utoc_path.exists() {
+                return Err(format!("Cannot find .utoc file for: {}", mod_path.display()));
+            }
+            extract_mod_assets(&utoc_path, dest_dir)
+        }
+        _ => {
+            Err(format!("Unsupported file type: .{}. Supported: .pak, .utoc, .ucas, .bak_repak", extension))
+        }
+    }
+}
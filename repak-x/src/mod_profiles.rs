@@ -0,0 +1,438 @@
+// Mod profiles - named loadouts of which mods are enabled, at what priority and in which folder
+//
+// Mods are matched to profile entries by key, the file stem without priority markers, so a mod
+// still matches after it was disabled or its priority changed. Applying a profile works out every
+// rename first and checks none of them collide, then moves the files in two phases (all to
+// temporary names, then to their targets) and undoes what it did if any rename fails.
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::mod_library::ModLibrary;
+use crate::mod_ops::{self, ScannedMod};
+
+/// Suffix of the temporary names files get while they are moved (see move_all)
+const MOVING_SUFFIX: &str = ".repak_profile";
+
+/// How one mod is set up in a profile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileMod {
+    pub key: String,
+    pub enabled: bool,
+    pub priority: usize,
+    /// Subfolder relative to the mods folder, "" for the root
+    pub folder: String,
+    /// Library entry to deploy the mod from if it is no longer installed
+    #[serde(default)]
+    pub library_entry: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModProfile {
+    pub name: String,
+    pub mods: Vec<ProfileMod>,
+    /// Seconds since the Unix epoch
+    pub saved: u64,
+}
+
+/// A mod that is renamed or moved by applying a profile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProfileChange {
+    pub key: String,
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// New value of each property that changes
+    pub enabled: Option<bool>,
+    pub priority: Option<usize>,
+    pub folder: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ProfileReport {
+    pub changes: Vec<ProfileChange>,
+    /// Installed mods the profile doesn't mention; they are disabled
+    pub unlisted: Vec<PathBuf>,
+    /// Mods the profile lists that aren't installed and couldn't be restored from the library
+    pub missing: Vec<String>,
+    /// Mods deployed from the library because the profile lists them but they weren't installed
+    pub restored: Vec<String>,
+}
+
+/// Key a mod file is matched to profile entries by
+pub fn mod_key(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    Some(mod_ops::base_stem(stem))
+}
+
+/// Folder of `path` relative to `mods_dir` with forward slashes, empty for mods directly in it
+pub fn relative_folder(mods_dir: &Path, path: &Path) -> String {
+    path.parent()
+        .and_then(|parent| parent.strip_prefix(mods_dir).ok())
+        .map(|rel| rel.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default()
+}
+
+/// Record the current state of the mods folder as profile `name`
+pub fn capture(name: &str, mods_dir: &Path, library: Option<&ModLibrary>) -> ModProfile {
+    let mut mods: Vec<ProfileMod> = mod_ops::scan_mods(mods_dir)
+        .into_iter()
+        .filter_map(|scanned| {
+            Some(ProfileMod {
+                key: mod_key(&scanned.path)?,
+                enabled: scanned.enabled,
+                priority: scanned.priority,
+                folder: relative_folder(mods_dir, &scanned.path),
+                library_entry: library.and_then(|l| l.deployed_entry(&scanned.path).ok().flatten()),
+            })
+        })
+        .collect();
+    mods.sort_by(|a, b| a.key.cmp(&b.key).then_with(|| a.folder.cmp(&b.folder)));
+
+    ModProfile {
+        name: name.to_string(),
+        mods,
+        saved: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+    }
+}
+
+/// Pair each profile entry with an installed mod of the same key, preferring one already in
+/// the entry's folder. Returns the installed mod index matched to each entry.
+fn match_mods(profile: &ModProfile, installed: &[ScannedMod], mods_dir: &Path) -> Vec<Option<usize>> {
+    let mut by_key: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, scanned) in installed.iter().enumerate() {
+        if let Some(key) = mod_key(&scanned.path) {
+            by_key.entry(key).or_default().push(i);
+        }
+    }
+
+    let mut matched = vec![None; profile.mods.len()];
+    let mut taken = HashSet::new();
+    for same_folder_only in [true, false] {
+        for (slot, wanted) in matched.iter_mut().zip(&profile.mods) {
+            if slot.is_some() {
+                continue;
+            }
+            *slot = by_key.get(&wanted.key).and_then(|candidates| {
+                candidates.iter().copied().find(|i| {
+                    !taken.contains(i)
+                        && (!same_folder_only || relative_folder(mods_dir, &installed[*i].path) == wanted.folder)
+                })
+            });
+            if let Some(i) = *slot {
+                taken.insert(i);
+            }
+        }
+    }
+    matched
+}
+
+/// Work out what applying `profile` would change, without touching any files
+pub fn plan(profile: &ModProfile, mods_dir: &Path) -> ProfileReport {
+    let installed = mod_ops::scan_mods(mods_dir);
+    let matched = match_mods(profile, &installed, mods_dir);
+    let mut report = ProfileReport::default();
+
+    for (wanted, index) in profile.mods.iter().zip(&matched) {
+        let Some(index) = *index else {
+            report.missing.push(wanted.key.clone());
+            continue;
+        };
+        let current = &installed[index];
+        let folder = relative_folder(mods_dir, &current.path);
+        let change = ProfileChange {
+            key: wanted.key.clone(),
+            from: current.path.clone(),
+            to: PathBuf::new(),
+            enabled: (current.enabled != wanted.enabled).then_some(wanted.enabled),
+            priority: (current.priority != wanted.priority).then_some(wanted.priority),
+            folder: (folder != wanted.folder).then(|| wanted.folder.clone()),
+        };
+        push_change(&mut report, mods_dir, change);
+    }
+
+    let matched: HashSet<usize> = matched.into_iter().flatten().collect();
+    for (_, current) in installed.iter().enumerate().filter(|(i, _)| !matched.contains(i)) {
+        report.unlisted.push(current.path.clone());
+        if current.enabled {
+            let change = ProfileChange {
+                key: mod_key(&current.path).unwrap_or_default(),
+                from: current.path.clone(),
+                to: PathBuf::new(),
+                enabled: Some(false),
+                priority: None,
+                folder: None,
+            };
+            push_change(&mut report, mods_dir, change);
+        }
+    }
+    report
+}
+
+/// Fill in the target path of `change` and add it to the report if anything changes
+fn push_change(report: &mut ProfileReport, mods_dir: &Path, mut change: ProfileChange) {
+    if change.enabled.is_none() && change.priority.is_none() && change.folder.is_none() {
+        return;
+    }
+    let from = &change.from;
+    let stem = from.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let stem = match change.priority {
+        Some(priority) => mod_ops::priority_stem(stem, priority),
+        None => stem.to_string(),
+    };
+    let extension = match change.enabled {
+        Some(true) => "pak",
+        Some(false) => "bak_repak",
+        None => from.extension().and_then(|e| e.to_str()).unwrap_or("pak"),
+    };
+    let dir = match &change.folder {
+        Some(folder) if folder.is_empty() => mods_dir.to_path_buf(),
+        Some(folder) => mods_dir.join(folder),
+        None => from.parent().map(Path::to_path_buf).unwrap_or_else(|| mods_dir.to_path_buf()),
+    };
+    change.to = dir.join(format!("{}.{}", stem, extension));
+    report.changes.push(change);
+}
+
+/// Every file that moves when the mod at `from` becomes `to`: the mod itself and its IoStore
+/// companions, which keep their own extension (and disabled suffix, if any) but follow the
+/// mod's stem and folder
+pub fn file_moves(from: &Path, to: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let mut moves = vec![(from.to_path_buf(), to.to_path_buf())];
+    let from_stem = from.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let to_stem = to.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let to_dir = to.parent().unwrap_or(Path::new(""));
+    for suffix in ["utoc", "ucas", "utoc.bak_repak", "ucas.bak_repak"] {
+        let companion = from.with_file_name(format!("{}.{}", from_stem, suffix));
+        if companion.exists() {
+            moves.push((companion, to_dir.join(format!("{}.{}", to_stem, suffix))));
+        }
+    }
+    moves
+}
+
+/// Apply `profile` to the mods folder. Mods the profile lists that are no longer installed are
+/// deployed from the library first when it has them. Either every rename happens or none does,
+/// and if none does the restored mods are taken out again.
+pub fn apply(profile: &ModProfile, mods_dir: &Path, library: Option<&ModLibrary>) -> Result<ProfileReport, String> {
+    let mut restored = Vec::new();
+    let mut deployed = Vec::new();
+    if let Some(library) = library {
+        let installed = mod_ops::scan_mods(mods_dir);
+        let matched = match_mods(profile, &installed, mods_dir);
+        for (wanted, index) in profile.mods.iter().zip(matched) {
+            let (None, Some(entry)) = (index, &wanted.library_entry) else {
+                continue;
+            };
+            let dir = if wanted.folder.is_empty() { mods_dir.to_path_buf() } else { mods_dir.join(&wanted.folder) };
+            // Undoing the restore deletes what was deployed, so it must not replace anything
+            let in_the_way = library
+                .entry(entry)
+                .ok()
+                .flatten()
+                .is_some_and(|e| e.files.iter().any(|f| dir.join(&f.name).exists()));
+            if in_the_way {
+                warn!("[Profiles] Couldn't restore {} from the library: its files are in the way", wanted.key);
+                continue;
+            }
+            match library.deploy(entry, &dir) {
+                Ok(paths) => {
+                    deployed.extend(paths);
+                    restored.push(wanted.key.clone());
+                }
+                Err(e) => warn!("[Profiles] Couldn't restore {} from the library: {}", wanted.key, e),
+            }
+        }
+    }
+
+    let mut report = plan(profile, mods_dir);
+    report.restored = restored;
+
+    let moves: Vec<(PathBuf, PathBuf)> = report.changes.iter().flat_map(|c| file_moves(&c.from, &c.to)).collect();
+    if let Err(e) = move_all(&moves) {
+        if let Some(library) = library.filter(|_| !deployed.is_empty()) {
+            if let Err(e) = library.undeploy(&deployed) {
+                warn!("[Profiles] Couldn't take out the mods restored from the library: {}", e);
+            }
+        }
+        return Err(e);
+    }
+    info!("[Profiles] Applied '{}': {} change(s), {} missing", profile.name, report.changes.len(), report.missing.len());
+    Ok(report)
+}
+
+/// Carry out `moves`, refusing up front if two of them share a target or a target is taken by
+/// something that isn't moving away. Either every rename happens or none does.
+pub fn move_all(moves: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    let sources: HashSet<&PathBuf> = moves.iter().map(|(from, _)| from).collect();
+    let mut targets = HashSet::new();
+    for (_, to) in moves {
+        if !targets.insert(to) {
+            return Err(format!("Two mods would both be renamed to {}", to.display()));
+        }
+        if to.exists() && !sources.contains(to) {
+            return Err(format!("{} already exists", to.display()));
+        }
+    }
+    rename_all(moves)
+}
+
+/// Rename every `(from, to)` pair, going through temporary names so targets may be other
+/// pairs' sources. On failure the renames done so far are undone.
+fn rename_all(moves: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    let temp = |from: &Path| PathBuf::from(format!("{}{}", from.display(), MOVING_SUFFIX));
+
+    let mut parked = Vec::new();
+    for (from, _) in moves {
+        if let Err(e) = std::fs::rename(from, temp(from)) {
+            undo(&parked, &[], &temp);
+            return Err(format!("Failed to move {}: {}", from.display(), e));
+        }
+        parked.push(from.clone());
+    }
+
+    let mut placed = Vec::new();
+    for (from, to) in moves {
+        let result = match to.parent() {
+            Some(dir) => std::fs::create_dir_all(dir),
+            None => Ok(()),
+        }
+        .and_then(|_| std::fs::rename(temp(from), to));
+        if let Err(e) = result {
+            undo(&parked, &placed, &temp);
+            return Err(format!("Failed to move {} to {}: {}", from.display(), to.display(), e));
+        }
+        placed.push((from.clone(), to.clone()));
+    }
+    Ok(())
+}
+
+fn undo(parked: &[PathBuf], placed: &[(PathBuf, PathBuf)], temp: &dyn Fn(&Path) -> PathBuf) {
+    for (from, to) in placed.iter().rev() {
+        if let Err(e) = std::fs::rename(to, temp(from)) {
+            warn!("Failed to undo move of {}: {}", to.display(), e);
+        }
+    }
+    for from in parked.iter().rev() {
+        if let Err(e) = std::fs::rename(temp(from), from) {
+            warn!("Failed to restore {}: {}", from.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write(dir: &Path, name: &str) -> PathBuf {
+        fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, name).unwrap();
+        path
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = walkdir::WalkDir::new(dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.path().strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn switching_profiles_restores_enabled_state_priority_and_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods = dir.path().join("~mods");
+        write(&mods, "Hero_9999999_P.pak");
+        write(&mods, "Hero_9999999_P.utoc");
+        write(&mods, "Hero_9999999_P.ucas");
+        write(&mods.join("Skins"), "Skin_9999999_P.pak");
+        let ranked = capture("Ranked", &mods, None);
+
+        mod_ops::set_mod_priority(&mods.join("Hero_9999999_P.pak"), 3).unwrap();
+        mod_ops::toggle_mod(&mods.join("Skins/Skin_9999999_P.pak")).unwrap();
+        fs::create_dir_all(mods.join("Testing")).unwrap();
+        fs::rename(mods.join("Hero_999999999_P.pak"), mods.join("Testing/Hero_999999999_P.pak")).unwrap();
+        fs::rename(mods.join("Hero_999999999_P.utoc"), mods.join("Testing/Hero_999999999_P.utoc")).unwrap();
+        fs::rename(mods.join("Hero_999999999_P.ucas"), mods.join("Testing/Hero_999999999_P.ucas")).unwrap();
+        write(&mods, "New_9999999_P.pak");
+
+        let report = apply(&ranked, &mods, None).unwrap();
+        assert_eq!(
+            names(&mods),
+            [
+                "Hero_9999999_P.pak",
+                "Hero_9999999_P.ucas",
+                "Hero_9999999_P.utoc",
+                "New_9999999_P.bak_repak",
+                "Skins/Skin_9999999_P.pak",
+            ]
+        );
+        assert_eq!(report.changes.len(), 3);
+        assert_eq!(report.unlisted, vec![mods.join("New_9999999_P.pak")]);
+        let hero = report.changes.iter().find(|c| c.key == "Hero").unwrap();
+        assert_eq!((hero.enabled, hero.priority, hero.folder.as_deref()), (None, Some(1), Some("")));
+
+        // Applying the same profile again changes nothing
+        assert!(apply(&ranked, &mods, None).unwrap().changes.is_empty());
+    }
+
+    #[test]
+    fn collisions_are_refused_before_anything_moves() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods = dir.path().join("~mods");
+        write(&mods, "Hero_9999999_P.pak");
+        write(&mods, "Other_9999999_P.pak");
+        let profile = capture("Before", &mods, None);
+
+        mod_ops::toggle_mod(&mods.join("Other_9999999_P.pak")).unwrap();
+        fs::create_dir_all(mods.join("Sub")).unwrap();
+        fs::rename(mods.join("Hero_9999999_P.pak"), mods.join("Sub/Hero_9999999_P.pak")).unwrap();
+        // Something that isn't a mod sitting where the profile wants Hero to go
+        fs::create_dir_all(mods.join("Hero_9999999_P.pak")).unwrap();
+
+        assert!(apply(&profile, &mods, None).is_err());
+        assert_eq!(names(&mods), ["Other_9999999_P.bak_repak", "Sub/Hero_9999999_P.pak"]);
+    }
+
+    #[test]
+    fn missing_mods_come_back_from_the_library() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods = dir.path().join("~mods");
+        let library = ModLibrary::open(dir.path().join("Library")).unwrap();
+        let installed = write(&mods.join("Sub"), "Hero_9999999_P.pak");
+        library.adopt("Hero", std::slice::from_ref(&installed), None).unwrap();
+        let profile = capture("Saved", &mods, Some(&library));
+        assert!(profile.mods[0].library_entry.is_some());
+
+        fs::remove_file(&installed).unwrap();
+        let report = apply(&profile, &mods, Some(&library)).unwrap();
+        assert_eq!(report.restored, ["Hero"]);
+        assert!(report.missing.is_empty());
+        assert!(installed.exists());
+    }
+
+    #[test]
+    fn a_failed_apply_takes_restored_mods_out_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods = dir.path().join("~mods");
+        let library = ModLibrary::open(dir.path().join("Library")).unwrap();
+        let hero = write(&mods, "Hero_9999999_P.pak");
+        let entry = library.adopt("Hero", std::slice::from_ref(&hero), None).unwrap();
+        write(&mods.join("Sub"), "Other_9999999_P.pak");
+        let profile = capture("Saved", &mods, Some(&library));
+
+        fs::remove_file(&hero).unwrap();
+        fs::rename(mods.join("Sub/Other_9999999_P.pak"), mods.join("Other_9999999_P.pak")).unwrap();
+        fs::create_dir_all(mods.join("Sub/Other_9999999_P.pak")).unwrap();
+
+        assert!(apply(&profile, &mods, Some(&library)).is_err());
+        assert_eq!(names(&mods), ["Other_9999999_P.pak"]);
+        assert!(!library.is_deployed(&entry.id).unwrap());
+    }
+}
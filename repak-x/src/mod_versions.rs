@@ -0,0 +1,380 @@
+// Mod version history - the versions of each mod kept in the library, newest first
+//
+// Every mod (see history_key) has a history file under <library>/history listing the library
+// entries it was installed or updated from, together with the name, tags and folder it had and
+// the options it was installed with. Rolling back makes the restored version the newest again,
+// so its options are the ones later updates of the mod are installed with. Only the current
+// version and the KEPT_VERSIONS before it are kept; entries that fall out of every history and
+// aren't deployed are deleted.
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+use crate::game_profile;
+use crate::install_mod::InstallableMod;
+use crate::mod_library::ModLibrary;
+use crate::mod_profiles;
+use crate::utoc_utils::try_read_utoc;
+
+/// Previous versions kept per mod, besides the current one
+pub const KEPT_VERSIONS: usize = 5;
+
+/// Options a version was installed with, so a rollback reinstates them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallOptions {
+    pub fix_textures: bool,
+    pub fix_serialsize_header: bool,
+    pub repak: bool,
+    pub force_legacy_pak: bool,
+    pub obfuscate: bool,
+}
+
+impl InstallOptions {
+    /// Install `installable` the way this version was
+    pub fn apply(&self, installable: &mut InstallableMod) {
+        installable.fix_textures = self.fix_textures;
+        installable.fix_serialsize_header = self.fix_serialsize_header;
+        installable.repak = self.repak;
+        installable.force_legacy_pak = self.force_legacy_pak;
+        installable.obfuscate = self.obfuscate;
+    }
+}
+
+impl From<&InstallableMod> for InstallOptions {
+    fn from(installable: &InstallableMod) -> Self {
+        Self {
+            fix_textures: installable.fix_textures,
+            fix_serialsize_header: installable.fix_serialsize_header,
+            repak: installable.repak,
+            force_legacy_pak: installable.force_legacy_pak,
+            obfuscate: installable.obfuscate,
+        }
+    }
+}
+
+/// One kept version of a mod
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModVersion {
+    pub entry_id: String,
+    /// Seconds since the Unix epoch
+    pub recorded: u64,
+    pub source: Option<String>,
+    pub custom_name: Option<String>,
+    #[serde(default)]
+    pub custom_tags: Vec<String>,
+    pub folder_id: Option<String>,
+    /// `None` for versions that weren't installed through the app
+    pub install_options: Option<InstallOptions>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ModHistory {
+    /// Newest first
+    versions: Vec<ModVersion>,
+}
+
+/// Contained files that differ between two versions
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct VersionDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// In both versions with different contents
+    pub changed: Vec<String>,
+    pub unchanged: Vec<String>,
+    /// In both versions, but IoStore containers don't expose per-file hashes to compare
+    pub uncompared: Vec<String>,
+}
+
+/// Key the history of the mod at `path` is kept under: its folder relative to `mods_dir` and
+/// its base name (see mod_profiles::mod_key), so same-named mods in different folders keep
+/// separate histories. Mods directly in `mods_dir` are keyed by their base name alone.
+pub fn history_key(mods_dir: &Path, path: &Path) -> Option<String> {
+    let name = mod_profiles::mod_key(path)?;
+    let folder = mod_profiles::relative_folder(mods_dir, path);
+    Some(if folder.is_empty() { name } else { format!("{}/{}", folder, name) })
+}
+
+fn history_path(library: &ModLibrary, key: &str) -> PathBuf {
+    library.root().join("history").join(format!("{}.json", key))
+}
+
+fn load(library: &ModLibrary, key: &str) -> io::Result<ModHistory> {
+    load_file(&history_path(library, key))
+}
+
+fn load_file(path: &Path) -> io::Result<ModHistory> {
+    if !path.exists() {
+        return Ok(ModHistory::default());
+    }
+    serde_json::from_reader(BufReader::new(File::open(path)?)).map_err(io::Error::from)
+}
+
+/// Every history file under `dir`, including those of mods in subfolders
+fn history_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for dirent in fs::read_dir(dir)? {
+        let path = dirent?.path();
+        if path.is_dir() {
+            history_files(&path, files)?;
+        } else if path.extension().is_some_and(|e| e == "json") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn save(library: &ModLibrary, key: &str, history: &ModHistory) -> io::Result<()> {
+    let path = history_path(library, key);
+    fs::create_dir_all(path.parent().unwrap())?;
+    let tmp = path.with_extension("json.tmp");
+    serde_json::to_writer_pretty(io::BufWriter::new(File::create(&tmp)?), history).map_err(io::Error::from)?;
+    fs::rename(tmp, path)
+}
+
+/// Versions of mod `key`, newest first
+pub fn versions(library: &ModLibrary, key: &str) -> io::Result<Vec<ModVersion>> {
+    Ok(load(library, key)?.versions)
+}
+
+/// Options version `entry_id` of mod `key` was installed with, if it is kept and they are known
+pub fn install_options(library: &ModLibrary, key: &str, entry_id: &str) -> io::Result<Option<InstallOptions>> {
+    Ok(load(library, key)?
+        .versions
+        .into_iter()
+        .find(|v| v.entry_id == entry_id)
+        .and_then(|v| v.install_options))
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Record `version` as the newest version of mod `key`, then drop versions beyond
+/// KEPT_VERSIONS. Recording an entry that is already in the history moves it to the front,
+/// keeping its install options if the new record doesn't know them.
+pub fn record(library: &ModLibrary, key: &str, mut version: ModVersion) -> io::Result<()> {
+    let mut history = load(library, key)?;
+    if let Some(pos) = history.versions.iter().position(|v| v.entry_id == version.entry_id) {
+        let previous = history.versions.remove(pos);
+        if version.install_options.is_none() {
+            version.install_options = previous.install_options;
+        }
+    }
+    history.versions.insert(0, version);
+    let dropped = history.versions.split_off(history.versions.len().min(KEPT_VERSIONS + 1));
+    save(library, key, &history)?;
+
+    for old in dropped {
+        if let Err(e) = forget_entry(library, &old.entry_id) {
+            warn!("[Versions] Failed to remove old version {} of {}: {}", old.entry_id, key, e);
+        }
+    }
+    Ok(())
+}
+
+/// Record `version` only if mod `key` doesn't have that entry yet
+pub fn record_if_missing(library: &ModLibrary, key: &str, version: ModVersion) -> io::Result<()> {
+    let mut history = load(library, key)?;
+    if history.versions.iter().any(|v| v.entry_id == version.entry_id) {
+        return Ok(());
+    }
+    // An older version found late goes after the ones already known
+    history.versions.push(version);
+    save(library, key, &history)
+}
+
+/// Delete a library entry nothing needs any more
+fn forget_entry(library: &ModLibrary, id: &str) -> io::Result<()> {
+    if library.is_deployed(id)? {
+        return Ok(());
+    }
+    let history_dir = library.root().join("history");
+    if history_dir.exists() {
+        let mut files = Vec::new();
+        history_files(&history_dir, &mut files)?;
+        for path in files {
+            if load_file(&path)?.versions.iter().any(|v| v.entry_id == id) {
+                return Ok(());
+            }
+        }
+    }
+    library.remove_entry(id)?;
+    info!("[Versions] Removed old version {}", id);
+    Ok(())
+}
+
+/// Files contained in library entry `id`, with a fingerprint of their contents where one is
+/// available. Pak entries without a stored hash are hashed from their data; those that can't
+/// be read (e.g. Oodle compressed without Oodle available) have no fingerprint.
+fn contained_files(library: &ModLibrary, id: &str) -> Result<BTreeMap<String, Option<String>>, String> {
+    let dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+    let (_, paths) = library.materialize_entry(id, dir.path()).map_err(|e| e.to_string())?;
+
+    let mut files = BTreeMap::new();
+    for path in paths {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("utoc") => {
+                for entry in try_read_utoc(&path)? {
+                    files.insert(entry.file_path, None);
+                }
+            }
+            Some("pak") if !path.with_extension("utoc").exists() => {
+                let mut reader = BufReader::new(File::open(&path).map_err(|e| e.to_string())?);
+                let pak = game_profile::active().open_pak(&mut reader).map_err(|e| e.to_string())?;
+                for name in pak.files() {
+                    let fingerprint = match pak.get_file_entry(&name).ok().and_then(|entry| entry.hash) {
+                        Some(hash) => Some(format!("sha1:{:?}", hash)),
+                        None => {
+                            let mut hasher = Sha256::new();
+                            pak.read_file(&name, &mut reader, &mut hasher)
+                                .ok()
+                                .map(|_| format!("sha256:{}", hex::encode(hasher.finalize())))
+                        }
+                    };
+                    files.insert(name, fingerprint);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(files)
+}
+
+/// What changed in the contained files from version `from` to version `to`
+pub fn diff(library: &ModLibrary, from: &str, to: &str) -> Result<VersionDiff, String> {
+    Ok(compare(&contained_files(library, from)?, &contained_files(library, to)?))
+}
+
+fn compare(from: &BTreeMap<String, Option<String>>, to: &BTreeMap<String, Option<String>>) -> VersionDiff {
+    let mut diff = VersionDiff::default();
+    for (name, old) in from {
+        match to.get(name) {
+            None => diff.removed.push(name.clone()),
+            Some(new) => match (old, new) {
+                (Some(old), Some(new)) if old == new => diff.unchanged.push(name.clone()),
+                // Only fingerprints of the same kind say anything about each other
+                (Some(old), Some(new)) if fingerprint_kind(old) == fingerprint_kind(new) => {
+                    diff.changed.push(name.clone())
+                }
+                _ => diff.uncompared.push(name.clone()),
+            },
+        }
+    }
+    let known: HashSet<&String> = from.keys().collect();
+    diff.added = to.keys().filter(|name| !known.contains(name)).cloned().collect();
+    diff
+}
+
+fn fingerprint_kind(fingerprint: &str) -> &str {
+    fingerprint.split_once(':').map_or("", |(kind, _)| kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn entry(library: &ModLibrary, dir: &Path, contents: &str) -> String {
+        let path = dir.join("Hero_9999999_P.pak");
+        fs::write(&path, contents).unwrap();
+        library.import("Hero", &[path], None).unwrap().id
+    }
+
+    #[test]
+    fn only_the_newest_versions_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = ModLibrary::open(dir.path().join("Library")).unwrap();
+
+        let ids: Vec<String> = (0..KEPT_VERSIONS + 3).map(|i| entry(&library, dir.path(), &format!("v{}", i))).collect();
+        // The oldest version is still deployed somewhere, so its files must survive pruning
+        library.deploy(&ids[0], &dir.path().join("~mods")).unwrap();
+        for id in &ids {
+            record(&library, "Hero", ModVersion { entry_id: id.clone(), ..Default::default() }).unwrap();
+        }
+
+        let kept: Vec<String> = versions(&library, "Hero").unwrap().into_iter().map(|v| v.entry_id).collect();
+        let newest: Vec<String> = ids.iter().rev().take(KEPT_VERSIONS + 1).cloned().collect();
+        assert_eq!(kept, newest);
+        assert!(library.entry(&ids[0]).unwrap().is_some());
+        assert!(library.entry(&ids[1]).unwrap().is_none());
+    }
+
+    #[test]
+    fn re_recording_a_version_moves_it_to_the_front_and_keeps_its_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = ModLibrary::open(dir.path().join("Library")).unwrap();
+        let v1 = entry(&library, dir.path(), "v1");
+        let v2 = entry(&library, dir.path(), "v2");
+        let options = InstallOptions { fix_textures: true, ..Default::default() };
+
+        record(&library, "Hero", ModVersion { entry_id: v1.clone(), install_options: Some(options.clone()), ..Default::default() }).unwrap();
+        record(&library, "Hero", ModVersion { entry_id: v2.clone(), ..Default::default() }).unwrap();
+        record(&library, "Hero", ModVersion { entry_id: v1.clone(), ..Default::default() }).unwrap();
+        record_if_missing(&library, "Hero", ModVersion { entry_id: v2.clone(), ..Default::default() }).unwrap();
+
+        let history = versions(&library, "Hero").unwrap();
+        assert_eq!(history.iter().map(|v| v.entry_id.as_str()).collect::<Vec<_>>(), [v1.as_str(), v2.as_str()]);
+        assert_eq!(history[0].install_options, Some(options));
+    }
+
+    #[test]
+    fn same_named_mods_in_different_folders_keep_separate_histories() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods = dir.path().join("~mods");
+        let library = ModLibrary::open(dir.path().join("Library")).unwrap();
+        let root = history_key(&mods, &mods.join("Hero_9999999_P.pak")).unwrap();
+        let nested = history_key(&mods, &mods.join("Skins").join("Hero_9999999_P.bak_repak")).unwrap();
+        assert_eq!(root, "Hero");
+        assert_eq!(nested, "Skins/Hero");
+
+        let shared = entry(&library, dir.path(), "shared");
+        record(&library, &nested, ModVersion { entry_id: shared.clone(), ..Default::default() }).unwrap();
+        record(&library, &root, ModVersion { entry_id: shared.clone(), ..Default::default() }).unwrap();
+        for i in 0..KEPT_VERSIONS + 1 {
+            let id = entry(&library, dir.path(), &format!("v{}", i));
+            record(&library, &root, ModVersion { entry_id: id, ..Default::default() }).unwrap();
+        }
+
+        // Pruned from the root mod's history, but the nested mod still has it
+        assert!(versions(&library, &root).unwrap().iter().all(|v| v.entry_id != shared));
+        assert_eq!(versions(&library, &nested).unwrap().len(), 1);
+        assert!(library.entry(&shared).unwrap().is_some());
+    }
+
+    #[test]
+    fn compare_sorts_files_by_what_happened_to_them() {
+        let files = |items: &[(&str, Option<&str>)]| -> BTreeMap<String, Option<String>> {
+            items.iter().map(|(name, hash)| (name.to_string(), hash.map(str::to_string))).collect()
+        };
+        let from = files(&[
+            ("a", Some("sha1:1")),
+            ("b", Some("sha1:2")),
+            ("c", None),
+            ("d", Some("sha1:5")),
+            ("gone", Some("sha1:3")),
+        ]);
+        let to = files(&[
+            ("a", Some("sha1:1")),
+            ("b", Some("sha1:9")),
+            ("c", None),
+            ("d", Some("sha256:6")),
+            ("new", Some("sha1:4")),
+        ]);
+
+        assert_eq!(
+            compare(&from, &to),
+            VersionDiff {
+                added: vec!["new".to_string()],
+                removed: vec!["gone".to_string()],
+                changed: vec!["b".to_string()],
+                unchanged: vec!["a".to_string()],
+                uncompared: vec!["c".to_string(), "d".to_string()],
+            }
+        );
+    }
+}
@@ -168,6 +168,30 @@ pub fn emit_move_failed(window: &Window, error: &str) {
     ));
 }
 
+/// Emit profile switch failed error
+pub fn emit_profile_failed(window: &Window, error: &str) {
+    emit_toast(window, ToastPayload::error(
+        "Profile Not Applied",
+        format!("Could not switch profile: {}", error)
+    ));
+}
+
+/// Emit load order failed error
+pub fn emit_load_order_failed(window: &Window, error: &str) {
+    emit_toast(window, ToastPayload::error(
+        "Load Order Not Applied",
+        format!("Could not apply the load order: {}", error)
+    ));
+}
+
+/// Emit asset split failed error
+pub fn emit_split_failed(window: &Window, error: &str) {
+    emit_toast(window, ToastPayload::error(
+        "Split Failed",
+        format!("Could not split the assets out: {}", error)
+    ));
+}
+
 /// Emit game path detection failed error
 pub fn emit_game_path_failed(window: &Window, error: &str) {
     emit_toast(window, ToastPayload::error(
@@ -1,5 +1,6 @@
 use std::path::Path;
 use log::{info, error};
+use uasset_toolkit::RequestOptions;
 
 // ============================================================================
 // TEXTURE MIPMAP STRIPPING IMPLEMENTATION
@@ -34,19 +35,19 @@ pub fn convert_texture_to_inline(uasset_path: &Path) -> Result<bool, Box<dyn std
 /// Native C# implementation using UAssetAPI TextureExport via UAssetTool
 #[allow(dead_code)]
 fn convert_texture_to_inline_csharp(uasset_path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
-    use uasset_toolkit::UAssetToolkitSync;
+    use uasset_toolkit::get_global_toolkit;
     
     info!("[C#] Stripping mipmaps using UAssetAPI TextureExport: {:?}", uasset_path);
     
     // Get USMAP path from environment variable
     let usmap_path = std::env::var("USMAP_PATH").ok();
     
-    match UAssetToolkitSync::new(None) {
+    match get_global_toolkit() {
         Ok(toolkit) => {
             let path_str = uasset_path.to_string_lossy();
             
             // Use the new strip_mipmaps_native action with usmap_path
-            match toolkit.strip_mipmaps_native(&path_str, usmap_path.as_deref()) {
+            match toolkit.strip_mipmaps_native(&path_str, usmap_path.as_deref(), &RequestOptions::default()) {
                 Ok(true) => {
                     info!("[C#] Successfully stripped mipmaps: {:?}", uasset_path);
                     Ok(true)
@@ -71,16 +72,16 @@ fn convert_texture_to_inline_csharp(uasset_path: &Path) -> Result<bool, Box<dyn
 /// Native C# implementation with explicit usmap_path parameter
 #[allow(dead_code)]
 fn convert_texture_to_inline_csharp_with_usmap(uasset_path: &Path, usmap_path: Option<&str>) -> Result<bool, Box<dyn std::error::Error>> {
-    use uasset_toolkit::UAssetToolkitSync;
+    use uasset_toolkit::get_global_toolkit;
     
     info!("[C#] Stripping mipmaps using UAssetAPI TextureExport: {:?}", uasset_path);
     
-    match UAssetToolkitSync::new(None) {
+    match get_global_toolkit() {
         Ok(toolkit) => {
             let path_str = uasset_path.to_string_lossy();
             
             // Use the strip_mipmaps_native action with usmap_path
-            match toolkit.strip_mipmaps_native(&path_str, usmap_path) {
+            match toolkit.strip_mipmaps_native(&path_str, usmap_path, &RequestOptions::default()) {
                 Ok(true) => {
                     info!("[C#] Successfully stripped mipmaps: {:?}", uasset_path);
                     Ok(true)
@@ -112,16 +113,16 @@ fn convert_texture_to_inline_rust(_uasset_path: &Path) -> Result<bool, Box<dyn s
 /// Python implementation using UE4-DDS-Tools via UAssetToolkit
 #[allow(dead_code)]
 fn convert_texture_to_inline_python(uasset_path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
-    use uasset_toolkit::UAssetToolkitSync;
+    use uasset_toolkit::get_global_toolkit;
     
     info!("[Python] Converting texture using UE4-DDS-Tools: {:?}", uasset_path);
     
-    match UAssetToolkitSync::new(None) {
+    match get_global_toolkit() {
         Ok(toolkit) => {
             let path_str = uasset_path.to_string_lossy();
             
             // Use the convert_texture method which calls UE4-DDS-Tools
-            match toolkit.convert_texture(&path_str) {
+            match toolkit.convert_texture(&path_str, &RequestOptions::default()) {
                 Ok(true) => {
                     info!("[Python] Successfully converted texture: {:?}", uasset_path);
                     Ok(true)
@@ -143,27 +144,35 @@ fn convert_texture_to_inline_python(uasset_path: &Path) -> Result<bool, Box<dyn
     }
 }
 
+/// (success_count, skip_count, error_count, processed_file_names) of a texture batch
+pub type TextureBatchResult = Result<(usize, usize, usize, Vec<String>), Box<dyn std::error::Error>>;
+
 /// Batch convert multiple textures to inline format by stripping mipmaps.
 /// Uses the global UAssetToolkit singleton and sends ALL files in a single batch request
 /// for optimal performance - no repeated process spawning.
 /// 
 /// Returns (success_count, skip_count, error_count, processed_file_names)
-pub fn batch_convert_textures_to_inline(uasset_paths: &[std::path::PathBuf]) -> Result<(usize, usize, usize, Vec<String>), Box<dyn std::error::Error>> {
-    batch_convert_textures_to_inline_with_parallel(uasset_paths, false)
+pub fn batch_convert_textures_to_inline(uasset_paths: &[std::path::PathBuf], usmap_path: Option<&str>) -> TextureBatchResult {
+    batch_convert_textures_to_inline_with_parallel(uasset_paths, usmap_path, false, None)
 }
 
 /// Batch convert multiple textures to inline format with optional parallel processing.
 /// When parallel=true, uses multi-threaded processing in UAssetTool for faster batch operations.
+/// `usmap_path` is the mappings file for unversioned assets.
+/// Cancelling `cancel` stops the UAssetTool workers mid-batch.
 /// 
 /// Returns (success_count, skip_count, error_count, processed_file_names)
-pub fn batch_convert_textures_to_inline_with_parallel(uasset_paths: &[std::path::PathBuf], parallel: bool) -> Result<(usize, usize, usize, Vec<String>), Box<dyn std::error::Error>> {
+pub fn batch_convert_textures_to_inline_with_parallel(
+    uasset_paths: &[std::path::PathBuf],
+    usmap_path: Option<&str>,
+    parallel: bool,
+    cancel: Option<&uasset_toolkit::CancellationToken>,
+) -> TextureBatchResult {
     if uasset_paths.is_empty() {
         info!("[C#] No textures to process, returning early");
         return Ok((0, 0, 0, Vec::new()));
     }
     
-    // Get USMAP path from environment variable
-    let usmap_path = std::env::var("USMAP_PATH").ok();
     info!("[C#] Batch stripping mipmaps for {} textures using global UAssetTool singleton (parallel={}, USMAP: {:?})", 
           uasset_paths.len(), parallel, usmap_path);
     
@@ -177,7 +186,8 @@ pub fn batch_convert_textures_to_inline_with_parallel(uasset_paths: &[std::path:
     
     // Use the global singleton batch function with parallel option
     // This is much faster than creating a new process for each file
-    match uasset_toolkit::batch_strip_mipmaps_native_parallel(&file_paths, usmap_path.as_deref(), parallel) {
+    let options = uasset_toolkit::RequestOptions { timeout: None, cancel: cancel.cloned() };
+    match uasset_toolkit::batch_strip_mipmaps_native_parallel(&file_paths, usmap_path, parallel, &options) {
         Ok((success_count, skip_count, error_count, processed_files)) => {
             info!("[C#] Batch complete: {} stripped, {} skipped, {} errors", success_count, skip_count, error_count);
             Ok((success_count, skip_count, error_count, processed_files))
@@ -193,18 +203,18 @@ pub fn batch_convert_textures_to_inline_with_parallel(uasset_paths: &[std::path:
 /// (Legacy function - kept for compatibility but prefer convert_texture_to_inline)
 #[allow(dead_code)]
 pub fn process_texture_with_uasset_api(uasset_path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
-    use uasset_toolkit::UAssetToolkitSync;
+    use uasset_toolkit::get_global_toolkit;
     
     info!("Processing texture with UAssetAPI toolkit: {:?}", uasset_path);
     
-    match UAssetToolkitSync::new(None) {
+    match get_global_toolkit() {
         Ok(toolkit) => {
             let path_str = uasset_path.to_string_lossy();
             
-            match toolkit.is_texture_uasset(&path_str) {
+            match toolkit.is_texture_uasset(&path_str, &RequestOptions::default()) {
                 Ok(is_texture) => {
                     if is_texture {
-                        match toolkit.set_no_mipmaps(&path_str) {
+                        match toolkit.set_no_mipmaps(&path_str, &RequestOptions::default()) {
                             Ok(()) => {
                                 info!("UAssetAPI toolkit successfully set NoMipmaps: {:?}", uasset_path);
                                 return Ok(true);
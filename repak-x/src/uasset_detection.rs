@@ -1,13 +1,49 @@
 //! UAsset detection using UAssetAPI (via UAssetTool)
 //! 
-//! All detection is done via UAssetAPI - no heuristic fallbacks.
-//! If UAssetAPI fails (e.g., missing USMAP), detection returns false.
+//! Export classes are read from the package headers in-process first
+//! (`uasset_toolkit::package`), which needs neither UAssetTool nor a USMAP.
+//! Only when a header can't be parsed (e.g. the file isn't on disk) is the
+//! batch handed to UAssetAPI. If UAssetAPI fails too, detection returns false.
 //!
 //! Uses the global UAssetToolkit singleton for optimal performance -
 //! the UAssetTool process is started once and reused for all operations.
 
-use log::info;
-use uasset_toolkit::get_global_toolkit;
+use log::{debug, info};
+use uasset_toolkit::{get_global_toolkit, RequestOptions};
+use uasset_toolkit::package::PackageHeader;
+
+use crate::game_profile;
+
+const SKELETAL_MESH_CLASSES: &[&str] = &["SkeletalMesh"];
+const STATIC_MESH_CLASSES: &[&str] = &["StaticMesh"];
+const TEXTURE_CLASSES: &[&str] = &["Texture2D"];
+const BLUEPRINT_CLASSES: &[&str] = &[
+    "BlueprintGeneratedClass",
+    "AnimBlueprintGeneratedClass",
+    "WidgetBlueprintGeneratedClass",
+];
+
+/// Check the export classes of `uasset_files` with the native header parser.
+/// Returns None if any header couldn't be read, so the caller can fall back to UAssetAPI.
+fn detect_export_class_natively(uasset_files: &[String], classes: &[&str]) -> Option<bool> {
+    let version = game_profile::active().package_version;
+    let mut found = false;
+    for file in uasset_files {
+        match PackageHeader::read(std::path::Path::new(file), version) {
+            Ok(header) => {
+                if header.has_export_of_class(classes) {
+                    debug!("[Detection] {} exports {:?}", file, header.export_classes().collect::<Vec<_>>());
+                    found = true;
+                }
+            }
+            Err(e) => {
+                debug!("[Detection] Native header parse failed for {}: {}", file, e);
+                return None;
+            }
+        }
+    }
+    Some(found)
+}
 
 /// Detects SKELETAL mesh files using UAssetAPI batch detection
 /// Async version for use in Tauri commands
@@ -22,6 +58,11 @@ pub async fn detect_mesh_files_async(mod_contents: &[String]) -> bool {
         return false;
     }
     
+    if let Some(found) = detect_export_class_natively(&uasset_files, SKELETAL_MESH_CLASSES) {
+        info!("[Detection] SkeletalMesh {} (native header scan)", if found { "found" } else { "not found" });
+        return found;
+    }
+    
     // Use global UAssetToolkit singleton for batch detection
     match get_global_toolkit() {
         Ok(toolkit) => {
@@ -34,7 +75,7 @@ pub async fn detect_mesh_files_async(mod_contents: &[String]) -> bool {
             }
             
             info!("[Detection] Calling batch_detect_skeletal_mesh...");
-            match toolkit.batch_detect_skeletal_mesh(&uasset_files) {
+            match toolkit.batch_detect_skeletal_mesh(&uasset_files, &RequestOptions::default()) {
                 Ok(true) => {
                     info!("[Detection] ✓ FOUND SkeletalMesh (UAssetAPI returned true)");
                     return true;
@@ -62,6 +103,7 @@ pub async fn detect_mesh_files_async(mod_contents: &[String]) -> bool {
 /// Detects texture files that need the texture fix (Texture2D with .ubulk companion)
 /// Uses UAssetAPI to find Texture2D assets, then checks if they have a matching .ubulk file
 /// Async version for use in Tauri commands
+#[allow(dead_code)]
 pub async fn detect_texture_files_async(mod_contents: &[String]) -> bool {
     info!("[Detection] Texture detection received {} files to check", mod_contents.len());
     
@@ -114,13 +156,18 @@ pub async fn detect_texture_files_async(mod_contents: &[String]) -> bool {
     
     info!("[Detection] Found {} uassets with matching .ubulk, batch checking for Texture2D", uasset_files_with_ubulk.len());
     
+    if let Some(found) = detect_export_class_natively(&uasset_files_with_ubulk, TEXTURE_CLASSES) {
+        info!("[Detection] Texture2D with matching .ubulk {} (native header scan)", if found { "found" } else { "not found" });
+        return found;
+    }
+    
     // Use global UAssetToolkit singleton with TRUE batch detection (all files in ONE request)
     match get_global_toolkit() {
         Ok(toolkit) => {
             info!("[Detection] Using global UAssetToolkit singleton");
             
             // Batch check all candidates at once - if any is a texture, we need the fix
-            match toolkit.batch_detect_texture(&uasset_files_with_ubulk) {
+            match toolkit.batch_detect_texture(&uasset_files_with_ubulk, &RequestOptions::default()) {
                 Ok(true) => {
                     info!("[Detection] FOUND Texture2D with matching .ubulk - texture fix ENABLED");
                     return true;
@@ -156,12 +203,17 @@ pub async fn detect_static_mesh_files_async(mod_contents: &[String]) -> bool {
         return false;
     }
     
+    if let Some(found) = detect_export_class_natively(&uasset_files, STATIC_MESH_CLASSES) {
+        info!("[Detection] StaticMesh {} (native header scan)", if found { "found" } else { "not found" });
+        return found;
+    }
+    
     // Use global UAssetToolkit singleton for batch detection
     match get_global_toolkit() {
         Ok(toolkit) => {
             info!("[Detection] Using global UAssetToolkit singleton");
             info!("[Detection] Using UAssetAPI batch detection for StaticMesh");
-            match toolkit.batch_detect_static_mesh(&uasset_files) {
+            match toolkit.batch_detect_static_mesh(&uasset_files, &RequestOptions::default()) {
                 Ok(true) => {
                     info!("[Detection] FOUND StaticMesh (UAssetAPI)");
                     return true;
@@ -199,10 +251,15 @@ pub async fn detect_blueprint_files_async(mod_contents: &[String]) -> bool {
         return false;
     }
     
+    if let Some(found) = detect_export_class_natively(&uasset_files, BLUEPRINT_CLASSES) {
+        info!("[Detection] Blueprint {} (native header scan)", if found { "found" } else { "not found" });
+        return found;
+    }
+    
     // Use global UAssetToolkit singleton for batch detection
     if let Ok(toolkit) = get_global_toolkit() {
         info!("[Detection] Using global UAssetToolkit singleton for Blueprint");
-        match toolkit.batch_detect_blueprint(&uasset_files) {
+        match toolkit.batch_detect_blueprint(&uasset_files, &RequestOptions::default()) {
             Ok(true) => {
                 info!("[Detection] FOUND Blueprint (UAssetAPI)");
                 return true;
@@ -234,10 +291,14 @@ pub fn detect_mesh_files(mod_contents: &[String]) -> bool {
         return false;
     }
     
+    if let Some(found) = detect_export_class_natively(&uasset_files, SKELETAL_MESH_CLASSES) {
+        return found;
+    }
+    
     // Use global singleton with TRUE batch detection (all files in ONE request)
     match uasset_toolkit::get_global_toolkit() {
         Ok(toolkit) => {
-            match toolkit.batch_detect_skeletal_mesh(&uasset_files) {
+            match toolkit.batch_detect_skeletal_mesh(&uasset_files, &RequestOptions::default()) {
                 Ok(result) => result,
                 Err(_) => false,
             }
@@ -248,8 +309,8 @@ pub fn detect_mesh_files(mod_contents: &[String]) -> bool {
 
 /// Detects texture files that need the texture fix (Texture2D with .ubulk companion)
 /// Uses UAssetAPI to find Texture2D assets, then checks if they have a matching .ubulk file
-/// Sync version for use in install_mod.rs
-pub fn detect_texture_files(mod_contents: &[String]) -> bool {
+/// Sync version for use in install_mod.rs; `options` carries the install batch's cancellation
+pub fn detect_texture_files(mod_contents: &[String], options: &RequestOptions) -> bool {
     // Collect all .ubulk file stems (without extension) for quick lookup
     let ubulk_stems: std::collections::HashSet<String> = mod_contents.iter()
         .filter(|f| f.to_lowercase().ends_with(".ubulk"))
@@ -282,10 +343,14 @@ pub fn detect_texture_files(mod_contents: &[String]) -> bool {
         return false;
     }
     
+    if let Some(found) = detect_export_class_natively(&uasset_files_with_ubulk, TEXTURE_CLASSES) {
+        return found;
+    }
+    
     // Use global singleton with TRUE batch detection (all candidates in ONE request)
     match uasset_toolkit::get_global_toolkit() {
         Ok(toolkit) => {
-            match toolkit.batch_detect_texture(&uasset_files_with_ubulk) {
+            match toolkit.batch_detect_texture(&uasset_files_with_ubulk, options) {
                 Ok(result) => result,
                 Err(_) => false,
             }
@@ -295,8 +360,8 @@ pub fn detect_texture_files(mod_contents: &[String]) -> bool {
 }
 
 /// Detects Static Mesh files using UAssetAPI
-/// Sync version for use in install_mod.rs
-pub fn detect_static_mesh_files(mod_contents: &[String]) -> bool {
+/// Sync version for use in install_mod.rs; `options` carries the install batch's cancellation
+pub fn detect_static_mesh_files(mod_contents: &[String], options: &RequestOptions) -> bool {
     let uasset_files: Vec<String> = mod_contents.iter()
         .filter(|f| f.to_lowercase().ends_with(".uasset"))
         .cloned()
@@ -306,10 +371,14 @@ pub fn detect_static_mesh_files(mod_contents: &[String]) -> bool {
         return false;
     }
     
+    if let Some(found) = detect_export_class_natively(&uasset_files, STATIC_MESH_CLASSES) {
+        return found;
+    }
+    
     // Use global singleton with TRUE batch detection (all files in ONE request)
     match uasset_toolkit::get_global_toolkit() {
         Ok(toolkit) => {
-            match toolkit.batch_detect_static_mesh(&uasset_files) {
+            match toolkit.batch_detect_static_mesh(&uasset_files, options) {
                 Ok(result) => result,
                 Err(_) => false,
             }
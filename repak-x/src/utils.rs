@@ -30,7 +30,7 @@ static FILENAME_CHAR_ID_REGEX: LazyLock<Regex> = LazyLock::new(|| {
 
 
 /// Result of mod characteristics detection, includes mod type and detected heroes
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ModCharacteristics {
     pub mod_type: String,
     pub heroes: Vec<String>,
@@ -59,6 +59,42 @@ impl ModCharacteristics {
     }
 }
 
+/// Extensions of the files a package is stored in
+const PACKAGE_EXTENSIONS: [&str; 5] = ["uasset", "uexp", "ubulk", "uptnl", "umap"];
+
+/// Package path of a file as listed by a pak or an IoStore container. Files outside any Content
+/// folder keep their path, made absolute.
+pub fn asset_path(file_path: &str) -> String {
+    let normalized = file_path.replace('\\', "/");
+    let parts: Vec<&str> = normalized.split('/').filter(|p| !p.is_empty() && *p != "." && *p != "..").collect();
+
+    let mounted = match parts.iter().position(|p| p.eq_ignore_ascii_case("Content")) {
+        // <Project>/Content/..., Engine/Content/... and <Project>/Plugins/.../<Plugin>/Content/...
+        Some(content) if content > 0 => {
+            let root = if parts[0].eq_ignore_ascii_case("Engine") && content == 1 {
+                "Engine"
+            } else if parts[..content].iter().any(|p| p.eq_ignore_ascii_case("Plugins")) {
+                parts[content - 1]
+            } else {
+                "Game"
+            };
+            format!("/{}/{}", root, parts[content + 1..].join("/"))
+        }
+        _ => format!("/{}", parts.join("/")),
+    };
+
+    match mounted.rsplit_once('.') {
+        Some((package, extension)) if PACKAGE_EXTENSIONS.iter().any(|e| extension.eq_ignore_ascii_case(e)) => package.to_string(),
+        _ => mounted,
+    }
+}
+
+/// Case-insensitive form of asset_path, for telling whether files of two mods replace the same
+/// asset
+pub fn asset_key(file_path: &str) -> String {
+    asset_path(file_path).to_lowercase()
+}
+
 pub fn collect_files(paths: &mut Vec<PathBuf>, dir: &Path) -> io::Result<()> {
     for entry in fs::read_dir(dir)? {
         let entry = entry?;
@@ -333,19 +369,50 @@ pub fn get_current_pak_characteristics(mod_contents: Vec<String>) -> String {
 }
 
 
-pub fn find_marvel_rivals() -> Option<PathBuf> {
+/// Per-user settings directory (`%APPDATA%/Repak-X`)
+pub fn app_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("Repak-X")
+}
+
+/// Directory for USMAP files - stored in roaming folder
+pub fn usmap_dir() -> PathBuf {
+    app_dir().join("Usmap")
+}
+
+/// Get the full path to a USMAP file by filename
+#[allow(dead_code)]
+pub fn get_usmap_full_path(usmap_filename: &str) -> Option<PathBuf> {
+    if usmap_filename.is_empty() {
+        return None;
+    }
+    
+    let usmap_path = usmap_dir().join(usmap_filename);
+    if usmap_path.exists() {
+        Some(usmap_path)
+    } else {
+        None
+    }
+}
+
+/// Finds the active game profile's install root in the Steam libraries
+#[allow(dead_code)]
+pub fn find_game_root() -> Option<PathBuf> {
+    let profile = crate::game_profile::active();
     let shit = get_steam_library_paths();
     if shit.is_empty() {
         return None;
     }
 
     for lib in shit {
-        let path = lib.join("steamapps/common/MarvelRivals/MarvelGame/Marvel/Content/Paks");
-        if path.exists() {
-            return Some(path);
+        let root = lib.join("steamapps/common").join(&profile.install_dir);
+        // The mods folder itself may not exist yet, but the folder it lives in (Paks) must
+        if profile.mods_path(&root).parent().is_some_and(|p| p.exists()) {
+            return Some(root);
         }
     }
-    println!("Marvel Rivals not found.");
+    println!("{} not found.", profile.name);
     None
 }
 
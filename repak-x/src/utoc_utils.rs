@@ -1,3 +1,4 @@
+use crate::game_profile;
 use std::path::Path;
 
 // Simplified file entry for mod file table display
@@ -22,10 +23,11 @@ pub fn read_utoc(utoc_path: &Path) -> Vec<UtocFileEntry> {
 
 pub fn try_read_utoc(utoc_path: &Path) -> Result<Vec<UtocFileEntry>, String> {
     // Use UAssetTool via uasset_toolkit to list IoStore files
-    // Pass the Marvel Rivals AES key explicitly so obfuscated (encrypted) containers can be read
+    // Pass the game's AES key explicitly so obfuscated (encrypted) containers can be read
+    let profile = game_profile::active();
     let result = uasset_toolkit::list_iostore_files(
         utoc_path.to_string_lossy().as_ref(),
-        Some("0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74"),
+        profile.primary_key_hex().as_deref(),
     ).map_err(|e| format!("Failed to read utoc: {}", e))?;
     
     // Convert to UtocFileEntry format, normalizing paths to remove /../ patterns
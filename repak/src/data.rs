@@ -55,6 +55,71 @@ pub(crate) fn decrypt(key: &super::Key, bytes: &mut [u8]) -> Result<(), super::E
     }
 }
 
+/// Uncompressed size of each compression block, matching Unreal's default so partial reads can
+/// be served one block at a time.
+///
+/// Block sizes must fit into the encoded entry flags (a multiple of 2 KiB up to 124 KiB) or they
+/// are stored out of line, which older Unreal Engine versions fail to read.
+pub const DEFAULT_COMPRESSION_BLOCK_SIZE: u32 = 0x10000;
+
+/// Largest block size the encoded entry flags hold; 0x3f in the 6 bit field marks an out of line size
+pub const MAX_INLINE_COMPRESSION_BLOCK_SIZE: u32 = 0x3e << 11;
+
+/// Encoder settings applied when compressing entry data
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionOptions {
+    #[cfg(feature = "oodle")]
+    pub oodle_compressor: oodle_loader::Compressor,
+    #[cfg(feature = "oodle")]
+    pub oodle_level: oodle_loader::CompressionLevel,
+    block_sizes: [u32; <Compression as strum::VariantNames>::VARIANTS.len()],
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "oodle")]
+            oodle_compressor: oodle_loader::Compressor::Mermaid,
+            #[cfg(feature = "oodle")]
+            oodle_level: oodle_loader::CompressionLevel::Normal,
+            block_sizes: [DEFAULT_COMPRESSION_BLOCK_SIZE;
+                <Compression as strum::VariantNames>::VARIANTS.len()],
+        }
+    }
+}
+
+impl CompressionOptions {
+    /// Uncompressed block size entries are split into when compressed with `compression`
+    pub fn block_size(&self, compression: Compression) -> u32 {
+        self.block_sizes[compression as usize]
+    }
+    /// Set the block size for `compression`. It must fit into the encoded entry flags; see
+    /// [`set_out_of_line_block_size`](Self::set_out_of_line_block_size) for anything else.
+    pub fn set_block_size(
+        &mut self,
+        compression: Compression,
+        block_size: u32,
+    ) -> Result<(), Error> {
+        if block_size % 0x800 != 0 || block_size > MAX_INLINE_COMPRESSION_BLOCK_SIZE {
+            return Err(Error::BlockSize(block_size));
+        }
+        self.set_out_of_line_block_size(compression, block_size)
+    }
+    /// Set any non-zero block size for `compression`. Sizes that don't fit into the encoded entry
+    /// flags are stored out of line, which older Unreal Engine versions fail to read.
+    pub fn set_out_of_line_block_size(
+        &mut self,
+        compression: Compression,
+        block_size: u32,
+    ) -> Result<(), Error> {
+        if block_size == 0 {
+            return Err(Error::BlockSize(block_size));
+        }
+        self.block_sizes[compression as usize] = block_size;
+        Ok(())
+    }
+}
+
 pub struct PartialEntry<D: AsRef<[u8]>> {
     compression: Option<Compression>,
     compressed_size: u64,
@@ -192,6 +257,7 @@ impl<D: AsRef<[u8]>> PartialEntry<D> {
 
 pub(crate) fn build_partial_entry<D>(
     allowed_compression: &[Compression],
+    #[allow(unused)] options: &CompressionOptions,
     data: D,
     #[allow(unused)] key: &super::Key,
     path: &str,
@@ -223,11 +289,11 @@ where
         Some(compression) if uncompressed_size > 0 => {
             // https://github.com/EpicGames/UnrealEngine/commit/3aad0ff7976be1073005dca2c1282af548b45d89
             // Block size must fit into flags field or it may cause unreadable paks for earlier Unreal Engine versions
-            compression_block_size = 0x10000;
+            compression_block_size = options.block_size(compression);
             let mut compressed_data = vec![];
             let mut blocks = vec![];
             for chunk in data.as_ref().chunks(compression_block_size as usize) {
-                let mut data = compress(compression, chunk)?;
+                let mut data = compress(compression, options, chunk)?;
                 if encrypted {
                     pad_zeros_to_alignment(&mut data, 16);
                 }
@@ -292,7 +358,11 @@ where
 }
 
 #[cfg(feature = "compression")]
-fn compress(compression: Compression, data: &[u8]) -> Result<Vec<u8>> {
+fn compress(
+    compression: Compression,
+    #[allow(unused)] options: &CompressionOptions,
+    data: &[u8],
+) -> Result<Vec<u8>> {
     use std::io::Write;
 
     let compressed = match compression {
@@ -317,8 +387,8 @@ fn compress(compression: Compression, data: &[u8]) -> Result<Vec<u8>> {
             {
                 oodle_loader::oodle().unwrap().compress(
                     data.as_ref(),
-                    oodle_loader::Compressor::Mermaid,
-                    oodle_loader::CompressionLevel::Normal,
+                    options.oodle_compressor,
+                    options.oodle_level,
                 )?
             }
         }
@@ -1,4 +1,4 @@
-use crate::{data::build_partial_entry, Error, Hash};
+use crate::{data::build_partial_entry, CompressionOptions, Error, Hash};
 
 use super::{ext::BoolExt, ext::ReadExt, Compression, Version, VersionMajor};
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
@@ -98,16 +98,18 @@ impl Entry {
         size
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn write_file<W: io::Write + io::Seek>(
         writer: &mut W,
         version: Version,
         compression_slots: &mut Vec<Option<Compression>>,
         allowed_compression: &[Compression],
+        options: &CompressionOptions,
         data: &[u8],
         #[allow(unused)] key: &super::Key,
         path: &str,
     ) -> Result<Self, Error> {
-        let partial_entry = build_partial_entry(allowed_compression, data, key, path)?;
+        let partial_entry = build_partial_entry(allowed_compression, options, data, key, path)?;
         let stream_position = writer.stream_position()?;
         let entry = partial_entry.build_entry(version, compression_slots, stream_position)?;
         entry.write(writer, version, crate::entry::EntryLocation::Data)?;
@@ -61,6 +61,9 @@ pub enum Error {
     #[error("Input is not a directory: \"{0}\"")]
     InputNotADirectory(String),
 
+    #[error("compression block size {0:#x} must be a non-zero multiple of 2 KiB up to 124 KiB, or set out of line")]
+    BlockSize(u32),
+
     #[error("{0} decompression failed")]
     DecompressionFailed(Compression),
 
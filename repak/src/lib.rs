@@ -7,7 +7,17 @@ mod footer;
 mod pak;
 pub mod utils;
 
-pub use {data::PartialEntry, error::*, pak::*};
+pub use {
+    data::{
+        CompressionOptions, PartialEntry, DEFAULT_COMPRESSION_BLOCK_SIZE,
+        MAX_INLINE_COMPRESSION_BLOCK_SIZE,
+    },
+    error::*,
+    pak::*,
+};
+
+#[cfg(feature = "oodle")]
+pub use oodle_loader::{CompressionLevel as OodleLevel, Compressor as OodleCompressor};
 
 pub const MAGIC: u32 = 0x5A6F12E1;
 
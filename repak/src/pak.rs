@@ -1,6 +1,6 @@
 use crate::data::{build_partial_entry, pad_length};
 use crate::entry::Entry;
-use crate::{Compression, Error, PartialEntry};
+use crate::{Compression, CompressionOptions, Error, PartialEntry};
 
 use super::ext::{ReadExt, WriteExt};
 use super::{Version, VersionMajor};
@@ -20,6 +20,7 @@ impl std::fmt::Debug for Hash {
 pub struct PakBuilder {
     key: super::Key,
     allowed_compression: Vec<Compression>,
+    compression_options: CompressionOptions,
 }
 
 impl Default for PakBuilder {
@@ -33,6 +34,7 @@ impl PakBuilder {
         Self {
             key: Default::default(),
             allowed_compression: Default::default(),
+            compression_options: Default::default(),
         }
     }
     #[cfg(feature = "encryption")]
@@ -45,15 +47,46 @@ impl PakBuilder {
         self.allowed_compression = compression.into_iter().collect();
         self
     }
+    /// Uncompressed size of the blocks entries compressed with `compression` are split into.
+    /// Fails unless it fits into the encoded entry flags, see [`CompressionOptions::set_block_size`].
+    #[cfg(feature = "compression")]
+    pub fn block_size(
+        mut self,
+        compression: Compression,
+        block_size: u32,
+    ) -> Result<Self, super::Error> {
+        self.compression_options
+            .set_block_size(compression, block_size)?;
+        Ok(self)
+    }
+    /// Like [`block_size`](Self::block_size), also allowing sizes stored out of line that older
+    /// Unreal Engine versions can't read
+    #[cfg(feature = "compression")]
+    pub fn out_of_line_block_size(
+        mut self,
+        compression: Compression,
+        block_size: u32,
+    ) -> Result<Self, super::Error> {
+        self.compression_options
+            .set_out_of_line_block_size(compression, block_size)?;
+        Ok(self)
+    }
+    /// Oodle compressor and level used for entries compressed with [`Compression::Oodle`]
+    #[cfg(feature = "oodle")]
+    pub fn oodle(mut self, compressor: super::OodleCompressor, level: super::OodleLevel) -> Self {
+        self.compression_options.oodle_compressor = compressor;
+        self.compression_options.oodle_level = level;
+        self
+    }
     pub fn reader<R: Read + Seek>(self, reader: &mut R) -> Result<PakReader, super::Error> {
-        PakReader::new_any_inner(reader, self.key)
+        PakReader::new_any_inner(reader, self.key, self.compression_options)
     }
     pub fn reader_with_version<R: Read + Seek>(
         self,
         reader: &mut R,
         version: super::Version,
     ) -> Result<PakReader, super::Error> {
-        PakReader::new_inner(reader, version, self.key)
+        PakReader::new_inner(reader, version, self.key, self.compression_options)
     }
     pub fn writer<W: Write + Seek>(
         self,
@@ -69,6 +102,7 @@ impl PakBuilder {
             mount_point,
             path_hash_seed,
             self.allowed_compression,
+            self.compression_options,
         )
     }
 }
@@ -77,6 +111,8 @@ impl PakBuilder {
 pub struct PakReader {
     pak: Pak,
     key: super::Key,
+    /// Used by [`PakReader::into_pakwriter`] for entries added afterwards
+    compression_options: CompressionOptions,
 }
 
 #[derive(Debug)]
@@ -85,6 +121,7 @@ pub struct PakWriter<W: Write + Seek> {
     writer: W,
     key: super::Key,
     allowed_compression: Vec<Compression>,
+    compression_options: CompressionOptions,
 }
 
 #[derive(Debug, Clone)]
@@ -151,13 +188,20 @@ impl PakReader {
     fn new_any_inner<R: Read + Seek>(
         reader: &mut R,
         key: super::Key,
+        compression_options: CompressionOptions,
     ) -> Result<Self, super::Error> {
         use std::fmt::Write;
         let mut log = "\n".to_owned();
 
         for ver in Version::iter() {
             match Pak::read(&mut *reader, ver, &key) {
-                Ok(pak) => return Ok(Self { pak, key }),
+                Ok(pak) => {
+                    return Ok(Self {
+                        pak,
+                        key,
+                        compression_options,
+                    })
+                }
                 Err(err) => writeln!(log, "trying version {} failed: {}", ver, err)?,
             }
         }
@@ -168,8 +212,13 @@ impl PakReader {
         reader: &mut R,
         version: super::Version,
         key: super::Key,
+        compression_options: CompressionOptions,
     ) -> Result<Self, super::Error> {
-        Pak::read(reader, version, &key).map(|pak| Self { pak, key })
+        Pak::read(reader, version, &key).map(|pak| Self {
+            pak,
+            key,
+            compression_options,
+        })
     }
 
     pub fn version(&self) -> super::Version {
@@ -232,6 +281,8 @@ impl PakReader {
     pub fn compression(&self) -> Vec<Compression> {
         self.pak.compression.iter().filter_map(|c| *c).collect()
     }
+    /// Append to the pak. New entries may use the compression methods already in it and are
+    /// compressed with the options of the [`PakBuilder`] this reader was created by.
     pub fn into_pakwriter<W: Write + Seek>(
         self,
         mut writer: W,
@@ -239,6 +290,7 @@ impl PakReader {
         writer.seek(io::SeekFrom::Start(self.pak.index_offset.unwrap()))?;
         Ok(PakWriter {
             allowed_compression: self.pak.compression.iter().filter_map(|c| *c).collect(),
+            compression_options: self.compression_options,
             pak: self.pak,
             key: self.key,
             writer,
@@ -255,12 +307,14 @@ impl<W: Write + Seek> PakWriter<W> {
         mount_point: String,
         path_hash_seed: Option<u64>,
         allowed_compression: Vec<Compression>,
+        compression_options: CompressionOptions,
     ) -> Self {
         PakWriter {
             pak: Pak::new(version, mount_point, path_hash_seed),
             writer,
             key,
             allowed_compression,
+            compression_options,
         }
     }
 
@@ -285,6 +339,7 @@ impl<W: Write + Seek> PakWriter<W> {
                 } else {
                     &[]
                 },
+                &self.compression_options,
                 data.as_ref(),
                 &self.key,
                 &root_path(&self.pak.mount_point, path),
@@ -297,6 +352,7 @@ impl<W: Write + Seek> PakWriter<W> {
     pub fn entry_builder(&self) -> EntryBuilder {
         EntryBuilder {
             allowed_compression: self.allowed_compression.clone(),
+            compression_options: self.compression_options,
             key: self.key.clone(),
             mount_point: self.pak.mount_point.clone(),
         }
@@ -357,6 +413,7 @@ impl AsRef<[u8]> for Data<'_> {
 #[derive(Clone)]
 pub struct EntryBuilder {
     allowed_compression: Vec<Compression>,
+    compression_options: CompressionOptions,
     #[allow(unused)]
     key: super::Key,
     mount_point: String,
@@ -374,6 +431,7 @@ impl EntryBuilder {
             .unwrap_or_default();
         build_partial_entry(
             compression,
+            &self.compression_options,
             data,
             &self.key,
             &root_path(&self.mount_point, path),
@@ -826,4 +884,235 @@ mod test {
         assert_eq!(split_path_child("/"), None);
         assert_eq!(split_path_child(""), None);
     }
+
+    /// Compressible but non-repeating data spanning several blocks
+    #[cfg(feature = "compression")]
+    fn block_test_data(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| ((i / 7) as u32).wrapping_mul(2654435761).to_le_bytes()[3] & 0x1f)
+            .collect()
+    }
+
+    /// Writes `data` with `compression`, then checks every block in the resulting table decodes
+    /// on its own to the matching slice of the input as well as through `PakReader::get`
+    #[cfg(feature = "compression")]
+    fn check_block_table(
+        compression: Compression,
+        block_size: Option<u32>,
+        decompress: impl Fn(&[u8], usize) -> Vec<u8>,
+    ) {
+        let data = block_test_data(0x10000 * 3 + 0x1234);
+
+        let mut builder = PakBuilder::new().compression([compression]);
+        if let Some(block_size) = block_size {
+            builder = if block_size <= crate::MAX_INLINE_COMPRESSION_BLOCK_SIZE {
+                builder.block_size(compression, block_size)
+            } else {
+                builder.out_of_line_block_size(compression, block_size)
+            }
+            .unwrap();
+        }
+        let block_size = block_size.unwrap_or(crate::DEFAULT_COMPRESSION_BLOCK_SIZE) as usize;
+
+        let mut writer = builder.writer(
+            io::Cursor::new(vec![]),
+            Version::V11,
+            "../../../".to_string(),
+            Some(0x205C5A7D),
+        );
+        writer.write_file("write_file.bin", true, &data).unwrap();
+        let entry_builder = writer.entry_builder();
+        let entry = entry_builder
+            .build_entry(true, data.clone(), "build_entry.bin")
+            .unwrap();
+        writer
+            .write_entry("build_entry.bin".to_string(), entry)
+            .unwrap();
+        let bytes = writer.write_index().unwrap().into_inner();
+
+        let mut reader = io::Cursor::new(bytes.as_slice());
+        let pak = PakBuilder::new().reader(&mut reader).unwrap();
+        assert_eq!(pak.compression(), vec![compression]);
+
+        for path in ["write_file.bin", "build_entry.bin"] {
+            let entry = pak.get_file_entry(path).unwrap();
+            assert_eq!(entry.compression_block_size as usize, block_size);
+            assert_eq!(entry.uncompressed as usize, data.len());
+
+            let blocks = entry.blocks.as_ref().unwrap();
+            let chunks = data.chunks(block_size).collect::<Vec<_>>();
+            assert_eq!(blocks.len(), chunks.len());
+
+            for (block, chunk) in blocks.iter().zip(chunks) {
+                // chunk offsets are relative to the entry record since v5
+                let start = (entry.offset + block.start) as usize;
+                let end = (entry.offset + block.end) as usize;
+                assert_eq!(decompress(&bytes[start..end], chunk.len()), chunk);
+            }
+
+            assert_eq!(pak.get(path, &mut reader).unwrap(), data);
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    fn decompress_lz4(block: &[u8], len: usize) -> Vec<u8> {
+        lz4_flex::block::decompress(block, len).unwrap()
+    }
+
+    #[cfg(feature = "compression")]
+    fn decompress_zstd(block: &[u8], _len: usize) -> Vec<u8> {
+        zstd::stream::decode_all(block).unwrap()
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_lz4_blocks() {
+        check_block_table(Compression::LZ4, None, decompress_lz4);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_zstd_blocks() {
+        check_block_table(Compression::Zstd, None, decompress_zstd);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_custom_block_size() {
+        check_block_table(Compression::LZ4, Some(0x8000), decompress_lz4);
+        check_block_table(Compression::Zstd, Some(0x20000), decompress_zstd);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_invalid_block_size() {
+        let builder = || PakBuilder::new().compression([Compression::Zstd]);
+        assert!(builder().block_size(Compression::Zstd, 0).is_err());
+        assert!(builder()
+            .out_of_line_block_size(Compression::Zstd, 0)
+            .is_err());
+        // Doesn't fit into the entry flags without opting in
+        assert!(builder().block_size(Compression::Zstd, 0x20000).is_err());
+        assert!(builder().block_size(Compression::Zstd, 0x8001).is_err());
+        assert!(builder()
+            .block_size(Compression::Zstd, crate::MAX_INLINE_COMPRESSION_BLOCK_SIZE)
+            .is_ok());
+    }
+
+    /// Writes `data` as "written.bin" with `writer`, then appends it as "appended.bin" through
+    /// [`PakReader::into_pakwriter`] of a reader created by `reader`
+    #[cfg(feature = "compression")]
+    fn write_and_append(
+        writer: PakBuilder,
+        reader: PakBuilder,
+        data: &[u8],
+    ) -> (Vec<u8>, PakReader) {
+        let mut pak_writer = writer.writer(
+            io::Cursor::new(vec![]),
+            Version::V11,
+            "../../../".to_string(),
+            Some(0x205C5A7D),
+        );
+        pak_writer.write_file("written.bin", true, data).unwrap();
+        let mut cursor = pak_writer.write_index().unwrap();
+
+        let pak = reader.reader(&mut cursor).unwrap();
+        let mut pak_writer = pak.into_pakwriter(cursor).unwrap();
+        pak_writer.write_file("appended.bin", true, data).unwrap();
+        let bytes = pak_writer.write_index().unwrap().into_inner();
+
+        let pak = PakBuilder::new()
+            .reader(&mut io::Cursor::new(bytes.as_slice()))
+            .unwrap();
+        for path in ["written.bin", "appended.bin"] {
+            assert_eq!(
+                pak.get(path, &mut io::Cursor::new(bytes.as_slice()))
+                    .unwrap(),
+                data
+            );
+        }
+        (bytes, pak)
+    }
+
+    #[cfg(feature = "compression")]
+    fn stored_blocks<'a>(bytes: &'a [u8], pak: &PakReader, path: &str) -> Vec<&'a [u8]> {
+        let entry = pak.get_file_entry(path).unwrap();
+        entry
+            .blocks
+            .unwrap()
+            .iter()
+            .map(|block| {
+                &bytes[(entry.offset + block.start) as usize..(entry.offset + block.end) as usize]
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_into_pakwriter_keeps_compression_options() {
+        let data = block_test_data(0x10000 * 2 + 0x1234);
+        let (_, pak) = write_and_append(
+            PakBuilder::new().compression([Compression::Zstd]),
+            PakBuilder::new()
+                .block_size(Compression::Zstd, 0x8000)
+                .unwrap(),
+            &data,
+        );
+        let block_size = |path| pak.get_file_entry(path).unwrap().compression_block_size;
+        assert_eq!(
+            block_size("written.bin"),
+            crate::DEFAULT_COMPRESSION_BLOCK_SIZE
+        );
+        assert_eq!(block_size("appended.bin"), 0x8000);
+    }
+
+    #[cfg(feature = "oodle")]
+    #[test]
+    fn test_oodle_options() {
+        use oodle_loader::{CompressionLevel, Compressor};
+
+        let data = block_test_data(0x10000 * 2 + 0x1234);
+        let (bytes, pak) = write_and_append(
+            PakBuilder::new()
+                .compression([Compression::Oodle])
+                .oodle(Compressor::Kraken, CompressionLevel::Optimal2),
+            PakBuilder::new().oodle(Compressor::Kraken, CompressionLevel::Optimal2),
+            &data,
+        );
+
+        let oodle = oodle_loader::oodle().unwrap();
+        let chunks = data
+            .chunks(crate::DEFAULT_COMPRESSION_BLOCK_SIZE as usize)
+            .collect::<Vec<_>>();
+        // Make sure the chosen settings are distinguishable from the defaults at all
+        assert_ne!(
+            oodle
+                .compress(chunks[0], Compressor::Kraken, CompressionLevel::Optimal2)
+                .unwrap(),
+            oodle
+                .compress(chunks[0], Compressor::Mermaid, CompressionLevel::Normal)
+                .unwrap()
+        );
+        for path in ["written.bin", "appended.bin"] {
+            let blocks = stored_blocks(&bytes, &pak, path);
+            assert_eq!(blocks.len(), chunks.len());
+            for (block, chunk) in blocks.iter().zip(&chunks) {
+                let expected = oodle
+                    .compress(chunk, Compressor::Kraken, CompressionLevel::Optimal2)
+                    .unwrap();
+                assert_eq!(*block, expected.as_slice(), "{path}");
+            }
+        }
+    }
+
+    #[cfg(feature = "oodle")]
+    #[test]
+    fn test_oodle_blocks() {
+        check_block_table(Compression::Oodle, None, |block, len| {
+            let mut out = vec![0; len];
+            let written = oodle_loader::oodle().unwrap().decompress(block, &mut out);
+            assert_eq!(written as usize, len);
+            out
+        });
+    }
 }
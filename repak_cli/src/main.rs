@@ -0,0 +1,393 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+use path_clean::PathClean;
+use path_slash::PathExt;
+use rayon::prelude::*;
+use repak::utils::AesKey;
+use repak::{Compression, PakBuilder, PakReader, Version};
+use sha2::{Digest, Sha256};
+
+#[derive(Parser, Debug)]
+#[command(name = "repak", author, version, about = "Inspect, unpack and create Unreal Engine .pak files")]
+struct Args {
+    /// 256 bit AES encryption key as base64 or hex string if the pak is encrypted; `pack` encrypts the index with it
+    #[arg(short, long, global = true)]
+    aes_key: Option<AesKey>,
+
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// Print .pak info
+    Info {
+        /// Input .pak path
+        input: PathBuf,
+    },
+    /// List .pak files
+    List {
+        /// Input .pak path
+        input: PathBuf,
+
+        /// Prefix to strip from entry paths
+        #[arg(short, long, default_value = "../../../")]
+        strip_prefix: String,
+    },
+    /// List .pak files and the SHA256 of their contents
+    HashList {
+        /// Input .pak path
+        input: PathBuf,
+
+        /// Prefix to strip from entry paths
+        #[arg(short, long, default_value = "../../../")]
+        strip_prefix: String,
+    },
+    /// Read every entry to check the pak can be fully decrypted and decompressed
+    Verify {
+        /// Input .pak path
+        input: PathBuf,
+    },
+    /// Unpack .pak files
+    Unpack {
+        /// Input .pak paths
+        #[arg(required = true)]
+        input: Vec<PathBuf>,
+
+        /// Output directory. Defaults to next to input pak
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Prefix to strip from entry paths
+        #[arg(short, long, default_value = "../../../")]
+        strip_prefix: String,
+
+        /// Verbose
+        #[arg(short, long, default_value = "false")]
+        verbose: bool,
+
+        /// Allow unpacking into an existing non-empty directory
+        #[arg(short, long, default_value = "false")]
+        force: bool,
+
+        /// Only unpack files matching these glob patterns (e.g. "**/*.uasset"); may be repeated
+        #[arg(short, long)]
+        include: Vec<String>,
+    },
+    /// Pack a directory into a .pak file
+    Pack {
+        /// Input directory
+        input: PathBuf,
+
+        /// Output .pak path. Defaults to the input directory with a .pak extension
+        output: Option<PathBuf>,
+
+        /// Mount point
+        #[arg(short, long, default_value = "../../../")]
+        mount_point: String,
+
+        /// Version
+        #[arg(long, default_value_t = Version::V11)]
+        version: Version,
+
+        /// Compression methods allowed for entries; may be repeated
+        #[arg(short, long)]
+        compression: Vec<Compression>,
+
+        /// Path hash seed for >= V10
+        #[arg(short, long, default_value = "0")]
+        path_hash_seed: u64,
+
+        /// Verbose
+        #[arg(short, long, default_value = "false")]
+        verbose: bool,
+    },
+    /// Write a single file from a .pak to stdout
+    Get {
+        /// Input .pak path
+        input: PathBuf,
+
+        /// Path of the file to get, as printed by `list`
+        file: String,
+
+        /// Prefix to strip from entry paths
+        #[arg(short, long, default_value = "../../../")]
+        strip_prefix: String,
+    },
+}
+
+fn main() -> Result<(), repak::Error> {
+    let args = Args::parse();
+    let aes_key = args.aes_key.map(|key| key.0);
+
+    match args.action {
+        Action::Info { input } => info(aes_key, input),
+        Action::List { input, strip_prefix } => list(aes_key, input, strip_prefix),
+        Action::HashList { input, strip_prefix } => hash_list(aes_key, input, strip_prefix),
+        Action::Verify { input } => verify(aes_key, input),
+        Action::Unpack { input, output, strip_prefix, verbose, force, include } => {
+            unpack(aes_key, input, output, strip_prefix, verbose, force, include)
+        }
+        Action::Pack { input, output, mount_point, version, compression, path_hash_seed, verbose } => {
+            pack(aes_key, input, output, mount_point, version, compression, path_hash_seed, verbose)
+        }
+        Action::Get { input, file, strip_prefix } => get(aes_key, input, file, strip_prefix),
+    }
+}
+
+fn open_pak(aes_key: &Option<aes::Aes256>, input: &Path) -> Result<PakReader, repak::Error> {
+    let mut builder = PakBuilder::new();
+    if let Some(key) = aes_key {
+        builder = builder.key(key.clone());
+    }
+    builder.reader(&mut BufReader::new(File::open(input)?))
+}
+
+/// Entry paths of a pak relative to `strip_prefix`, keyed by stripped path
+fn stripped_paths(pak: &PakReader, strip_prefix: &str) -> Result<BTreeMap<String, String>, repak::Error> {
+    let mount_point = PathBuf::from(pak.mount_point());
+    let prefix = Path::new(strip_prefix);
+
+    pak.files()
+        .into_iter()
+        .map(|entry| {
+            let full_path = mount_point.join(&entry);
+            let stripped = full_path.strip_prefix(prefix).map_err(|_| repak::Error::PrefixMismatch {
+                path: full_path.to_string_lossy().to_string(),
+                prefix: prefix.to_string_lossy().to_string(),
+            })?;
+            let stripped = stripped
+                .to_slash()
+                .ok_or_else(|| repak::Error::Other(format!("non UTF-8 path: {}", stripped.display())))?
+                .to_string();
+            Ok((stripped, entry))
+        })
+        .collect()
+}
+
+fn info(aes_key: Option<aes::Aes256>, input: PathBuf) -> Result<(), repak::Error> {
+    let pak = open_pak(&aes_key, &input)?;
+    println!("mount point: {}", pak.mount_point());
+    println!("version: {}", pak.version());
+    println!("version major: {}", pak.version().version_major());
+    println!("encrypted index: {}", pak.encrypted_index());
+    if let Some(guid) = pak.encryption_guid() {
+        println!("encryption guid: {:032X}", guid);
+    }
+    if let Some(seed) = pak.path_hash_seed() {
+        println!("path hash seed: {:08X}", seed);
+    }
+    let compression = pak.compression();
+    if compression.is_empty() {
+        println!("compression: none");
+    } else {
+        let names: Vec<String> = compression.iter().map(|c| c.to_string()).collect();
+        println!("compression: {}", names.join(", "));
+    }
+    println!("{} file entries", pak.files().len());
+    Ok(())
+}
+
+fn list(aes_key: Option<aes::Aes256>, input: PathBuf, strip_prefix: String) -> Result<(), repak::Error> {
+    let pak = open_pak(&aes_key, &input)?;
+    for path in stripped_paths(&pak, &strip_prefix)?.keys() {
+        println!("{path}");
+    }
+    Ok(())
+}
+
+fn hash_list(aes_key: Option<aes::Aes256>, input: PathBuf, strip_prefix: String) -> Result<(), repak::Error> {
+    let pak = open_pak(&aes_key, &input)?;
+    let paths: Vec<(String, String)> = stripped_paths(&pak, &strip_prefix)?.into_iter().collect();
+
+    let hashes = paths
+        .par_iter()
+        .map_init(
+            || File::open(&input).map(BufReader::new),
+            |reader, (path, entry)| {
+                let reader = reader.as_mut().map_err(|e| repak::Error::Other(e.to_string()))?;
+                let mut hasher = Sha256::new();
+                pak.read_file(entry, reader, &mut hasher)?;
+                Ok((path, hex::encode(hasher.finalize())))
+            },
+        )
+        .collect::<Result<Vec<_>, repak::Error>>()?;
+
+    for (path, hash) in hashes {
+        println!("{hash} {path}");
+    }
+    Ok(())
+}
+
+fn verify(aes_key: Option<aes::Aes256>, input: PathBuf) -> Result<(), repak::Error> {
+    let pak = open_pak(&aes_key, &input)?;
+    let files = pak.files();
+
+    let failures: Vec<(String, repak::Error)> = files
+        .par_iter()
+        .map_init(
+            || File::open(&input).map(BufReader::new),
+            |reader, entry| {
+                let result = match reader {
+                    Ok(reader) => pak.read_file(entry, reader, &mut io::sink()),
+                    Err(e) => Err(repak::Error::Other(e.to_string())),
+                };
+                result.err().map(|e| (entry.clone(), e))
+            },
+        )
+        .flatten()
+        .collect();
+
+    for (entry, error) in &failures {
+        eprintln!("{entry}: {error}");
+    }
+    if failures.is_empty() {
+        println!("OK: {} file entries", files.len());
+        Ok(())
+    } else {
+        Err(repak::Error::Other(format!("{} of {} entries failed to read", failures.len(), files.len())))
+    }
+}
+
+fn unpack(
+    aes_key: Option<aes::Aes256>,
+    input: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    strip_prefix: String,
+    verbose: bool,
+    force: bool,
+    include: Vec<String>,
+) -> Result<(), repak::Error> {
+    let patterns = include
+        .iter()
+        .map(|pattern| glob::Pattern::new(pattern).map_err(|e| repak::Error::Other(format!("bad include pattern {pattern:?}: {e}"))))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for input in input {
+        let pak = open_pak(&aes_key, &input)?;
+        let output = output.clone().unwrap_or_else(|| input.with_extension("")).clean();
+
+        if !force && output.exists() && output.read_dir()?.next().is_some() {
+            return Err(repak::Error::OutputNotEmpty(output.to_string_lossy().to_string()));
+        }
+
+        let entries = stripped_paths(&pak, &strip_prefix)?
+            .into_iter()
+            .filter(|(path, _)| patterns.is_empty() || patterns.iter().any(|p| p.matches(path)))
+            .map(|(path, entry)| {
+                let out_path = output.join(&path).clean();
+                if !out_path.starts_with(&output) {
+                    return Err(repak::Error::WriteOutsideOutput(out_path.to_string_lossy().to_string()));
+                }
+                Ok((entry, out_path))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        entries.par_iter().try_for_each_init(
+            || File::open(&input).map(BufReader::new),
+            |reader, (entry, out_path)| {
+                let reader = reader.as_mut().map_err(|e| repak::Error::Other(e.to_string()))?;
+                if let Some(dir) = out_path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                let mut writer = BufWriter::new(File::create(out_path)?);
+                pak.read_file(entry, reader, &mut writer)?;
+                writer.flush()?;
+                if verbose {
+                    println!("unpacked: {}", out_path.display());
+                }
+                Ok::<(), repak::Error>(())
+            },
+        )?;
+
+        println!("Unpacked {} files to {}", entries.len(), output.display());
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn pack(
+    aes_key: Option<aes::Aes256>,
+    input: PathBuf,
+    output: Option<PathBuf>,
+    mount_point: String,
+    version: Version,
+    compression: Vec<Compression>,
+    path_hash_seed: u64,
+    verbose: bool,
+) -> Result<(), repak::Error> {
+    if !input.is_dir() {
+        return Err(repak::Error::InputNotADirectory(input.to_string_lossy().to_string()));
+    }
+    let output = output.unwrap_or_else(|| input.with_extension("pak"));
+
+    let mut paths = Vec::new();
+    collect_files(&mut paths, &input)?;
+    paths.sort();
+
+    let mut builder = PakBuilder::new().compression(compression);
+    if let Some(key) = aes_key {
+        builder = builder.key(key);
+    }
+    let mut pak = builder.writer(
+        BufWriter::new(File::create(&output)?),
+        version,
+        mount_point,
+        Some(path_hash_seed),
+    );
+    let entry_builder = pak.entry_builder();
+
+    let entries = paths
+        .par_iter()
+        .map(|path| {
+            let rel = path
+                .strip_prefix(&input)
+                .expect("file not in input directory")
+                .to_slash()
+                .ok_or_else(|| repak::Error::Other(format!("non UTF-8 path: {}", path.display())))?
+                .to_string();
+            let entry = entry_builder.build_entry(true, fs::read(path)?, &rel)?;
+            Ok((rel, entry))
+        })
+        .collect::<Result<Vec<_>, repak::Error>>()?;
+
+    for (rel, entry) in entries {
+        if verbose {
+            println!("packed: {rel}");
+        }
+        pak.write_entry(rel, entry)?;
+    }
+    pak.write_index()?;
+
+    println!("Packed {} files to {}", paths.len(), output.display());
+    Ok(())
+}
+
+fn get(aes_key: Option<aes::Aes256>, input: PathBuf, file: String, strip_prefix: String) -> Result<(), repak::Error> {
+    let pak = open_pak(&aes_key, &input)?;
+    let file = file.replace('\\', "/");
+    let paths = stripped_paths(&pak, &strip_prefix)?;
+    let entry = paths.get(&file).ok_or(repak::Error::MissingEntry(file))?;
+
+    let mut reader = BufReader::new(File::open(&input)?);
+    let mut stdout = io::stdout().lock();
+    pak.read_file(entry, &mut reader, &mut stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn collect_files(paths: &mut Vec<PathBuf>, dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(paths, &path)?;
+        } else {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
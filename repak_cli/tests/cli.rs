@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+const KEY: &str = "0x0C263D8C22DCB085894899C3A3796383E9BF9DE0CBFB08C9BF2DEF2E84F29D74";
+
+fn repak(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_repak")).args(args).output().unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Directory laid out like a mod: a texture and a mesh under Content, plus a config file
+fn mod_dir(root: &Path) -> std::path::PathBuf {
+    let input = root.join("Mod");
+    let content = input.join("Game/Content");
+    fs::create_dir_all(&content).unwrap();
+    fs::write(content.join("T_Hero.uasset"), b"texture").unwrap();
+    fs::write(content.join("SM_Hero.uasset"), b"mesh").unwrap();
+    fs::write(input.join("Game/Config.ini"), b"[Core]").unwrap();
+    input
+}
+
+/// Files under `dir`, relative to it with forward slashes
+fn walk(dir: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(next) = pending.pop() {
+        for entry in fs::read_dir(next).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path.strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    files
+}
+
+#[test]
+fn pack_list_unpack_round_trip() {
+    let root = tempfile::tempdir().unwrap();
+    let input = mod_dir(root.path());
+    let pak = root.path().join("Mod.pak");
+    let out = root.path().join("out");
+    let (input, pak, out) = (input.to_str().unwrap(), pak.to_str().unwrap(), out.to_str().unwrap());
+
+    stdout(repak(&["pack", input, pak]));
+    let listed = stdout(repak(&["list", pak]));
+    assert_eq!(
+        listed.lines().collect::<Vec<_>>(),
+        ["Game/Config.ini", "Game/Content/SM_Hero.uasset", "Game/Content/T_Hero.uasset"]
+    );
+
+    stdout(repak(&["unpack", pak, "--output", out]));
+    for file in ["Game/Config.ini", "Game/Content/SM_Hero.uasset", "Game/Content/T_Hero.uasset"] {
+        assert_eq!(fs::read(Path::new(out).join(file)).unwrap(), fs::read(Path::new(input).join(file)).unwrap());
+    }
+}
+
+#[test]
+fn pack_encrypts_with_the_aes_key() {
+    let root = tempfile::tempdir().unwrap();
+    let input = mod_dir(root.path());
+    let pak = root.path().join("Mod.pak");
+    let (input, pak) = (input.to_str().unwrap(), pak.to_str().unwrap());
+
+    stdout(repak(&["--aes-key", KEY, "pack", input, pak]));
+    assert!(stdout(repak(&["--aes-key", KEY, "info", pak])).contains("encrypted index: true"));
+    assert_eq!(stdout(repak(&["--aes-key", KEY, "list", pak])).lines().count(), 3);
+    // Without the key the index can't be read
+    assert!(!repak(&["list", pak]).status.success());
+}
+
+#[test]
+fn unpack_only_writes_included_files() {
+    let root = tempfile::tempdir().unwrap();
+    let input = mod_dir(root.path());
+    let pak = root.path().join("Mod.pak");
+    let out = root.path().join("out");
+    let (input, pak, out) = (input.to_str().unwrap(), pak.to_str().unwrap(), out.to_str().unwrap());
+
+    stdout(repak(&["pack", input, pak]));
+    stdout(repak(&["unpack", pak, "--output", out, "--include", "**/*.uasset"]));
+
+    let mut unpacked: Vec<String> = walk(Path::new(out));
+    unpacked.sort();
+    assert_eq!(unpacked, ["Game/Content/SM_Hero.uasset", "Game/Content/T_Hero.uasset"]);
+}
@@ -0,0 +1,231 @@
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+use crate::process::decode_data;
+use crate::{BatchStripMipmapsResponse, ExtractIoStoreResponse, ExtractScriptObjectsResponse, IoStoreCompressionResponse};
+use crate::{IoStoreListResult, IoStoreResult, RequestOptions, Result, ToolkitError, UAssetRequest};
+
+/// Payload of a successful response (`Null` if it had none), with the lines the tool wrote to
+/// stderr meanwhile so a payload that fails to decode can still be explained
+#[derive(Debug, Default)]
+pub struct Reply {
+    pub data: serde_json::Value,
+    pub stderr: Vec<String>,
+}
+
+/// Anything that can answer UAssetTool requests: the process pool in `SyncToolkit`, or
+/// `MockToolkit` in tests. The typed helpers are provided on top of `send`, so every
+/// backend encodes requests and decodes responses the same way.
+pub trait ToolkitBackend: Send + Sync {
+    /// Send a request and return the `data` payload of its response.
+    /// Failures reported by the tool are returned as `ToolkitError::Failed`.
+    fn send(&self, request: &UAssetRequest, options: &RequestOptions) -> Result<Reply>;
+
+    /// Number of requests the backend can run at the same time
+    fn workers(&self) -> usize {
+        1
+    }
+
+    fn batch_detect_skeletal_mesh(&self, file_paths: &[String], options: &RequestOptions) -> Result<bool> {
+        typed_with(self, &UAssetRequest::BatchDetectSkeletalMesh { file_paths: file_paths.to_vec() }, options)
+    }
+
+    fn batch_detect_static_mesh(&self, file_paths: &[String], options: &RequestOptions) -> Result<bool> {
+        typed_with(self, &UAssetRequest::BatchDetectStaticMesh { file_paths: file_paths.to_vec() }, options)
+    }
+
+    fn batch_detect_texture(&self, file_paths: &[String], options: &RequestOptions) -> Result<bool> {
+        typed_with(self, &UAssetRequest::BatchDetectTexture { file_paths: file_paths.to_vec() }, options)
+    }
+
+    fn batch_detect_blueprint(&self, file_paths: &[String], options: &RequestOptions) -> Result<bool> {
+        typed_with(self, &UAssetRequest::BatchDetectBlueprint { file_paths: file_paths.to_vec() }, options)
+    }
+
+    fn is_texture_uasset(&self, file_path: &str, options: &RequestOptions) -> Result<bool> {
+        self.batch_detect_texture(&[file_path.to_string()], options)
+    }
+
+    fn strip_mipmaps_native(&self, file_path: &str, usmap_path: Option<&str>, options: &RequestOptions) -> Result<bool> {
+        self.send(&UAssetRequest::StripMipmapsNative {
+            file_path: file_path.to_string(),
+            usmap_path: usmap_path.map(|s| s.to_string()),
+        }, options)?;
+        Ok(true)
+    }
+
+    fn convert_texture(&self, file_path: &str, options: &RequestOptions) -> Result<bool> {
+        self.send(&UAssetRequest::ConvertTexture {
+            file_path: file_path.to_string(),
+        }, options)?;
+        Ok(true)
+    }
+
+    fn set_no_mipmaps(&self, file_path: &str, options: &RequestOptions) -> Result<()> {
+        self.send(&UAssetRequest::SetMipGen {
+            file_path: file_path.to_string(),
+            mip_gen: "NoMipmaps".to_string(),
+        }, options)?;
+        Ok(())
+    }
+
+    /// Returns the file paths whose texture data is stored inline
+    fn batch_has_inline_texture_data(&self, file_paths: &[String], usmap_path: Option<&str>, options: &RequestOptions) -> Result<Vec<String>> {
+        typed_with(self, &UAssetRequest::BatchHasInlineTextureData {
+            file_paths: file_paths.to_vec(),
+            usmap_path: usmap_path.map(|s| s.to_string()),
+        }, options)
+    }
+
+    /// Typed response of `batch_strip_mipmaps_native`.
+    /// With `parallel` set, the files are split across all workers and the results merged;
+    /// files of a chunk whose request failed are counted as errors. Cancelling `options.cancel`
+    /// aborts every chunk and fails the whole batch.
+    fn batch_strip_mipmaps(&self, file_paths: &[String], usmap_path: Option<&str>, parallel: bool, options: &RequestOptions) -> Result<BatchStripMipmapsResponse> {
+        let request = |files: &[String], parallel: bool| {
+            typed_with::<BatchStripMipmapsResponse, _>(self, &UAssetRequest::BatchStripMipmapsNative {
+                file_paths: files.to_vec(),
+                usmap_path: usmap_path.map(|s| s.to_string()),
+                parallel,
+            }, options)
+        };
+
+        let workers = self.workers();
+        if !parallel || workers < 2 || file_paths.len() < 2 {
+            return request(file_paths, parallel);
+        }
+
+        let chunk_size = file_paths.len().div_ceil(workers);
+        let chunks: Vec<&[String]> = file_paths.chunks(chunk_size).collect();
+        log::info!("[SyncToolkit] Stripping mipmaps of {} files on {} workers", file_paths.len(), chunks.len());
+
+        let results: Vec<(usize, Result<BatchStripMipmapsResponse>)> = std::thread::scope(|scope| {
+            // The pool already spreads the work, so each tool process handles its chunk on its own
+            let handles: Vec<_> = chunks.iter()
+                .map(|chunk| (chunk.len(), scope.spawn(|| request(chunk, false))))
+                .collect();
+            handles.into_iter()
+                .map(|(len, handle)| (len, handle.join().unwrap_or_else(|_| Err(ToolkitError::Lock("worker thread panicked".to_string())))))
+                .collect()
+        });
+
+        if options.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+            return Err(ToolkitError::Cancelled { action: "batch_strip_mipmaps_native".to_string() });
+        }
+
+        let mut merged = BatchStripMipmapsResponse::default();
+        let mut first_error = None;
+        for (len, result) in results {
+            match result {
+                Ok(response) => {
+                    merged.success_count += response.success_count;
+                    merged.skip_count += response.skip_count;
+                    merged.error_count += response.error_count;
+                    merged.results.extend(response.results);
+                }
+                Err(e) => {
+                    log::error!("[SyncToolkit] Mipmap strip chunk of {} files failed: {}", len, e);
+                    merged.error_count += len;
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) if merged.results.is_empty() && merged.success_count == 0 => Err(e),
+            _ => Ok(merged),
+        }
+    }
+
+    /// Returns (success_count, skip_count, error_count, processed_file_names)
+    fn batch_strip_mipmaps_native(&self, file_paths: &[String], usmap_path: Option<&str>, parallel: bool, options: &RequestOptions) -> Result<(usize, usize, usize, Vec<String>)> {
+        let response = self.batch_strip_mipmaps(file_paths, usmap_path, parallel, options)?;
+
+        let processed_files = response.results.iter()
+            .filter(|result| result.success && !result.skipped)
+            .filter_map(|result| Path::new(&result.path).file_stem())
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .collect();
+
+        Ok((response.success_count, response.skip_count, response.error_count, processed_files))
+    }
+
+    fn list_iostore_files(&self, file_path: &str, aes_key: Option<&str>, options: &RequestOptions) -> Result<IoStoreListResult> {
+        typed_with(self, &UAssetRequest::ListIoStoreFiles {
+            file_path: file_path.to_string(),
+            aes_key: aes_key.map(|s| s.to_string()),
+        }, options)
+    }
+
+    /// parallel: when true, uses 75% of CPU threads; when false, uses 50%
+    #[allow(clippy::too_many_arguments)]
+    fn create_mod_iostore(&self, output_path: &str, input_dir: &str, usmap_path: Option<&str>, mount_point: Option<&str>, compress: Option<bool>, aes_key: Option<&str>, parallel: bool, obfuscate: bool, options: &RequestOptions) -> Result<IoStoreResult> {
+        typed_with(self, &UAssetRequest::CreateModIoStore {
+            output_path: output_path.to_string(),
+            input_dir: input_dir.to_string(),
+            usmap_path: usmap_path.map(|s| s.to_string()),
+            mount_point: mount_point.map(|s| s.to_string()),
+            compress,
+            aes_key: aes_key.map(|s| s.to_string()),
+            parallel,
+            obfuscate,
+        }, options)
+    }
+
+    fn recompress_iostore(&self, file_path: &str, options: &RequestOptions) -> Result<()> {
+        self.send(&UAssetRequest::RecompressIoStore {
+            file_path: file_path.to_string(),
+        }, options)?;
+        Ok(())
+    }
+
+    fn extract_iostore(&self, file_path: &str, output_path: &str, aes_key: Option<&str>, options: &RequestOptions) -> Result<usize> {
+        let response: ExtractIoStoreResponse = typed_with(self, &UAssetRequest::ExtractIoStore {
+            file_path: file_path.to_string(),
+            output_path: output_path.to_string(),
+            aes_key: aes_key.map(|s| s.to_string()),
+        }, options)?;
+        Ok(response.extracted_count)
+    }
+
+    fn extract_script_objects(&self, file_path: &str, output_path: &str, options: &RequestOptions) -> Result<usize> {
+        let response: ExtractScriptObjectsResponse = typed_with(self, &UAssetRequest::ExtractScriptObjects {
+            file_path: file_path.to_string(),
+            output_path: output_path.to_string(),
+        }, options)?;
+        Ok(response.count)
+    }
+
+    fn is_iostore_compressed(&self, file_path: &str, options: &RequestOptions) -> Result<bool> {
+        let response: IoStoreCompressionResponse = typed_with(self, &UAssetRequest::IsIoStoreCompressed {
+            file_path: file_path.to_string(),
+        }, options)?;
+        Ok(response.compressed)
+    }
+
+    fn patch_mesh(&self, file_path: &str, uexp_path: &str, options: &RequestOptions) -> Result<()> {
+        self.send(&UAssetRequest::PatchMesh {
+            file_path: file_path.to_string(),
+            uexp_path: uexp_path.to_string(),
+        }, options)?;
+        Ok(())
+    }
+}
+
+impl dyn ToolkitBackend + '_ {
+    /// Send a request and decode its typed response
+    pub fn request<T: DeserializeOwned + Default>(&self, request: &UAssetRequest) -> Result<T> {
+        self.request_with(request, &RequestOptions::default())
+    }
+
+    /// Like `request`, with a custom timeout and/or cancellation token
+    pub fn request_with<T: DeserializeOwned + Default>(&self, request: &UAssetRequest, options: &RequestOptions) -> Result<T> {
+        let reply = self.send(request, options)?;
+        decode_data(request, Some(reply.data), reply.stderr)
+    }
+}
+
+fn typed_with<T: DeserializeOwned + Default, B: ToolkitBackend + ?Sized>(backend: &B, request: &UAssetRequest, options: &RequestOptions) -> Result<T> {
+    let reply = backend.send(request, options)?;
+    decode_data(request, Some(reply.data), reply.stderr)
+}
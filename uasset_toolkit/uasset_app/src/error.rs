@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+/// Errors returned by the UAssetTool bridge.
+///
+/// Variants raised while a request was in flight carry the lines the tool wrote to stderr
+/// during that request, which usually holds the .NET exception behind the failure.
+#[derive(thiserror::Error, Debug)]
+pub enum ToolkitError {
+    #[error("UAssetTool executable not found at: {0}")]
+    ToolNotFound(String),
+
+    #[error("failed to spawn UAssetTool process: {0}")]
+    Spawn(#[source] std::io::Error),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to encode request: {0}")]
+    Encode(#[source] serde_json::Error),
+
+    #[error("timeout waiting for UAssetTool response to '{action}' after {timeout:?}")]
+    Timeout {
+        action: String,
+        timeout: Duration,
+        stderr: Vec<String>,
+    },
+
+    #[error("'{action}' was cancelled")]
+    Cancelled { action: String },
+
+    #[error("UAssetTool process exited while handling '{action}'")]
+    ProcessExited { action: String, stderr: Vec<String> },
+
+    #[error("invalid response to '{action}': {source} (line: {line})")]
+    InvalidResponse {
+        action: String,
+        line: String,
+        #[source]
+        source: serde_json::Error,
+        stderr: Vec<String>,
+    },
+
+    #[error("'{action}' failed: {message}")]
+    Failed {
+        action: String,
+        message: String,
+        stderr: Vec<String>,
+    },
+
+    #[error("UAssetTool speaks protocol version {tool}, expected {min}..={max}")]
+    UnsupportedProtocol { tool: u32, min: u32, max: u32 },
+
+    /// The tool didn't answer the handshake at all, rather than rejecting it
+    #[error("UAssetTool did not answer the protocol handshake: {0}")]
+    Handshake(#[source] Box<ToolkitError>),
+
+    #[error("failed to acquire process lock: {0}")]
+    Lock(String),
+}
+
+impl ToolkitError {
+    /// Stderr output captured from the tool while the failing request was running
+    pub fn stderr(&self) -> &[String] {
+        match self {
+            ToolkitError::Timeout { stderr, .. }
+            | ToolkitError::ProcessExited { stderr, .. }
+            | ToolkitError::InvalidResponse { stderr, .. }
+            | ToolkitError::Failed { stderr, .. } => stderr,
+            ToolkitError::Handshake(e) => e.stderr(),
+            _ => &[],
+        }
+    }
+}
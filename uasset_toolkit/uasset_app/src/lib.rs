@@ -1,15 +1,25 @@
-use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
 use std::path::Path;
-use std::process::Stdio;
-use std::sync::{OnceLock, Mutex as StdMutex, mpsc};
-use std::io::{BufRead, BufReader as StdBufReader, Write};
-use std::process::{Command as StdCommand, Child as StdChild, ChildStdin as StdChildStdin, ChildStdout as StdChildStdout};
-use std::time::Duration;
-use std::thread;
+use std::sync::{Arc, OnceLock, RwLock};
 
-#[cfg(windows)]
-use std::os::windows::process::CommandExt;
+mod backend;
+mod error;
+pub mod mock;
+pub mod package;
+mod pool;
+mod process;
+mod protocol;
+pub mod usmap;
+
+pub use backend::{Reply, ToolkitBackend};
+pub use error::ToolkitError;
+pub use pool::{CancellationToken, PoolConfig, RequestOptions};
+pub use protocol::*;
+
+use pool::WorkerPool;
+use process::decode_data;
+
+pub type Result<T, E = ToolkitError> = std::result::Result<T, E>;
 
 // ============================================================================
 // SYNCHRONOUS UASSETTOOL WRAPPER
@@ -17,44 +27,55 @@ use std::os::windows::process::CommandExt;
 // This module provides a synchronous interface to UAssetTool using standard
 // library primitives only (no async/tokio) to avoid cross-runtime deadlock issues.
 //
-// Thread-safety: Uses std::sync::Mutex for thread-safe access to the child process.
+// Thread-safety: Requests are spread over a pool of UAssetTool processes, each
+// leased to one request at a time (see pool.rs).
+//
+// Callers go through the `ToolkitBackend` trait (see backend.rs); tests can swap
+// the global backend for a `mock::MockToolkit` with `set_global_toolkit`.
 // ============================================================================
 
-/// Global singleton for the synchronous UAssetToolkit
-static GLOBAL_TOOLKIT_SYNC: OnceLock<SyncToolkit> = OnceLock::new();
+/// Backend behind the global API; holds the `SyncToolkit` singleton unless replaced
+static GLOBAL_TOOLKIT_SYNC: RwLock<Option<Arc<dyn ToolkitBackend>>> = RwLock::new(None);
 
-/// Synchronous child process handle using channel-based communication for timeout support
-struct SyncChildProcess {
-    _child: StdChild,
-    stdin: StdChildStdin,
-    response_rx: mpsc::Receiver<std::io::Result<String>>,
-}
+/// Pool settings used when the global singleton is created
+static GLOBAL_POOL_CONFIG: OnceLock<PoolConfig> = OnceLock::new();
 
-/// Synchronous toolkit that manages a persistent UAssetTool process
+/// Synchronous toolkit that manages a pool of persistent UAssetTool processes
 pub struct SyncToolkit {
-    tool_path: String,
-    process: StdMutex<Option<SyncChildProcess>>,
+    pool: WorkerPool,
 }
 
 impl SyncToolkit {
     pub fn new(tool_path: Option<String>) -> Result<Self> {
+        Self::with_config(tool_path, PoolConfig::default())
+    }
+    
+    pub fn with_config(tool_path: Option<String>, config: PoolConfig) -> Result<Self> {
         let tool_path = match tool_path {
             Some(path) => path,
             None => Self::find_tool_path()?,
         };
         
         Ok(Self {
-            tool_path,
-            process: StdMutex::new(None),
+            pool: WorkerPool::new(tool_path, config),
         })
     }
     
+    pub fn tool_path(&self) -> &str {
+        self.pool.tool_path()
+    }
+    
+    /// Number of worker processes requests are spread over
+    pub fn workers(&self) -> usize {
+        self.pool.config().workers.max(1)
+    }
+    
     fn find_tool_path() -> Result<String> {
         let exe_name = Self::get_tool_executable_name();
         let exe_path = std::env::current_exe()?;
-        let exe_dir = exe_path.parent().context("Failed to get executable directory")?;
+        let exe_dir = exe_path.parent()
+            .ok_or_else(|| ToolkitError::ToolNotFound("failed to get executable directory".to_string()))?;
         let tool_path = exe_dir.join("uassettool").join(exe_name);
-        
         if tool_path.exists() {
             return Ok(tool_path.to_string_lossy().to_string());
         }
@@ -99,328 +120,92 @@ impl SyncToolkit {
         { "win-x64" } // fallback
     }
     
-    fn send_request(&self, request: &UAssetRequest) -> Result<UAssetResponse> {
-        let mut process_guard = self.process.lock()
-            .map_err(|e| anyhow::anyhow!("Failed to acquire process lock: {}", e))?;
-        
-        // Start process if not running
-        if process_guard.is_none() {
-            log::info!("[SyncToolkit] Starting new UAssetTool process: {}", self.tool_path);
-            
-            if !Path::new(&self.tool_path).exists() {
-                anyhow::bail!("UAssetTool executable not found at: {}", self.tool_path);
-            }
-            
-            let mut cmd = StdCommand::new(&self.tool_path);
-            cmd.stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::inherit()); // MUST inherit stderr to avoid deadlock from buffer filling
-            
-            // Pass USMAP_PATH to child process
-            if let Ok(usmap_path) = std::env::var("USMAP_PATH") {
-                cmd.env("USMAP_PATH", &usmap_path);
-                log::info!("[SyncToolkit] Passing USMAP_PATH: {}", usmap_path);
-            }
-            
-            #[cfg(windows)]
-            cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
-            
-            let mut child = cmd.spawn()
-                .context("Failed to spawn UAssetTool process")?;
-            
-            let stdin = child.stdin.take().context("Failed to get stdin")?;
-            let stdout = child.stdout.take().context("Failed to get stdout")?;
-            
-            // Create channel for timeout-safe reading
-            let (tx, rx) = mpsc::channel();
-            
-            // Spawn reader thread that sends lines through channel
-            thread::spawn(move || {
-                let reader = StdBufReader::new(stdout);
-                for line in reader.lines() {
-                    if tx.send(line).is_err() {
-                        break; // Channel closed, stop reading
-                    }
-                }
-            });
-            
-            *process_guard = Some(SyncChildProcess { _child: child, stdin, response_rx: rx });
-            log::info!("[SyncToolkit] UAssetTool process started successfully");
-        }
-        
-        let proc = process_guard.as_mut().unwrap();
-        let request_json = serde_json::to_string(request)?;
-        
-        log::info!("[SyncToolkit] Sending request: {}...", &request_json[..std::cmp::min(200, request_json.len())]);
-        
-        // Write request
-        if let Err(e) = writeln!(proc.stdin, "{}", request_json) {
-            *process_guard = None;
-            anyhow::bail!("Failed to write to UAssetTool: {}", e);
-        }
-        
-        if let Err(e) = proc.stdin.flush() {
-            *process_guard = None;
-            anyhow::bail!("Failed to flush to UAssetTool: {}", e);
-        }
-        
-        log::info!("[SyncToolkit] Request sent, waiting for response (timeout: 5 min)...");
-        
-        // Read response with timeout (5 minutes for large batch operations)
-        // Skip non-JSON lines (e.g. log output that leaked to stdout) until we get a valid JSON response
-        let timeout = Duration::from_secs(300);
-        let deadline = std::time::Instant::now() + timeout;
-        loop {
-            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
-            if remaining.is_zero() {
-                log::error!("[SyncToolkit] TIMEOUT waiting for UAssetTool response after {:?}", timeout);
-                *process_guard = None;
-                anyhow::bail!("Timeout waiting for UAssetTool response after {:?}", timeout);
-            }
-            match proc.response_rx.recv_timeout(remaining) {
-                Ok(Ok(line)) => {
-                    let trimmed = line.trim();
-                    if trimmed.is_empty() {
-                        continue;
-                    }
-                    // JSON responses start with '{' — skip anything else (log lines)
-                    if !trimmed.starts_with('{') {
-                        log::warn!("[SyncToolkit] Skipping non-JSON stdout line: {}", &trimmed[..std::cmp::min(200, trimmed.len())]);
-                        continue;
-                    }
-                    log::info!("[SyncToolkit] Got response: {} bytes", line.len());
-                    match serde_json::from_str::<UAssetResponse>(&line) {
-                        Ok(response) => return Ok(response),
-                        Err(e) => {
-                            *process_guard = None;
-                            anyhow::bail!("Failed to parse response: {} (Line: {})", e, &line[..std::cmp::min(500, line.len())]);
-                        }
-                    }
-                }
-                Ok(Err(e)) => {
-                    *process_guard = None;
-                    anyhow::bail!("Failed to read from UAssetTool: {}", e);
-                }
-                Err(mpsc::RecvTimeoutError::Timeout) => {
-                    log::error!("[SyncToolkit] TIMEOUT waiting for UAssetTool response after {:?}", timeout);
-                    *process_guard = None;
-                    anyhow::bail!("Timeout waiting for UAssetTool response after {:?}", timeout);
-                }
-                Err(mpsc::RecvTimeoutError::Disconnected) => {
-                    *process_guard = None;
-                    anyhow::bail!("UAssetTool process closed connection (channel disconnected)");
-                }
-            }
-        }
+    /// Protocol revision negotiated with the tool, starting a worker if needed
+    pub fn protocol_version(&self) -> Result<u32> {
+        let mut lease = self.pool.checkout("handshake", None)?;
+        Ok(lease.process()?.protocol_version)
     }
-    
-    pub fn batch_detect_skeletal_mesh(&self, file_paths: &[String]) -> Result<bool> {
-        let request = UAssetRequest::BatchDetectSkeletalMesh { file_paths: file_paths.to_vec() };
-        let response = self.send_request(&request)?;
-        if !response.success {
-            anyhow::bail!("Failed to batch detect skeletal mesh: {}", response.message);
-        }
-        Ok(response.data.and_then(|d| d.as_bool()).unwrap_or(false))
-    }
-    
-    pub fn batch_detect_static_mesh(&self, file_paths: &[String]) -> Result<bool> {
-        let request = UAssetRequest::BatchDetectStaticMesh { file_paths: file_paths.to_vec() };
-        let response = self.send_request(&request)?;
-        if !response.success {
-            anyhow::bail!("Failed to batch detect static mesh: {}", response.message);
-        }
-        Ok(response.data.and_then(|d| d.as_bool()).unwrap_or(false))
-    }
-    
-    pub fn batch_detect_texture(&self, file_paths: &[String]) -> Result<bool> {
-        let request = UAssetRequest::BatchDetectTexture { file_paths: file_paths.to_vec() };
-        let response = self.send_request(&request)?;
-        if !response.success {
-            anyhow::bail!("Failed to batch detect texture: {}", response.message);
-        }
-        Ok(response.data.and_then(|d| d.as_bool()).unwrap_or(false))
-    }
-    
-    pub fn batch_detect_blueprint(&self, file_paths: &[String]) -> Result<bool> {
-        let request = UAssetRequest::BatchDetectBlueprint { file_paths: file_paths.to_vec() };
-        let response = self.send_request(&request)?;
-        if !response.success {
-            anyhow::bail!("Failed to batch detect blueprint: {}", response.message);
-        }
-        Ok(response.data.and_then(|d| d.as_bool()).unwrap_or(false))
-    }
-    
-    pub fn is_texture_uasset(&self, file_path: &str) -> Result<bool> {
-        self.batch_detect_texture(&[file_path.to_string()])
-    }
-    
-    pub fn strip_mipmaps_native(&self, file_path: &str, usmap_path: Option<&str>) -> Result<bool> {
-        let request = UAssetRequest::StripMipmapsNative {
-            file_path: file_path.to_string(),
-            usmap_path: usmap_path.map(|s| s.to_string()),
-        };
-        let response = self.send_request(&request)?;
-        if !response.success {
-            anyhow::bail!("Failed to strip mipmaps native: {}", response.message);
-        }
-        Ok(true)
-    }
-    
-    pub fn convert_texture(&self, file_path: &str) -> Result<bool> {
-        let request = UAssetRequest::ConvertTexture {
-            file_path: file_path.to_string(),
-        };
-        let response = self.send_request(&request)?;
-        if !response.success {
-            anyhow::bail!("Failed to convert texture: {}", response.message);
-        }
-        Ok(true)
-    }
-    
-    pub fn set_no_mipmaps(&self, file_path: &str) -> Result<()> {
-        let request = UAssetRequest::SetMipGen {
-            file_path: file_path.to_string(),
-            mip_gen: "NoMipmaps".to_string(),
-        };
-        let response = self.send_request(&request)?;
-        if !response.success {
-            anyhow::bail!("Failed to set no mipmaps: {}", response.message);
-        }
-        Ok(())
+
+    /// Send a request and decode its typed response. Failures reported by the tool become
+    /// `ToolkitError::Failed`; transport errors drop the worker so the next request restarts it.
+    pub fn request<T: DeserializeOwned + Default>(&self, request: &UAssetRequest) -> Result<T> {
+        self.request_with(request, &RequestOptions::default())
     }
     
-    pub fn batch_has_inline_texture_data(&self, file_paths: &[String], usmap_path: Option<&str>) -> Result<Vec<String>> {
-        let request = UAssetRequest::BatchHasInlineTextureData {
-            file_paths: file_paths.to_vec(),
-            usmap_path: usmap_path.map(|s| s.to_string()),
-        };
-        
-        let response = self.send_request(&request)?;
-        
-        if !response.success {
-            anyhow::bail!("Failed to batch check inline texture data: {}", response.message);
-        }
-        
-        // Parse response as list of file paths with inline data
-        let inline_files = response.data
-            .and_then(|d| d.as_array().cloned())
-            .map(|arr| {
-                arr.into_iter()
-                    .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                    .collect()
-            })
-            .unwrap_or_default();
-        
-        Ok(inline_files)
+    /// Like `request`, with a custom timeout and/or cancellation token
+    pub fn request_with<T: DeserializeOwned + Default>(&self, request: &UAssetRequest, options: &RequestOptions) -> Result<T> {
+        let reply = self.reply(request, options)?;
+        decode_data(request, Some(reply.data), reply.stderr)
     }
     
-    pub fn batch_strip_mipmaps_native(&self, file_paths: &[String], usmap_path: Option<&str>, parallel: bool) -> Result<(usize, usize, usize, Vec<String>)> {
-        let request = UAssetRequest::BatchStripMipmapsNative {
-            file_paths: file_paths.to_vec(),
-            usmap_path: usmap_path.map(|s| s.to_string()),
-            parallel,
-        };
-        
-        let response = self.send_request(&request)?;
+    fn reply(&self, request: &UAssetRequest, options: &RequestOptions) -> Result<Reply> {
+        let action = request.action();
+        let cancel = options.cancel.as_ref();
+        let timeout = options.timeout.unwrap_or(self.pool.config().request_timeout);
         
-        if !response.success {
-            anyhow::bail!("Failed to batch strip mipmaps: {}", response.message);
-        }
-        
-        let data = response.data.unwrap_or(serde_json::json!({}));
-        let success_count = data.get("success_count").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-        let skip_count = data.get("skip_count").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-        let error_count = data.get("error_count").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-        
-        let mut processed_files = Vec::new();
-        if let Some(results) = data.get("results").and_then(|v| v.as_array()) {
-            for result in results {
-                if result.get("success").and_then(|v| v.as_bool()).unwrap_or(false) {
-                    if result.get("skipped").and_then(|v| v.as_bool()).unwrap_or(false) {
-                        continue;
-                    }
-                    if let Some(path) = result.get("path").and_then(|v| v.as_str()) {
-                        if let Some(file_name) = std::path::Path::new(path).file_stem() {
-                            processed_files.push(file_name.to_string_lossy().to_string());
-                        }
-                    }
-                }
+        let mut lease = self.pool.checkout(&action, cancel)?;
+        let proc = lease.process()?;
+        let response = match proc.exchange(request, timeout, cancel) {
+            Ok(response) => response,
+            Err(e) => {
+                lease.discard();
+                return Err(e);
             }
-        }
-        
-        Ok((success_count, skip_count, error_count, processed_files))
-    }
-    
-    pub fn list_iostore_files(&self, file_path: &str, aes_key: Option<&str>) -> Result<IoStoreListResult> {
-        let request = UAssetRequest::ListIoStoreFiles {
-            file_path: file_path.to_string(),
-            aes_key: aes_key.map(|s| s.to_string()),
         };
-        
-        let response = self.send_request(&request)?;
+        let stderr = proc.take_stderr();
         
         if !response.success {
-            anyhow::bail!("Failed to list IoStore files: {}", response.message);
+            return Err(ToolkitError::Failed {
+                action,
+                message: response.message,
+                stderr,
+            });
         }
-        
-        let data = response.data.unwrap_or(serde_json::json!({}));
-        let package_count = data.get("package_count").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-        let container_name = data.get("container_name").and_then(|v| v.as_str()).unwrap_or("").to_string();
-        let files = data.get("files")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
-            .unwrap_or_default();
-        
-        Ok(IoStoreListResult { package_count, container_name, files })
+        Ok(Reply { data: response.data.unwrap_or_default(), stderr })
     }
-    
-    pub fn create_mod_iostore(&self, output_path: &str, input_dir: &str, usmap_path: Option<&str>, mount_point: Option<&str>, compress: Option<bool>, aes_key: Option<&str>, parallel: bool, obfuscate: bool) -> Result<IoStoreResult> {
-        let request = UAssetRequest::CreateModIoStore {
-            output_path: output_path.to_string(),
-            input_dir: input_dir.to_string(),
-            usmap_path: usmap_path.map(|s| s.to_string()),
-            mount_point: mount_point.map(|s| s.to_string()),
-            compress,
-            aes_key: aes_key.map(|s| s.to_string()),
-            parallel,
-            obfuscate,
-        };
-        
-        let response = self.send_request(&request)?;
-        
-        if !response.success {
-            anyhow::bail!("Failed to create mod IoStore: {}", response.message);
-        }
-        
-        let data = response.data.unwrap_or(serde_json::json!({}));
-        let utoc_path = data.get("utoc_path").and_then(|v| v.as_str()).unwrap_or("").to_string();
-        let ucas_path = data.get("ucas_path").and_then(|v| v.as_str()).unwrap_or("").to_string();
-        let pak_path = data.get("pak_path").and_then(|v| v.as_str()).unwrap_or("").to_string();
-        let converted_count = data.get("converted_count").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-        let file_count = data.get("file_count").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-        
-        Ok(IoStoreResult { utoc_path, ucas_path, pak_path, converted_count, file_count })
+}
+
+impl ToolkitBackend for SyncToolkit {
+    fn send(&self, request: &UAssetRequest, options: &RequestOptions) -> Result<Reply> {
+        self.reply(request, options)
+    }
+
+    fn workers(&self) -> usize {
+        SyncToolkit::workers(self)
     }
 }
 
-/// Get or initialize the global synchronous toolkit
-pub fn get_global_toolkit() -> Result<&'static SyncToolkit> {
-    let toolkit = GLOBAL_TOOLKIT_SYNC.get_or_init(|| {
-        log::info!("[SyncToolkit] Initializing global singleton...");
-        match SyncToolkit::new(None) {
-            Ok(t) => {
-                log::info!("[SyncToolkit] Global singleton created successfully");
-                t
-            }
-            Err(e) => {
-                log::error!("[SyncToolkit] Failed to create singleton: {}", e);
-                panic!("Failed to initialize SyncToolkit: {}", e);
-            }
+/// Get the global toolkit backend, creating the `SyncToolkit` singleton on first use
+pub fn get_global_toolkit() -> Result<Arc<dyn ToolkitBackend>> {
+    if let Some(toolkit) = GLOBAL_TOOLKIT_SYNC.read().map_err(|e| ToolkitError::Lock(e.to_string()))?.as_ref() {
+        return Ok(toolkit.clone());
+    }
+
+    let mut global = GLOBAL_TOOLKIT_SYNC.write().map_err(|e| ToolkitError::Lock(e.to_string()))?;
+    if let Some(toolkit) = global.as_ref() {
+        return Ok(toolkit.clone());
+    }
+    log::info!("[SyncToolkit] Initializing global singleton...");
+    let config = GLOBAL_POOL_CONFIG.get().cloned().unwrap_or_default();
+    let toolkit: Arc<dyn ToolkitBackend> = match SyncToolkit::with_config(None, config) {
+        Ok(t) => Arc::new(t),
+        Err(e) => {
+            log::error!("[SyncToolkit] Failed to create singleton: {}", e);
+            return Err(e);
         }
-    });
+    };
+    log::info!("[SyncToolkit] Global singleton created successfully");
+    *global = Some(toolkit.clone());
     Ok(toolkit)
 }
 
+/// Replace the backend used by the global API, e.g. with a `MockToolkit` in tests.
+/// Returns the previous backend, if one was created.
+pub fn set_global_toolkit(toolkit: Arc<dyn ToolkitBackend>) -> Option<Arc<dyn ToolkitBackend>> {
+    let mut global = GLOBAL_TOOLKIT_SYNC.write().unwrap_or_else(|e| e.into_inner());
+    global.replace(toolkit)
+}
+
 /// Initialize the global toolkit at app startup
 pub fn init_global_toolkit() -> Result<()> {
     get_global_toolkit()?;
@@ -428,102 +213,14 @@ pub fn init_global_toolkit() -> Result<()> {
     Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "action")]
-pub enum UAssetRequest {
-    #[serde(rename = "detect_texture")]
-    DetectTexture { file_path: String },
-    #[serde(rename = "set_mip_gen")]
-    SetMipGen { file_path: String, mip_gen: String },
-    #[serde(rename = "get_texture_info")]
-    GetTextureInfo { file_path: String },
-    #[serde(rename = "detect_mesh")]
-    DetectMesh { file_path: String },
-    #[serde(rename = "detect_skeletal_mesh")]
-    DetectSkeletalMesh { file_path: String },
-    #[serde(rename = "detect_static_mesh")]
-    DetectStaticMesh { file_path: String },
-    #[serde(rename = "patch_mesh")]
-    PatchMesh { file_path: String, uexp_path: String },
-    #[serde(rename = "get_mesh_info")]
-    GetMeshInfo { file_path: String },
-    // Batch detection - sends all files at once, returns first match
-    #[serde(rename = "batch_detect_skeletal_mesh")]
-    BatchDetectSkeletalMesh { file_paths: Vec<String> },
-    #[serde(rename = "batch_detect_static_mesh")]
-    BatchDetectStaticMesh { file_paths: Vec<String> },
-    #[serde(rename = "batch_detect_texture")]
-    BatchDetectTexture { file_paths: Vec<String> },
-    #[serde(rename = "batch_detect_blueprint")]
-    BatchDetectBlueprint { file_paths: Vec<String> },
-    // Texture conversion using UE4-DDS-Tools (export -> re-inject with no_mipmaps)
-    #[serde(rename = "convert_texture")]
-    ConvertTexture { file_path: String },
-    #[serde(rename = "strip_mipmaps")]
-    StripMipmaps { file_path: String },
-    // Native C# mipmap stripping using UAssetAPI TextureExport
-    #[serde(rename = "strip_mipmaps_native")]
-    StripMipmapsNative { file_path: String, usmap_path: Option<String> },
-    // Batch native C# mipmap stripping - processes multiple files in one call
-    #[serde(rename = "batch_strip_mipmaps_native")]
-    BatchStripMipmapsNative { file_paths: Vec<String>, usmap_path: Option<String>, #[serde(default)] parallel: bool },
-    // Check if texture has inline data (no .ubulk needed)
-    #[serde(rename = "has_inline_texture_data")]
-    HasInlineTextureData { file_path: String, usmap_path: Option<String> },
-    // Batch check for inline texture data - returns list of files with inline data
-    #[serde(rename = "batch_has_inline_texture_data")]
-    BatchHasInlineTextureData { file_paths: Vec<String>, usmap_path: Option<String> },
-    
-    // PAK operations
-    #[serde(rename = "list_pak_files")]
-    ListPakFiles { file_path: String, aes_key: Option<String> },
-    #[serde(rename = "extract_pak_file")]
-    ExtractPakFile { file_path: String, internal_path: String, output_path: String, aes_key: Option<String> },
-    #[serde(rename = "extract_pak_all")]
-    ExtractPakAll { file_path: String, output_path: String, aes_key: Option<String> },
-    #[serde(rename = "create_pak")]
-    CreatePak { output_path: String, file_paths: Vec<String>, mount_point: Option<String>, path_hash_seed: Option<u64>, aes_key: Option<String> },
-    #[serde(rename = "create_companion_pak")]
-    CreateCompanionPak { output_path: String, file_paths: Vec<String>, mount_point: Option<String>, path_hash_seed: Option<u64>, aes_key: Option<String> },
-    
-    // IoStore operations
-    #[serde(rename = "list_iostore_files")]
-    ListIoStoreFiles { file_path: String, aes_key: Option<String> },
-    #[serde(rename = "create_iostore")]
-    CreateIoStore { output_path: String, input_dir: String, usmap_path: Option<String>, compress: Option<bool>, aes_key: Option<String> },
-    #[serde(rename = "is_iostore_compressed")]
-    IsIoStoreCompressed { file_path: String },
-    #[serde(rename = "recompress_iostore")]
-    RecompressIoStore { file_path: String },
-    #[serde(rename = "extract_iostore")]
-    ExtractIoStore { file_path: String, output_path: String, aes_key: Option<String> },
-    #[serde(rename = "extract_script_objects")]
-    ExtractScriptObjects { file_path: String, output_path: String },
-    #[serde(rename = "create_mod_iostore")]
-    CreateModIoStore { output_path: String, input_dir: String, usmap_path: Option<String>, mount_point: Option<String>, compress: Option<bool>, aes_key: Option<String>, #[serde(default)] parallel: bool, #[serde(default)] obfuscate: bool },
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UAssetResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: Option<serde_json::Value>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TextureInfo {
-    pub mip_gen_settings: Option<String>,
-    pub width: Option<i32>,
-    pub height: Option<i32>,
-    pub format: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct MeshInfo {
-    pub material_count: Option<i32>,
-    pub vertex_count: Option<i32>,
-    pub triangle_count: Option<i32>,
-    pub is_skeletal_mesh: Option<bool>,
+/// Initialize the global toolkit with custom pool settings.
+/// Has no effect on the pool if the singleton was already created.
+pub fn init_global_toolkit_with(config: PoolConfig) -> Result<()> {
+    let created = GLOBAL_TOOLKIT_SYNC.read().map(|global| global.is_some()).unwrap_or(true);
+    if GLOBAL_POOL_CONFIG.set(config).is_err() || created {
+        log::warn!("[SyncToolkit] Global singleton already configured, keeping existing pool");
+    }
+    init_global_toolkit()
 }
 
 // ============================================================================
@@ -533,14 +230,14 @@ pub struct MeshInfo {
 /// Batch strip mipmaps from multiple textures (using global singleton)
 /// Returns (success_count, skip_count, error_count, processed_file_names)
 pub fn batch_strip_mipmaps_native(file_paths: &[String], usmap_path: Option<&str>) -> Result<(usize, usize, usize, Vec<String>)> {
-    batch_strip_mipmaps_native_parallel(file_paths, usmap_path, false)
+    batch_strip_mipmaps_native_parallel(file_paths, usmap_path, false, &RequestOptions::default())
 }
 
 /// Batch strip mipmaps with parallel processing option (using global singleton)
 /// Returns (success_count, skip_count, error_count, processed_file_names)
-pub fn batch_strip_mipmaps_native_parallel(file_paths: &[String], usmap_path: Option<&str>, parallel: bool) -> Result<(usize, usize, usize, Vec<String>)> {
+pub fn batch_strip_mipmaps_native_parallel(file_paths: &[String], usmap_path: Option<&str>, parallel: bool, options: &RequestOptions) -> Result<(usize, usize, usize, Vec<String>)> {
     let toolkit = get_global_toolkit()?;
-    toolkit.batch_strip_mipmaps_native(file_paths, usmap_path, parallel)
+    toolkit.batch_strip_mipmaps_native(file_paths, usmap_path, parallel, options)
 }
 
 // Type aliases for backward compatibility
@@ -550,94 +247,44 @@ pub type UAssetToolkitSync = SyncToolkit;
 /// Check if a file is a skeletal mesh (using global singleton)
 pub fn is_skeletal_mesh_uasset(file_path: &str) -> Result<bool> {
     let toolkit = get_global_toolkit()?;
-    toolkit.batch_detect_skeletal_mesh(&[file_path.to_string()])
+    toolkit.batch_detect_skeletal_mesh(&[file_path.to_string()], &RequestOptions::default())
 }
 
 /// Check if a file is a texture (using global singleton)
 pub fn is_texture_uasset(file_path: &str) -> Result<bool> {
     let toolkit = get_global_toolkit()?;
-    toolkit.batch_detect_texture(&[file_path.to_string()])
+    toolkit.batch_detect_texture(&[file_path.to_string()], &RequestOptions::default())
 }
 
 /// Check if a file is a static mesh (using global singleton)
 pub fn is_static_mesh_uasset(file_path: &str) -> Result<bool> {
     let toolkit = get_global_toolkit()?;
-    toolkit.batch_detect_static_mesh(&[file_path.to_string()])
+    toolkit.batch_detect_static_mesh(&[file_path.to_string()], &RequestOptions::default())
 }
 
 /// Recompress an IoStore file
 pub fn recompress_iostore(file_path: &str) -> Result<()> {
-    let toolkit = get_global_toolkit()?;
-    let request = UAssetRequest::RecompressIoStore {
-        file_path: file_path.to_string(),
-    };
-    let response = toolkit.send_request(&request)?;
-    if !response.success {
-        anyhow::bail!("Failed to recompress IoStore: {}", response.message);
-    }
-    Ok(())
+    get_global_toolkit()?.recompress_iostore(file_path, &RequestOptions::default())
 }
 
 /// Extract files from an IoStore to legacy format
 pub fn extract_iostore(file_path: &str, output_path: &str, aes_key: Option<&str>) -> Result<usize> {
-    let toolkit = get_global_toolkit()?;
-    let request = UAssetRequest::ExtractIoStore {
-        file_path: file_path.to_string(),
-        output_path: output_path.to_string(),
-        aes_key: aes_key.map(|s| s.to_string()),
-    };
-    let response = toolkit.send_request(&request)?;
-    if !response.success {
-        anyhow::bail!("Failed to extract IoStore: {}", response.message);
-    }
-    let data = response.data.unwrap_or(serde_json::json!({}));
-    let converted = data.get("extracted_count").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-    Ok(converted)
+    get_global_toolkit()?.extract_iostore(file_path, output_path, aes_key, &RequestOptions::default())
 }
 
 /// Extract script objects from an IoStore
 pub fn extract_script_objects(file_path: &str, output_path: &str) -> Result<usize> {
-    let toolkit = get_global_toolkit()?;
-    let request = UAssetRequest::ExtractScriptObjects {
-        file_path: file_path.to_string(),
-        output_path: output_path.to_string(),
-    };
-    let response = toolkit.send_request(&request)?;
-    if !response.success {
-        anyhow::bail!("Failed to extract script objects: {}", response.message);
-    }
-    let data = response.data.unwrap_or(serde_json::json!({}));
-    let count = data.get("count").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-    Ok(count)
+    get_global_toolkit()?.extract_script_objects(file_path, output_path, &RequestOptions::default())
 }
 
 /// Check if IoStore is compressed
 pub fn is_iostore_compressed(file_path: &str) -> Result<bool> {
-    let toolkit = get_global_toolkit()?;
-    let request = UAssetRequest::IsIoStoreCompressed {
-        file_path: file_path.to_string(),
-    };
-    let response = toolkit.send_request(&request)?;
-    if !response.success {
-        anyhow::bail!("Failed to check IoStore compression: {}", response.message);
-    }
-    let data = response.data.unwrap_or(serde_json::json!({}));
-    let compressed = data.get("compressed").and_then(|v| v.as_bool()).unwrap_or(false);
-    Ok(compressed)
-}
-
-/// IoStore creation result
-#[derive(Debug, Serialize, Deserialize)]
-pub struct IoStoreResult {
-    pub utoc_path: String,
-    pub ucas_path: String,
-    pub pak_path: String,
-    pub converted_count: usize,
-    pub file_count: usize,
+    get_global_toolkit()?.is_iostore_compressed(file_path, &RequestOptions::default())
 }
 
 /// Create mod IoStore
 /// parallel: when true, uses 75% of CPU threads; when false, uses 50%
+#[allow(clippy::too_many_arguments)]
 pub fn create_mod_iostore(
     output_path: &str,
     input_dir: &str,
@@ -647,35 +294,196 @@ pub fn create_mod_iostore(
     aes_key: Option<&str>,
     parallel: bool,
     obfuscate: bool,
+    options: &RequestOptions,
 ) -> Result<IoStoreResult> {
     let toolkit = get_global_toolkit()?;
-    toolkit.create_mod_iostore(output_path, input_dir, usmap_path, mount_point, compress, aes_key, parallel, obfuscate)
+    toolkit.create_mod_iostore(output_path, input_dir, usmap_path, mount_point, compress, aes_key, parallel, obfuscate, options)
 }
 
 /// Patch mesh materials
 pub fn patch_mesh(file_path: &str, uexp_path: &str) -> Result<()> {
-    let toolkit = get_global_toolkit()?;
-    let request = UAssetRequest::PatchMesh {
-        file_path: file_path.to_string(),
-        uexp_path: uexp_path.to_string(),
-    };
-    let response = toolkit.send_request(&request)?;
-    if !response.success {
-        anyhow::bail!("Failed to patch mesh: {}", response.message);
-    }
-    Ok(())
+    get_global_toolkit()?.patch_mesh(file_path, uexp_path, &RequestOptions::default())
 }
 
 /// List files in IoStore
 pub fn list_iostore_files(file_path: &str, aes_key: Option<&str>) -> Result<IoStoreListResult> {
     let toolkit = get_global_toolkit()?;
-    toolkit.list_iostore_files(file_path, aes_key)
+    toolkit.list_iostore_files(file_path, aes_key, &RequestOptions::default())
 }
 
-/// IoStore listing result
-#[derive(Debug, Serialize, Deserialize)]
-pub struct IoStoreListResult {
-    pub package_count: usize,
-    pub container_name: String,
-    pub files: Vec<String>,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn request_action_matches_wire_name() {
+        let request = UAssetRequest::BatchDetectTexture { file_paths: vec![] };
+        assert_eq!(request.action(), "batch_detect_texture");
+        let handshake = UAssetRequest::Handshake { protocol_version: PROTOCOL_VERSION };
+        assert_eq!(handshake.action(), "handshake");
+    }
+
+    #[test]
+    fn decode_typed_response_data() {
+        let request = UAssetRequest::ListIoStoreFiles { file_path: String::new(), aes_key: None };
+        let data = serde_json::json!({ "package_count": 2, "files": ["a.uasset", "b.uasset"] });
+        let result: IoStoreListResult = decode_data(&request, Some(data), Vec::new()).unwrap();
+        assert_eq!(result.package_count, 2);
+        assert_eq!(result.container_name, "");
+        assert_eq!(result.files, vec!["a.uasset", "b.uasset"]);
+
+        let missing: IoStoreListResult = decode_data(&request, None, Vec::new()).unwrap();
+        assert_eq!(missing.package_count, 0);
+    }
+
+    #[test]
+    fn decode_mismatched_data_keeps_stderr() {
+        let request = UAssetRequest::IsIoStoreCompressed { file_path: String::new() };
+        let stderr = vec!["Unhandled exception".to_string()];
+        let err = decode_data::<IoStoreCompressionResponse>(
+            &request,
+            Some(serde_json::json!({ "compressed": "yes" })),
+            stderr.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ToolkitError::InvalidResponse { .. }));
+        assert_eq!(err.stderr(), stderr.as_slice());
+    }
+
+    /// Answer to the handshake of a tool that speaks protocol v2
+    #[cfg(unix)]
+    const HANDSHAKE_V2: &str = r#"echo '{"success":true,"message":"","data":{"protocol_version":2}}'"#;
+
+    /// Shell script standing in for UAssetTool: runs `handshake` on the handshake, answers every
+    /// request with `data: true`, sleeps on paths named "sleep", exits with a stderr message on
+    /// paths named "crash" and answers paths named "garbled" with a payload that doesn't decode
+    #[cfg(unix)]
+    fn scripted_tool(name: &str, handshake: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("uassettool-{}-{}.sh", name, std::process::id()));
+        std::fs::write(&path, r#"#!/bin/sh
+while read -r line; do
+  case "$line" in
+    *'"handshake"'*) HANDSHAKE ;;
+    *'"crash"'*) echo "boom" >&2; exit 3 ;;
+    *'"sleep"'*) sleep 5; echo '{"success":true,"message":"","data":true}' ;;
+    *'"garbled"'*) echo "bad payload" >&2; sleep 0.2; echo '{"success":true,"message":"","data":{"compressed":"yes"}}' ;;
+    *) echo '{"success":true,"message":"","data":true}' ;;
+  esac
+done
+"#.replace("HANDSHAKE", handshake)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[cfg(unix)]
+    fn detect(file: &str) -> UAssetRequest {
+        UAssetRequest::BatchDetectTexture { file_paths: vec![file.to_string()] }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pool_restarts_crashed_worker() {
+        let config = PoolConfig { workers: 1, ..PoolConfig::default() };
+        let toolkit = SyncToolkit::with_config(Some(scripted_tool("crash", HANDSHAKE_V2)), config).unwrap();
+        assert_eq!(toolkit.protocol_version().unwrap(), 2);
+
+        let err = toolkit.request::<bool>(&detect("crash")).unwrap_err();
+        assert!(matches!(err, ToolkitError::ProcessExited { .. }), "{err}");
+        assert_eq!(err.stderr(), ["boom".to_string()]);
+
+        assert!(toolkit.request::<bool>(&detect("ok")).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tools_without_handshake_speak_protocol_v1() {
+        // Rejects the unknown action
+        let rejecting = r#"echo '{"success":false,"message":"Unknown action: handshake","data":null}'"#;
+        let toolkit = SyncToolkit::with_config(Some(scripted_tool("rejecting", rejecting)), PoolConfig::default()).unwrap();
+        assert_eq!(toolkit.protocol_version().unwrap(), 1);
+        assert!(toolkit.request::<bool>(&detect("ok")).unwrap());
+
+        // Never answers it; each worker is restarted without a handshake
+        let config = PoolConfig { workers: 2, handshake_timeout: Duration::from_millis(300), ..PoolConfig::default() };
+        let toolkit = SyncToolkit::with_config(Some(scripted_tool("silent", ":")), config).unwrap();
+        assert_eq!(toolkit.protocol_version().unwrap(), 1);
+        std::thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| assert!(toolkit.request::<bool>(&detect("ok")).unwrap()));
+            }
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn handshake_failures_other_than_silence_are_reported() {
+        // Crashes while answering: no fallback, the exit is reported with its stderr
+        let crashing = r#"echo "no such action" >&2; exit 3"#;
+        let toolkit = SyncToolkit::with_config(Some(scripted_tool("hs-crash", crashing)), PoolConfig::default()).unwrap();
+        let err = toolkit.protocol_version().unwrap_err();
+        assert!(matches!(err, ToolkitError::ProcessExited { .. }), "{err}");
+        assert_eq!(err.stderr(), ["no such action".to_string()]);
+
+        // Announces a revision older than the client accepts
+        let ancient = r#"echo '{"success":true,"message":"","data":{"protocol_version":0}}'"#;
+        let toolkit = SyncToolkit::with_config(Some(scripted_tool("hs-ancient", ancient)), PoolConfig::default()).unwrap();
+        let err = toolkit.protocol_version().unwrap_err();
+        assert!(matches!(err, ToolkitError::UnsupportedProtocol { tool: 0, .. }), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn typed_decode_failures_keep_the_tools_stderr() {
+        let config = PoolConfig { workers: 1, ..PoolConfig::default() };
+        let toolkit = SyncToolkit::with_config(Some(scripted_tool("garbled", HANDSHAKE_V2)), config).unwrap();
+        let err = toolkit.is_iostore_compressed("garbled", &RequestOptions::default()).unwrap_err();
+        assert!(matches!(err, ToolkitError::InvalidResponse { .. }), "{err}");
+        assert_eq!(err.stderr(), ["bad payload".to_string()]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pool_times_out_and_cancels_requests() {
+        let config = PoolConfig { workers: 1, request_timeout: Duration::from_millis(300), ..PoolConfig::default() };
+        let toolkit = SyncToolkit::with_config(Some(scripted_tool("timeout", HANDSHAKE_V2)), config).unwrap();
+
+        let err = toolkit.request::<bool>(&detect("sleep")).unwrap_err();
+        assert!(matches!(err, ToolkitError::Timeout { .. }), "{err}");
+
+        let options = RequestOptions {
+            timeout: Some(Duration::from_secs(30)),
+            cancel: Some(CancellationToken::new()),
+        };
+        let cancel = options.cancel.clone().unwrap();
+        let started = std::time::Instant::now();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(200));
+                cancel.cancel();
+            });
+            let err = toolkit.request_with::<bool>(&detect("sleep"), &options).unwrap_err();
+            assert!(matches!(err, ToolkitError::Cancelled { .. }), "{err}");
+        });
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        assert!(toolkit.request::<bool>(&detect("ok")).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pool_runs_requests_concurrently() {
+        let config = PoolConfig { workers: 3, request_timeout: Duration::from_secs(30), ..PoolConfig::default() };
+        let toolkit = SyncToolkit::with_config(Some(scripted_tool("parallel", HANDSHAKE_V2)), config).unwrap();
+
+        let started = std::time::Instant::now();
+        std::thread::scope(|scope| {
+            for _ in 0..3 {
+                scope.spawn(|| assert!(toolkit.request::<bool>(&detect("sleep")).unwrap()));
+            }
+        });
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
 }
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use std::io::{self, BufRead};
-use uasset_toolkit::{SyncToolkit, patch_mesh};
+use uasset_toolkit::{RequestOptions, SyncToolkit, ToolkitBackend, patch_mesh};
 
 fn main() -> Result<()> {
     let args: Vec<String> = std::env::args().collect();
@@ -13,6 +13,7 @@ fn main() -> Result<()> {
     };
     
     let toolkit = SyncToolkit::new(bridge_path)?;
+    let options = RequestOptions::default();
     
     // Determine if we have file arguments
     let file_args: Vec<&String> = if args.len() >= 2 && !args[1].ends_with(".uasset") {
@@ -27,7 +28,7 @@ fn main() -> Result<()> {
         // Command line mode - process files
         for file_path in file_args {
             println!("Processing: {}", file_path);
-            match toolkit.is_texture_uasset(file_path) {
+            match toolkit.is_texture_uasset(file_path, &options) {
                 Ok(true) => println!("✓ {} - Texture detected and set to NoMipmaps", file_path),
                 Ok(false) => println!("- {} - Not a texture uasset", file_path),
                 Err(e) => eprintln!("✗ {} - Error: {}", file_path, e),
@@ -63,7 +64,7 @@ fn main() -> Result<()> {
             
             match parts.as_slice() {
                 ["info", file_path] => {
-                    match toolkit.is_texture_uasset(file_path) {
+                    match toolkit.is_texture_uasset(file_path, &options) {
                         Ok(is_texture) => {
                             println!("File: {} - Is Texture: {}", file_path, is_texture);
                         }
@@ -71,7 +72,7 @@ fn main() -> Result<()> {
                     }
                 }
                 ["mesh", file_path] => {
-                    match toolkit.batch_detect_skeletal_mesh(&[file_path.to_string()]) {
+                    match toolkit.batch_detect_skeletal_mesh(&[file_path.to_string()], &options) {
                         Ok(is_mesh) => {
                             if is_mesh {
                                 println!("✓ {} is a mesh uasset", file_path);
@@ -83,7 +84,7 @@ fn main() -> Result<()> {
                     }
                 }
                 ["mesh-info", file_path] => {
-                    match toolkit.batch_detect_skeletal_mesh(&[file_path.to_string()]) {
+                    match toolkit.batch_detect_skeletal_mesh(&[file_path.to_string()], &options) {
                         Ok(is_skeletal) => {
                             println!("File: {} - Is Skeletal Mesh: {}", file_path, is_skeletal);
                         }
@@ -98,7 +99,7 @@ fn main() -> Result<()> {
                 }
                 _ => {
                     // Treat as file path
-                    match toolkit.is_texture_uasset(line) {
+                    match toolkit.is_texture_uasset(line, &options) {
                         Ok(true) => println!("✓ {} - Texture detected and set to NoMipmaps", line),
                         Ok(false) => println!("- {} - Not a texture uasset", line),
                         Err(e) => eprintln!("✗ {} - Error: {}", line, e),
@@ -0,0 +1,196 @@
+//! In-process stand-in for UAssetTool, so code that goes through the toolkit can be
+//! tested without the .NET binary.
+//!
+//! Responses are scripted per action name (the `action` field on the wire). Requests for
+//! actions without a script fail the same way the tool reports an unknown action.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use uasset_toolkit::{mock::MockToolkit, RequestOptions, ToolkitBackend};
+//!
+//! let mock = Arc::new(
+//!     MockToolkit::new()
+//!         .respond("batch_detect_texture", serde_json::json!(true))
+//!         .fail("batch_detect_static_mesh", "no usmap"),
+//! );
+//! let options = RequestOptions::default();
+//! assert!(mock.batch_detect_texture(&["T_Hero.uasset".to_string()], &options).unwrap());
+//! assert!(mock.batch_detect_static_mesh(&[], &options).is_err());
+//! assert_eq!(mock.requests().len(), 2);
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Mutex as StdMutex;
+
+use crate::{Reply, RequestOptions, Result, ToolkitBackend, ToolkitError, UAssetRequest};
+
+/// Scripted reply to one request: the response `data` on success, the error message otherwise
+pub type MockHandler = Box<dyn Fn(&UAssetRequest) -> std::result::Result<serde_json::Value, String> + Send + Sync>;
+
+/// Toolkit backend that answers requests from scripted handlers and records every request it got
+#[derive(Default)]
+pub struct MockToolkit {
+    handlers: HashMap<String, MockHandler>,
+    requests: StdMutex<Vec<UAssetRequest>>,
+    workers: usize,
+}
+
+impl MockToolkit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer `action` with the result of `handler`
+    pub fn on<F>(mut self, action: &str, handler: F) -> Self
+    where
+        F: Fn(&UAssetRequest) -> std::result::Result<serde_json::Value, String> + Send + Sync + 'static,
+    {
+        self.handlers.insert(action.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Answer `action` with a fixed `data` payload
+    pub fn respond(self, action: &str, data: serde_json::Value) -> Self {
+        self.on(action, move |_| Ok(data.clone()))
+    }
+
+    /// Make `action` fail with `message`
+    pub fn fail(self, action: &str, message: &str) -> Self {
+        let message = message.to_string();
+        self.on(action, move |_| Err(message.clone()))
+    }
+
+    /// Number of workers reported to callers that split batches
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Every request received so far, in order
+    pub fn requests(&self) -> Vec<UAssetRequest> {
+        self.requests.lock().map(|requests| requests.clone()).unwrap_or_default()
+    }
+
+    /// Requests received so far for one action
+    pub fn requests_for(&self, action: &str) -> Vec<UAssetRequest> {
+        self.requests().into_iter().filter(|request| request.action() == action).collect()
+    }
+}
+
+impl ToolkitBackend for MockToolkit {
+    fn send(&self, request: &UAssetRequest, options: &RequestOptions) -> Result<Reply> {
+        let action = request.action();
+        if options.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+            return Err(ToolkitError::Cancelled { action });
+        }
+        self.requests.lock()
+            .map_err(|e| ToolkitError::Lock(e.to_string()))?
+            .push(request.clone());
+
+        let handler = self.handlers.get(&action).ok_or_else(|| ToolkitError::Failed {
+            action: action.clone(),
+            message: format!("Unknown action: {}", action),
+            stderr: Vec::new(),
+        })?;
+        handler(request)
+            .map(|data| Reply { data, stderr: Vec::new() })
+            .map_err(|message| ToolkitError::Failed { action, message, stderr: Vec::new() })
+    }
+
+    fn workers(&self) -> usize {
+        self.workers.max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BatchStripMipmapsResponse, CancellationToken, IoStoreResult};
+    use serde_json::json;
+
+    fn files(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn unscripted_action_fails() {
+        let mock = MockToolkit::new();
+        let err = mock.batch_detect_texture(&files(&["a.uasset"]), &RequestOptions::default()).unwrap_err();
+        assert!(matches!(err, ToolkitError::Failed { ref action, .. } if action == "batch_detect_texture"), "{err}");
+        assert_eq!(mock.requests_for("batch_detect_texture").len(), 1);
+    }
+
+    #[test]
+    fn typed_helpers_decode_scripted_data() {
+        let mock = MockToolkit::new()
+            .respond("batch_has_inline_texture_data", json!(["a.uasset"]))
+            .on("create_mod_iostore", |request| match request {
+                UAssetRequest::CreateModIoStore { output_path, .. } => Ok(json!({
+                    "utoc_path": format!("{output_path}.utoc"),
+                    "converted_count": 3,
+                })),
+                _ => Err("unexpected request".to_string()),
+            });
+
+        let inline = mock.batch_has_inline_texture_data(&files(&["a.uasset", "b.uasset"]), None, &RequestOptions::default()).unwrap();
+        assert_eq!(inline, vec!["a.uasset"]);
+
+        let result: IoStoreResult = mock
+            .create_mod_iostore("out/Mod_P", "in", None, None, Some(true), None, false, false, &RequestOptions::default())
+            .unwrap();
+        assert_eq!(result.utoc_path, "out/Mod_P.utoc");
+        assert_eq!(result.converted_count, 3);
+        assert_eq!(result.ucas_path, "");
+
+        let err = (&mock as &dyn ToolkitBackend).request::<bool>(&UAssetRequest::IsIoStoreCompressed {
+            file_path: String::new(),
+        });
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn parallel_strip_is_split_across_workers() {
+        let mock = MockToolkit::new().with_workers(2).on("batch_strip_mipmaps_native", |request| match request {
+            UAssetRequest::BatchStripMipmapsNative { file_paths, .. } if file_paths.iter().any(|p| p.contains("bad")) => {
+                Err("corrupt texture".to_string())
+            }
+            UAssetRequest::BatchStripMipmapsNative { file_paths, .. } => Ok(json!({
+                "success_count": file_paths.len(),
+                "results": file_paths.iter().map(|p| json!({ "path": p, "success": true })).collect::<Vec<_>>(),
+            })),
+            _ => Err("unexpected request".to_string()),
+        });
+
+        let response: BatchStripMipmapsResponse = mock
+            .batch_strip_mipmaps(&files(&["T_A.uasset", "T_B.uasset", "T_bad.uasset", "T_D.uasset"]), None, true, &RequestOptions::default())
+            .unwrap();
+        let chunks = mock.requests_for("batch_strip_mipmaps_native");
+        assert_eq!(chunks.len(), 2);
+        // Each chunk already has a worker of its own
+        assert!(chunks.iter().all(|r| matches!(r, UAssetRequest::BatchStripMipmapsNative { parallel: false, .. })));
+        assert_eq!(response.success_count, 2);
+        assert_eq!(response.error_count, 2);
+
+        let (success, _, _, processed) = mock.batch_strip_mipmaps_native(&files(&["T_A.uasset"]), None, false, &RequestOptions::default()).unwrap();
+        assert_eq!(success, 1);
+        assert_eq!(processed, vec!["T_A"]);
+    }
+
+    #[test]
+    fn cancelled_requests_are_not_answered() {
+        let mock = MockToolkit::new().respond("batch_detect_texture", json!(true));
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let options = RequestOptions { timeout: None, cancel: Some(cancel) };
+        let err = mock.batch_detect_texture(&files(&["a.uasset"]), &options).unwrap_err();
+        assert!(matches!(err, ToolkitError::Cancelled { .. }));
+        assert!(mock.requests().is_empty());
+
+        // A cancelled batch fails as a whole instead of counting its chunks as errors
+        let mock = MockToolkit::new().with_workers(2).respond("batch_strip_mipmaps_native", json!({ "success_count": 1 }));
+        let err = mock
+            .batch_strip_mipmaps(&files(&["T_A.uasset", "T_B.uasset"]), None, true, &options)
+            .unwrap_err();
+        assert!(matches!(err, ToolkitError::Cancelled { .. }));
+    }
+}
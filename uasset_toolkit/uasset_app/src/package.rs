@@ -0,0 +1,504 @@
+//! Native reader for Unreal package headers.
+//!
+//! Parses just enough of a package to tell what it contains without starting UAssetTool:
+//! the name map, the import map and the export map of cooked legacy packages (`.uasset`),
+//! and the same tables of zen packages as stored in IoStore containers. Export data
+//! (`.uexp`) is never read, so no usmap is needed.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+mod cityhash;
+mod legacy;
+mod zen;
+
+/// `PACKAGE_FILE_TAG`, the first four bytes of every legacy package
+pub const PACKAGE_FILE_TAG: u32 = 0x9E2A83C1;
+
+/// Object versions a package was saved with (`FPackageFileVersion`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageVersion {
+    pub ue4: i32,
+    pub ue5: i32,
+}
+
+impl PackageVersion {
+    /// Last UE4 object version, also written by every UE5 release
+    pub const UE4_LATEST: i32 = 522;
+
+    pub const UE4_27: Self = Self { ue4: Self::UE4_LATEST, ue5: 0 };
+    pub const UE5_3: Self = Self { ue4: Self::UE4_LATEST, ue5: 1009 };
+    pub const UE5_4: Self = Self { ue4: Self::UE4_LATEST, ue5: 1012 };
+}
+
+impl Default for PackageVersion {
+    /// Marvel Rivals ships unversioned UE 5.3 packages
+    fn default() -> Self {
+        Self::UE5_3
+    }
+}
+
+/// Container layout the header was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageFormat {
+    /// `FPackageFileSummary` followed by name/import/export maps (`.uasset`)
+    Legacy,
+    /// `FZenPackageSummary` as stored in IoStore (`.ucas`) chunks
+    Zen,
+}
+
+/// An object the package depends on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportInfo {
+    /// Package of the import's class, e.g. `/Script/Engine` (empty for zen packages)
+    pub class_package: String,
+    /// Class of the import, e.g. `Class` or `Package` (empty for zen packages)
+    pub class_name: String,
+    /// Name of the imported object; for zen packages the script object path when it is known
+    pub object_name: String,
+}
+
+/// An object defined by the package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportInfo {
+    pub object_name: String,
+    /// Class of the export, e.g. `Texture2D`, or `None` if it can't be resolved from the header
+    pub class_name: Option<String>,
+    pub serial_size: u64,
+    /// Where `serial_size` is stored; only known for legacy packages
+    pub serial_size_field: Option<SerialSizeField>,
+}
+
+/// Location of an export's `SerialSize` in a legacy package header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialSizeField {
+    /// Byte offset from the start of the `.uasset`
+    pub offset: usize,
+    /// 8 bytes since `VER_UE4_64BIT_EXPORTMAP_SERIALSIZES`, 4 before
+    pub width: usize,
+}
+
+/// Name, import and export tables of a package
+#[derive(Debug, Clone)]
+pub struct PackageHeader {
+    pub format: PackageFormat,
+    pub package_name: String,
+    pub package_flags: u32,
+    /// Versions stored in the package, `None` for unversioned packages
+    pub version: Option<PackageVersion>,
+    pub names: Vec<String>,
+    pub imports: Vec<ImportInfo>,
+    pub exports: Vec<ExportInfo>,
+}
+
+impl PackageHeader {
+    /// Parse a legacy or zen package header. `fallback` is used for unversioned legacy
+    /// packages, which don't record the versions their layout depends on.
+    pub fn parse(data: &[u8], fallback: PackageVersion) -> Result<Self, PackageError> {
+        let tag = data.get(..4).map(|tag| u32::from_le_bytes(tag.try_into().unwrap()));
+        match tag {
+            Some(PACKAGE_FILE_TAG) => legacy::parse(data, fallback),
+            Some(tag) if tag == PACKAGE_FILE_TAG.swap_bytes() => Err(PackageError::Unsupported(
+                "big-endian package".to_string(),
+            )),
+            _ => zen::parse(data),
+        }
+    }
+
+    /// Read the header of a `.uasset` file (or an extracted zen package)
+    pub fn read(path: &Path, fallback: PackageVersion) -> Result<Self, PackageError> {
+        Self::parse(&std::fs::read(path)?, fallback)
+    }
+
+    /// Resolved classes of all exports, in export order
+    pub fn export_classes(&self) -> impl Iterator<Item = &str> {
+        self.exports.iter().filter_map(|export| export.class_name.as_deref())
+    }
+
+    /// Whether any export is an instance of one of `classes`
+    pub fn has_export_of_class(&self, classes: &[&str]) -> bool {
+        self.export_classes().any(|class| classes.contains(&class))
+    }
+}
+
+/// Set the `SerialSize` of export `index` in the legacy package `data` to `new_size`.
+///
+/// Only that export's field is written, at the offset found by walking the summary and the
+/// export map. The header is then parsed again to check the export reads back `new_size` and
+/// every other table entry is unchanged; if not, `data` is restored and an error returned.
+pub fn patch_serial_size(data: &mut [u8], fallback: PackageVersion, index: usize, new_size: u64) -> Result<(), PackageError> {
+    let before = PackageHeader::parse(data, fallback)?;
+    if before.format != PackageFormat::Legacy {
+        return Err(PackageError::Unsupported("SerialSize patching of zen packages".to_string()));
+    }
+    let export = before.exports.get(index).ok_or_else(|| {
+        PackageError::Malformed(format!("export index {index} out of range ({} exports)", before.exports.len()))
+    })?;
+    let field = export
+        .serial_size_field
+        .ok_or_else(|| PackageError::Malformed(format!("no SerialSize location for export {}", export.object_name)))?;
+
+    let too_large = || PackageError::Malformed(format!("SerialSize {new_size} doesn't fit in {} bytes", field.width));
+    let bytes = match field.width {
+        8 => i64::try_from(new_size).map_err(|_| too_large())?.to_le_bytes().to_vec(),
+        _ => i32::try_from(new_size).map_err(|_| too_large())?.to_le_bytes().to_vec(),
+    };
+    let range = field.offset..field.offset + field.width;
+    let original = data[range.clone()].to_vec();
+    data[range.clone()].copy_from_slice(&bytes);
+
+    let verified = PackageHeader::parse(data, fallback).and_then(|after| {
+        let mut expected = before.clone();
+        expected.exports[index].serial_size = new_size;
+        if after.names != expected.names || after.imports != expected.imports || after.exports != expected.exports {
+            return Err(PackageError::Malformed(format!(
+                "re-reading the header after patching export {} didn't give the expected tables",
+                expected.exports[index].object_name
+            )));
+        }
+        Ok(())
+    });
+    if let Err(e) = verified {
+        data[range].copy_from_slice(&original);
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PackageError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("unexpected end of package header at offset {0}")]
+    UnexpectedEof(usize),
+
+    #[error("malformed package header: {0}")]
+    Malformed(String),
+
+    #[error("unsupported package: {0}")]
+    Unsupported(String),
+}
+
+/// Little-endian cursor over the header bytes
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(crate) fn at(data: &'a [u8], pos: usize) -> Result<Self, PackageError> {
+        if pos > data.len() {
+            return Err(PackageError::UnexpectedEof(pos));
+        }
+        Ok(Self { data, pos })
+    }
+
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], PackageError> {
+        if len > self.remaining() {
+            return Err(PackageError::UnexpectedEof(self.pos));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub(crate) fn skip(&mut self, len: usize) -> Result<(), PackageError> {
+        self.bytes(len).map(|_| ())
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, PackageError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn i32(&mut self) -> Result<i32, PackageError> {
+        Ok(i32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, PackageError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn i64(&mut self) -> Result<i64, PackageError> {
+        Ok(i64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    /// Element count of a table whose entries take at least `min_size` bytes each,
+    /// rejecting counts the remaining data can't hold
+    pub(crate) fn count(&self, count: i64, min_size: usize, what: &str) -> Result<usize, PackageError> {
+        let count = usize::try_from(count)
+            .map_err(|_| PackageError::Malformed(format!("negative {what} count {count}")))?;
+        if count.saturating_mul(min_size) > self.remaining() {
+            return Err(PackageError::Malformed(format!("{what} count {count} exceeds header size")));
+        }
+        Ok(count)
+    }
+
+    /// `FString`: length including the terminator, negative for UTF-16
+    pub(crate) fn fstring(&mut self) -> Result<String, PackageError> {
+        let len = self.i32()?;
+        let string = if len < 0 {
+            let units = self.count(-(len as i64), 2, "string")?;
+            let bytes = self.bytes(units * 2)?;
+            let utf16: Vec<u16> = bytes.as_chunks::<2>().0.iter().map(|c| u16::from_le_bytes(*c)).collect();
+            String::from_utf16_lossy(&utf16)
+        } else {
+            let len = self.count(len as i64, 1, "string")?;
+            String::from_utf8_lossy(self.bytes(len)?).into_owned()
+        };
+        Ok(string.trim_end_matches('\0').to_string())
+    }
+}
+
+/// Display form of an `FName`: numbered names get a `_{number - 1}` suffix
+pub(crate) fn name_at(names: &[String], index: u64, number: u32) -> Result<String, PackageError> {
+    let name = usize::try_from(index)
+        .ok()
+        .and_then(|i| names.get(i))
+        .ok_or_else(|| PackageError::Malformed(format!("name index {index} out of range ({} names)", names.len())))?;
+    Ok(match number {
+        0 => name.clone(),
+        n => format!("{}_{}", name, n - 1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: &[&str] = &["/Script/CoreUObject", "/Script/Engine", "Package", "Class", "Texture2D", "T_Hero", "/Game/T_Hero"];
+
+    fn name(s: &str) -> i32 {
+        NAMES.iter().position(|n| *n == s).unwrap() as i32
+    }
+
+    fn fstring(buf: &mut Vec<u8>, s: &str) {
+        buf.extend((s.len() as i32 + 1).to_le_bytes());
+        buf.extend(s.as_bytes());
+        buf.push(0);
+    }
+
+    fn fname(buf: &mut Vec<u8>, s: &str) {
+        buf.extend(name(s).to_le_bytes());
+        buf.extend(0i32.to_le_bytes());
+    }
+
+    fn patch(buf: &mut [u8], at: usize, value: i32) {
+        buf[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Cooked package with a Texture2D export; `version` None writes an unversioned UE 5.3 package
+    fn legacy_package(legacy_version: i32, version: Option<PackageVersion>) -> Vec<u8> {
+        let layout = version.unwrap_or(PackageVersion::UE5_3);
+        let mut buf = Vec::new();
+        buf.extend(PACKAGE_FILE_TAG.to_le_bytes());
+        buf.extend(legacy_version.to_le_bytes());
+        buf.extend(864i32.to_le_bytes());
+        let stored = version.unwrap_or(PackageVersion { ue4: 0, ue5: 0 });
+        buf.extend(stored.ue4.to_le_bytes());
+        if legacy_version <= -8 {
+            buf.extend(stored.ue5.to_le_bytes());
+        }
+        buf.extend(0i32.to_le_bytes()); // licensee
+        buf.extend(0i32.to_le_bytes()); // custom versions
+        buf.extend(0i32.to_le_bytes()); // total header size
+        fstring(&mut buf, "/Game/T_Hero");
+        buf.extend(0x80000000u32.to_le_bytes());
+        let name_fields = buf.len();
+        buf.extend([0; 8]);
+        if layout.ue5 >= 1008 {
+            buf.extend([0; 8]);
+        }
+        buf.extend([0; 8]); // gatherable text
+        let table_fields = buf.len();
+        buf.extend([0; 16]);
+        buf.extend([0; 16]); // depends offset and the rest of the summary
+
+        patch(&mut buf, name_fields, NAMES.len() as i32);
+        let at = buf.len() as i32;
+        patch(&mut buf, name_fields + 4, at);
+        for n in NAMES {
+            fstring(&mut buf, n);
+            buf.extend([0; 4]);
+        }
+
+        patch(&mut buf, table_fields + 8, 2);
+        let at = buf.len() as i32;
+        patch(&mut buf, table_fields + 12, at);
+        for (class_package, class, outer, object) in [
+            ("/Script/CoreUObject", "Package", 0i32, "/Script/Engine"),
+            ("/Script/CoreUObject", "Class", -1, "Texture2D"),
+        ] {
+            fname(&mut buf, class_package);
+            fname(&mut buf, class);
+            buf.extend(outer.to_le_bytes());
+            fname(&mut buf, object);
+            if layout.ue5 >= 1003 {
+                buf.extend([0; 4]);
+            }
+        }
+
+        patch(&mut buf, table_fields, 1);
+        let at = buf.len() as i32;
+        patch(&mut buf, table_fields + 4, at);
+        buf.extend((-2i32).to_le_bytes()); // class: Texture2D import
+        buf.extend([0; 12]); // super, template, outer
+        fname(&mut buf, "T_Hero");
+        buf.extend([0; 4]);
+        buf.extend(1234i64.to_le_bytes());
+        buf.extend([0; 8 + 12]);
+        if layout.ue5 < 1005 {
+            buf.extend([0; 16]);
+        }
+        if layout.ue5 >= 1006 {
+            buf.extend([0; 4]);
+        }
+        buf.extend([0; 12]);
+        if layout.ue5 >= 1003 {
+            buf.extend([0; 4]);
+        }
+        buf.extend([0; 20]);
+        if layout.ue5 >= 1010 {
+            buf.extend([0; 16]);
+        }
+        buf
+    }
+
+    fn assert_texture(header: &PackageHeader) {
+        assert_eq!(header.package_name, "/Game/T_Hero");
+        assert_eq!(header.exports.len(), 1);
+        assert_eq!(header.exports[0].object_name, "T_Hero");
+        assert_eq!(header.exports[0].class_name.as_deref(), Some("Texture2D"));
+        assert!(header.has_export_of_class(&["Texture2D"]));
+        assert!(!header.has_export_of_class(&["StaticMesh", "SkeletalMesh"]));
+    }
+
+    #[test]
+    fn legacy_unversioned_package() {
+        let header = PackageHeader::parse(&legacy_package(-8, None), PackageVersion::default()).unwrap();
+        assert_eq!(header.format, PackageFormat::Legacy);
+        assert_eq!(header.version, None);
+        assert_eq!(header.exports[0].serial_size, 1234);
+        assert_eq!(header.imports[1].class_name, "Class");
+        assert_texture(&header);
+    }
+
+    #[test]
+    fn legacy_versioned_packages() {
+        for (legacy_version, version) in [(-7, PackageVersion::UE4_27), (-8, PackageVersion::UE5_4)] {
+            let header = PackageHeader::parse(&legacy_package(legacy_version, Some(version)), PackageVersion::default()).unwrap();
+            assert_eq!(header.version, Some(version));
+            assert_texture(&header);
+        }
+    }
+
+    #[test]
+    fn legacy_newer_versions_are_rejected() {
+        let data = legacy_package(-8, Some(PackageVersion { ue4: 522, ue5: 1017 }));
+        assert!(matches!(PackageHeader::parse(&data, PackageVersion::default()), Err(PackageError::Unsupported(_))));
+    }
+
+    /// Zen package with a Texture2D export, in the UE 5.3 layout or the older 5.0-5.2 one
+    fn zen_package(layout_5_3: bool) -> Vec<u8> {
+        let summary_size = if layout_5_3 { 52 } else { 44 };
+        let mut buf = vec![0u8; summary_size];
+        patch(&mut buf, 8, name("/Game/T_Hero"));
+
+        buf.extend((NAMES.len() as u32).to_le_bytes());
+        buf.extend((NAMES.iter().map(|n| n.len()).sum::<usize>() as u32).to_le_bytes());
+        buf.extend(0u64.to_le_bytes());
+        buf.extend(vec![0; NAMES.len() * 8]);
+        for n in NAMES {
+            buf.extend((n.len() as u16).to_be_bytes());
+        }
+        for n in NAMES {
+            buf.extend(n.as_bytes());
+        }
+
+        let hashes = buf.len() as i32;
+        patch(&mut buf, 24, hashes);
+        patch(&mut buf, 28, hashes);
+        let texture2d = zen::script_object_hash("/Script/Engine.Texture2D") | (1 << 62);
+        buf.extend(texture2d.to_le_bytes());
+        buf.extend((zen::script_object_hash("/Script/Game.Unknown") | (1 << 62)).to_le_bytes());
+
+        let at = buf.len() as i32;
+        patch(&mut buf, 32, at);
+        buf.extend(0u64.to_le_bytes());
+        buf.extend(1234u64.to_le_bytes());
+        buf.extend((name("T_Hero") as u32).to_le_bytes());
+        buf.extend(0u32.to_le_bytes());
+        buf.extend(u64::MAX.to_le_bytes()); // outer
+        buf.extend(texture2d.to_le_bytes());
+        buf.extend([0; 32]);
+
+        let end = buf.len() as i32;
+        for field in if layout_5_3 { 36..52 } else { 36..44 }.step_by(4) {
+            patch(&mut buf, field, end);
+        }
+        patch(&mut buf, 4, end);
+        buf
+    }
+
+    #[test]
+    fn serial_size_patch_targets_the_export_entry() {
+        for (legacy_version, version) in [(-7, Some(PackageVersion::UE4_27)), (-8, None)] {
+            let mut data = legacy_package(legacy_version, version);
+            // export data holding the same value as the SerialSize field
+            let decoy = data.len();
+            data.extend(1234i64.to_le_bytes());
+
+            patch_serial_size(&mut data, PackageVersion::default(), 0, 4321).unwrap();
+            let header = PackageHeader::parse(&data, PackageVersion::default()).unwrap();
+            assert_eq!(header.exports[0].serial_size, 4321);
+            assert_eq!(&data[decoy..], 1234i64.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn serial_size_patch_errors_leave_data_untouched() {
+        let original = legacy_package(-8, None);
+        let mut data = original.clone();
+        assert!(matches!(patch_serial_size(&mut data, PackageVersion::default(), 1, 4321), Err(PackageError::Malformed(_))));
+        assert_eq!(data, original);
+
+        let mut data = zen_package(true);
+        assert!(matches!(patch_serial_size(&mut data, PackageVersion::default(), 0, 4321), Err(PackageError::Unsupported(_))));
+    }
+
+    #[test]
+    fn zen_packages() {
+        for layout_5_3 in [true, false] {
+            let header = PackageHeader::parse(&zen_package(layout_5_3), PackageVersion::default()).unwrap();
+            assert_eq!(header.format, PackageFormat::Zen);
+            assert_eq!(header.names.len(), NAMES.len());
+            assert_eq!(header.imports[0].object_name, "/Script/Engine.Texture2D");
+            assert!(header.imports[1].object_name.starts_with("0x"));
+            assert_eq!(header.exports[0].serial_size, 1234);
+            assert_texture(&header);
+        }
+    }
+
+    #[test]
+    fn truncated_headers_are_errors() {
+        for data in [legacy_package(-8, None), zen_package(true)] {
+            for len in [0, 3, 20, data.len() / 2, data.len() - 1] {
+                assert!(PackageHeader::parse(&data[..len], PackageVersion::default()).is_err(), "length {len}");
+            }
+        }
+        assert!(PackageHeader::parse(b"not an unreal package at all", PackageVersion::default()).is_err());
+    }
+}
@@ -0,0 +1,237 @@
+//! `FPackageFileSummary` and the tables it points to, as written by the cooker to `.uasset` files
+
+use super::{name_at, ExportInfo, ImportInfo, PackageError, PackageFormat, PackageHeader, PackageVersion, Reader, SerialSizeField};
+
+/// `PKG_FilterEditorOnly`: set on cooked packages, which drop editor-only summary fields
+const PKG_FILTER_EDITOR_ONLY: u32 = 0x80000000;
+
+// EUnrealEngineObjectUE4Version
+const VER_UE4_LOAD_FOR_EDITOR_GAME: i32 = 365;
+const VER_UE4_SERIALIZE_TEXT_IN_PACKAGES: i32 = 459;
+const VER_UE4_COOKED_ASSETS_IN_EDITOR_SUPPORT: i32 = 485;
+const VER_UE4_NAME_HASHES_SERIALIZED: i32 = 504;
+const VER_UE4_PRELOAD_DEPENDENCIES_IN_COOKED_EXPORTS: i32 = 507;
+const VER_UE4_TEMPLATE_INDEX_IN_COOKED_EXPORTS: i32 = 508;
+const VER_UE4_64BIT_EXPORTMAP_SERIALSIZES: i32 = 511;
+const VER_UE4_ADDED_PACKAGE_SUMMARY_LOCALIZATION_ID: i32 = 516;
+const VER_UE4_NON_OUTER_PACKAGE_IMPORT: i32 = 520;
+
+// EUnrealEngineObjectUE5Version
+const VER_UE5_OPTIONAL_RESOURCES: i32 = 1003;
+const VER_UE5_REMOVE_OBJECT_EXPORT_PACKAGE_GUID: i32 = 1005;
+const VER_UE5_TRACK_OBJECT_EXPORT_IS_INHERITED: i32 = 1006;
+const VER_UE5_ADD_SOFTOBJECTPATH_LIST: i32 = 1008;
+const VER_UE5_SCRIPT_SERIALIZATION_OFFSET: i32 = 1010;
+/// Newest layout this reader knows; later versions moved summary fields around
+const VER_UE5_LATEST_SUPPORTED: i32 = 1012;
+
+pub(super) fn parse(data: &[u8], fallback: PackageVersion) -> Result<PackageHeader, PackageError> {
+    let mut r = Reader::new(data);
+    r.u32()?; // tag, checked by the caller
+
+    // -4 dropped the UE3 version, -8 added the UE5 version
+    let legacy_version = r.i32()?;
+    if !(-8..=-2).contains(&legacy_version) {
+        return Err(PackageError::Unsupported(format!("legacy file version {legacy_version}")));
+    }
+    if legacy_version != -4 {
+        r.i32()?; // LegacyUE3Version
+    }
+    let ue4 = r.i32()?;
+    let ue5 = if legacy_version <= -8 { r.i32()? } else { 0 };
+    let licensee = r.i32()?;
+    skip_custom_versions(&mut r, legacy_version)?;
+
+    let unversioned = ue4 == 0 && ue5 == 0 && licensee == 0;
+    let version = if unversioned { fallback } else { PackageVersion { ue4, ue5 } };
+    if version.ue5 > VER_UE5_LATEST_SUPPORTED {
+        return Err(PackageError::Unsupported(format!("UE5 object version {}", version.ue5)));
+    }
+
+    r.i32()?; // TotalHeaderSize
+    let package_name = r.fstring()?;
+    let package_flags = r.u32()?;
+    let filter_editor_only = package_flags & PKG_FILTER_EDITOR_ONLY != 0;
+
+    let name_count = r.i32()?;
+    let name_offset = r.i32()?;
+    if version.ue5 >= VER_UE5_ADD_SOFTOBJECTPATH_LIST {
+        r.skip(8)?; // SoftObjectPathsCount, SoftObjectPathsOffset
+    }
+    if !filter_editor_only && version.ue4 >= VER_UE4_ADDED_PACKAGE_SUMMARY_LOCALIZATION_ID {
+        r.fstring()?; // LocalizationId
+    }
+    if version.ue4 >= VER_UE4_SERIALIZE_TEXT_IN_PACKAGES {
+        r.skip(8)?; // GatherableTextDataCount, GatherableTextDataOffset
+    }
+    let export_count = r.i32()?;
+    let export_offset = r.i32()?;
+    let import_count = r.i32()?;
+    let import_offset = r.i32()?;
+
+    let names = read_names(data, name_offset, name_count, version)?;
+    let imports = read_imports(data, import_offset, import_count, version, filter_editor_only, &names)?;
+    let raw_exports = read_exports(data, export_offset, export_count, version, &names)?;
+
+    let exports = raw_exports
+        .iter()
+        .map(|(class_index, object_name, serial_size, field)| ExportInfo {
+            object_name: object_name.clone(),
+            class_name: resolve_class(*class_index, &imports, &raw_exports),
+            serial_size: *serial_size,
+            serial_size_field: Some(*field),
+        })
+        .collect();
+
+    Ok(PackageHeader {
+        format: PackageFormat::Legacy,
+        package_name,
+        package_flags,
+        version: (!unversioned).then_some(version),
+        names,
+        imports,
+        exports,
+    })
+}
+
+fn skip_custom_versions(r: &mut Reader<'_>, legacy_version: i32) -> Result<(), PackageError> {
+    let count = r.i32()? as i64;
+    match legacy_version {
+        // ECustomVersionSerializationFormat::Enums: tag, version
+        -2 => r.skip(r.count(count, 8, "custom version")? * 8),
+        // ::Guids: key, version, friendly name
+        -5..=-3 => {
+            for _ in 0..r.count(count, 24, "custom version")? {
+                r.skip(20)?;
+                r.fstring()?;
+            }
+            Ok(())
+        }
+        // ::Optimized: key, version
+        _ => r.skip(r.count(count, 20, "custom version")? * 20),
+    }
+}
+
+fn table_reader<'a>(data: &'a [u8], offset: i32, what: &str) -> Result<Reader<'a>, PackageError> {
+    let offset = usize::try_from(offset)
+        .map_err(|_| PackageError::Malformed(format!("negative {what} offset {offset}")))?;
+    Reader::at(data, offset)
+}
+
+fn read_names(data: &[u8], offset: i32, count: i32, version: PackageVersion) -> Result<Vec<String>, PackageError> {
+    let mut r = table_reader(data, offset, "name map")?;
+    let count = r.count(count as i64, 5, "name")?;
+    let mut names = Vec::with_capacity(count);
+    for _ in 0..count {
+        names.push(r.fstring()?);
+        if version.ue4 >= VER_UE4_NAME_HASHES_SERIALIZED {
+            r.skip(4)?; // NonCasePreservingHash, CasePreservingHash
+        }
+    }
+    Ok(names)
+}
+
+fn read_fname(r: &mut Reader<'_>, names: &[String]) -> Result<String, PackageError> {
+    let index = r.i32()?;
+    let number = r.u32()?;
+    let index = u64::try_from(index).map_err(|_| PackageError::Malformed(format!("negative name index {index}")))?;
+    name_at(names, index, number)
+}
+
+fn read_imports(
+    data: &[u8],
+    offset: i32,
+    count: i32,
+    version: PackageVersion,
+    filter_editor_only: bool,
+    names: &[String],
+) -> Result<Vec<ImportInfo>, PackageError> {
+    let mut r = table_reader(data, offset, "import map")?;
+    let count = r.count(count as i64, 28, "import")?;
+    let mut imports = Vec::with_capacity(count);
+    for _ in 0..count {
+        let class_package = read_fname(&mut r, names)?;
+        let class_name = read_fname(&mut r, names)?;
+        r.i32()?; // OuterIndex
+        let object_name = read_fname(&mut r, names)?;
+        if !filter_editor_only && version.ue4 >= VER_UE4_NON_OUTER_PACKAGE_IMPORT {
+            r.skip(8)?; // PackageName
+        }
+        if version.ue5 >= VER_UE5_OPTIONAL_RESOURCES {
+            r.skip(4)?; // bImportOptional
+        }
+        imports.push(ImportInfo { class_package, class_name, object_name });
+    }
+    Ok(imports)
+}
+
+/// ClassIndex, ObjectName, SerialSize and where SerialSize is stored, of one export
+type RawExport = (i32, String, u64, SerialSizeField);
+
+fn read_exports(
+    data: &[u8],
+    offset: i32,
+    count: i32,
+    version: PackageVersion,
+    names: &[String],
+) -> Result<Vec<RawExport>, PackageError> {
+    let mut r = table_reader(data, offset, "export map")?;
+    let count = r.count(count as i64, 56, "export")?;
+    let mut exports = Vec::with_capacity(count);
+    for _ in 0..count {
+        let class_index = r.i32()?;
+        r.i32()?; // SuperIndex
+        if version.ue4 >= VER_UE4_TEMPLATE_INDEX_IN_COOKED_EXPORTS {
+            r.i32()?; // TemplateIndex
+        }
+        r.i32()?; // OuterIndex
+        let object_name = read_fname(&mut r, names)?;
+        r.u32()?; // ObjectFlags
+        let field_offset = r.pos();
+        let (serial_size, width) = if version.ue4 >= VER_UE4_64BIT_EXPORTMAP_SERIALSIZES {
+            let size = r.i64()?;
+            r.i64()?; // SerialOffset
+            (size, 8)
+        } else {
+            let size = r.i32()? as i64;
+            r.i32()?;
+            (size, 4)
+        };
+        r.skip(12)?; // bForcedExport, bNotForClient, bNotForServer
+        if version.ue5 < VER_UE5_REMOVE_OBJECT_EXPORT_PACKAGE_GUID {
+            r.skip(16)?; // PackageGuid
+        }
+        if version.ue5 >= VER_UE5_TRACK_OBJECT_EXPORT_IS_INHERITED {
+            r.skip(4)?; // bIsInheritedInstance
+        }
+        r.u32()?; // PackageFlags
+        if version.ue4 >= VER_UE4_LOAD_FOR_EDITOR_GAME {
+            r.skip(4)?; // bNotAlwaysLoadedForEditorGame
+        }
+        if version.ue4 >= VER_UE4_COOKED_ASSETS_IN_EDITOR_SUPPORT {
+            r.skip(4)?; // bIsAsset
+        }
+        if version.ue5 >= VER_UE5_OPTIONAL_RESOURCES {
+            r.skip(4)?; // bGeneratePublicHash
+        }
+        if version.ue4 >= VER_UE4_PRELOAD_DEPENDENCIES_IN_COOKED_EXPORTS {
+            r.skip(20)?; // FirstExportDependency and the four dependency counts
+        }
+        if version.ue5 >= VER_UE5_SCRIPT_SERIALIZATION_OFFSET {
+            r.skip(16)?; // ScriptSerializationStartOffset, ScriptSerializationEndOffset
+        }
+        let serial_size = u64::try_from(serial_size)
+            .map_err(|_| PackageError::Malformed(format!("negative serial size of export {object_name}")))?;
+        exports.push((class_index, object_name, serial_size, SerialSizeField { offset: field_offset, width }));
+    }
+    Ok(exports)
+}
+
+/// `FPackageIndex` of an export's class: negative for imports, positive for exports, 0 for `UClass`
+fn resolve_class(class_index: i32, imports: &[ImportInfo], exports: &[RawExport]) -> Option<String> {
+    match class_index {
+        0 => Some("Class".to_string()),
+        i if i < 0 => imports.get((-(i as i64) - 1) as usize).map(|import| import.object_name.clone()),
+        i => exports.get(i as usize - 1).map(|(_, name, _, _)| name.clone()),
+    }
+}
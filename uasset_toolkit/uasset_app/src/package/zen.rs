@@ -0,0 +1,291 @@
+//! `FZenPackageSummary` and the tables it points to, as stored in IoStore containers
+
+use super::cityhash::cityhash64;
+use super::{name_at, ExportInfo, ImportInfo, PackageError, PackageFormat, PackageHeader, PackageVersion, Reader};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+/// Size of `FExportMapEntry`
+const EXPORT_ENTRY_SIZE: usize = 72;
+
+/// Script classes that assets commonly instantiate. Zen packages only store the hash of a
+/// script import's path, so these are the classes that can be named without the game's
+/// script object table.
+const KNOWN_SCRIPT_CLASSES: &[&str] = &[
+    "/Script/CoreUObject.Class",
+    "/Script/CoreUObject.Function",
+    "/Script/CoreUObject.Package",
+    "/Script/CoreUObject.ScriptStruct",
+    "/Script/CoreUObject.Enum",
+    "/Script/CoreUObject.UserDefinedStruct",
+    "/Script/Engine.AnimBlueprintGeneratedClass",
+    "/Script/Engine.AnimMontage",
+    "/Script/Engine.AnimSequence",
+    "/Script/Engine.BlendSpace",
+    "/Script/Engine.BlueprintGeneratedClass",
+    "/Script/Engine.BodySetup",
+    "/Script/Engine.CurveFloat",
+    "/Script/Engine.CurveTable",
+    "/Script/Engine.DataTable",
+    "/Script/Engine.Font",
+    "/Script/Engine.FontFace",
+    "/Script/Engine.Level",
+    "/Script/Engine.Material",
+    "/Script/Engine.MaterialFunction",
+    "/Script/Engine.MaterialInstanceConstant",
+    "/Script/Engine.MaterialParameterCollection",
+    "/Script/Engine.NavCollision",
+    "/Script/Engine.ParticleSystem",
+    "/Script/Engine.PhysicsAsset",
+    "/Script/Engine.SceneComponent",
+    "/Script/Engine.SkeletalMesh",
+    "/Script/Engine.SkeletalMeshComponent",
+    "/Script/Engine.SkeletalMeshSocket",
+    "/Script/Engine.Skeleton",
+    "/Script/Engine.SoundCue",
+    "/Script/Engine.SoundWave",
+    "/Script/Engine.StaticMesh",
+    "/Script/Engine.StaticMeshComponent",
+    "/Script/Engine.StringTable",
+    "/Script/Engine.Texture2D",
+    "/Script/Engine.Texture2DArray",
+    "/Script/Engine.TextureCube",
+    "/Script/Engine.TextureRenderTarget2D",
+    "/Script/Engine.VolumeTexture",
+    "/Script/Engine.World",
+    "/Script/Niagara.NiagaraEmitter",
+    "/Script/Niagara.NiagaraSystem",
+    "/Script/UMG.WidgetBlueprintGeneratedClass",
+];
+
+static SCRIPT_CLASSES_BY_HASH: LazyLock<HashMap<u64, &'static str>> = LazyLock::new(|| {
+    KNOWN_SCRIPT_CLASSES.iter().map(|path| (script_object_hash(path), *path)).collect()
+});
+
+/// `FPackageObjectIndex::GenerateImportHashFromObjectPath`: CityHash64 of the lowercased
+/// UTF-16 path with `.` and `:` replaced by `/`, with the two type bits cleared
+pub(crate) fn script_object_hash(path: &str) -> u64 {
+    let bytes: Vec<u8> = path
+        .chars()
+        .map(|c| if c == '.' || c == ':' { '/' } else { c.to_ascii_lowercase() })
+        .collect::<String>()
+        .encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect();
+    cityhash64(&bytes) & !(3u64 << 62)
+}
+
+/// `FPackageObjectIndex`: two type bits above a 62-bit index or hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ObjectIndex {
+    Export(u64),
+    ScriptImport(u64),
+    PackageImport(u64),
+    Null,
+}
+
+impl From<u64> for ObjectIndex {
+    fn from(value: u64) -> Self {
+        let index = value & !(3u64 << 62);
+        match value >> 62 {
+            0 => ObjectIndex::Export(index),
+            1 => ObjectIndex::ScriptImport(index),
+            2 => ObjectIndex::PackageImport(index),
+            _ => ObjectIndex::Null,
+        }
+    }
+}
+
+/// Offsets of `FZenPackageSummary`, relative to the start of the package
+struct Summary {
+    has_versioning_info: bool,
+    header_size: usize,
+    name: (u64, u32),
+    package_flags: u32,
+    import_map_offset: usize,
+    export_map_offset: usize,
+    export_bundle_entries_offset: usize,
+    /// End of the summary; the versioning info and name map follow
+    end: usize,
+}
+
+fn offset(value: i32, header_size: usize, what: &str) -> Result<usize, PackageError> {
+    usize::try_from(value)
+        .ok()
+        .filter(|&offset| offset <= header_size)
+        .ok_or_else(|| PackageError::Malformed(format!("{what} offset {value} outside header")))
+}
+
+fn read_summary(data: &[u8]) -> Result<Summary, PackageError> {
+    let mut r = Reader::new(data);
+    let has_versioning_info = match r.u32()? {
+        0 => false,
+        1 => true,
+        other => return Err(PackageError::Malformed(format!("not a package (first word {other:#x})"))),
+    };
+    let header_size = r.u32()? as usize;
+    if header_size > data.len() {
+        return Err(PackageError::Malformed(format!("header size {header_size} exceeds data size {}", data.len())));
+    }
+    let name = ((r.u32()? & 0x3FFF_FFFF) as u64, r.u32()?);
+    let package_flags = r.u32()?;
+    r.u32()?; // CookedHeaderSize
+    let imported_public_export_hashes = offset(r.i32()?, header_size, "imported export hashes")?;
+    let import_map_offset = offset(r.i32()?, header_size, "import map")?;
+    let export_map_offset = offset(r.i32()?, header_size, "export map")?;
+    let export_bundle_entries_offset = offset(r.i32()?, header_size, "export bundle")?;
+
+    // UE 5.3 replaced GraphDataOffset with three dependency offsets. In the older layout the
+    // name count follows GraphDataOffset and is far smaller than it, so the 5.3 offsets are
+    // only accepted if they ascend.
+    let graph_or_dependency_headers = r.i32()?;
+    let summary_end_5_0 = r.pos();
+    let chain = [r.i32(), r.i32()];
+    let is_5_3_layout = match chain {
+        [Ok(entries), Ok(package_names)] => {
+            let offsets = [export_bundle_entries_offset as i64, graph_or_dependency_headers as i64, entries as i64, package_names as i64];
+            offsets.windows(2).all(|w| w[0] <= w[1]) && package_names as i64 <= header_size as i64
+        }
+        _ => false,
+    };
+    let end = if is_5_3_layout { r.pos() } else { summary_end_5_0 };
+
+    if !(imported_public_export_hashes <= import_map_offset
+        && import_map_offset <= export_map_offset
+        && export_map_offset <= export_bundle_entries_offset)
+    {
+        return Err(PackageError::Malformed("zen header tables out of order".to_string()));
+    }
+
+    Ok(Summary {
+        has_versioning_info,
+        header_size,
+        name,
+        package_flags,
+        import_map_offset,
+        export_map_offset,
+        export_bundle_entries_offset,
+        end,
+    })
+}
+
+/// `FZenPackageVersioningInfo`
+fn read_versioning_info(r: &mut Reader) -> Result<PackageVersion, PackageError> {
+    r.u32()?; // ZenVersion
+    let ue4 = r.i32()?;
+    let ue5 = r.i32()?;
+    r.i32()?; // LicenseeVersion
+    let count = r.i32()? as i64;
+    r.skip(r.count(count, 20, "custom version")? * 20)?;
+    Ok(PackageVersion { ue4, ue5 })
+}
+
+/// Name batch written by `SaveNameBatch`: hashes, then big-endian length headers, then the strings
+fn read_name_batch(r: &mut Reader) -> Result<Vec<String>, PackageError> {
+    let count = r.u32()? as i64;
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    let string_bytes = r.u32()? as usize;
+    r.u64()?; // HashVersion
+    let count = r.count(count, 10, "name")?;
+    r.skip(count * 8)?; // hashes
+    let headers = r.bytes(count * 2)?;
+    let strings = r.bytes(string_bytes)?;
+
+    let mut names = Vec::with_capacity(count);
+    let mut at = 0;
+    for header in headers.as_chunks::<2>().0 {
+        let utf16 = header[0] & 0x80 != 0;
+        let len = (((header[0] & 0x7F) as usize) << 8) | header[1] as usize;
+        let name = if utf16 {
+            at += at % 2;
+            let bytes = strings.get(at..at + len * 2).ok_or(PackageError::UnexpectedEof(r.pos()))?;
+            at += len * 2;
+            let units: Vec<u16> = bytes.as_chunks::<2>().0.iter().map(|c| u16::from_le_bytes(*c)).collect();
+            String::from_utf16_lossy(&units)
+        } else {
+            let bytes = strings.get(at..at + len).ok_or(PackageError::UnexpectedEof(r.pos()))?;
+            at += len;
+            String::from_utf8_lossy(bytes).into_owned()
+        };
+        names.push(name);
+    }
+    Ok(names)
+}
+
+pub(super) fn parse(data: &[u8]) -> Result<PackageHeader, PackageError> {
+    let summary = read_summary(data)?;
+    let header = &data[..summary.header_size];
+
+    let mut r = Reader::at(header, summary.end)?;
+    let version = if summary.has_versioning_info { Some(read_versioning_info(&mut r)?) } else { None };
+    let names = read_name_batch(&mut r)?;
+    let package_name = name_at(&names, summary.name.0, summary.name.1)?;
+
+    let import_count = (summary.export_map_offset - summary.import_map_offset) / 8;
+    let mut r = Reader::at(header, summary.import_map_offset)?;
+    let mut import_indices = Vec::with_capacity(import_count);
+    for _ in 0..import_count {
+        import_indices.push(ObjectIndex::from(r.u64()?));
+    }
+    let imports = import_indices
+        .iter()
+        .map(|index| ImportInfo {
+            class_package: String::new(),
+            class_name: String::new(),
+            object_name: match index {
+                ObjectIndex::ScriptImport(hash) => SCRIPT_CLASSES_BY_HASH
+                    .get(hash)
+                    .map(|path| path.to_string())
+                    .unwrap_or_else(|| format!("{hash:#018x}")),
+                ObjectIndex::PackageImport(index) => format!("{index:#018x}"),
+                _ => String::new(),
+            },
+        })
+        .collect();
+
+    let export_count = (summary.export_bundle_entries_offset - summary.export_map_offset) / EXPORT_ENTRY_SIZE;
+    let mut r = Reader::at(header, summary.export_map_offset)?;
+    let mut raw_exports = Vec::with_capacity(export_count);
+    for _ in 0..export_count {
+        r.u64()?; // CookedSerialOffset
+        let serial_size = r.u64()?;
+        let object_name = name_at(&names, (r.u32()? & 0x3FFF_FFFF) as u64, r.u32()?)?;
+        r.u64()?; // OuterIndex
+        let class_index = ObjectIndex::from(r.u64()?);
+        r.skip(EXPORT_ENTRY_SIZE - 40)?; // SuperIndex .. padding
+        raw_exports.push((class_index, object_name, serial_size));
+    }
+
+    let exports = raw_exports
+        .iter()
+        .map(|(class_index, object_name, serial_size)| ExportInfo {
+            object_name: object_name.clone(),
+            class_name: resolve_class(*class_index, &raw_exports),
+            serial_size: *serial_size,
+            serial_size_field: None,
+        })
+        .collect();
+
+    Ok(PackageHeader {
+        format: PackageFormat::Zen,
+        package_name,
+        package_flags: summary.package_flags,
+        version,
+        names,
+        imports,
+        exports,
+    })
+}
+
+fn resolve_class(class_index: ObjectIndex, exports: &[(ObjectIndex, String, u64)]) -> Option<String> {
+    match class_index {
+        ObjectIndex::ScriptImport(hash) => SCRIPT_CLASSES_BY_HASH
+            .get(&hash)
+            .map(|path| path.rsplit(['.', '/']).next().unwrap_or(path).to_string()),
+        ObjectIndex::Export(index) => exports.get(index as usize).map(|(_, name, _)| name.clone()),
+        // Classes from other packages are only known by their public export hash
+        ObjectIndex::PackageImport(_) | ObjectIndex::Null => None,
+    }
+}
@@ -0,0 +1,151 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex as StdMutex};
+use std::time::Duration;
+
+use crate::process::{SyncChildProcess, POLL_INTERVAL};
+use crate::{Result, ToolkitError};
+
+/// Worker pool settings for a `SyncToolkit`
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Number of UAssetTool processes that may run requests at the same time
+    pub workers: usize,
+    /// Timeout applied to requests that don't set their own
+    pub request_timeout: Duration,
+    /// How long a new process may take to answer the protocol handshake
+    pub handshake_timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        // Each UAssetTool process is multi-threaded itself, so a few workers are enough
+        let cpus = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Self {
+            workers: (cpus / 4).clamp(1, 4),
+            request_timeout: Duration::from_secs(300), // 5 minutes for large batch operations
+            handshake_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Shared flag used to abort pending and in-flight requests
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Per-request overrides
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    /// Overrides `PoolConfig::request_timeout`
+    pub timeout: Option<Duration>,
+    /// Aborts the request while it waits for a worker or for the tool's response
+    pub cancel: Option<CancellationToken>,
+}
+
+/// Fixed set of worker slots; a slot holds `None` until its process is first needed
+/// or after the process was discarded.
+pub(crate) struct WorkerPool {
+    tool_path: String,
+    config: PoolConfig,
+    idle: StdMutex<Vec<Option<SyncChildProcess>>>,
+    available: Condvar,
+}
+
+impl WorkerPool {
+    pub(crate) fn new(tool_path: String, config: PoolConfig) -> Self {
+        let workers = config.workers.max(1);
+        Self {
+            tool_path,
+            config,
+            idle: StdMutex::new((0..workers).map(|_| None).collect()),
+            available: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    pub(crate) fn tool_path(&self) -> &str {
+        &self.tool_path
+    }
+
+    /// Start a process for a worker slot. A tool that doesn't answer the handshake at all, instead
+    /// of rejecting it, predates it too: it is restarted without one and assumed to speak revision 1.
+    /// Each start tries the handshake again, so one slow start doesn't downgrade the whole pool.
+    fn start(&self) -> Result<SyncChildProcess> {
+        match SyncChildProcess::spawn(&self.tool_path, Some(self.config.handshake_timeout)) {
+            Err(ToolkitError::Handshake(e)) => {
+                log::warn!("[SyncToolkit] {}; restarting UAssetTool without it", e);
+                SyncChildProcess::spawn(&self.tool_path, None)
+            }
+            result => result,
+        }
+    }
+
+    /// Wait for a free worker slot
+    pub(crate) fn checkout(&self, action: &str, cancel: Option<&CancellationToken>) -> Result<WorkerLease<'_>> {
+        let mut idle = self.idle.lock().map_err(|e| ToolkitError::Lock(e.to_string()))?;
+        loop {
+            if let Some(process) = idle.pop() {
+                return Ok(WorkerLease { pool: self, process });
+            }
+            if cancel.is_some_and(|c| c.is_cancelled()) {
+                return Err(ToolkitError::Cancelled { action: action.to_string() });
+            }
+            idle = self.available
+                .wait_timeout(idle, POLL_INTERVAL)
+                .map_err(|e| ToolkitError::Lock(e.to_string()))?
+                .0;
+        }
+    }
+}
+
+/// Exclusive use of one worker slot, returned to the pool on drop
+pub(crate) struct WorkerLease<'a> {
+    pool: &'a WorkerPool,
+    process: Option<SyncChildProcess>,
+}
+
+impl WorkerLease<'_> {
+    /// The slot's process, (re)starting it if it was never started or has exited
+    pub(crate) fn process(&mut self) -> Result<&mut SyncChildProcess> {
+        if let Some(process) = &mut self.process {
+            if !process.is_alive() {
+                log::warn!("[SyncToolkit] UAssetTool worker exited unexpectedly, restarting");
+                self.process = None;
+            }
+        }
+        if self.process.is_none() {
+            self.process = Some(self.pool.start()?);
+        }
+        Ok(self.process.as_mut().unwrap())
+    }
+
+    /// Drop the slot's process so the next request starts a fresh one
+    pub(crate) fn discard(&mut self) {
+        self.process = None;
+    }
+}
+
+impl Drop for WorkerLease<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut idle) = self.pool.idle.lock() {
+            idle.push(self.process.take());
+        }
+        self.pool.available.notify_one();
+    }
+}
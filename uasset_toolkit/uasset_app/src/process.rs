@@ -0,0 +1,255 @@
+use serde::de::DeserializeOwned;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader as StdBufReader, Write};
+use std::path::Path;
+use std::process::{Child as StdChild, ChildStdin as StdChildStdin, Command as StdCommand, Stdio};
+use std::sync::{mpsc, Arc, Mutex as StdMutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+
+use crate::pool::CancellationToken;
+use crate::{HandshakeResponse, Result, ToolkitError, UAssetRequest, UAssetResponse};
+use crate::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+
+/// How often a pending request checks for cancellation
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of stderr lines kept for error reports
+const STDERR_HISTORY: usize = 200;
+
+/// Synchronous child process handle using channel-based communication for timeout support
+pub(crate) struct SyncChildProcess {
+    child: StdChild,
+    stdin: StdChildStdin,
+    response_rx: mpsc::Receiver<std::io::Result<String>>,
+    /// Stderr lines written since the current request was sent
+    stderr: Arc<StdMutex<VecDeque<String>>>,
+    /// Protocol revision agreed on during the handshake
+    pub(crate) protocol_version: u32,
+}
+
+impl SyncChildProcess {
+    /// Start the tool and agree on a protocol revision, waiting up to `handshake` for its answer.
+    /// With no handshake the tool is assumed to speak revision 1.
+    pub(crate) fn spawn(tool_path: &str, handshake: Option<Duration>) -> Result<Self> {
+        log::info!("[SyncToolkit] Starting new UAssetTool process: {}", tool_path);
+
+        if !Path::new(tool_path).exists() {
+            return Err(ToolkitError::ToolNotFound(tool_path.to_string()));
+        }
+
+        let mut cmd = StdCommand::new(tool_path);
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()); // drained by a reader thread so the buffer never fills
+
+        // Pass USMAP_PATH to child process
+        if let Ok(usmap_path) = std::env::var("USMAP_PATH") {
+            cmd.env("USMAP_PATH", &usmap_path);
+            log::info!("[SyncToolkit] Passing USMAP_PATH: {}", usmap_path);
+        }
+
+        #[cfg(windows)]
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+
+        let mut child = cmd.spawn().map_err(ToolkitError::Spawn)?;
+
+        let stdin = child.stdin.take()
+            .ok_or_else(|| ToolkitError::Spawn(std::io::Error::other("failed to get stdin")))?;
+        let stdout = child.stdout.take()
+            .ok_or_else(|| ToolkitError::Spawn(std::io::Error::other("failed to get stdout")))?;
+        let child_stderr = child.stderr.take()
+            .ok_or_else(|| ToolkitError::Spawn(std::io::Error::other("failed to get stderr")))?;
+
+        // Create channel for timeout-safe reading
+        let (tx, rx) = mpsc::channel();
+
+        // Spawn reader thread that sends lines through channel
+        thread::spawn(move || {
+            let reader = StdBufReader::new(stdout);
+            for line in reader.lines() {
+                if tx.send(line).is_err() {
+                    break; // Channel closed, stop reading
+                }
+            }
+        });
+
+        // Spawn stderr thread that keeps recent lines for error reports
+        let stderr = Arc::new(StdMutex::new(VecDeque::new()));
+        let stderr_lines = stderr.clone();
+        thread::spawn(move || {
+            let reader = StdBufReader::new(child_stderr);
+            for line in reader.lines().map_while(|l| l.ok()) {
+                log::debug!("[UAssetTool] {}", line);
+                if let Ok(mut lines) = stderr_lines.lock() {
+                    if lines.len() >= STDERR_HISTORY {
+                        lines.pop_front();
+                    }
+                    lines.push_back(line);
+                }
+            }
+        });
+
+        let mut process = SyncChildProcess {
+            child,
+            stdin,
+            response_rx: rx,
+            stderr,
+            protocol_version: MIN_PROTOCOL_VERSION,
+        };
+        if let Some(timeout) = handshake {
+            // Only an unanswered handshake may mean an older tool; crashes and IO errors are real failures
+            process.protocol_version = process.negotiate_protocol(timeout).map_err(|e| match e {
+                ToolkitError::Timeout { .. } => ToolkitError::Handshake(Box::new(e)),
+                e => e,
+            })?;
+        }
+        log::info!(
+            "[SyncToolkit] UAssetTool process {} started successfully (protocol v{})",
+            process.child.id(),
+            process.protocol_version
+        );
+        Ok(process)
+    }
+
+    /// Agree on a protocol revision. Tools that reject the handshake predate it and speak revision 1;
+    /// tools announcing a revision older than `MIN_PROTOCOL_VERSION` are refused.
+    fn negotiate_protocol(&mut self, timeout: Duration) -> Result<u32> {
+        let request = UAssetRequest::Handshake { protocol_version: PROTOCOL_VERSION };
+        let response = self.exchange(&request, timeout, None)?;
+
+        let tool_version = if response.success {
+            let handshake: HandshakeResponse = decode_data(&request, response.data, self.take_stderr())?;
+            if let Some(tool_version) = &handshake.tool_version {
+                log::info!("[SyncToolkit] UAssetTool version: {}", tool_version);
+            }
+            handshake.protocol_version
+        } else {
+            log::info!("[SyncToolkit] UAssetTool has no handshake support, assuming protocol v{}", MIN_PROTOCOL_VERSION);
+            MIN_PROTOCOL_VERSION
+        };
+
+        let negotiated = tool_version.min(PROTOCOL_VERSION);
+        if negotiated < MIN_PROTOCOL_VERSION {
+            return Err(ToolkitError::UnsupportedProtocol {
+                tool: tool_version,
+                min: MIN_PROTOCOL_VERSION,
+                max: PROTOCOL_VERSION,
+            });
+        }
+        Ok(negotiated)
+    }
+
+    /// Whether the process is still running
+    pub(crate) fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    pub(crate) fn take_stderr(&self) -> Vec<String> {
+        self.stderr.lock().map(|mut lines| lines.drain(..).collect()).unwrap_or_default()
+    }
+
+    /// Write one request and wait for its response line.
+    /// The process is killed when the request times out or is cancelled, since it may still be busy.
+    pub(crate) fn exchange(
+        &mut self,
+        request: &UAssetRequest,
+        timeout: Duration,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<UAssetResponse> {
+        let action = request.action();
+        let request_json = serde_json::to_string(request).map_err(ToolkitError::Encode)?;
+
+        log::info!("[SyncToolkit] Sending request: {}...", &request_json[..std::cmp::min(200, request_json.len())]);
+
+        // Only report stderr written while handling this request
+        self.take_stderr();
+
+        writeln!(self.stdin, "{}", request_json)?;
+        self.stdin.flush()?;
+
+        log::info!("[SyncToolkit] Request sent, waiting for response (timeout: {:?})...", timeout);
+
+        // Skip non-JSON lines (e.g. log output that leaked to stdout) until we get a valid JSON response
+        let deadline = Instant::now() + timeout;
+        loop {
+            if cancel.is_some_and(|c| c.is_cancelled()) {
+                log::warn!("[SyncToolkit] Request '{}' cancelled, stopping UAssetTool process", action);
+                self.kill();
+                return Err(ToolkitError::Cancelled { action });
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                log::error!("[SyncToolkit] TIMEOUT waiting for UAssetTool response after {:?}", timeout);
+                self.kill();
+                return Err(ToolkitError::Timeout { action, timeout, stderr: self.take_stderr() });
+            }
+            match self.response_rx.recv_timeout(remaining.min(POLL_INTERVAL)) {
+                Ok(Ok(line)) => {
+                    let trimmed = line.trim();
+                    if trimmed.is_empty() {
+                        continue;
+                    }
+                    // JSON responses start with '{' — skip anything else (log lines)
+                    if !trimmed.starts_with('{') {
+                        log::warn!("[SyncToolkit] Skipping non-JSON stdout line: {}", &trimmed[..std::cmp::min(200, trimmed.len())]);
+                        continue;
+                    }
+                    log::info!("[SyncToolkit] Got response: {} bytes", line.len());
+                    return serde_json::from_str::<UAssetResponse>(&line).map_err(|source| {
+                        ToolkitError::InvalidResponse {
+                            action,
+                            line: line[..std::cmp::min(500, line.len())].to_string(),
+                            source,
+                            stderr: self.take_stderr(),
+                        }
+                    });
+                }
+                Ok(Err(e)) => return Err(ToolkitError::Io(e)),
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    // Give the stderr thread a moment to collect the crash output
+                    thread::sleep(POLL_INTERVAL);
+                    return Err(ToolkitError::ProcessExited { action, stderr: self.take_stderr() });
+                }
+            }
+        }
+    }
+
+    fn kill(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Drop for SyncChildProcess {
+    fn drop(&mut self) {
+        // Discarded workers must not linger in the background
+        if self.is_alive() {
+            self.kill();
+        }
+    }
+}
+
+/// Decode the `data` payload of a successful response into its typed form
+pub(crate) fn decode_data<T: DeserializeOwned + Default>(
+    request: &UAssetRequest,
+    data: Option<serde_json::Value>,
+    stderr: Vec<String>,
+) -> Result<T> {
+    match data {
+        None | Some(serde_json::Value::Null) => Ok(T::default()),
+        Some(data) => serde_json::from_value(data.clone()).map_err(|source| {
+            let line = data.to_string();
+            ToolkitError::InvalidResponse {
+                action: request.action(),
+                line: line[..std::cmp::min(500, line.len())].to_string(),
+                source,
+                stderr,
+            }
+        }),
+    }
+}
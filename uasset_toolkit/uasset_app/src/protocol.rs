@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// WIRE PROTOCOL
+// ============================================================================
+// Requests are written to UAssetTool's stdin as one JSON object per line and
+// answered with one `UAssetResponse` line on stdout. The `data` payload of each
+// response is decoded into the typed struct for that request below.
+// ============================================================================
+
+/// Newest protocol revision this client speaks
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Oldest protocol revision this client accepts. Tools that predate the
+/// `handshake` action are treated as revision 1.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action")]
+pub enum UAssetRequest {
+    // Protocol negotiation - sent once after the process starts
+    #[serde(rename = "handshake")]
+    Handshake { protocol_version: u32 },
+    #[serde(rename = "detect_texture")]
+    DetectTexture { file_path: String },
+    #[serde(rename = "set_mip_gen")]
+    SetMipGen { file_path: String, mip_gen: String },
+    #[serde(rename = "get_texture_info")]
+    GetTextureInfo { file_path: String },
+    #[serde(rename = "detect_mesh")]
+    DetectMesh { file_path: String },
+    #[serde(rename = "detect_skeletal_mesh")]
+    DetectSkeletalMesh { file_path: String },
+    #[serde(rename = "detect_static_mesh")]
+    DetectStaticMesh { file_path: String },
+    #[serde(rename = "patch_mesh")]
+    PatchMesh { file_path: String, uexp_path: String },
+    #[serde(rename = "get_mesh_info")]
+    GetMeshInfo { file_path: String },
+    // Batch detection - sends all files at once, returns first match
+    #[serde(rename = "batch_detect_skeletal_mesh")]
+    BatchDetectSkeletalMesh { file_paths: Vec<String> },
+    #[serde(rename = "batch_detect_static_mesh")]
+    BatchDetectStaticMesh { file_paths: Vec<String> },
+    #[serde(rename = "batch_detect_texture")]
+    BatchDetectTexture { file_paths: Vec<String> },
+    #[serde(rename = "batch_detect_blueprint")]
+    BatchDetectBlueprint { file_paths: Vec<String> },
+    // Texture conversion using UE4-DDS-Tools (export -> re-inject with no_mipmaps)
+    #[serde(rename = "convert_texture")]
+    ConvertTexture { file_path: String },
+    #[serde(rename = "strip_mipmaps")]
+    StripMipmaps { file_path: String },
+    // Native C# mipmap stripping using UAssetAPI TextureExport
+    #[serde(rename = "strip_mipmaps_native")]
+    StripMipmapsNative { file_path: String, usmap_path: Option<String> },
+    // Batch native C# mipmap stripping - processes multiple files in one call
+    #[serde(rename = "batch_strip_mipmaps_native")]
+    BatchStripMipmapsNative { file_paths: Vec<String>, usmap_path: Option<String>, #[serde(default)] parallel: bool },
+    // Check if texture has inline data (no .ubulk needed)
+    #[serde(rename = "has_inline_texture_data")]
+    HasInlineTextureData { file_path: String, usmap_path: Option<String> },
+    // Batch check for inline texture data - returns list of files with inline data
+    #[serde(rename = "batch_has_inline_texture_data")]
+    BatchHasInlineTextureData { file_paths: Vec<String>, usmap_path: Option<String> },
+
+    // PAK operations
+    #[serde(rename = "list_pak_files")]
+    ListPakFiles { file_path: String, aes_key: Option<String> },
+    #[serde(rename = "extract_pak_file")]
+    ExtractPakFile { file_path: String, internal_path: String, output_path: String, aes_key: Option<String> },
+    #[serde(rename = "extract_pak_all")]
+    ExtractPakAll { file_path: String, output_path: String, aes_key: Option<String> },
+    #[serde(rename = "create_pak")]
+    CreatePak { output_path: String, file_paths: Vec<String>, mount_point: Option<String>, path_hash_seed: Option<u64>, aes_key: Option<String> },
+    #[serde(rename = "create_companion_pak")]
+    CreateCompanionPak { output_path: String, file_paths: Vec<String>, mount_point: Option<String>, path_hash_seed: Option<u64>, aes_key: Option<String> },
+
+    // IoStore operations
+    #[serde(rename = "list_iostore_files")]
+    ListIoStoreFiles { file_path: String, aes_key: Option<String> },
+    #[serde(rename = "create_iostore")]
+    CreateIoStore { output_path: String, input_dir: String, usmap_path: Option<String>, compress: Option<bool>, aes_key: Option<String> },
+    #[serde(rename = "is_iostore_compressed")]
+    IsIoStoreCompressed { file_path: String },
+    #[serde(rename = "recompress_iostore")]
+    RecompressIoStore { file_path: String },
+    #[serde(rename = "extract_iostore")]
+    ExtractIoStore { file_path: String, output_path: String, aes_key: Option<String> },
+    #[serde(rename = "extract_script_objects")]
+    ExtractScriptObjects { file_path: String, output_path: String },
+    #[serde(rename = "create_mod_iostore")]
+    CreateModIoStore { output_path: String, input_dir: String, usmap_path: Option<String>, mount_point: Option<String>, compress: Option<bool>, aes_key: Option<String>, #[serde(default)] parallel: bool, #[serde(default)] obfuscate: bool },
+}
+
+impl UAssetRequest {
+    /// Wire name of the request, as sent in the `action` field
+    pub fn action(&self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|v| v.get("action").and_then(|a| a.as_str()).map(|s| s.to_string()))
+            .unwrap_or_default()
+    }
+}
+
+/// Raw response envelope; `data` is decoded into the typed response of the request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UAssetResponse {
+    pub success: bool,
+    pub message: String,
+    pub data: Option<serde_json::Value>,
+}
+
+/// Response to `handshake`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HandshakeResponse {
+    pub protocol_version: u32,
+    pub tool_version: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureInfo {
+    pub mip_gen_settings: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeshInfo {
+    pub material_count: Option<i32>,
+    pub vertex_count: Option<i32>,
+    pub triangle_count: Option<i32>,
+    pub is_skeletal_mesh: Option<bool>,
+}
+
+/// Response to `batch_strip_mipmaps_native`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchStripMipmapsResponse {
+    pub success_count: usize,
+    pub skip_count: usize,
+    pub error_count: usize,
+    pub results: Vec<StripMipmapsResult>,
+}
+
+/// Per-file outcome inside a `BatchStripMipmapsResponse`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StripMipmapsResult {
+    pub path: String,
+    pub success: bool,
+    pub skipped: bool,
+    pub message: Option<String>,
+}
+
+/// Response to `extract_iostore`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtractIoStoreResponse {
+    pub extracted_count: usize,
+}
+
+/// Response to `extract_script_objects`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtractScriptObjectsResponse {
+    pub count: usize,
+}
+
+/// Response to `is_iostore_compressed`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IoStoreCompressionResponse {
+    pub compressed: bool,
+}
+
+/// IoStore creation result
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IoStoreResult {
+    pub utoc_path: String,
+    pub ucas_path: String,
+    pub pak_path: String,
+    pub converted_count: usize,
+    pub file_count: usize,
+}
+
+/// IoStore listing result
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IoStoreListResult {
+    pub package_count: usize,
+    pub container_name: String,
+    pub files: Vec<String>,
+}
@@ -0,0 +1,704 @@
+//! Native reader for `.usmap` mappings files.
+//!
+//! Unversioned packages (everything Marvel Rivals ships) store properties without names or
+//! types, so UAssetTool needs the game's mappings to read them. This reader checks that a
+//! file really is a usmap before it is handed over, reports which build it was dumped from,
+//! and exposes the struct schemas to Rust code that needs property layouts.
+//!
+//! [`UsmapHeader`] checks a file without decompressing it, so Oodle compressed mappings can be
+//! listed and imported even where Oodle isn't available; [`Usmap`] decodes everything.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use crate::package::PackageVersion;
+
+/// First two bytes of every usmap file
+pub const USMAP_MAGIC: u16 = 0x30C4;
+
+/// Refuse to inflate payloads larger than this; real mappings are a few MB
+const MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024;
+
+/// Sentinel for "no name" in name references
+const NO_NAME: u32 = u32::MAX;
+
+/// Layout revision of a usmap file (`EUsmapVersion`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UsmapVersion {
+    Initial,
+    /// Adds the optional package version block to the header
+    PackageVersioning,
+    /// Name lengths are u16 instead of u8
+    LongFName,
+    /// Enum entry counts are u16 instead of u8
+    LargeEnums,
+    /// Enum entries store their value next to the name
+    ExplicitEnumValues,
+}
+
+impl UsmapVersion {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Self::Initial,
+            1 => Self::PackageVersioning,
+            2 => Self::LongFName,
+            3 => Self::LargeEnums,
+            4 => Self::ExplicitEnumValues,
+            _ => return None,
+        })
+    }
+}
+
+/// Compression of the payload following the header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsmapCompression {
+    None,
+    Oodle,
+    Brotli,
+    ZStandard,
+}
+
+impl UsmapCompression {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Self::None,
+            1 => Self::Oodle,
+            2 => Self::Brotli,
+            3 => Self::ZStandard,
+            _ => return None,
+        })
+    }
+}
+
+/// Type of a mapped property; containers carry their element types
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertyType {
+    Byte,
+    Bool,
+    Int,
+    Float,
+    Object,
+    Name,
+    Delegate,
+    Double,
+    Array(Box<PropertyType>),
+    Struct(String),
+    Str,
+    Text,
+    Interface,
+    MulticastDelegate,
+    WeakObject,
+    LazyObject,
+    AssetObject,
+    SoftObject,
+    UInt64,
+    UInt32,
+    UInt16,
+    Int64,
+    Int16,
+    Int8,
+    Map { key: Box<PropertyType>, value: Box<PropertyType> },
+    Set(Box<PropertyType>),
+    Enum { inner: Box<PropertyType>, enum_name: String },
+    FieldPath,
+    Optional(Box<PropertyType>),
+    Utf8Str,
+    AnsiStr,
+}
+
+/// A serialized property of a struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsmapProperty {
+    pub name: String,
+    /// Position of the property in the struct's serialization order
+    pub schema_index: u16,
+    /// Number of elements of a static array, 1 for plain properties
+    pub array_size: u8,
+    pub property_type: PropertyType,
+}
+
+/// Property layout of a class or struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsmapStruct {
+    pub name: String,
+    pub super_name: Option<String>,
+    /// Number of properties including static array elements
+    pub property_count: u16,
+    pub properties: Vec<UsmapProperty>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsmapEnum {
+    pub name: String,
+    /// Value and name of every entry
+    pub entries: Vec<(u64, String)>,
+}
+
+/// Header of a usmap file: everything before the (possibly compressed) payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsmapHeader {
+    pub version: UsmapVersion,
+    pub compression: UsmapCompression,
+    /// Object versions of the build the mappings were dumped from, if recorded
+    pub package_version: Option<PackageVersion>,
+    /// Changelist of the build the mappings were dumped from, if recorded
+    pub net_cl: Option<u32>,
+    pub compressed_size: usize,
+    pub decompressed_size: usize,
+}
+
+impl UsmapHeader {
+    /// Check the header of a usmap file and that its payload is all there, without
+    /// decompressing it
+    pub fn parse(data: &[u8]) -> Result<Self, UsmapError> {
+        Ok(Self::read_from(&mut Reader::new(data))?.0)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, UsmapError> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    /// The header and the compressed payload following it
+    fn read_from<'a>(reader: &mut Reader<'a>) -> Result<(Self, &'a [u8]), UsmapError> {
+        let magic = reader.u16()?;
+        if magic != USMAP_MAGIC {
+            return Err(UsmapError::Malformed(format!("bad magic {magic:#06x}, not a usmap file")));
+        }
+        let version = reader.u8()?;
+        let version = UsmapVersion::from_u8(version)
+            .ok_or_else(|| UsmapError::Unsupported(format!("usmap version {version}")))?;
+
+        let mut package_version = None;
+        let mut net_cl = None;
+        if version >= UsmapVersion::PackageVersioning && reader.i32()? != 0 {
+            package_version = Some(PackageVersion { ue4: reader.i32()?, ue5: reader.i32()? });
+            // Custom versions: GUID + version each
+            let count = reader.i32()?;
+            let custom_versions = reader.count(count as i64, 20, "custom version")?;
+            reader.skip(custom_versions * 20)?;
+            net_cl = Some(reader.u32()?);
+        }
+
+        let method = reader.u8()?;
+        let compression = UsmapCompression::from_u8(method)
+            .ok_or_else(|| UsmapError::Unsupported(format!("compression method {method}")))?;
+        let compressed_size = reader.u32()? as usize;
+        let decompressed_size = reader.u32()? as usize;
+        if decompressed_size > MAX_DECOMPRESSED_SIZE {
+            return Err(UsmapError::Malformed(format!("payload size {decompressed_size} is too large")));
+        }
+        if compression == UsmapCompression::None && compressed_size != decompressed_size {
+            return Err(UsmapError::Malformed(format!(
+                "uncompressed payload of {compressed_size} bytes claims to be {decompressed_size}"
+            )));
+        }
+        let compressed = reader.bytes(compressed_size)?;
+        let header = Self { version, compression, package_version, net_cl, compressed_size, decompressed_size };
+        Ok((header, compressed))
+    }
+}
+
+/// Contents of a usmap file
+#[derive(Debug, Clone)]
+pub struct Usmap {
+    pub version: UsmapVersion,
+    pub compression: UsmapCompression,
+    /// Object versions of the build the mappings were dumped from, if recorded
+    pub package_version: Option<PackageVersion>,
+    /// Changelist of the build the mappings were dumped from, if recorded
+    pub net_cl: Option<u32>,
+    pub enums: Vec<UsmapEnum>,
+    pub structs: Vec<UsmapStruct>,
+    struct_index: HashMap<String, usize>,
+}
+
+impl Usmap {
+    pub fn parse(data: &[u8]) -> Result<Self, UsmapError> {
+        let (header, compressed) = UsmapHeader::read_from(&mut Reader::new(data))?;
+        let payload = decompress(header.compression, compressed, header.decompressed_size)?;
+
+        let mut usmap = Self {
+            version: header.version,
+            compression: header.compression,
+            package_version: header.package_version,
+            net_cl: header.net_cl,
+            enums: Vec::new(),
+            structs: Vec::new(),
+            struct_index: HashMap::new(),
+        };
+        usmap.read_payload(&mut Reader::new(&payload))?;
+        Ok(usmap)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, UsmapError> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    /// Number of class and struct schemas
+    pub fn schema_count(&self) -> usize {
+        self.structs.len()
+    }
+
+    /// Schema of a class or struct by name, e.g. `Texture2D`
+    pub fn schema(&self, name: &str) -> Option<&UsmapStruct> {
+        self.struct_index.get(name).map(|&i| &self.structs[i])
+    }
+
+    pub fn enum_by_name(&self, name: &str) -> Option<&UsmapEnum> {
+        self.enums.iter().find(|e| e.name == name)
+    }
+
+    /// Properties of `name` including inherited ones, base class first
+    pub fn all_properties(&self, name: &str) -> Vec<&UsmapProperty> {
+        let mut chain = Vec::new();
+        let mut current = self.schema(name);
+        while let Some(schema) = current {
+            // Guard against cycles in broken files
+            if chain.len() > self.structs.len() {
+                break;
+            }
+            chain.push(schema);
+            current = schema.super_name.as_deref().and_then(|parent| self.schema(parent));
+        }
+        chain.iter().rev().flat_map(|schema| schema.properties.iter()).collect()
+    }
+
+    fn read_payload(&mut self, reader: &mut Reader) -> Result<(), UsmapError> {
+        let name_count = reader.table_len(1, "name")?;
+        let mut names = Vec::with_capacity(name_count);
+        for _ in 0..name_count {
+            let len = if self.version >= UsmapVersion::LongFName {
+                reader.u16()? as usize
+            } else {
+                reader.u8()? as usize
+            };
+            names.push(String::from_utf8_lossy(reader.bytes(len)?).into_owned());
+        }
+        let names = Names(&names);
+
+        let enum_count = reader.table_len(5, "enum")?;
+        self.enums.reserve(enum_count);
+        for _ in 0..enum_count {
+            let name = names.get(reader.u32()?)?;
+            let entry_count = if self.version >= UsmapVersion::LargeEnums {
+                reader.u16()? as usize
+            } else {
+                reader.u8()? as usize
+            };
+            let mut entries = Vec::with_capacity(entry_count.min(reader.remaining() / 4));
+            for i in 0..entry_count {
+                let value = if self.version >= UsmapVersion::ExplicitEnumValues {
+                    reader.u64()?
+                } else {
+                    i as u64
+                };
+                entries.push((value, names.get(reader.u32()?)?));
+            }
+            self.enums.push(UsmapEnum { name, entries });
+        }
+
+        let struct_count = reader.table_len(12, "struct")?;
+        self.structs.reserve(struct_count);
+        for _ in 0..struct_count {
+            let name = names.get(reader.u32()?)?;
+            let super_name = names.get_optional(reader.u32()?)?;
+            let property_count = reader.u16()?;
+            let serializable_count = reader.u16()? as usize;
+            let mut properties = Vec::with_capacity(serializable_count.min(reader.remaining() / 8));
+            for _ in 0..serializable_count {
+                let schema_index = reader.u16()?;
+                let array_size = reader.u8()?;
+                let name = names.get(reader.u32()?)?;
+                let property_type = read_property_type(reader, &names, 0)?;
+                properties.push(UsmapProperty { name, schema_index, array_size, property_type });
+            }
+            self.struct_index.insert(name.clone(), self.structs.len());
+            self.structs.push(UsmapStruct { name, super_name, property_count, properties });
+        }
+        Ok(())
+    }
+}
+
+/// Containers nest at most a few levels in practice; deeper means the data is garbage
+const MAX_TYPE_DEPTH: usize = 16;
+
+fn read_property_type(reader: &mut Reader, names: &Names, depth: usize) -> Result<PropertyType, UsmapError> {
+    if depth > MAX_TYPE_DEPTH {
+        return Err(UsmapError::Malformed("property types nested too deeply".to_string()));
+    }
+    let inner = |reader: &mut Reader| read_property_type(reader, names, depth + 1).map(Box::new);
+    let id = reader.u8()?;
+    Ok(match id {
+        0 => PropertyType::Byte,
+        1 => PropertyType::Bool,
+        2 => PropertyType::Int,
+        3 => PropertyType::Float,
+        4 => PropertyType::Object,
+        5 => PropertyType::Name,
+        6 => PropertyType::Delegate,
+        7 => PropertyType::Double,
+        8 => PropertyType::Array(inner(reader)?),
+        9 => PropertyType::Struct(names.get(reader.u32()?)?),
+        10 => PropertyType::Str,
+        11 => PropertyType::Text,
+        12 => PropertyType::Interface,
+        13 => PropertyType::MulticastDelegate,
+        14 => PropertyType::WeakObject,
+        15 => PropertyType::LazyObject,
+        16 => PropertyType::AssetObject,
+        17 => PropertyType::SoftObject,
+        18 => PropertyType::UInt64,
+        19 => PropertyType::UInt32,
+        20 => PropertyType::UInt16,
+        21 => PropertyType::Int64,
+        22 => PropertyType::Int16,
+        23 => PropertyType::Int8,
+        24 => {
+            let key = inner(reader)?;
+            PropertyType::Map { key, value: inner(reader)? }
+        }
+        25 => PropertyType::Set(inner(reader)?),
+        26 => {
+            let underlying = inner(reader)?;
+            PropertyType::Enum { inner: underlying, enum_name: names.get(reader.u32()?)? }
+        }
+        27 => PropertyType::FieldPath,
+        28 => PropertyType::Optional(inner(reader)?),
+        29 => PropertyType::Utf8Str,
+        30 => PropertyType::AnsiStr,
+        _ => return Err(UsmapError::Malformed(format!("unknown property type {id}"))),
+    })
+}
+
+fn decompress(method: UsmapCompression, data: &[u8], size: usize) -> Result<Vec<u8>, UsmapError> {
+    let decompressed = match method {
+        UsmapCompression::None => data.to_vec(),
+        UsmapCompression::Oodle => {
+            let oodle = oodle_loader::oodle().map_err(|e| UsmapError::Decompression(e.to_string()))?;
+            let mut out = vec![0; size];
+            let written = oodle.decompress(data, &mut out);
+            if written < 0 {
+                return Err(UsmapError::Decompression("Oodle decompression failed".to_string()));
+            }
+            out.truncate(written as usize);
+            out
+        }
+        UsmapCompression::Brotli => {
+            let mut out = Vec::with_capacity(size);
+            brotli_decompressor::Decompressor::new(data, 4096)
+                .take(size as u64 + 1)
+                .read_to_end(&mut out)
+                .map_err(|e| UsmapError::Decompression(e.to_string()))?;
+            out
+        }
+        UsmapCompression::ZStandard => {
+            zstd::bulk::decompress(data, size).map_err(|e| UsmapError::Decompression(e.to_string()))?
+        }
+    };
+    if decompressed.len() != size {
+        return Err(UsmapError::Decompression(format!(
+            "expected {size} bytes, got {}",
+            decompressed.len()
+        )));
+    }
+    Ok(decompressed)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum UsmapError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("unexpected end of usmap data at offset {0}")]
+    UnexpectedEof(usize),
+
+    #[error("malformed usmap: {0}")]
+    Malformed(String),
+
+    #[error("unsupported usmap: {0}")]
+    Unsupported(String),
+
+    #[error("failed to decompress usmap: {0}")]
+    Decompression(String),
+}
+
+/// Name table lookups
+struct Names<'a>(&'a [String]);
+
+impl Names<'_> {
+    fn get(&self, index: u32) -> Result<String, UsmapError> {
+        self.get_optional(index)?
+            .ok_or_else(|| UsmapError::Malformed("missing required name".to_string()))
+    }
+
+    fn get_optional(&self, index: u32) -> Result<Option<String>, UsmapError> {
+        if index == NO_NAME {
+            return Ok(None);
+        }
+        self.0
+            .get(index as usize)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| UsmapError::Malformed(format!("name index {index} out of range ({} names)", self.0.len())))
+    }
+}
+
+/// Little-endian cursor over usmap bytes
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], UsmapError> {
+        if len > self.remaining() {
+            return Err(UsmapError::UnexpectedEof(self.pos));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), UsmapError> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, UsmapError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, UsmapError> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, UsmapError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, UsmapError> {
+        Ok(i32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, UsmapError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    /// Read a u32 table length, see `count`
+    fn table_len(&mut self, min_size: usize, what: &str) -> Result<usize, UsmapError> {
+        let count = self.u32()?;
+        self.count(count as i64, min_size, what)
+    }
+
+    /// Element count of a table whose entries take at least `min_size` bytes each
+    fn count(&self, count: i64, min_size: usize, what: &str) -> Result<usize, UsmapError> {
+        let count = usize::try_from(count)
+            .map_err(|_| UsmapError::Malformed(format!("negative {what} count {count}")))?;
+        if count.saturating_mul(min_size) > self.remaining() {
+            return Err(UsmapError::Malformed(format!("{what} count {count} exceeds file size")));
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: &[&str] = &["Object", "Texture", "Texture2D", "LODGroup", "TextureGroup", "TEXTUREGROUP_World", "TEXTUREGROUP_UI", "AddressX", "Tags", "Map"];
+
+    fn name(s: &str) -> u32 {
+        NAMES.iter().position(|n| *n == s).unwrap() as u32
+    }
+
+    fn payload(version: UsmapVersion) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend((NAMES.len() as u32).to_le_bytes());
+        for n in NAMES {
+            if version >= UsmapVersion::LongFName {
+                buf.extend((n.len() as u16).to_le_bytes());
+            } else {
+                buf.push(n.len() as u8);
+            }
+            buf.extend(n.as_bytes());
+        }
+
+        // enums
+        buf.extend(1u32.to_le_bytes());
+        buf.extend(name("TextureGroup").to_le_bytes());
+        if version >= UsmapVersion::LargeEnums {
+            buf.extend(2u16.to_le_bytes());
+        } else {
+            buf.push(2);
+        }
+        for (value, entry) in [(0u64, "TEXTUREGROUP_World"), (7, "TEXTUREGROUP_UI")] {
+            if version >= UsmapVersion::ExplicitEnumValues {
+                buf.extend(value.to_le_bytes());
+            }
+            buf.extend(name(entry).to_le_bytes());
+        }
+
+        // structs: Texture (no super) and Texture2D : Texture
+        buf.extend(2u32.to_le_bytes());
+        buf.extend(name("Texture").to_le_bytes());
+        buf.extend(NO_NAME.to_le_bytes());
+        buf.extend(2u16.to_le_bytes());
+        buf.extend(2u16.to_le_bytes());
+        // LODGroup: EnumProperty<ByteProperty, TextureGroup>
+        buf.extend(0u16.to_le_bytes());
+        buf.push(1);
+        buf.extend(name("LODGroup").to_le_bytes());
+        buf.extend([26, 0]);
+        buf.extend(name("TextureGroup").to_le_bytes());
+        // Tags: MapProperty<NameProperty, ArrayProperty<IntProperty>>
+        buf.extend(1u16.to_le_bytes());
+        buf.push(1);
+        buf.extend(name("Tags").to_le_bytes());
+        buf.extend([24, 5, 8, 2]);
+
+        buf.extend(name("Texture2D").to_le_bytes());
+        buf.extend(name("Texture").to_le_bytes());
+        buf.extend(1u16.to_le_bytes());
+        buf.extend(1u16.to_le_bytes());
+        buf.extend(0u16.to_le_bytes());
+        buf.push(1);
+        buf.extend(name("AddressX").to_le_bytes());
+        buf.push(0);
+        buf
+    }
+
+    fn usmap_file(version: UsmapVersion, versioning: Option<PackageVersion>, method: u8, payload: &[u8], compressed: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(USMAP_MAGIC.to_le_bytes());
+        buf.push(version as u8);
+        if version >= UsmapVersion::PackageVersioning {
+            buf.extend((versioning.is_some() as i32).to_le_bytes());
+            if let Some(v) = versioning {
+                buf.extend(v.ue4.to_le_bytes());
+                buf.extend(v.ue5.to_le_bytes());
+                buf.extend(1i32.to_le_bytes());
+                buf.extend([0xAB; 16]);
+                buf.extend(3i32.to_le_bytes());
+                buf.extend(123456u32.to_le_bytes());
+            }
+        }
+        buf.push(method);
+        buf.extend((compressed.len() as u32).to_le_bytes());
+        buf.extend((payload.len() as u32).to_le_bytes());
+        buf.extend(compressed);
+        buf
+    }
+
+    fn check_schemas(usmap: &Usmap) {
+        assert_eq!(usmap.schema_count(), 2);
+        let texture = usmap.schema("Texture").unwrap();
+        assert_eq!(texture.super_name, None);
+        assert_eq!(
+            texture.properties[0].property_type,
+            PropertyType::Enum { inner: Box::new(PropertyType::Byte), enum_name: "TextureGroup".to_string() }
+        );
+        assert_eq!(
+            texture.properties[1].property_type,
+            PropertyType::Map {
+                key: Box::new(PropertyType::Name),
+                value: Box::new(PropertyType::Array(Box::new(PropertyType::Int))),
+            }
+        );
+
+        let names: Vec<_> = usmap.all_properties("Texture2D").iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["LODGroup", "Tags", "AddressX"]);
+        assert!(usmap.schema("Material").is_none());
+    }
+
+    #[test]
+    fn uncompressed_versions() {
+        for version in [UsmapVersion::Initial, UsmapVersion::LongFName, UsmapVersion::LargeEnums, UsmapVersion::ExplicitEnumValues] {
+            let payload = payload(version);
+            let usmap = Usmap::parse(&usmap_file(version, None, 0, &payload, &payload)).unwrap();
+            assert_eq!(usmap.version, version);
+            assert_eq!(usmap.package_version, None);
+            check_schemas(&usmap);
+
+            let group = usmap.enum_by_name("TextureGroup").unwrap();
+            let expected_ui = if version >= UsmapVersion::ExplicitEnumValues { 7 } else { 1 };
+            assert_eq!(group.entries[1], (expected_ui, "TEXTUREGROUP_UI".to_string()));
+        }
+    }
+
+    #[test]
+    fn versioned_header() {
+        let payload = payload(UsmapVersion::LargeEnums);
+        let data = usmap_file(UsmapVersion::LargeEnums, Some(PackageVersion::UE5_3), 0, &payload, &payload);
+        let usmap = Usmap::parse(&data).unwrap();
+        assert_eq!(usmap.package_version, Some(PackageVersion::UE5_3));
+        assert_eq!(usmap.net_cl, Some(123456));
+        check_schemas(&usmap);
+    }
+
+    #[test]
+    fn compressed_payloads() {
+        let payload = payload(UsmapVersion::LargeEnums);
+
+        let zstd = zstd::bulk::compress(&payload, 3).unwrap();
+        let usmap = Usmap::parse(&usmap_file(UsmapVersion::LargeEnums, None, 3, &payload, &zstd)).unwrap();
+        assert_eq!(usmap.compression, UsmapCompression::ZStandard);
+        check_schemas(&usmap);
+
+        let mut brotli = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut brotli, 4096, 9, 22);
+            std::io::Write::write_all(&mut writer, &payload).unwrap();
+        }
+        let usmap = Usmap::parse(&usmap_file(UsmapVersion::LargeEnums, None, 2, &payload, &brotli)).unwrap();
+        assert_eq!(usmap.compression, UsmapCompression::Brotli);
+        check_schemas(&usmap);
+
+        let err = Usmap::parse(&usmap_file(UsmapVersion::LargeEnums, None, 2, &payload, &zstd)).unwrap_err();
+        assert!(matches!(err, UsmapError::Decompression(_)), "{err}");
+    }
+
+    #[test]
+    fn headers_are_checked_without_decompressing() {
+        let payload = payload(UsmapVersion::LargeEnums);
+        // Not valid Oodle data, and Oodle may not be available at all; the header check
+        // mustn't care either way
+        let data = usmap_file(UsmapVersion::LargeEnums, Some(PackageVersion::UE5_3), 1, &payload, &[0x8C; 64]);
+        let header = UsmapHeader::parse(&data).unwrap();
+        assert_eq!(header.compression, UsmapCompression::Oodle);
+        assert_eq!(header.package_version, Some(PackageVersion::UE5_3));
+        assert_eq!((header.compressed_size, header.decompressed_size), (64, payload.len()));
+        assert!(matches!(Usmap::parse(&data), Err(UsmapError::Decompression(_))));
+
+        assert!(UsmapHeader::parse(&data[..data.len() - 1]).is_err());
+        assert!(matches!(UsmapHeader::parse(b"PK\x03\x04"), Err(UsmapError::Malformed(_))));
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        let payload = payload(UsmapVersion::LargeEnums);
+        let data = usmap_file(UsmapVersion::LargeEnums, None, 0, &payload, &payload);
+
+        assert!(matches!(Usmap::parse(b"PK\x03\x04"), Err(UsmapError::Malformed(_))));
+
+        let mut future = data.clone();
+        future[2] = 9;
+        assert!(matches!(Usmap::parse(&future), Err(UsmapError::Unsupported(_))));
+
+        let mut unknown_method = data.clone();
+        // magic, version, has_versioning, then the method
+        unknown_method[7] = 7;
+        assert!(matches!(Usmap::parse(&unknown_method), Err(UsmapError::Unsupported(_))));
+
+        for len in [1, 3, 10, data.len() - 1] {
+            assert!(Usmap::parse(&data[..len]).is_err(), "truncated at {len}");
+        }
+    }
+}